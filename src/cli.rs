@@ -2,13 +2,55 @@ use std::any::Any;
 
 use crate::edcas::{
     self,
-    backend::evm::journal_uploader,
-    settings::{self, Settings},
-    EliteRustClient,
+    backend::evm::{journal_uploader, journal_uploader::UploadOptions, keystore},
+    settings::{self, EvmMode, Settings},
+    EliteRustClient, KeystoreUnlock,
 };
 use ethers::prelude::*;
 
-pub fn upload_journal(client: EliteRustClient) {
+/// Value following the option `name`, an option without value is an error
+pub fn get_arg(args: &[String], name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        None => Ok(None),
+        Some(index) => args
+            .get(index + 1)
+            .cloned()
+            .map(Some)
+            .ok_or(format!("Missing argument for {}", name)),
+    }
+}
+
+pub fn upload_journal(mut client: EliteRustClient, args: &[String]) {
+    let options = match UploadOptions::from_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    if let Some(unlock) = &mut client.keystore_unlock {
+        let keystore_path = client
+            .settings
+            .lock()
+            .unwrap()
+            .evm_settings
+            .keystore_path
+            .clone();
+        if let Some(passphrase) =
+            keystore::read_passphrase(&format!("Passphrase for {}", keystore_path))
+        {
+            unlock.passphrase = passphrase;
+            client.unlock_keystore();
+        }
+        if let Some(KeystoreUnlock {
+            error: Some(err), ..
+        }) = &client.keystore_unlock
+        {
+            println!("{}", err);
+        }
+    }
+
     let journal_path = client
         .settings
         .lock()
@@ -18,35 +60,46 @@ pub fn upload_journal(client: EliteRustClient) {
         .clone();
 
     let evm_settings = &client.settings.lock().unwrap().evm_settings;
+    if !options.dry_run && evm_settings.get_mode() != EvmMode::Signing {
+        println!("Uploading needs sharing enabled and a valid private key");
+        return;
+    }
+
+    // the client may send transactions of its own, so only the difference is reported
+    let (submitted, skipped, failed) = {
+        let status = evm_settings.queue_status.lock().unwrap();
+        (status.submitted, status.skipped, status.failed)
+    };
 
     // start evm uploader thread
-    let (mut progress_bus_reader, total) = journal_uploader::initialize(evm_settings, journal_path);
+    let (mut progress_bus_reader, total) =
+        journal_uploader::initialize(evm_settings, journal_path, options);
     println!("Uploading {} logs", total);
 
-    // loop until uploading is done
-    loop {
-        match progress_bus_reader.recv() {
-            Ok(index) => {
-                if index <= 0 {
-                    println!("done uploading");
-                    break;
-                }
-                print!("{} ", total - index);
-            }
-            Err(err) => {
-                println!("\npanicked: {}", err);
-                break;
-            }
-        }
+    // the bus closes once every call is sent
+    while let Ok(progress) = progress_bus_reader.recv() {
+        println!(
+            "[{}/{}] Journal.{}.01.log: {} events",
+            progress.done, progress.total, progress.journal, progress.lines
+        );
     }
+
+    let status = evm_settings.queue_status.lock().unwrap();
+    println!("done uploading");
+    println!(
+        "Submitted: {}, skipped: {}, failed: {}",
+        status.submitted - submitted,
+        status.skipped - skipped,
+        status.failed - failed
+    );
 }
 
-pub fn set_sc_address(smart_contract_address: String, client: EliteRustClient) {
+pub fn set_sc_address(smart_contract_address: String) {
     let _ = smart_contract_address
         .parse::<Address>()
         .unwrap_or_else(|_| panic!("Address is incorrect"));
 
-    let mut settings = client.settings.lock().unwrap();
+    let mut settings = edcas::settings::Settings::default();
 
     settings.evm_settings.smart_contract_address = smart_contract_address;
     settings.save_settings_to_file();
@@ -72,3 +125,62 @@ pub fn set_graphics_path(new_graphics_path: String) {
     settings.graphic_editor_settings.graphics_directory = new_graphics_path;
     settings.save_settings_to_file();
 }
+
+pub fn generate_key() {
+    store_key(keystore::generate_private_key());
+}
+
+pub fn import_key() {
+    let private_key = match keystore::read_hidden("Private key: ") {
+        Some(private_key) => private_key,
+        None => return,
+    };
+    if private_key.parse::<LocalWallet>().is_err() {
+        println!("Invalid private key");
+        return;
+    }
+    store_key(private_key);
+}
+
+/// Writes the key into a new keystore and points the settings to it
+fn store_key(private_key: String) {
+    let mut settings = edcas::settings::Settings::default();
+    let path = match settings.evm_settings.keystore_path.as_str() {
+        "" => keystore::get_default_path(),
+        path => path.to_string(),
+    };
+    let passphrase = match keystore::read_new_passphrase("Passphrase for the new keystore") {
+        Some(passphrase) => passphrase,
+        None => return,
+    };
+    match keystore::create(&path, &private_key, &passphrase) {
+        Ok(address) => {
+            settings.evm_settings.private_key = private_key;
+            settings.evm_settings.keystore_path = path.clone();
+            settings.save_settings_to_file();
+            println!("Stored key of {} in {}", address, path);
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+pub fn export_key() {
+    let settings = edcas::settings::Settings::default();
+    let evm_settings = &settings.evm_settings;
+    if !evm_settings.private_key.is_empty() {
+        println!("{}", evm_settings.private_key);
+        return;
+    }
+    if evm_settings.keystore_path.is_empty() {
+        println!("No private key configured");
+        return;
+    }
+    if let Some(passphrase) =
+        keystore::read_passphrase(&format!("Passphrase for {}", evm_settings.keystore_path))
+    {
+        match keystore::unlock(&evm_settings.keystore_path, &passphrase) {
+            Ok(private_key) => println!("{}", private_key),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
@@ -1,28 +1,35 @@
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::mpsc::Sender;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
-use std::{env, fs, thread};
+use std::{env, fs, process, thread};
 
 use bus::{Bus, BusReader};
 use chrono::Local;
 
 use crate::edcas::backend::cargo_reader;
 use crate::edcas::backend::cargo_reader::CargoReader;
-use crate::edcas::backend::evm::request_handler;
-use crate::edcas::backend::evm::request_handler::{EvmRequest, EvmUpdate};
+use crate::edcas::backend::data_backend;
+use crate::edcas::backend::data_backend::{BackendUpdater, DataRequest, DataUpdate};
 use crate::edcas::backend::journal_reader;
-use crate::edcas::carrier::CarrierState;
+use crate::edcas::backend::journal_reader::LiveEvents;
+use crate::edcas::carrier::{CarrierFilter, CarrierState};
 
 use crate::edcas::backend::evm::edcas_contract::StationIdentity;
+use crate::edcas::backend::evm::keystore;
 use crate::edcas::explorer::system::PlanetSignal;
+use crate::edcas::finance::FinanceState;
 use json::JsonValue;
-use log::info;
+use log::{error, info, warn};
 
 use crate::edcas::materials::MaterialState;
-use crate::edcas::station::{Station, StationState};
+use crate::edcas::session::SessionState;
+use crate::edcas::settings::{ActionAtShutdownSignal, EvmMode, Settings};
+use crate::edcas::station::{MarketSortKey, Station, StationFilter, StationState};
+use crate::edcas::trade::TradeState;
 use crate::edcas::State::News;
 
 pub mod explorer;
@@ -30,17 +37,24 @@ pub mod explorer;
 pub(crate) mod about;
 pub(crate) mod backend;
 pub(crate) mod carrier;
+pub(crate) mod finance;
+pub(crate) mod ledger;
 pub mod materials;
 pub mod mining;
 pub(crate) mod news;
+pub(crate) mod session;
 pub(crate) mod settings;
 pub(crate) mod station;
+pub(crate) mod trade;
 
 pub struct EliteRustClient {
     pub about: about::About,
     pub explorer: explorer::Explorer,
     pub station: StationState,
     pub carrier: CarrierState,
+    pub trade: TradeState,
+    pub finance: FinanceState,
+    pub session: SessionState,
     pub state: State,
     pub materials: MaterialState,
     pub settings: Arc<Mutex<settings::Settings>>,
@@ -48,34 +62,131 @@ pub struct EliteRustClient {
     pub mining: mining::Mining,
     pub cargo_reader: Arc<Mutex<CargoReader>>,
     pub journal_log_bus_reader: BusReader<JsonValue>,
-    pub evm_update_reader: BusReader<EvmUpdate>,
-    pub evm_request_writer: Sender<EvmRequest>,
+    /// Events received from the journal bus, to tell live events from replayed ones
+    pub journal_event_count: u64,
+    pub live_events: LiveEvents,
+    pub data_update_reader: BusReader<DataUpdate>,
+    pub data_request_writer: Sender<DataRequest>,
     pub timestamp: String,
+    /// Set while the keystore waits for its passphrase
+    pub keystore_unlock: Option<KeystoreUnlock>,
+}
+
+/// Keystore waiting for the passphrase from the unlock dialog.
+/// Journal events before the unlock are not shared, they can be uploaded later with --upload-journal.
+pub struct KeystoreUnlock {
+    pub passphrase: String,
+    pub error: Option<String>,
+    journal_bus_reader: BusReader<JsonValue>,
 }
 
 impl EliteRustClient {
+    /// Unlocks the keystore with the entered passphrase and starts sharing
+    pub fn unlock_keystore(&mut self) {
+        let Some(unlock) = &mut self.keystore_unlock else {
+            return;
+        };
+        let result = keystore::unlock_settings(
+            &mut self.settings.lock().unwrap().evm_settings,
+            &unlock.passphrase,
+        );
+        match result {
+            Ok(()) => {
+                if let Some(unlock) = self.keystore_unlock.take() {
+                    start_evm_interpreter(unlock.journal_bus_reader, self.settings.clone());
+                }
+            }
+            Err(err) => {
+                unlock.passphrase.clear();
+                unlock.error = Some(err);
+            }
+        }
+    }
+
+    /// Keeps the keystore locked, the EDCAS network is only read
+    pub fn skip_unlock(&mut self) {
+        if self.keystore_unlock.take().is_some() {
+            warn!("Keystore stays locked, EVM is read-only");
+        }
+    }
+
     pub fn update_values(&mut self) {
+        //Nothing is shared while the keystore is locked, but the journal reader must not block
+        if let Some(unlock) = &mut self.keystore_unlock {
+            while unlock.journal_bus_reader.try_recv().is_ok() {}
+        }
+        self.explorer.remove_expired_signals();
+        self.explorer.update_highlights();
         if let Ok(json) = self.journal_log_bus_reader.try_recv() {
+            self.journal_event_count += 1;
             self.timestamp = json["timestamp"].to_string();
+            if let "FSDJump" | "Location" | "CarrierJump" = json["event"].as_str().unwrap_or("") {
+                if let (Some(x), Some(y), Some(z)) = (
+                    json["StarPos"][0].as_f64(),
+                    json["StarPos"][1].as_f64(),
+                    json["StarPos"][2].as_f64(),
+                ) {
+                    self.station.position = Some([x, y, z]);
+                    self.carrier.position = Some([x, y, z]);
+                    self.carrier
+                        .system_coordinates
+                        .insert(json["StarSystem"].to_string(), [x, y, z]);
+                }
+            }
             backend::journal_interpreter::interpret_json(
                 json.clone(),
-                &self.evm_request_writer,
+                &self.data_request_writer,
                 &mut self.explorer,
                 &mut self.materials,
                 &mut self.mining,
+                &mut self.trade,
                 self.settings.clone(),
             );
+            self.finance.interpret_json(&json);
+            self.session.interpret_json(&json);
+            if json["event"].as_str() == Some("Shutdown") {
+                self.finance.save();
+                //A replayed shutdown of an older session only starts a new session
+                if !self.live_events.is_live(self.journal_event_count) {
+                    self.session.restart();
+                } else {
+                    self.session.finish(&self.finance);
+                    //Exiting here instead of in the journal reader, so the report is written first
+                    if let ActionAtShutdownSignal::Exit = self
+                        .settings
+                        .lock()
+                        .unwrap()
+                        .journal_reader_settings
+                        .action_at_shutdown_signal
+                    {
+                        process::exit(0);
+                    }
+                }
+            }
         }
+        self.finance.save_if_due();
+        self.trade.poll();
         {
             self.cargo_reader.lock().unwrap().run();
         }
         {
-            if let Ok(update) = self.evm_update_reader.try_recv() {
+            if let Ok(update) = self.data_update_reader.try_recv() {
                 match update {
-                    EvmUpdate::CarrierList(carriers) => {
+                    DataUpdate::CarrierList(carriers) => {
                         self.carrier.carriers = carriers;
                     }
-                    EvmUpdate::StationList(stations) => {
+                    DataUpdate::Carrier(carrier) => {
+                        match self
+                            .carrier
+                            .carriers
+                            .iter_mut()
+                            .find(|other| other.callsign == carrier.callsign)
+                        {
+                            Some(other) => *other = carrier,
+                            None => self.carrier.carriers.push(carrier),
+                        }
+                    }
+                    DataUpdate::StationList(stations) => {
                         for station_identity in stations {
                             //List is sorted by name -> If name exceeds alphabetic order, it is not in the list and can be added
                             if !self.is_station_registered(&station_identity) {
@@ -92,7 +203,7 @@ impl EliteRustClient {
                         }
                         self.station.stations.sort_by_key(|a| a.name.clone());
                     }
-                    EvmUpdate::StationMetaData(market_id, meta_data) => {
+                    DataUpdate::StationMetaData(market_id, meta_data) => {
                         for station in &mut self.station.stations {
                             if station.market_id == market_id {
                                 station.meta_data = Some(meta_data);
@@ -100,10 +211,27 @@ impl EliteRustClient {
                             }
                         }
                     }
-                    EvmUpdate::StationCommodityListening(_market_id, _listenings) => {
-                        todo!("Implement");
+                    DataUpdate::StationCommodityListening(market_id, mut listenings) => {
+                        self.station.market_sort.sort(&mut listenings);
+                        for station in &mut self.station.stations {
+                            if station.market_id == market_id {
+                                station.market = Some(listenings);
+                                break;
+                            }
+                        }
+                    }
+                    DataUpdate::SystemCoordinates(name, coordinates) => {
+                        self.carrier.system_coordinates.insert(name, coordinates);
                     }
-                    EvmUpdate::SystemMetaData(system_address, system_meta_data) => {
+                    DataUpdate::SystemMetaData(system_address, system_meta_data) => {
+                        self.station.system_coordinates.insert(
+                            system_address,
+                            [system_meta_data.x, system_meta_data.y, system_meta_data.z],
+                        );
+                        self.carrier.system_coordinates.insert(
+                            system_meta_data.name.clone(),
+                            [system_meta_data.x, system_meta_data.y, system_meta_data.z],
+                        );
                         let length = self.explorer.systems.len();
                         for i in 0..length {
                             if self.explorer.systems[i].address == system_address {
@@ -144,7 +272,7 @@ impl EliteRustClient {
                             }
                         }
                     }
-                    EvmUpdate::PlanetList(system_address, planet_list) => {
+                    DataUpdate::PlanetList(system_address, planet_list) => {
                         let length = self.explorer.systems.len();
                         for i in 0..length {
                             if self.explorer.systems[i].address == system_address {
@@ -176,12 +304,9 @@ impl EliteRustClient {
                                         }
                                     }
                                     if !found {
-                                        self.explorer.systems[i].body_list.push(body.clone());
+                                        self.explorer.systems[i].insert_body(body.clone());
                                     }
                                 }
-                                self.explorer.systems[i]
-                                    .body_list
-                                    .sort_by_key(|planet| planet.get_id());
                             }
                         }
                     }
@@ -206,10 +331,22 @@ impl EliteRustClient {
 impl Default for EliteRustClient {
     fn default() -> Self {
         let log_path = initialize_logger();
-        let settings = settings::Settings {
+        let mut settings = settings::Settings {
             log_path,
             ..Default::default()
         };
+        if keystore::migrate_private_key(&mut settings.evm_settings) {
+            //The plain key has to disappear from the settings file
+            settings.save_settings_to_file();
+        }
+        if settings.evm_settings.is_locked() {
+            if let Ok(passphrase) = env::var(keystore::PASSPHRASE_VARIABLE) {
+                if let Err(err) = keystore::unlock_settings(&mut settings.evm_settings, &passphrase)
+                {
+                    error!("{}", err);
+                }
+            }
+        }
         let settings_pointer = Arc::new(Mutex::new(settings));
         info!("Starting...");
         info!("Current directory: {:?}", env::current_dir().unwrap());
@@ -218,34 +355,48 @@ impl Default for EliteRustClient {
         let materials = MaterialState::default();
 
         info!("Starting threads");
-        info!("Starting Evm Updater");
-        let (evm_request_writer, evm_request_receiver) = mpsc::channel::<EvmRequest>();
+        info!("Starting data backend");
+        let (data_request_writer, data_request_receiver) = mpsc::channel::<DataRequest>();
 
-        let mut evm_update_bus: Bus<EvmUpdate> = Bus::new(100);
-        let evm_update_reader = evm_update_bus.add_rx();
-        let settings_pointer_clone = settings_pointer.clone();
-        let mut evm_updater = request_handler::initialize(
-            evm_update_bus,
-            evm_request_receiver,
-            settings_pointer_clone,
-        );
-        thread::Builder::new()
-            .name("edcas-evm-handler".into())
-            .spawn(move || loop {
-                evm_updater.run_update();
-                sleep(Duration::from_secs(1));
-            })
-            .expect("Failed to create thread jevm-handler");
+        let mut data_update_bus: Bus<DataUpdate> = Bus::new(100);
+        let data_update_reader = data_update_bus.add_rx();
+        match data_backend::initialize(settings_pointer.clone()) {
+            Ok(backend) => {
+                let mut updater = BackendUpdater {
+                    backend,
+                    writer: data_update_bus,
+                    receiver: data_request_receiver,
+                };
+                thread::Builder::new()
+                    .name("edcas-data-backend".into())
+                    .spawn(move || loop {
+                        updater.run_update();
+                        sleep(Duration::from_secs(1));
+                    })
+                    .expect("Failed to create thread data-backend");
+            }
+            Err(err) => error!("{}", err),
+        }
 
         info!("Starting Journal reader");
         let mut journal_bus: Bus<JsonValue> = Bus::new(1000);
         let journal_bus_reader = journal_bus.add_rx();
         let tangle_journal_bus_reader = journal_bus.add_rx();
+        //A reader nobody drains would fill up and block the bus
+        let eddn_journal_bus_reader = settings_pointer
+            .lock()
+            .unwrap()
+            .eddn_settings
+            .is_enabled()
+            .then(|| journal_bus.add_rx());
         let settings_pointer_clone = settings_pointer.clone();
+        let live_events = LiveEvents::default();
+        let live_events_clone = live_events.clone();
         thread::Builder::new()
             .name("edcas-journal-reader".into())
             .spawn(move || {
-                let mut j_reader = journal_reader::initialize(settings_pointer_clone);
+                let mut j_reader =
+                    journal_reader::initialize(settings_pointer_clone, live_events_clone);
                 loop {
                     //Sleep needed, because too frequent reads can lead to read the file while being written to it -> exception from json parser because json is not complete
                     sleep(Duration::from_millis(100));
@@ -253,36 +404,42 @@ impl Default for EliteRustClient {
                 }
             })
             .expect("Failed to create thread journal-reader");
-        let settings_pointer_clone = settings_pointer.clone();
 
-        info!(
-            "Allow to share data over edcas: {}",
-            settings_pointer
-                .lock()
-                .unwrap()
-                .evm_settings
-                .allow_share_data
-        );
-        if settings_pointer
-            .lock()
-            .unwrap()
-            .evm_settings
-            .allow_share_data
-        {
-            info!("Starting Evm Interpreter");
-            //Buffer needs to be this large or in development, when the reader timeout is set to 0 the buffer can get full
-            let settings_pointer = settings_pointer_clone;
-            let mut evm_interpreter = backend::evm::journal_interpreter::initialize(
-                tangle_journal_bus_reader,
-                &settings_pointer.lock().unwrap().evm_settings,
-            );
-            thread::Builder::new()
-                .name("edcas-evm-interpreter".into())
-                .spawn(move || evm_interpreter.run_loop())
-                .expect("Failed to create thread evm-interpreter");
+        let evm_mode = settings_pointer.lock().unwrap().evm_settings.get_mode();
+        info!("EVM mode: {}", evm_mode);
+        let mut keystore_unlock = None;
+        if evm_mode == EvmMode::Signing {
+            start_evm_interpreter(tangle_journal_bus_reader, settings_pointer.clone());
+        } else if settings_pointer.lock().unwrap().evm_settings.is_locked() {
+            info!("Keystore locked, waiting for the passphrase");
+            keystore_unlock = Some(KeystoreUnlock {
+                passphrase: String::new(),
+                error: None,
+                journal_bus_reader: tangle_journal_bus_reader,
+            });
+        }
+
+        match eddn_journal_bus_reader {
+            Some(eddn_journal_bus_reader) => {
+                info!("Starting EDDN publisher");
+                let mut eddn_publisher = backend::eddn_publisher::initialize(
+                    eddn_journal_bus_reader,
+                    settings_pointer.clone(),
+                );
+                thread::Builder::new()
+                    .name("edcas-eddn-publisher".into())
+                    .spawn(move || eddn_publisher.run_loop())
+                    .expect("Failed to create thread eddn-publisher");
+            }
+            None => info!("EDDN publishing disabled"),
         }
         info!("Done starting threads");
 
+        let saved_highlight_rules_revision = settings_pointer
+            .lock()
+            .unwrap()
+            .highlight_rules_revision
+            .clone();
         let cargo_reader = Arc::new(Mutex::new(cargo_reader::initialize(
             settings_pointer.clone(),
         )));
@@ -296,40 +453,80 @@ impl Default for EliteRustClient {
             station: StationState {
                 stations: vec![],
                 search: "".to_string(),
-                evm_request_writer: evm_request_writer.clone(),
+                data_request_writer: data_request_writer.clone(),
                 settings: settings_pointer.clone(),
+                market_sort: MarketSortKey::Name,
+                filter: StationFilter::default(),
+                system_coordinates: HashMap::new(),
+                requested_systems: HashSet::new(),
+                position: None,
+                filter_requested_at: None,
             },
             carrier: CarrierState {
                 carriers: vec![],
                 search: "".to_string(),
                 settings: settings_pointer.clone(),
+                filter: CarrierFilter::default(),
+                data_request_writer: data_request_writer.clone(),
+                system_coordinates: HashMap::new(),
+                requested_systems: HashSet::new(),
+                position: None,
+                filter_requested_at: None,
             },
+            trade: TradeState::load(settings_pointer.clone()),
+            finance: FinanceState::load(),
+            session: SessionState::default(),
             explorer: explorer::Explorer {
                 systems: vec![],
                 index: 0,
                 body_list_index: None,
                 settings: settings_pointer.clone(),
+                system_map: Default::default(),
+                galaxy_map: Default::default(),
+                signal_store: explorer::signal::SignalStore::load(),
+                signal_filter: None,
+                highlight_rules_revision: 0,
+                saved_highlight_rules_revision,
             },
             state: News,
             cargo_reader,
             journal_log_bus_reader: journal_bus_reader,
-            evm_request_writer,
-            evm_update_reader,
+            journal_event_count: 0,
+            live_events,
+            data_request_writer,
+            data_update_reader,
             materials,
             settings: settings_pointer.clone(),
             mining,
             timestamp: String::from(""),
+            keystore_unlock,
         }
     }
 }
 
+fn start_evm_interpreter(bus_reader: BusReader<JsonValue>, settings: Arc<Mutex<Settings>>) {
+    info!("Starting Evm Interpreter");
+    let mut evm_interpreter = backend::evm::journal_interpreter::initialize(
+        bus_reader,
+        &settings.lock().unwrap().evm_settings,
+        "transactions.log",
+    );
+    thread::Builder::new()
+        .name("edcas-evm-interpreter".into())
+        .spawn(move || evm_interpreter.run_loop())
+        .expect("Failed to create thread evm-interpreter");
+}
+
 pub enum State {
     News,
     About,
     StationPage,
     CarrierPage,
+    TradePage,
+    FinancePage,
     Settings,
     Explorer,
+    GalaxyMap,
     MaterialInventory,
     Mining,
 }
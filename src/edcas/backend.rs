@@ -1,6 +1,11 @@
 pub mod cargo_reader;
+pub mod data_backend;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod eddn_publisher;
 pub mod evm;
-mod floating;
+pub(crate) mod floating;
 pub mod journal_interpreter;
 pub mod journal_reader;
+pub mod rest_backend;
 pub mod translator;
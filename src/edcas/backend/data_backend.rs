@@ -0,0 +1,204 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+
+use bus::Bus;
+use log::{error, info};
+
+#[cfg(feature = "database")]
+use crate::edcas::backend::database;
+use crate::edcas::backend::evm::edcas_contract::StationIdentity;
+use crate::edcas::backend::{evm, rest_backend};
+use crate::edcas::carrier::Carrier;
+use crate::edcas::explorer::body::BodyType;
+use crate::edcas::settings::Settings;
+use crate::edcas::station::{CommodityListening, StationMetaData};
+
+#[derive(Clone)]
+pub enum DataUpdate {
+    CarrierList(Vec<Carrier>),
+    /// A single carrier changed, identified by its callsign
+    Carrier(Carrier),
+    StationList(Vec<StationIdentity>),
+    StationMetaData(u64, StationMetaData),
+    StationCommodityListening(u64, Vec<CommodityListening>),
+    SystemMetaData(u64, SystemMetaData),
+    /// Star position of a system, identified by its name
+    SystemCoordinates(String, [f64; 3]),
+    PlanetList(u64, Vec<BodyType>),
+}
+
+/**
+* StationMetaData(u64): MarketId from station
+* StationCommodityListener(u64): MarketId from station
+* SystemMetaData(u64): SystemAddress from system
+* SystemPlanetData(u64): SystemAddress from system
+* SystemCoordinates(String): Name of the system, carriers only know that
+*/
+#[derive(Clone)]
+pub enum DataRequest {
+    StationMetaData(u64),
+    StationCommodityListener(u64),
+    SystemMetaData(u64),
+    SystemPlanetData(u64),
+    SystemCoordinates(String),
+}
+
+/// What the pages need of a system the journal didn't tell about, the body count and its position
+#[derive(Clone)]
+pub struct SystemMetaData {
+    pub name: String,
+    pub body_count: u8,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Source of the galaxy data shown on the station, carrier and explorer pages.
+/// `None` means the backend doesn't know the system or station.
+pub trait DataBackend: Send {
+    fn get_system_meta_data(&self, system_address: u64) -> Result<Option<SystemMetaData>, String>;
+    fn get_bodies(&self, system_address: u64) -> Result<Vec<BodyType>, String>;
+    fn get_stations(&self) -> Result<Vec<StationIdentity>, String>;
+    fn get_station_meta_data(&self, market_id: u64) -> Result<Option<StationMetaData>, String>;
+    fn get_market(&self, market_id: u64) -> Result<Vec<CommodityListening>, String>;
+    fn get_carriers(&self) -> Result<Vec<Carrier>, String>;
+
+    /// Star position of a system by its name. Only backends which can look systems up by name know it.
+    fn get_system_coordinates(&self, _name: &str) -> Result<Option<[f64; 3]>, String> {
+        Ok(None)
+    }
+
+    /// Called every second. Loads the carrier and station lists again, backends which know
+    /// what changed only send that.
+    fn get_changes(&mut self) -> Result<Vec<DataUpdate>, String> {
+        let mut updates = vec![];
+        let carriers = self.get_carriers()?;
+        if !carriers.is_empty() {
+            updates.push(DataUpdate::CarrierList(carriers));
+        }
+        let stations = self.get_stations()?;
+        if !stations.is_empty() {
+            updates.push(DataUpdate::StationList(stations));
+        }
+        Ok(updates)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Evm,
+    Rest,
+    Database,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 3] = [BackendKind::Evm, BackendKind::Rest, BackendKind::Database];
+}
+
+impl Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BackendKind::Evm => "evm",
+            BackendKind::Rest => "rest",
+            BackendKind::Database => "database",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BackendKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or(format!("Unknown data backend: {}", s))
+    }
+}
+
+/// Creates the backend chosen in the settings
+pub fn initialize(settings: Arc<Mutex<Settings>>) -> Result<Box<dyn DataBackend>, String> {
+    let (kind, rest_url, database_path) = {
+        let settings = settings.lock().unwrap();
+        (
+            settings.backend_settings.kind,
+            settings.backend_settings.rest_url.clone(),
+            settings.database_settings.path.clone(),
+        )
+    };
+    info!("Using the {} data backend", kind);
+    Ok(match kind {
+        BackendKind::Evm => Box::new(evm::request_handler::initialize(settings)?),
+        BackendKind::Rest => Box::new(rest_backend::initialize(&rest_url, settings)?),
+        #[cfg(feature = "database")]
+        BackendKind::Database => Box::new(database::initialize(settings, &database_path)?),
+        #[cfg(not(feature = "database"))]
+        BackendKind::Database => {
+            return Err(format!(
+                "Built without the database feature, can't open {}",
+                database_path
+            ))
+        }
+    })
+}
+
+/// Answers the requests of the pages with the backend and forwards its changes
+pub struct BackendUpdater {
+    pub backend: Box<dyn DataBackend>,
+    pub writer: Bus<DataUpdate>,
+    pub receiver: Receiver<DataRequest>,
+}
+
+impl BackendUpdater {
+    pub fn run_update(&mut self) {
+        while let Ok(request) = self.receiver.try_recv() {
+            let result = match request {
+                DataRequest::StationMetaData(market_id) => self
+                    .backend
+                    .get_station_meta_data(market_id)
+                    .map(|meta_data| {
+                        meta_data.map(|meta_data| DataUpdate::StationMetaData(market_id, meta_data))
+                    }),
+                DataRequest::StationCommodityListener(market_id) => self
+                    .backend
+                    .get_market(market_id)
+                    .map(|market| Some(DataUpdate::StationCommodityListening(market_id, market))),
+                DataRequest::SystemMetaData(system_address) => self
+                    .backend
+                    .get_system_meta_data(system_address)
+                    .map(|meta_data| {
+                        meta_data
+                            .map(|meta_data| DataUpdate::SystemMetaData(system_address, meta_data))
+                    }),
+                DataRequest::SystemCoordinates(name) => self
+                    .backend
+                    .get_system_coordinates(&name)
+                    .map(|coordinates| {
+                        coordinates
+                            .map(|coordinates| DataUpdate::SystemCoordinates(name, coordinates))
+                    }),
+                DataRequest::SystemPlanetData(system_address) => self
+                    .backend
+                    .get_bodies(system_address)
+                    .map(|bodies| Some(DataUpdate::PlanetList(system_address, bodies))),
+            };
+            match result {
+                Ok(Some(update)) => self.writer.broadcast(update),
+                Ok(None) => {}
+                Err(err) => error!("Error answering request: {}", err),
+            }
+        }
+
+        match self.backend.get_changes() {
+            Ok(updates) => {
+                for update in updates {
+                    self.writer.broadcast(update);
+                }
+            }
+            Err(err) => error!("Error getting changes: {}", err),
+        }
+    }
+}
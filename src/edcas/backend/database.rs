@@ -0,0 +1,524 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::edcas::backend::data_backend::{DataBackend, DataUpdate, SystemMetaData};
+use crate::edcas::backend::evm::edcas_contract::{Faction, StationIdentity};
+use crate::edcas::backend::floating;
+use crate::edcas::carrier::Carrier;
+use crate::edcas::explorer::body;
+use crate::edcas::explorer::body::BodyType;
+use crate::edcas::settings::Settings;
+use crate::edcas::station::{CommodityListening, PadSize, StationMetaData};
+use crate::edcas::trade::MarketSnapshot;
+
+/// Tables of a local galaxy mirror, filled by the eddn adapter. Timestamps are unix seconds.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS systems (
+    address INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    z REAL NOT NULL,
+    allegiance TEXT NOT NULL,
+    economy TEXT NOT NULL,
+    second_economy TEXT NOT NULL,
+    government TEXT NOT NULL,
+    security TEXT NOT NULL,
+    population INTEGER NOT NULL,
+    body_count INTEGER NOT NULL DEFAULT 0,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS bodies (
+    system_address INTEGER NOT NULL,
+    body_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    scan TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (system_address, body_id)
+);
+CREATE TABLE IF NOT EXISTS stations (
+    market_id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    system_address INTEGER NOT NULL,
+    system_name TEXT NOT NULL,
+    faction_name TEXT NOT NULL,
+    faction_state TEXT NOT NULL,
+    government TEXT NOT NULL,
+    economy TEXT NOT NULL,
+    services TEXT NOT NULL,
+    distance TEXT NOT NULL,
+    landing_pads TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS carriers (
+    market_id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    callsign TEXT NOT NULL,
+    services TEXT NOT NULL,
+    docking_access TEXT NOT NULL,
+    allow_notorious INTEGER NOT NULL,
+    current_system TEXT NOT NULL,
+    current_body TEXT NOT NULL,
+    next_system TEXT NOT NULL,
+    next_body TEXT NOT NULL,
+    departure INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS commodities (
+    market_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    buy_price INTEGER NOT NULL,
+    sell_price INTEGER NOT NULL,
+    mean_price INTEGER NOT NULL,
+    demand INTEGER NOT NULL,
+    demand_bracket INTEGER NOT NULL,
+    stock INTEGER NOT NULL,
+    stock_bracket INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (market_id, name)
+);
+";
+
+/// How long a connection waits for the lock of another one, the eddn adapter writes while the client reads
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database in WAL mode, so readers don't block the writer, and creates missing tables
+pub fn open(path: &str) -> Result<Connection, String> {
+    let connection =
+        Connection::open(path).map_err(|err| format!("Can't open database {}: {}", path, err))?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(|err| format!("Can't set busy timeout of {}: {}", path, err))?;
+    connection
+        .query_row("PRAGMA journal_mode=WAL", [], |row| row.get::<_, String>(0))
+        .map_err(|err| format!("Can't enable WAL in {}: {}", path, err))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|err| format!("Can't create tables in {}: {}", path, err))?;
+    Ok(connection)
+}
+
+/// Markets of every station with their system position, for the trade route finder
+pub fn load_market_snapshots(path: &str) -> Result<Vec<MarketSnapshot>, String> {
+    let connection = open(path)?;
+    query_market_snapshots(&connection).map_err(|err| err.to_string())
+}
+
+fn query_market_snapshots(connection: &Connection) -> rusqlite::Result<Vec<MarketSnapshot>> {
+    let mut statement = connection.prepare(
+        "SELECT stations.market_id, stations.name, stations.system_name, stations.landing_pads, \
+         stations.distance, systems.x, systems.y, systems.z FROM stations \
+         LEFT JOIN systems ON systems.address = stations.system_address",
+    )?;
+    let mut snapshots: HashMap<u64, MarketSnapshot> = statement
+        .query_map([], |row| {
+            let position = match (row.get(5)?, row.get(6)?, row.get(7)?) {
+                (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+                _ => None,
+            };
+            Ok(MarketSnapshot {
+                market_id: row.get(0)?,
+                station_name: row.get(1)?,
+                system_name: row.get(2)?,
+                position,
+                largest_pad: PadSize::get_largest(&row.get::<_, String>(3)?),
+                arrival_distance: row.get::<_, String>(4)?.parse().unwrap_or(0.0),
+                timestamp: DateTime::default(),
+                commodities: vec![],
+            })
+        })?
+        .map(|snapshot| snapshot.map(|snapshot| (snapshot.market_id, snapshot)))
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut statement = connection.prepare(
+        "SELECT market_id, name, buy_price, sell_price, mean_price, demand, demand_bracket, \
+         stock, stock_bracket, timestamp FROM commodities",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let Some(snapshot) = snapshots.get_mut(&row.get(0)?) else {
+            continue;
+        };
+        snapshot.commodities.push(CommodityListening {
+            name: row.get(1)?,
+            buy_price: row.get(2)?,
+            sell_price: row.get(3)?,
+            mean_price: row.get(4)?,
+            demand: row.get(5)?,
+            demand_bracket: row.get(6)?,
+            stock: row.get(7)?,
+            stock_bracket: row.get(8)?,
+        });
+        //A market is as old as its oldest price
+        let timestamp = DateTime::from_timestamp(row.get(9)?, 0).unwrap_or_default();
+        if snapshot.commodities.len() == 1 || timestamp < snapshot.timestamp {
+            snapshot.timestamp = timestamp;
+        }
+    }
+    Ok(snapshots.into_values().collect())
+}
+
+/// Reads the station, carrier and explorer data from the local database instead of the smart contract
+pub struct DatabaseBackend {
+    settings: Arc<Mutex<Settings>>,
+    connection: Connection,
+    /// Changes with every write of another connection, `None` until the lists were sent once
+    data_version: Option<i64>,
+    /// Timestamps of the carriers and stations already sent, to find what changed
+    carrier_timestamps: HashMap<String, DateTime<Utc>>,
+    station_timestamps: HashMap<u64, i64>,
+}
+
+pub fn initialize(settings: Arc<Mutex<Settings>>, path: &str) -> Result<DatabaseBackend, String> {
+    if path.is_empty() {
+        return Err("No local database path configured".to_string());
+    }
+    let connection = open(path)?;
+    info!("Using local database {}", path);
+    Ok(DatabaseBackend {
+        settings,
+        connection,
+        data_version: None,
+        carrier_timestamps: HashMap::new(),
+        station_timestamps: HashMap::new(),
+    })
+}
+
+impl DataBackend for DatabaseBackend {
+    fn get_system_meta_data(&self, system_address: u64) -> Result<Option<SystemMetaData>, String> {
+        self.query_system_meta_data(system_address)
+            .map_err(|err| err.to_string())
+    }
+
+    fn get_bodies(&self, system_address: u64) -> Result<Vec<BodyType>, String> {
+        self.query_bodies(system_address)
+    }
+
+    fn get_stations(&self) -> Result<Vec<StationIdentity>, String> {
+        self.query_stations().map_err(|err| err.to_string())
+    }
+
+    fn get_station_meta_data(&self, market_id: u64) -> Result<Option<StationMetaData>, String> {
+        self.query_station_meta_data(market_id)
+            .map_err(|err| err.to_string())
+    }
+
+    fn get_market(&self, market_id: u64) -> Result<Vec<CommodityListening>, String> {
+        self.query_market(market_id).map_err(|err| err.to_string())
+    }
+
+    fn get_carriers(&self) -> Result<Vec<Carrier>, String> {
+        self.query_carriers().map_err(|err| err.to_string())
+    }
+
+    fn get_system_coordinates(&self, name: &str) -> Result<Option<[f64; 3]>, String> {
+        self.connection
+            .query_row(
+                "SELECT x, y, z FROM systems WHERE name = ?1",
+                params![name],
+                |row| Ok([row.get(0)?, row.get(1)?, row.get(2)?]),
+            )
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    /// The lists are loaded once, afterwards only new or updated carriers and stations are sent
+    fn get_changes(&mut self) -> Result<Vec<DataUpdate>, String> {
+        self.query_changes().map_err(|err| err.to_string())
+    }
+}
+
+impl DatabaseBackend {
+    fn query_changes(&mut self) -> rusqlite::Result<Vec<DataUpdate>> {
+        let data_version: i64 = self
+            .connection
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        let first = self.data_version.is_none();
+        if self.data_version == Some(data_version) {
+            return Ok(vec![]);
+        }
+
+        let mut updates = vec![];
+        let carriers: Vec<Carrier> = self
+            .query_carriers()?
+            .into_iter()
+            .filter(|carrier| {
+                self.carrier_timestamps.get(&carrier.callsign) != Some(&carrier.timestamp)
+            })
+            .collect();
+        for carrier in &carriers {
+            self.carrier_timestamps
+                .insert(carrier.callsign.clone(), carrier.timestamp);
+        }
+        if first {
+            if !carriers.is_empty() {
+                updates.push(DataUpdate::CarrierList(carriers));
+            }
+        } else {
+            updates.extend(carriers.into_iter().map(DataUpdate::Carrier));
+        }
+
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT market_id, timestamp FROM stations")?;
+        let station_timestamps = statement
+            .query_map([], |row| Ok((row.get::<_, u64>(0)?, row.get::<_, i64>(1)?)))?
+            .collect::<rusqlite::Result<Vec<(u64, i64)>>>()?;
+        let mut new_stations = HashSet::new();
+        let mut updated_stations = vec![];
+        for (market_id, timestamp) in station_timestamps {
+            match self.station_timestamps.insert(market_id, timestamp) {
+                None => {
+                    new_stations.insert(market_id);
+                }
+                Some(previous) if previous != timestamp => updated_stations.push(market_id),
+                Some(_) => {}
+            }
+        }
+        if !new_stations.is_empty() {
+            let stations = self
+                .query_stations()?
+                .into_iter()
+                .filter(|station| new_stations.contains(&station.market_id))
+                .collect();
+            updates.push(DataUpdate::StationList(stations));
+        }
+        for market_id in updated_stations {
+            if let Some(meta_data) = self.query_station_meta_data(market_id)? {
+                updates.push(DataUpdate::StationMetaData(market_id, meta_data));
+            }
+        }
+
+        debug!(
+            "Database version {}: {} changes",
+            data_version,
+            updates.len()
+        );
+        self.data_version = Some(data_version);
+        Ok(updates)
+    }
+
+    fn query_stations(&self) -> rusqlite::Result<Vec<StationIdentity>> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT name, market_id, type FROM stations ORDER BY name")?;
+        let stations = statement.query_map([], |row| {
+            Ok(StationIdentity {
+                name: row.get(0)?,
+                market_id: row.get(1)?,
+                type_: row.get(2)?,
+            })
+        })?;
+        stations.collect()
+    }
+
+    fn query_carriers(&self) -> rusqlite::Result<Vec<Carrier>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT timestamp, name, callsign, services, docking_access, allow_notorious, \
+             current_system, current_body, next_system, next_body, departure FROM carriers",
+        )?;
+        let carriers = statement.query_map([], |row| {
+            Ok(Carrier {
+                timestamp: DateTime::from_timestamp(row.get(0)?, 0).unwrap_or_default(),
+                name: row.get(1)?,
+                callsign: row.get(2)?,
+                services: row.get(3)?,
+                docking_access: row.get(4)?,
+                allow_notorious: row.get(5)?,
+                current_system: row.get(6)?,
+                current_body: row.get(7)?,
+                next_system: row.get(8)?,
+                next_body: row.get(9)?,
+                departure: DateTime::from_timestamp(row.get(10)?, 0).unwrap_or_default(),
+            })
+        })?;
+        carriers.collect()
+    }
+
+    fn query_station_meta_data(&self, market_id: u64) -> rusqlite::Result<Option<StationMetaData>> {
+        self.connection
+            .query_row(
+                "SELECT timestamp, services, system_name, faction_name, faction_state, \
+                 government, economy, distance, landing_pads, system_address FROM stations \
+                 WHERE market_id = ?1",
+                params![market_id],
+                |row| {
+                    Ok(StationMetaData {
+                        timestamp: DateTime::from_timestamp(row.get(0)?, 0).unwrap_or_default(),
+                        services: row.get(1)?,
+                        system_name: row.get(2)?,
+                        faction: Faction {
+                            name: row.get(3)?,
+                            state: row.get(4)?,
+                        },
+                        government: row.get(5)?,
+                        economy: row.get(6)?,
+                        distance: floating::generate_floating_from_string(row.get(7)?),
+                        landingpads: row.get(8)?,
+                        system_address: row.get(9)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    fn query_market(&self, market_id: u64) -> rusqlite::Result<Vec<CommodityListening>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT name, buy_price, sell_price, mean_price, demand, demand_bracket, stock, \
+             stock_bracket FROM commodities WHERE market_id = ?1 ORDER BY name",
+        )?;
+        let market = statement.query_map(params![market_id], |row| {
+            Ok(CommodityListening {
+                name: row.get(0)?,
+                buy_price: row.get(1)?,
+                sell_price: row.get(2)?,
+                mean_price: row.get(3)?,
+                demand: row.get(4)?,
+                demand_bracket: row.get(5)?,
+                stock: row.get(6)?,
+                stock_bracket: row.get(7)?,
+            })
+        })?;
+        market.collect()
+    }
+
+    fn query_system_meta_data(&self, address: u64) -> rusqlite::Result<Option<SystemMetaData>> {
+        self.connection
+            .query_row(
+                "SELECT name, body_count, x, y, z FROM systems WHERE address = ?1",
+                params![address],
+                |row| {
+                    Ok(SystemMetaData {
+                        name: row.get(0)?,
+                        body_count: row.get(1)?,
+                        x: row.get(2)?,
+                        y: row.get(3)?,
+                        z: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Bodies are stored as their scan event, so they are built like the ones from the journal
+    fn query_bodies(&self, system_address: u64) -> Result<Vec<BodyType>, String> {
+        let scans = self
+            .connection
+            .prepare_cached("SELECT scan FROM bodies WHERE system_address = ?1 ORDER BY body_id")
+            .and_then(|mut statement| {
+                statement
+                    .query_map(params![system_address], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .map_err(|err| err.to_string())?;
+        scans
+            .iter()
+            .filter_map(|scan| json::parse(scan).ok())
+            .map(|scan| body::try_generate_from_json(scan, self.settings.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+    use std::fs;
+
+    fn get_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("edcas-{}-{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        path.display().to_string()
+    }
+
+    fn insert_station(connection: &Connection, market_id: u64, name: &str, timestamp: i64) {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO stations (market_id, name, type, system_address, \
+                 system_name, faction_name, faction_state, government, economy, services, \
+                 distance, landing_pads, timestamp) \
+                 VALUES (?1, ?2, 'Coriolis', 1, 'Sol', '', '', '', '', '', '0', '{}', ?3)",
+                params![market_id, name, timestamp],
+            )
+            .unwrap();
+    }
+
+    fn insert_carrier(connection: &Connection, callsign: &str, timestamp: i64) {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO carriers (market_id, name, callsign, services, \
+                 docking_access, allow_notorious, current_system, current_body, next_system, \
+                 next_body, departure, timestamp) \
+                 VALUES (1, 'Carrier', ?1, '', 'all', 0, 'Sol', '', '', '', 0, ?2)",
+                params![callsign, timestamp],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn open_uses_wal() {
+        let path = get_path("wal");
+        let connection = open(&path).unwrap();
+        let journal_mode: String = connection
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+    }
+
+    #[test]
+    fn finds_system_coordinates_by_name() {
+        let path = get_path("coordinates");
+        let writer = open(&path).unwrap();
+        writer
+            .execute(
+                "INSERT INTO systems (address, name, x, y, z, allegiance, economy, \
+                 second_economy, government, security, population, timestamp) \
+                 VALUES (10477373803, 'Sol', 0, 0, 0, '', '', '', '', '', 0, 0)",
+                [],
+            )
+            .unwrap();
+        let backend = initialize(get_test_settings(), &path).unwrap();
+        assert_eq!(
+            backend.get_system_coordinates("Sol").unwrap(),
+            Some([0.0, 0.0, 0.0])
+        );
+        assert_eq!(backend.get_system_coordinates("Colonia").unwrap(), None);
+    }
+
+    #[test]
+    fn changes_are_only_sent_once() {
+        let path = get_path("changes");
+        let writer = open(&path).unwrap();
+        insert_station(&writer, 1, "Abraham Lincoln", 100);
+        insert_carrier(&writer, "Q2K-BHB", 100);
+        let mut backend = initialize(get_test_settings(), &path).unwrap();
+
+        let updates = backend.get_changes().unwrap();
+        assert_eq!(updates.len(), 2);
+        assert!(matches!(&updates[0], DataUpdate::CarrierList(carriers) if carriers.len() == 1));
+        assert!(matches!(&updates[1], DataUpdate::StationList(stations) if stations.len() == 1));
+        assert!(backend.get_changes().unwrap().is_empty());
+
+        //One new station, one updated station and one carrier which moved
+        insert_station(&writer, 2, "Li Qing Jao", 200);
+        insert_station(&writer, 1, "Abraham Lincoln", 200);
+        insert_carrier(&writer, "Q2K-BHB", 200);
+        let updates = backend.get_changes().unwrap();
+        assert_eq!(updates.len(), 3);
+        assert!(
+            matches!(&updates[0], DataUpdate::Carrier(carrier) if carrier.callsign == "Q2K-BHB")
+        );
+        assert!(matches!(&updates[1], DataUpdate::StationList(stations)
+            if stations.len() == 1 && stations[0].market_id == 2));
+        assert!(matches!(&updates[2], DataUpdate::StationMetaData(1, _)));
+        assert!(backend.get_changes().unwrap().is_empty());
+    }
+}
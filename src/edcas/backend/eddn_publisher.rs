@@ -0,0 +1,736 @@
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+use bus::BusReader;
+use chrono::{DateTime, Utc};
+use json::{object, JsonValue};
+use log::{debug, error, info, warn};
+use tokio::runtime::Runtime;
+
+use crate::edcas::settings::Settings;
+
+pub const DEFAULT_UPLOAD_URL: &str = "https://eddn.edcd.io:4430/upload/";
+const SOFTWARE_NAME: &str = "EDCAS";
+/// Events older than this are replayed from the journal and must not be published again
+const MAX_EVENT_AGE_SECONDS: i64 = 300;
+/// Ticks of the loop a companion file may lag behind its event
+const MAX_COMPANION_FILE_ATTEMPTS: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EddnSchema {
+    Journal,
+    Commodity,
+    Outfitting,
+    Shipyard,
+    FssDiscoveryScan,
+    FssSignalDiscovered,
+    FssAllBodiesFound,
+    ScanBaryCentre,
+    NavRoute,
+}
+
+impl EddnSchema {
+    pub const ALL: [EddnSchema; 9] = [
+        EddnSchema::Journal,
+        EddnSchema::Commodity,
+        EddnSchema::Outfitting,
+        EddnSchema::Shipyard,
+        EddnSchema::FssDiscoveryScan,
+        EddnSchema::FssSignalDiscovered,
+        EddnSchema::FssAllBodiesFound,
+        EddnSchema::ScanBaryCentre,
+        EddnSchema::NavRoute,
+    ];
+
+    pub fn get_schema_ref(&self) -> String {
+        let version = match self {
+            EddnSchema::Journal => 1,
+            EddnSchema::Commodity => 3,
+            EddnSchema::Outfitting => 2,
+            EddnSchema::Shipyard => 2,
+            EddnSchema::FssDiscoveryScan => 1,
+            EddnSchema::FssSignalDiscovered => 1,
+            EddnSchema::FssAllBodiesFound => 1,
+            EddnSchema::ScanBaryCentre => 1,
+            EddnSchema::NavRoute => 1,
+        };
+        format!("https://eddn.edcd.io/schemas/{}/{}", self, version)
+    }
+}
+
+impl Display for EddnSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EddnSchema::Journal => "journal",
+            EddnSchema::Commodity => "commodity",
+            EddnSchema::Outfitting => "outfitting",
+            EddnSchema::Shipyard => "shipyard",
+            EddnSchema::FssDiscoveryScan => "fssdiscoveryscan",
+            EddnSchema::FssSignalDiscovered => "fsssignaldiscovered",
+            EddnSchema::FssAllBodiesFound => "fssallbodiesfound",
+            EddnSchema::ScanBaryCentre => "scanbarycentre",
+            EddnSchema::NavRoute => "navroute",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for EddnSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EddnSchema::ALL
+            .into_iter()
+            .find(|schema| schema.to_string() == s)
+            .ok_or(format!("Unknown EDDN schema: {}", s))
+    }
+}
+
+/// Location and game state the journal events get augmented with
+struct GameState {
+    uploader_id: String,
+    game_version: String,
+    game_build: String,
+    horizons: Option<bool>,
+    odyssey: Option<bool>,
+    system_name: String,
+    system_address: u64,
+    star_pos: JsonValue,
+}
+
+pub struct EddnPublisher {
+    bus: BusReader<JsonValue>,
+    settings: Arc<Mutex<Settings>>,
+    runtime: Runtime,
+    state: GameState,
+    /// Signals are published as one message per system
+    pending_signals: Vec<JsonValue>,
+    pending_files: Vec<PendingFile>,
+}
+
+/// Event whose companion file was not written yet
+struct PendingFile {
+    event: JsonValue,
+    attempts: u32,
+}
+
+pub fn initialize(bus: BusReader<JsonValue>, settings: Arc<Mutex<Settings>>) -> EddnPublisher {
+    EddnPublisher {
+        bus,
+        settings,
+        runtime: tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+        state: GameState {
+            uploader_id: "".to_string(),
+            game_version: "".to_string(),
+            game_build: "".to_string(),
+            horizons: None,
+            odyssey: None,
+            system_name: "".to_string(),
+            system_address: 0,
+            star_pos: JsonValue::Null,
+        },
+        pending_signals: vec![],
+        pending_files: vec![],
+    }
+}
+
+impl EddnPublisher {
+    pub fn run_loop(&mut self) {
+        loop {
+            while let Ok(json) = self.bus.try_recv() {
+                self.handle_event(json);
+            }
+            if !self.pending_files.is_empty() {
+                self.read_pending_files();
+            }
+            sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn handle_event(&mut self, json: JsonValue) {
+        let event = json["event"].as_str().unwrap_or("").to_string();
+        if event != "FSSSignalDiscovered" && !self.pending_signals.is_empty() {
+            self.publish_signals();
+        }
+        self.update_state(&json);
+
+        if !is_recent(&json) {
+            return;
+        }
+
+        match event.as_str() {
+            "Docked" | "FSDJump" | "Scan" | "Location" | "SAASignalsFound" | "CarrierJump" => {
+                if let Some(message) = self.create_journal_message(json) {
+                    self.publish(EddnSchema::Journal, message);
+                }
+            }
+            "FSSDiscoveryScan" => {
+                let mut message = object! {
+                    timestamp: json["timestamp"].clone(),
+                    event: "FSSDiscoveryScan",
+                    BodyCount: json["BodyCount"].clone(),
+                    NonBodyCount: json["NonBodyCount"].clone(),
+                    SystemName: json["SystemName"].clone(),
+                    SystemAddress: json["SystemAddress"].clone(),
+                };
+                if self.augment(&mut message, json["SystemAddress"].as_u64()) {
+                    self.publish(EddnSchema::FssDiscoveryScan, message);
+                }
+            }
+            "FSSSignalDiscovered" if self.is_shared(EddnSchema::FssSignalDiscovered) => {
+                self.pending_signals.push(json);
+            }
+            "FSSAllBodiesFound" => {
+                let mut message = json.clone();
+                strip_localised(&mut message);
+                if self.augment(&mut message, json["SystemAddress"].as_u64()) {
+                    self.publish(EddnSchema::FssAllBodiesFound, message);
+                }
+            }
+            "ScanBaryCentre" => {
+                let mut message = json.clone();
+                strip_localised(&mut message);
+                if self.augment(&mut message, json["SystemAddress"].as_u64()) {
+                    self.publish(EddnSchema::ScanBaryCentre, message);
+                }
+            }
+            "NavRoute" | "Market" | "Outfitting" | "Shipyard" => {
+                self.pending_files.push(PendingFile {
+                    event: json,
+                    attempts: 0,
+                });
+                self.read_pending_files();
+            }
+            _ => {}
+        }
+    }
+
+    fn update_state(&mut self, json: &JsonValue) {
+        match json["event"].as_str().unwrap_or("") {
+            "Fileheader" => {
+                self.state.game_version = json["gameversion"].to_string();
+                self.state.game_build = json["build"].to_string();
+                self.state.odyssey = json["Odyssey"].as_bool();
+            }
+            "LoadGame" => {
+                self.state.uploader_id = json["Commander"].to_string();
+                if !json["gameversion"].is_null() {
+                    self.state.game_version = json["gameversion"].to_string();
+                    self.state.game_build = json["build"].to_string();
+                }
+                self.state.horizons = json["Horizons"].as_bool();
+                self.state.odyssey = json["Odyssey"].as_bool();
+            }
+            "Commander" => {
+                self.state.uploader_id = json["Name"].to_string();
+            }
+            "FSDJump" | "Location" | "CarrierJump" => {
+                self.state.system_name = json["StarSystem"].to_string();
+                self.state.system_address = json["SystemAddress"].as_u64().unwrap_or(0);
+                self.state.star_pos = json["StarPos"].clone();
+            }
+            _ => {}
+        }
+    }
+
+    fn is_shared(&self, schema: EddnSchema) -> bool {
+        self.settings
+            .lock()
+            .unwrap()
+            .eddn_settings
+            .shared_schemas
+            .contains(&schema)
+    }
+
+    /// Removes personal data and adds the location the event lacks
+    fn create_journal_message(&self, mut json: JsonValue) -> Option<JsonValue> {
+        strip_localised(&mut json);
+        for key in [
+            "ActiveFine",
+            "CockpitBreach",
+            "BoostUsed",
+            "FuelLevel",
+            "FuelUsed",
+            "JumpDist",
+            "Latitude",
+            "Longitude",
+            "Wanted",
+            "Taxi",
+            "Multicrew",
+        ] {
+            json.remove(key);
+        }
+        for faction in json["Factions"].members_mut() {
+            for key in [
+                "HappiestSystem",
+                "HomeSystem",
+                "MyReputation",
+                "SquadronFaction",
+            ] {
+                faction.remove(key);
+            }
+        }
+        let system_address = json["SystemAddress"].as_u64();
+        if json["StarSystem"].is_null() {
+            json["StarSystem"] = self.state.system_name.clone().into();
+        }
+        if self.augment(&mut json, system_address) {
+            Some(json)
+        } else {
+            None
+        }
+    }
+
+    fn create_commodity_message(&self, market: JsonValue) -> Option<JsonValue> {
+        let commodities: Vec<JsonValue> = market["Items"]
+            .members()
+            .filter(|item| {
+                item["Category"].as_str() != Some("$MARKET_category_nonmarketable;")
+                    && item["Legality"].as_str().unwrap_or("").is_empty()
+            })
+            .map(|item| {
+                object! {
+                    name: item["Name"]
+                        .to_string()
+                        .trim_start_matches('$')
+                        .trim_end_matches("_name;")
+                        .to_lowercase(),
+                    meanPrice: item["MeanPrice"].clone(),
+                    buyPrice: item["BuyPrice"].clone(),
+                    stock: item["Stock"].clone(),
+                    stockBracket: item["StockBracket"].clone(),
+                    sellPrice: item["SellPrice"].clone(),
+                    demand: item["Demand"].clone(),
+                    demandBracket: item["DemandBracket"].clone(),
+                }
+            })
+            .collect();
+        if commodities.is_empty() {
+            return None;
+        }
+        let mut message = object! {
+            systemName: market["StarSystem"].clone(),
+            stationName: market["StationName"].clone(),
+            marketId: market["MarketID"].clone(),
+            timestamp: market["timestamp"].clone(),
+            commodities: commodities,
+        };
+        if !market["StationType"].is_null() {
+            message["stationType"] = market["StationType"].clone();
+        }
+        self.add_game_flags(&mut message);
+        Some(message)
+    }
+
+    fn publish_signals(&mut self) {
+        let signals: Vec<JsonValue> = self
+            .pending_signals
+            .drain(..)
+            //Mission targets are personal
+            .filter(|signal| signal["USSType"].as_str() != Some("$USS_Type_MissionTarget;"))
+            .map(|mut signal| {
+                strip_localised(&mut signal);
+                for key in ["event", "SystemAddress", "TimeRemaining"] {
+                    signal.remove(key);
+                }
+                signal
+            })
+            .collect();
+        if signals.is_empty() {
+            return;
+        }
+        let system_address = self.state.system_address;
+        let mut message = object! {
+            event: "FSSSignalDiscovered",
+            timestamp: signals[0]["timestamp"].clone(),
+            SystemAddress: system_address,
+            signals: signals,
+        };
+        if self.augment(&mut message, Some(system_address)) {
+            self.publish(EddnSchema::FssSignalDiscovered, message);
+        }
+    }
+
+    /// Adds StarSystem, StarPos and SystemAddress. Fails if the event belongs to another system
+    /// than the one the commander is known to be in.
+    fn augment(&self, message: &mut JsonValue, system_address: Option<u64>) -> bool {
+        if self.state.star_pos.is_null() {
+            debug!("Location unknown, not augmenting {}", message["event"]);
+            return false;
+        }
+        if let Some(system_address) = system_address {
+            if system_address != self.state.system_address {
+                debug!(
+                    "Event of system {} while in {}",
+                    system_address, self.state.system_address
+                );
+                return false;
+            }
+        }
+        if message["StarSystem"].is_null() && message["SystemName"].is_null() {
+            message["StarSystem"] = self.state.system_name.clone().into();
+        }
+        message["SystemAddress"] = self.state.system_address.into();
+        message["StarPos"] = self.state.star_pos.clone();
+        self.add_game_flags(message);
+        true
+    }
+
+    fn add_game_flags(&self, message: &mut JsonValue) {
+        if let Some(horizons) = self.state.horizons {
+            message["horizons"] = horizons.into();
+        }
+        if let Some(odyssey) = self.state.odyssey {
+            message["odyssey"] = odyssey.into();
+        }
+    }
+
+    /// Market, outfitting, shipyard and route data is written to separate files next to the journal.
+    /// `None` if the file doesn't belong to the event yet.
+    fn read_companion_file(&self, event: &JsonValue) -> Result<Option<JsonValue>, String> {
+        let directory = self
+            .settings
+            .lock()
+            .unwrap()
+            .journal_reader_settings
+            .journal_directory
+            .clone();
+        let path = format!("{}/{}.json", directory, event["event"]);
+        let content =
+            fs::read_to_string(&path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+        match json::parse(&content) {
+            Ok(json) if json["timestamp"] == event["timestamp"] => Ok(Some(json)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The game may still be writing the file when the event arrives, so it is read again on the
+    /// next ticks of the loop instead of waiting for it
+    fn read_pending_files(&mut self) {
+        for mut pending in std::mem::take(&mut self.pending_files) {
+            match self.read_companion_file(&pending.event) {
+                Ok(Some(companion)) => self.publish_companion_file(&pending.event, companion),
+                Ok(None) if pending.attempts < MAX_COMPANION_FILE_ATTEMPTS => {
+                    pending.attempts += 1;
+                    self.pending_files.push(pending);
+                }
+                Ok(None) => warn!(
+                    "{}.json does not match event from {}",
+                    pending.event["event"], pending.event["timestamp"]
+                ),
+                Err(err) => warn!("{}", err),
+            }
+        }
+    }
+
+    fn publish_companion_file(&self, event: &JsonValue, companion: JsonValue) {
+        match event["event"].as_str().unwrap_or("") {
+            //An empty route is written when the route is cleared
+            "NavRoute" if !companion["Route"].is_empty() => {
+                let mut message = object! {
+                    timestamp: companion["timestamp"].clone(),
+                    event: "NavRoute",
+                    Route: companion["Route"].clone(),
+                };
+                self.add_game_flags(&mut message);
+                self.publish(EddnSchema::NavRoute, message);
+            }
+            "Market" => {
+                if let Some(message) = self.create_commodity_message(companion) {
+                    self.publish(EddnSchema::Commodity, message);
+                }
+            }
+            "Outfitting" => {
+                let mut message = object! {
+                    systemName: companion["StarSystem"].clone(),
+                    stationName: companion["StationName"].clone(),
+                    marketId: companion["MarketID"].clone(),
+                    timestamp: companion["timestamp"].clone(),
+                    modules: companion["Items"]
+                        .members()
+                        .map(|item| item["Name"].to_string())
+                        .filter(|name| is_published_module(name))
+                        .collect::<Vec<String>>(),
+                };
+                self.add_game_flags(&mut message);
+                self.publish(EddnSchema::Outfitting, message);
+            }
+            "Shipyard" => {
+                let mut message = object! {
+                    systemName: companion["StarSystem"].clone(),
+                    stationName: companion["StationName"].clone(),
+                    marketId: companion["MarketID"].clone(),
+                    timestamp: companion["timestamp"].clone(),
+                    ships: companion["PriceList"]
+                        .members()
+                        .map(|ship| ship["ShipType"].to_string())
+                        .collect::<Vec<String>>(),
+                    allowCobraMkIV: companion["AllowCobraMkIV"].as_bool().unwrap_or(false),
+                };
+                self.add_game_flags(&mut message);
+                self.publish(EddnSchema::Shipyard, message);
+            }
+            _ => {}
+        }
+    }
+
+    fn publish(&self, schema: EddnSchema, message: JsonValue) {
+        if !self.is_shared(schema) {
+            return;
+        }
+        if self.state.uploader_id.is_empty() {
+            warn!("Commander unknown, not publishing to EDDN");
+            return;
+        }
+        let upload_url = self
+            .settings
+            .lock()
+            .unwrap()
+            .eddn_settings
+            .upload_url
+            .clone();
+        let envelope = object! {
+            "$schemaRef": schema.get_schema_ref(),
+            header: {
+                uploaderID: self.state.uploader_id.clone(),
+                softwareName: SOFTWARE_NAME,
+                softwareVersion: env!("CARGO_PKG_VERSION"),
+                gameversion: self.state.game_version.clone(),
+                gamebuild: self.state.game_build.clone(),
+            },
+            message: message,
+        };
+
+        self.runtime.block_on(async {
+            let result = reqwest::Client::new()
+                .post(&upload_url)
+                .header("Content-Type", "application/json")
+                .body(envelope.dump())
+                .send()
+                .await;
+            match result {
+                Ok(response) => {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    if status.is_success() {
+                        info!("Published {} to EDDN", schema);
+                    } else {
+                        error!("EDDN rejected {}: {} {}", schema, status, text);
+                    }
+                }
+                Err(err) => {
+                    error!("Couldn't publish {} to EDDN: {}", schema, err);
+                }
+            }
+        });
+    }
+}
+
+fn is_recent(json: &JsonValue) -> bool {
+    match DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap_or("")) {
+        Ok(timestamp) => {
+            (Utc::now() - timestamp.with_timezone(&Utc)).num_seconds() < MAX_EVENT_AGE_SECONDS
+        }
+        Err(_) => false,
+    }
+}
+
+/// EDDN does not accept localised strings
+fn strip_localised(json: &mut JsonValue) {
+    if json.is_object() {
+        let keys: Vec<String> = json
+            .entries()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| key.ends_with("_Localised"))
+            .collect();
+        for key in keys {
+            json.remove(&key);
+        }
+        for (_, value) in json.entries_mut() {
+            strip_localised(value);
+        }
+    } else if json.is_array() {
+        for value in json.members_mut() {
+            strip_localised(value);
+        }
+    }
+}
+
+/// Only weapons, utilities, internals and armour, no cosmetics or suits
+fn is_published_module(name: &str) -> bool {
+    let name = name.to_lowercase();
+    (name.starts_with("hpt_") || name.starts_with("int_") || name.contains("_armour_"))
+        && name != "int_planetapproachsuite"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bus::Bus;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Accepts the given number of uploads and returns their bodies
+    fn serve(uploads: usize) -> (String, thread::JoinHandle<Vec<JsonValue>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/upload/", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = vec![];
+            for _ in 0..uploads {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(json::parse(&String::from_utf8(body).unwrap()).unwrap());
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nOK",
+                    )
+                    .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn get_publisher(upload_url: String) -> EddnPublisher {
+        let mut settings = Settings::from_example();
+        settings.eddn_settings.upload_url = upload_url;
+        settings.eddn_settings.shared_schemas = vec![EddnSchema::Journal];
+        initialize(Bus::new(1).add_rx(), Arc::new(Mutex::new(settings)))
+    }
+
+    fn get_jump(timestamp: DateTime<Utc>, system_name: &str) -> JsonValue {
+        object! {
+            timestamp: timestamp.to_rfc3339(),
+            event: "FSDJump",
+            StarSystem: system_name,
+            SystemAddress: 10477373803u64,
+            StarPos: [0.0, 0.0, 0.0],
+            SystemAllegiance_Localised: "Federation",
+            FuelUsed: 2.5,
+            JumpDist: 8.2,
+        }
+    }
+
+    #[test]
+    fn publishes_recent_journal_events() {
+        let (url, server) = serve(1);
+        let mut publisher = get_publisher(url);
+        publisher.handle_event(object! {
+            timestamp: Utc::now().to_rfc3339(),
+            event: "LoadGame",
+            Commander: "Jameson",
+            gameversion: "4.0.0.1800",
+            build: "r300000/r0 ",
+            Horizons: true,
+            Odyssey: true,
+        });
+        //Replayed events are not published again
+        publisher.handle_event(get_jump(
+            Utc::now() - chrono::Duration::hours(1),
+            "Alpha Centauri",
+        ));
+        publisher.handle_event(get_jump(Utc::now(), "Sol"));
+
+        let uploads = server.join().unwrap();
+        assert_eq!(uploads.len(), 1);
+        let upload = &uploads[0];
+        assert_eq!(
+            upload["$schemaRef"],
+            "https://eddn.edcd.io/schemas/journal/1"
+        );
+        assert_eq!(upload["header"]["uploaderID"], "Jameson");
+        assert_eq!(upload["header"]["softwareName"], SOFTWARE_NAME);
+        assert_eq!(upload["header"]["gameversion"], "4.0.0.1800");
+        let message = &upload["message"];
+        assert_eq!(message["StarSystem"], "Sol");
+        assert_eq!(message["SystemAddress"], 10477373803u64);
+        assert_eq!(message["odyssey"], true);
+        assert!(message["SystemAllegiance_Localised"].is_null());
+        assert!(message["FuelUsed"].is_null());
+        assert!(message["JumpDist"].is_null());
+    }
+
+    #[test]
+    fn unknown_commanders_are_not_published() {
+        let (url, server) = serve(1);
+        let mut publisher = get_publisher(url.clone());
+        publisher.handle_event(get_jump(Utc::now(), "Sol"));
+
+        //The stand-in only answers the upload of the second publisher
+        let mut publisher = get_publisher(url);
+        publisher.handle_event(object! {
+            timestamp: Utc::now().to_rfc3339(),
+            event: "Commander",
+            Name: "Jameson",
+        });
+        publisher.handle_event(get_jump(Utc::now(), "Sol"));
+        let uploads = server.join().unwrap();
+        assert_eq!(uploads[0]["header"]["uploaderID"], "Jameson");
+    }
+
+    #[test]
+    fn companion_files_written_late_are_published() {
+        let directory = crate::edcas::backend::evm::test_utils::get_temp_path("companion-files");
+        fs::create_dir_all(&directory).unwrap();
+        let (url, server) = serve(1);
+        let mut publisher = get_publisher(url);
+        {
+            let mut settings = publisher.settings.lock().unwrap();
+            settings.journal_reader_settings.journal_directory = directory.clone();
+            settings.eddn_settings.shared_schemas = vec![EddnSchema::NavRoute];
+        }
+        publisher.handle_event(object! {
+            timestamp: Utc::now().to_rfc3339(),
+            event: "Commander",
+            Name: "Jameson",
+        });
+        let timestamp = Utc::now().to_rfc3339();
+        let route = object! {
+            timestamp: timestamp.clone(),
+            event: "NavRoute",
+            Route: [{ StarSystem: "Sol", SystemAddress: 10477373803u64, StarPos: [0.0, 0.0, 0.0], StarClass: "G" }],
+        };
+        //The previous route is still in the file when the event arrives
+        let mut stale_route = route.clone();
+        stale_route["timestamp"] = (Utc::now() - chrono::Duration::hours(1))
+            .to_rfc3339()
+            .into();
+        fs::write(format!("{}/NavRoute.json", directory), stale_route.dump()).unwrap();
+        publisher.handle_event(object! {
+            timestamp: timestamp.clone(),
+            event: "NavRoute",
+        });
+        assert_eq!(publisher.pending_files.len(), 1);
+
+        fs::write(format!("{}/NavRoute.json", directory), route.dump()).unwrap();
+        publisher.read_pending_files();
+        assert!(publisher.pending_files.is_empty());
+        let uploads = server.join().unwrap();
+        assert_eq!(uploads[0]["message"]["timestamp"], timestamp);
+        assert_eq!(uploads[0]["message"]["Route"][0]["StarSystem"], "Sol");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,4 +1,12 @@
+pub mod audit_log;
 pub mod edcas_contract;
 pub mod journal_interpreter;
 pub mod journal_uploader;
+pub mod keystore;
+pub mod nonce_manager;
+pub mod privacy;
 pub mod request_handler;
+pub mod state_check;
+#[cfg(test)]
+pub mod test_utils;
+pub mod transaction_store;
@@ -0,0 +1,86 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use chrono::Utc;
+use ethers::prelude::{TxHash, U256};
+use log::error;
+use serde_json::json;
+
+use crate::edcas::settings::get_data_path;
+
+pub fn get_path() -> String {
+    get_data_path("audit.log")
+}
+
+/// Local record of every transaction sent to the EDCAS network, one json line per transaction
+pub struct AuditLog {
+    path: String,
+    file: Option<File>,
+}
+
+impl AuditLog {
+    pub fn open() -> AuditLog {
+        AuditLog::from_file(get_path())
+    }
+
+    pub fn from_file(path: String) -> AuditLog {
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                error!("Couldn't open audit log {}: {}", path, err);
+                None
+            }
+        };
+        AuditLog { path, file }
+    }
+
+    /// Called once the transaction is mined, so the hash is the one which made it on chain.
+    /// Of a stuck transaction found mined later it is unknown which replacement made it.
+    pub fn record(&mut self, nonce: U256, hash: Option<TxHash>, call: &str) {
+        let line = json!({
+            "time": Utc::now().to_rfc3339(),
+            "nonce": nonce.to_string(),
+            "hash": hash.map(|hash| format!("{:?}", hash)),
+            "call": call,
+        });
+        if let Some(file) = &mut self.file {
+            if let Err(err) = writeln!(file, "{}", line) {
+                error!("Couldn't write audit log {}: {}", self.path, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::get_temp_path;
+
+    #[test]
+    fn transactions_are_appended_as_json_lines() {
+        let path = get_temp_path("audit-log");
+        let mut audit_log = AuditLog::from_file(path.clone());
+        audit_log.record(
+            7.into(),
+            Some(TxHash::repeat_byte(0xab)),
+            "set_body_count(1, c, 3)",
+        );
+        //Reopening appends instead of truncating
+        let mut audit_log = AuditLog::from_file(path.clone());
+        audit_log.record(8.into(), None, "set_body_count(1, c, 4)");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<json::JsonValue> = content
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["nonce"], "7");
+        assert_eq!(lines[0]["hash"], format!("{:?}", TxHash::repeat_byte(0xab)));
+        assert_eq!(lines[0]["call"], "set_body_count(1, c, 3)");
+        assert!(lines[0]["time"].is_string());
+        assert_eq!(lines[1]["nonce"], "8");
+        assert!(lines[1]["hash"].is_null());
+        let _ = std::fs::remove_file(path);
+    }
+}
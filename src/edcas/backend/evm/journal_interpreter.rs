@@ -1,23 +1,29 @@
-use std::collections::VecDeque;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
 use std::{env, thread};
-use std::str::FromStr;
 use bus::BusReader;
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
+use ethers::abi::Token;
 use ethers::core::k256::ecdsa::SigningKey;
+use ethers::core::rand::thread_rng;
 use ethers::middleware::signer::SignerMiddlewareError;
 use ethers::prelude::*;
 use ethers::utils::hex;
 use json::JsonValue;
 use log::{debug, error, info};
 use tokio::runtime::Runtime;
-use regex::Regex;
+use crate::edcas::backend::evm::audit_log::AuditLog;
 use crate::edcas::backend::evm::edcas_contract;
 use crate::edcas::backend::evm::edcas_contract::{
     BodyProperties, PlanetProperties, StarProperties,
 };
-use crate::edcas::backend::evm::journal_interpreter::SendError::{NonceRecalculationRequired, NonRepeatableError, RepeatableError};
+use crate::edcas::backend::evm::journal_interpreter::SendError::{NonceRecalculationRequired, NonRepeatableError, RepeatableError, Stuck};
+use crate::edcas::backend::evm::nonce_manager::NonceManager;
+use crate::edcas::backend::evm::privacy::PrivacyFilter;
+use crate::edcas::backend::evm::state_check::{Record, StateCheck};
+use crate::edcas::backend::evm::transaction_store::{StoredCall, TransactionQueueStatus, TransactionStore};
 use crate::edcas::backend::floating;
 
 use crate::edcas::settings::EvmSettings;
@@ -29,15 +35,44 @@ pub type ContractCall = FunctionCall<
     (),
 >;
 
+type Worker = thread::JoinHandle<Result<TransactionReceipt, SendError>>;
+/// Running worker with the store id, nonce and description of its transaction
+type Job = (u64, U256, String, Worker);
+
 const NUMBER_OF_WORKERS: usize = 1000;
+/// Seconds to wait for a receipt before a transaction counts as stuck
+const REPLACEMENT_TIMEOUT: u64 = 120;
+const MAX_REPLACEMENTS: u32 = 5;
+/// Attempts of a worker before a call with a temporary error goes back to the end of the queue
+const MAX_RETRIES: u32 = 5;
 
 pub struct EvmInterpreter {
     bus: BusReader<JsonValue>,
     contract: Edcas,
-    nonce: U256,
-    /// Queue for function calls and its nonce. The nonce needs to be changed if a previous transaction failed
-    queue: VecDeque<ContractCall>,
-    pool: Vec<thread::JoinHandle<Result<TransactionReceipt, SendError>>>,
+    nonces: NonceManager,
+    /// Queue for function calls and their id in the store. The nonce needs to be changed if a previous transaction failed
+    queue: VecDeque<(u64, ContractCall)>,
+    pool: Vec<Job>,
+    /// Keeps the queue on disk until the receipts are confirmed
+    store: TransactionStore,
+    /// Skips systems, bodies and stations the contract already has
+    state_check: StateCheck,
+    /// Records written by the queued calls by store id, confirmed in the state check once mined
+    records: HashMap<u64, (Record, U256)>,
+    status: Arc<Mutex<TransactionQueueStatus>>,
+    /// Prints the calls instead of sending them
+    dry_run: bool,
+    privacy: PrivacyFilter,
+    /// Calls held back by the publish delay with the unix time they are released at and their id in the store
+    delayed: VecDeque<(i64, u64, ContractCall)>,
+    /// Release time of the event which is currently interpreted
+    release_time: i64,
+    audit_log: AuditLog,
+    /// Set once all writers of the bus are gone, the loop ends after the remaining transactions
+    bus_closed: bool,
+    /// Calls whose transaction is still in the mempool after all replacements, with their id in the store and nonce
+    stuck: Vec<(u64, U256, ContractCall)>,
+    resynced_at: Instant,
 }
 
 impl EvmInterpreter {
@@ -45,9 +80,40 @@ impl EvmInterpreter {
         //This loop should never block so thread communication is never blocked
         loop {
             //Emptying the thread communication bus
-            while let Ok(json) = self.bus.try_recv() {
+            loop {
+                let json = match self.bus.try_recv() {
+                    Ok(json) => json,
+                    Err(TryRecvError::Disconnected) => {
+                        self.bus_closed = true;
+                        break;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                };
+                let Some(category) = self.privacy.get_category(&json) else {
+                    continue;
+                };
+                if !self.privacy.is_shared(category) {
+                    debug!("Not sharing {} ({})", json["event"], category);
+                    continue;
+                }
+                self.release_time = self.privacy.get_release_time(&json);
+                //Every event is stored with the time it happened, so it is parsed once
+                let timestamp: U256 = match json["timestamp"]
+                    .as_str()
+                    .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+                {
+                    Some(timestamp) => timestamp.timestamp().into(),
+                    None => {
+                        error!("Skipping {} without valid timestamp: {}", json["event"], json["timestamp"]);
+                        continue;
+                    }
+                };
                 match json["event"].as_str().unwrap_or("") {
                     "FSDJump" => {
+                        let record = Record::System(json["SystemAddress"].as_u64().unwrap());
+                        if !self.state_check.is_newer(record, timestamp) {
+                            continue;
+                        }
                         debug!("Add register_system to queue");
                         let function_call: ContractCall = self.contract.register_system(
                             json["SystemAddress"].as_u64().unwrap(),
@@ -61,12 +127,9 @@ impl EvmInterpreter {
                             floating::generate_floating_from_string(json["StarPos"][0].to_string()),
                             floating::generate_floating_from_string(json["StarPos"][1].to_string()),
                             floating::generate_floating_from_string(json["StarPos"][2].to_string()),
-                            DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap())
-                                .unwrap()
-                                .timestamp()
-                                .into(),
+                            timestamp,
                         );
-                        self.queue.push_back(function_call);
+                        self.enqueue_record(function_call, record, timestamp);
                     },
                     "FSSDiscoveryScan" => {
                         debug!("Add set_body_count to queue");
@@ -75,12 +138,9 @@ impl EvmInterpreter {
                         let function_call: ContractCall = self.contract.set_body_count(
                             system_address,
                             body_count,
-                            DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap())
-                                .unwrap()
-                                .timestamp()
-                                .into(),
+                            timestamp,
                         );
-                        self.queue.push_back(function_call);
+                        self.enqueue(function_call);
                     },
                     "Scan" => {
                         if !json["BodyName"].to_string().contains("Belt Cluster")
@@ -100,6 +160,10 @@ impl EvmInterpreter {
                                 // "MeanAnomaly":339.074691, "RotationPeriod":37417.276422, "AxialTilt":0.018931, "WasDiscovered":true, "WasMapped":true }
                                 let body_id = json["BodyID"].as_u8().unwrap();
                                 let system_address = json["SystemAddress"].as_u64().unwrap();
+                                let record = Record::Planet(system_address, body_id);
+                                if !self.state_check.is_newer(record, timestamp) {
+                                    continue;
+                                }
                                 let function_call: ContractCall = self.contract.register_planet(
                                     system_address,
                                     body_id,
@@ -108,15 +172,10 @@ impl EvmInterpreter {
                                     json["WasMapped"].as_bool().unwrap(),
                                     extract_planet_properties(&json),
                                     extract_body_properties(&json),
-                                    DateTime::parse_from_rfc3339(
-                                        json["timestamp"].as_str().unwrap(),
-                                    )
-                                        .unwrap()
-                                        .timestamp()
-                                        .into(),
+                                    timestamp,
                                 );
                                 debug!("Add register_planet to queue");
-                                self.queue.push_back(function_call);
+                                self.enqueue_record(function_call, record, timestamp);
                             } else {
                                 //Star
                                 //{"AbsoluteMagnitude":8.518448,"Age_MY":446,"AxialTilt":0,"BodyID":0,"BodyName":"Hyades Sector BB-N b7-5",
@@ -126,6 +185,10 @@ impl EvmInterpreter {
                                 // "odyssey":true,"timestamp":"2024-03-26T21:27:53Z"}
                                 let body_id = json["BodyID"].as_u8().unwrap();
                                 let system_address = json["SystemAddress"].as_u64().unwrap();
+                                let record = Record::Star(system_address, body_id);
+                                if !self.state_check.is_newer(record, timestamp) {
+                                    continue;
+                                }
                                 let function_call: ContractCall = self.contract.register_star(
                                     system_address,
                                     body_id,
@@ -134,15 +197,10 @@ impl EvmInterpreter {
                                     json["WasMapped"].as_bool().unwrap(),
                                     extract_star_properties(&json),
                                     extract_body_properties(&json),
-                                    DateTime::parse_from_rfc3339(
-                                        json["timestamp"].as_str().unwrap(),
-                                    )
-                                        .unwrap()
-                                        .timestamp()
-                                        .into(),
+                                    timestamp,
                                 );
                                 debug!("Add register_star to queue");
-                                self.queue.push_back(function_call);
+                                self.enqueue_record(function_call, record, timestamp);
                             }
                         }
                     },
@@ -167,15 +225,10 @@ impl EvmInterpreter {
                                 body_id,
                                 type_,
                                 json["Signals"][i]["Count"].as_u8().unwrap(),
-                                DateTime::parse_from_rfc3339(
-                                    json["timestamp"].as_str().unwrap(),
-                                )
-                                    .unwrap()
-                                    .timestamp()
-                                    .into(),
+                                timestamp,
                             );
                             debug!("Add register_planet_signal to queue");
-                            self.queue.push_back(function_call);
+                            self.enqueue(function_call);
                         }
                     },
                     "CarrierJumpRequest" => {
@@ -191,12 +244,12 @@ impl EvmInterpreter {
                                 .into(),
                         );
                         debug!("Add emit_carrier_jump to queue");
-                        self.queue.push_back(function_call);
+                        self.enqueue(function_call);
                     },
                     "CarrierJumpCancelled" => {
                         let function_call: ContractCall = self.contract.cancel_carrier_jump(json["CarrierID"].as_u64().unwrap());
                         debug!("Add cancel_carrier_jump to queue");
-                        self.queue.push_back(function_call);
+                        self.enqueue(function_call);
                     },
                     "CarrierBuy" => {
                         let function_call: ContractCall = self.contract.register_carrier(
@@ -206,13 +259,10 @@ impl EvmInterpreter {
                             "".to_string(),
                             "".to_string(),
                             false,
-                            DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap())
-                                .unwrap()
-                                .timestamp()
-                                .into(),
+                            timestamp,
                         );
                         debug!("Add register_carrier to queue");
-                        self.queue.push_back(function_call);
+                        self.enqueue(function_call);
                     },
                     "CarrierStats" => {
                         //{"timestamp":"2022-09-08T21:27:21Z","event":"CarrierStats","CarrierID":3704402432,"Callsign":"Q2K-BHB","Name":"FUXBAU","DockingAccess":"squadron","AllowNotorious":false,"FuelLevel":529,"JumpRangeCurr":500.000000,
@@ -240,12 +290,9 @@ impl EvmInterpreter {
                             services,
                             json["DockingAccess"].as_str().unwrap().to_string(),
                             json["AllowNotorious"].as_bool().unwrap(),
-                            DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap())
-                                .unwrap()
-                                .timestamp()
-                                .into(),
+                            timestamp,
                         );
-                        self.queue.push_back(function_call);
+                        self.enqueue(function_call);
                     }
                     "Docked" => {
                         //{ "timestamp":"2024-04-02T21:22:42Z", "event":"Docked", "StationName":"Q2K-BHB", "StationType":"FleetCarrier", "Taxi":false, "Multicrew":false,
@@ -271,29 +318,23 @@ impl EvmInterpreter {
                                 services,
                                 "".to_string(),
                                 false,
-                                DateTime::parse_from_rfc3339(
-                                    json["timestamp"].as_str().unwrap(),
-                                )
-                                    .unwrap()
-                                    .timestamp()
-                                    .into(),
+                                timestamp,
                             );
                             debug!("Add register_carrier to queue");
-                            self.queue.push_back(function_call);
+                            self.enqueue(function_call);
                             let function_call: ContractCall = self.contract.report_carrier_location(
                                 json["MarketID"].as_u64().unwrap(),
                                 json["StarSystem"].as_str().unwrap().to_string(),
                                 "Unkown".to_string(),
-                                DateTime::parse_from_rfc3339(
-                                    json["timestamp"].as_str().unwrap(),
-                                )
-                                    .unwrap()
-                                    .timestamp()
-                                    .into(),
+                                timestamp,
                             );
                             debug!("Call report_carrier_location to queue");
-                            self.queue.push_back(function_call);
+                            self.enqueue(function_call);
                         } else {
+                            let record = Record::Station(json["MarketID"].as_u64().unwrap());
+                            if !self.state_check.is_newer(record, timestamp) {
+                                continue;
+                            }
                             debug!("Call register_station  to queue");
                             let function_call: ContractCall = self.contract.register_station(
                                 json["MarketID"].as_u64().unwrap(),
@@ -318,15 +359,10 @@ impl EvmInterpreter {
                                     json["DistFromStarLS"].to_string(),
                                 ),
                                 json["LandingPads"].to_string(),
-                                DateTime::parse_from_rfc3339(
-                                    json["timestamp"].as_str().unwrap(),
-                                )
-                                    .unwrap()
-                                    .timestamp()
-                                    .into(),
+                                timestamp,
                             );
                             //execute_send(function_call).await;
-                            self.queue.push_back(function_call);
+                            self.enqueue_record(function_call, record, timestamp);
                         }
                     },
                     "" => {
@@ -358,141 +394,290 @@ impl EvmInterpreter {
                                 );
                                 //execute_send(function_call).await;
                                 debug!("Call register_commodity_listening to queue");
-                                self.queue.push_back(function_call);
+                                self.enqueue(function_call);
                             }
                         }
                     }
                     &_ => {}
                 }
             }
+            self.release_delayed();
             //Queue has a list of transactions which has to be executed sequentially
 
             //There is a maximum of parallel working workers (NUMBER_OF_WORKERS)
             //If there are fewer workers than the maximum number of workers are allowed, a new job can be applied
             if !self.queue.is_empty() && self.pool.len() < NUMBER_OF_WORKERS {
-                if let Some(function_call) = self.queue.pop_front() {
-                    let nonce = self.nonce;
-                    self.nonce = nonce + 1;
-                    let thread: thread::JoinHandle<Result<TransactionReceipt, SendError>> =
+                if let Some((id, function_call)) = self.queue.pop_front() {
+                    let nonce = self.nonces.next_nonce();
+                    let description = describe_call(&self.contract, &function_call);
+                    let client = self.contract.client();
+                    let thread: Worker =
                         thread::Builder::new()
                             .name(String::from("Evm-Int-Worker"))
                             .spawn(move || {
                                 Runtime::new().unwrap().block_on(async {
-                                    execute_send_repeatable(function_call,nonce).await
+                                    execute_send_repeatable(function_call,nonce,client).await
                                 })
                             })
                             .unwrap();
-                    self.pool.push(thread);
+                    self.pool.push((id, nonce, description, thread));
                     debug!("Queue size: {}",self.queue.len());
                 }
             } else {
+                if !self.stuck.is_empty() && self.pool.is_empty()
+                    && self.resynced_at.elapsed() > Duration::from_secs(REPLACEMENT_TIMEOUT) {
+                    self.resync();
+                }
                 thread::sleep(Duration::from_millis(50));
                 //Here we have max number of workers reached.
                 let mut num_of_failes = 0;
 
                 let handles = std::mem::take(&mut self.pool);
-                for handle in handles {
+                for (id, nonce, description, handle) in handles {
                     if handle.is_finished() {
-                        match handle.join().unwrap() {
-                            Ok(transaction) => {
-                                info!("Evm call successfully: 0x{:?}",hex::encode(transaction.transaction_hash.0));
-                            }
-                            Err(err) => {
-                                //If something failed we have to recalculate the nonces
-                                match err {
-                                    RepeatableError(error) |
-                                    NonRepeatableError(error) => {
-                                        info!("Evm call unsuccessfully: {:?}", error);
-                                    }
-                                    NonceRecalculationRequired(function_call, error) => {
-                                        self.queue.push_front(function_call);
-                                        info!("Evm call unsuccessfully: {:?}", error);
-                                        if error.contains("invalid transaction nonce") {
-                                            let re = Regex::new(r"want (\d+)").unwrap();
-                                            if let Some(captures) = re.captures(error.as_str()) {
-                                                if let Some(want_number) = captures.get(1) {
-                                                    self.nonce = U256::from_str(want_number.as_str()).unwrap();
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                num_of_failes += 1;
-                            }
+                        if !self.finish_worker(id, nonce, &description, handle) {
+                            num_of_failes += 1;
                         }
                     }else {
-                        self.pool.push(handle);
+                        self.pool.push((id, nonce, description, handle));
                     }
                 }
                 if num_of_failes > 0 {
                     while !self.pool.is_empty() {
                         info!("Waiting pool to finish... (Remaining: {})",self.pool.len());
                         let handles = std::mem::take(&mut self.pool);
-                        for handle in handles {
+                        for (id, nonce, description, handle) in handles {
                             if !handle.is_finished() {
-                                self.pool.push(handle);
-                            }else {
-                                match handle.join().unwrap() {
-                                    Ok(transaction) => {
-                                        info!("Evm call successfully: 0x{:?}",hex::encode(transaction.transaction_hash.0));
-                                    }
-                                    Err(err) => {
-                                        match err {
-                                            RepeatableError(error) |
-                                            NonRepeatableError(error) => {
-                                                info!("Evm call unsuccessfully: {:?}", error);
-                                            }
-                                            NonceRecalculationRequired(function_call, error) => {
-                                                self.queue.push_front(function_call);
-                                                info!("Evm call unsuccessfully: {:?}", error);
-                                                if error.contains("invalid transaction nonce") {
-                                                    let re = Regex::new(r"want (\d+)").unwrap();
-                                                    if let Some(captures) = re.captures(error.as_str()) {
-                                                        if let Some(want_number) = captures.get(1) {
-                                                            self.nonce = U256::from_str(want_number.as_str()).unwrap();
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        num_of_failes += 1;
-                                    }
-                                }
+                                self.pool.push((id, nonce, description, handle));
+                            }else if !self.finish_worker(id, nonce, &description, handle) {
+                                num_of_failes += 1;
                             }
                         }
                         thread::sleep(Duration::from_secs(1));
                     }
 
-                    info!("Finished waiting -> calculating actual nonce (Current: {})",self.nonce);
-                    // Set new nonces for every transaction
-                    self.nonce = self.nonce - num_of_failes;
-                    info!("New nonce: {}",self.nonce);
+                    info!("Finished waiting -> resyncing nonce after {} failed transactions", num_of_failes);
+                    self.resync();
                 }
             }
+            if self.bus_closed && self.queue.is_empty() && self.pool.is_empty() && self.delayed.is_empty() {
+                let status = self.status.lock().unwrap();
+                info!("Bus closed and all transactions done, stopping (Submitted: {}, skipped: {}, failed: {})", status.submitted, status.skipped, status.failed);
+                return;
+            }
             thread::sleep(Duration::from_millis(50));
         }
     }
+
+    /// Stored calls of earlier runs stay in the store for the next real run
+    pub fn set_dry_run(&mut self) {
+        self.dry_run = true;
+        self.queue.clear();
+        self.delayed.clear();
+    }
+
+    fn enqueue(&mut self, function_call: ContractCall) {
+        self.enqueue_call(function_call, None);
+    }
+
+    /// Queues a call which writes a record the state check said is newer
+    fn enqueue_record(&mut self, function_call: ContractCall, record: Record, timestamp: U256) {
+        self.enqueue_call(function_call, Some((record, timestamp)));
+    }
+
+    /// Delayed calls are stored with their release time as well, so they survive a restart
+    fn enqueue_call(&mut self, function_call: ContractCall, record: Option<(Record, U256)>) {
+        self.status.lock().unwrap().submitted += 1;
+        if self.dry_run {
+            println!("Would call {}", describe_call(&self.contract, &function_call));
+            return;
+        }
+        let release_time = if self.release_time > Utc::now().timestamp() {
+            self.release_time
+        } else {
+            0
+        };
+        let id = self.store.add(&function_call, release_time);
+        if let Some(record) = record {
+            self.records.insert(id, record);
+        }
+        self.add_stored(StoredCall {
+            id,
+            release_time,
+            call: function_call,
+        });
+    }
+
+    /// Queues a stored call or holds it back until its release time
+    fn add_stored(&mut self, stored: StoredCall) {
+        if stored.release_time > Utc::now().timestamp() {
+            self.delayed
+                .push_back((stored.release_time, stored.id, stored.call));
+        } else {
+            self.queue.push_back((stored.id, stored.call));
+        }
+    }
+
+    fn release_delayed(&mut self) {
+        let now = Utc::now().timestamp();
+        while let Some((release_time, _, _)) = self.delayed.front() {
+            if *release_time > now {
+                break;
+            }
+            if let Some((_, id, function_call)) = self.delayed.pop_front() {
+                self.queue.push_back((id, function_call));
+            }
+        }
+    }
+
+    /// Joins the finished worker, a mined transaction is written to the audit log with its hash
+    fn finish_worker(&mut self, id: u64, nonce: U256, description: &str, worker: Worker) -> bool {
+        let result = worker.join().unwrap();
+        if let Ok(receipt) = &result {
+            self.audit_log.record(nonce, Some(receipt.transaction_hash), description);
+        }
+        self.handle_result(id, nonce, result)
+    }
+
+    /// Returns false if the transaction failed. Calls which need a new nonce or failed temporarily
+    /// go back to the queue. A stuck call keeps its nonce and stays in the store until a resync
+    /// finds its transaction mined or dropped, every other call is removed from the store.
+    /// The nonce of a failed call was not used on chain.
+    fn handle_result(&mut self, id: u64, nonce: U256, result: Result<TransactionReceipt, SendError>) -> bool {
+        match result {
+            Ok(transaction) => {
+                info!("Evm call successfully: 0x{:?}",hex::encode(transaction.transaction_hash.0));
+                self.nonces.confirm(nonce);
+                self.store.remove(id);
+                if let Some((record, timestamp)) = self.records.remove(&id) {
+                    self.state_check.confirm(record, timestamp);
+                }
+                true
+            }
+            Err(err) => {
+                match err {
+                    RepeatableError(function_call, error) => {
+                        info!("Evm call unsuccessfully, sending it again later: {:?}", error);
+                        self.nonces.release(nonce);
+                        self.queue.push_back((id, function_call));
+                        self.store.set_error(error);
+                    }
+                    NonRepeatableError(error) => {
+                        info!("Evm call unsuccessfully: {:?}", error);
+                        self.nonces.release(nonce);
+                        self.store.fail(id, error);
+                        if let Some((record, timestamp)) = self.records.remove(&id) {
+                            self.state_check.release(record, timestamp);
+                        }
+                    }
+                    NonceRecalculationRequired(function_call, error) => {
+                        self.nonces.release(nonce);
+                        self.queue.push_front((id, function_call));
+                        info!("Evm call unsuccessfully: {:?}", error);
+                        self.store.set_error(error);
+                    }
+                    Stuck(function_call, error) => {
+                        info!("Evm call unsuccessfully, waiting for it in the mempool: {:?}", error);
+                        self.nonces.mark_stuck(nonce);
+                        self.stuck.push((id, nonce, function_call));
+                        self.store.set_error(error);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Resyncs the nonces and removes mined stuck calls from the store. Calls whose transaction
+    /// was dropped from the mempool are sent again with one of the freed nonces.
+    fn resync(&mut self) {
+        self.nonces.resync();
+        self.resynced_at = Instant::now();
+        for (id, nonce, function_call) in std::mem::take(&mut self.stuck) {
+            if self.nonces.is_mined(nonce) {
+                info!("Stuck transaction with nonce {} was mined", nonce);
+                let description = describe_call(&self.contract, &function_call);
+                self.audit_log.record(nonce, None, &description);
+                self.store.remove(id);
+                if let Some((record, timestamp)) = self.records.remove(&id) {
+                    self.state_check.confirm(record, timestamp);
+                }
+            } else if self.nonces.is_stuck(nonce) {
+                self.stuck.push((id, nonce, function_call));
+            } else {
+                info!("Stuck transaction with nonce {} was dropped, sending it again", nonce);
+                self.queue.push_front((id, function_call));
+            }
+        }
+    }
 }
-pub fn initialize(bus_reader: BusReader<JsonValue>, evm_settings: &EvmSettings) -> EvmInterpreter {
-    let (contract, nonce) = tokio::runtime::Builder::new_multi_thread()
+/// The store name keeps the queues of the client, the journal upload and the eddn adapter apart
+pub fn initialize(
+    bus_reader: BusReader<JsonValue>,
+    evm_settings: &EvmSettings,
+    store_name: &str,
+) -> EvmInterpreter {
+    let contract = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(async move {
-            (
-                get_contract(evm_settings).await,
-                get_nonce(evm_settings).await,
-            )
-        });
+        .block_on(async move { get_contract(evm_settings).await });
+    let nonces = NonceManager::new(contract.client());
+    let state_check = StateCheck::new(contract.clone(), evm_settings.queue_status.clone());
 
-    debug!("Current Nonce: {}", nonce);
+    let (store, mut calls) =
+        TransactionStore::load(store_name, &contract, evm_settings.queue_status.clone());
+    calls.sort_by_key(|stored| stored.release_time);
 
-    EvmInterpreter {
+    let mut evm_interpreter = EvmInterpreter {
         bus: bus_reader,
         contract,
-        nonce,
+        nonces,
         queue: VecDeque::new(),
         pool: Vec::new(),
+        store,
+        state_check,
+        records: HashMap::new(),
+        status: evm_settings.queue_status.clone(),
+        dry_run: false,
+        privacy: PrivacyFilter::open(
+            evm_settings.shared_categories.clone(),
+            evm_settings.publish_delay,
+        ),
+        delayed: VecDeque::new(),
+        release_time: 0,
+        audit_log: AuditLog::open(),
+        bus_closed: false,
+        stuck: Vec::new(),
+        resynced_at: Instant::now(),
+    };
+    for stored in calls {
+        evm_interpreter.add_stored(stored);
+    }
+    evm_interpreter
+}
+
+/// Function name and arguments of the call, decoded with the contract abi
+fn describe_call(contract: &Edcas, function_call: &ContractCall) -> String {
+    let calldata = function_call.calldata().unwrap_or_default();
+    if calldata.len() < 4 {
+        return "<no calldata>".to_string();
+    }
+    let (selector, arguments) = calldata.split_at(4);
+    match contract
+        .abi()
+        .functions()
+        .find(|function| function.short_signature() == selector)
+    {
+        Some(function) => match function.decode_input(arguments) {
+            Ok(tokens) => format!(
+                "{}({})",
+                function.name,
+                tokens.iter().map(Token::to_string).collect::<Vec<String>>().join(", ")
+            ),
+            Err(err) => format!("{}(<{}>)", function.name, err),
+        },
+        None => format!("unknown function 0x{}", hex::encode(selector)),
     }
 }
 pub async fn get_contract(
@@ -514,21 +699,6 @@ pub async fn get_contract(
     edcas_contract::EDCAS::new(edcas_address, Arc::new(client.clone()))
 }
 
-pub async fn get_nonce(evm_settings: &EvmSettings) -> U256 {
-    let node_url = evm_settings.url.to_string();
-    let private = evm_settings.private_key.to_string();
-    let retry = evm_settings.n_attempts;
-    let timeout = evm_settings.n_timeout;
-
-    let client: SignerMiddleware<Provider<Http>, LocalWallet> =
-        get_client(node_url, private, retry, timeout).await;
-
-    client
-        .get_transaction_count(client.address(), None)
-        .await
-        .unwrap()
-}
-
 pub async fn get_client(
     node_url: String,
     private_key: String,
@@ -541,7 +711,13 @@ pub async fn get_client(
 
     let provider = Provider::connect(node_url.as_str()).await;
 
-    let wallet: LocalWallet = private_key.parse::<LocalWallet>().unwrap();
+    //Without a key only reading and dry runs work
+    let wallet: LocalWallet = if private_key.is_empty() {
+        info!("No private key, using a throwaway wallet");
+        LocalWallet::new(&mut thread_rng())
+    } else {
+        private_key.parse::<LocalWallet>().unwrap()
+    };
     info!("EVM Address: {:?}", wallet.address());
 
     let mut result =
@@ -622,21 +798,31 @@ fn extract_body_properties(json: &JsonValue) -> BodyProperties {
     }
 }
 enum SendError {
-    RepeatableError(String),
+    /// Temporary error like a node which can't be reached, the call is sent again later
+    RepeatableError(ContractCall,String),
     NonRepeatableError(String),
     NonceRecalculationRequired(ContractCall,String),
+    /// The transaction is still in the mempool after all replacements, so its nonce is used
+    Stuck(ContractCall,String),
 }
 
 async fn execute_send_repeatable(
     function_call: ContractCall,
     nonce: U256,
+    client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
 ) -> Result<TransactionReceipt, SendError> {
-    while let Err(err) = match execute_send(function_call.clone(), nonce).await {
+    let mut retries = 0;
+    let timeout = Duration::from_secs(REPLACEMENT_TIMEOUT);
+    while let Err(err) = match execute_send(function_call.clone(), nonce, client.clone(), timeout).await {
         Ok(receipt) => return Ok(receipt),
         Err(err) => Err::<(), SendError>(err),
     } {
         match err {
-            RepeatableError(_) => {
+            RepeatableError(function_call, err) if retries == MAX_RETRIES => {
+                return Err(RepeatableError(function_call, err))
+            }
+            RepeatableError(_, _) => {
+                retries += 1;
                 tokio::time::sleep(Duration::from_secs(
                     env::var("DURATION_TIMEOUT")
                         .unwrap_or("1".into())
@@ -647,26 +833,70 @@ async fn execute_send_repeatable(
             }
             NonRepeatableError(err) => return Err(NonRepeatableError(err)),
             NonceRecalculationRequired(function_call,err) => return Err(NonceRecalculationRequired(function_call,err)),
+            Stuck(function_call, err) => return Err(Stuck(function_call, err)),
         }
     }
     Err(NonRepeatableError("Unknown".into()))
 }
+/// Sends the call and waits for the receipt. A transaction without receipt after the timeout
+/// is stuck and gets replaced by the same call with a higher gas price.
 async fn execute_send(
     function_call: ContractCall,
     nonce: U256,
+    client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    timeout: Duration,
 ) -> Result<TransactionReceipt, SendError> {
-    match function_call.clone().legacy().nonce(nonce).send().await {
-        Ok(pending) => match pending.await {
+    let mut gas_price: Option<U256> = None;
+    let mut last_hash: Option<TxHash> = None;
+    let mut replacements = 0;
+    let sent = loop {
+        let mut call = function_call.clone().legacy().nonce(nonce);
+        if let Some(gas_price) = gas_price {
+            call = call.gas_price(gas_price);
+        }
+        let result = call.send().await;
+        match result {
+            Ok(pending) => {
+                let hash = pending.tx_hash();
+                match tokio::time::timeout(timeout, pending).await {
+                    Ok(result) => break Ok(result),
+                    Err(_) if replacements < MAX_REPLACEMENTS => {
+                        replacements += 1;
+                        last_hash = Some(hash);
+                        let current = match gas_price {
+                            Some(gas_price) => gas_price,
+                            None => client.get_gas_price().await.unwrap_or_default(),
+                        };
+                        //Nodes only accept a replacement with at least 10% more gas price
+                        gas_price = Some(current * 5 / 4);
+                        info!("Transaction {:?} with nonce {} is stuck, replacing it", hash, nonce);
+                    }
+                    Err(_) => return Err(Stuck(function_call, format!("Transaction with nonce {} is stuck", nonce))),
+                }
+            }
+            Err(err) => {
+                //The replaced transaction might have been mined in the meantime, which uses up the nonce
+                if let Some(hash) = last_hash {
+                    if let Ok(Some(receipt)) = client.get_transaction_receipt(hash).await {
+                        return Ok(receipt);
+                    }
+                }
+                break Err(err);
+            }
+        }
+    };
+    match sent {
+        Ok(result) => match result {
             Ok(receipt) => {
                 if let Some(receipt) = receipt {
                     if let Some(_hash) = receipt.block_hash {
                         //info!("Success calling function: {:?}", hash);
                         Ok(receipt)
                     } else {
-                        Err(RepeatableError("Receipt without hash".into()))
+                        Err(RepeatableError(function_call, "Receipt without hash".into()))
                     }
                 } else {
-                    Err(RepeatableError("No Receipt".into()))
+                    Err(RepeatableError(function_call, "No Receipt".into()))
                 }
             }
             Err(err) => match err {
@@ -743,7 +973,7 @@ async fn execute_send(
                             },
                             ProviderError::HTTPError(_) => {
                                 error!("MiddlewareError: HTTPError: {}", e.to_string());
-                                Err(RepeatableError(format!("MiddlewareError: HTTPError: {}",e)))
+                                Err(RepeatableError(function_call, format!("MiddlewareError: HTTPError: {}",e)))
                             },
                             ProviderError::SerdeJson(_) |
                             ProviderError::HexError(_) |
@@ -784,33 +1014,6 @@ async fn execute_send(
         },
     }
 }
-async fn process_jump(
-    json: JsonValue,
-    contract: edcas_contract::EDCAS<SignerMiddleware<Provider<Http>, LocalWallet>>,
-    nonce: U256,
-) -> Result<TransactionReceipt, SendError> {
-    debug!("Call register_system");
-    let function_call: ContractCall = contract.register_system(
-        json["SystemAddress"].as_u64().unwrap(),
-        json["StarSystem"].to_string(),
-        json["SystemGovernment"].to_string(),
-        json["SystemAllegiance"].to_string(),
-        json["SystemEconomy"].to_string(),
-        json["SystemSecondEconomy"].to_string(),
-        json["SystemSecurity"].to_string(),
-        json["Population"].as_u64().unwrap_or(0),
-        floating::generate_floating_from_string(json["StarPos"][0].to_string()),
-        floating::generate_floating_from_string(json["StarPos"][1].to_string()),
-        floating::generate_floating_from_string(json["StarPos"][2].to_string()),
-        DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap())
-            .unwrap()
-            .timestamp()
-            .into(),
-    );
-    //execute_send(function_call).await;
-    execute_send_repeatable(function_call, nonce).await
-}
-
 fn get_revert_message(bytes: Bytes) -> String {
     if bytes.len() < 134 {
         let n = bytes.split_at(134 / 2).1;
@@ -819,3 +1022,236 @@ fn get_revert_message(bytes: Bytes) -> String {
     }
     bytes.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::{
+        get_anvil_contract, get_offline_contract, get_temp_path, mine, start_anvil,
+    };
+    use bus::Bus;
+
+    /// Interpreter without journal events which keeps its calls in the given store
+    fn get_interpreter(contract: Edcas, path: &str) -> EvmInterpreter {
+        let status = Arc::new(Mutex::new(TransactionQueueStatus::default()));
+        let (store, calls) =
+            TransactionStore::from_file(path.to_string(), &contract, status.clone());
+        let mut interpreter = EvmInterpreter {
+            bus: Bus::new(1).add_rx(),
+            nonces: NonceManager::new(contract.client()),
+            queue: VecDeque::new(),
+            pool: Vec::new(),
+            store,
+            state_check: StateCheck::new(contract.clone(), status.clone()),
+            records: HashMap::new(),
+            status,
+            dry_run: false,
+            privacy: PrivacyFilter::from_file(vec![], 0, format!("{}-carrier", path)),
+            delayed: VecDeque::new(),
+            release_time: 0,
+            audit_log: AuditLog::from_file(format!("{}-audit", path)),
+            bus_closed: false,
+            stuck: Vec::new(),
+            resynced_at: Instant::now(),
+            contract,
+        };
+        for stored in calls {
+            interpreter.add_stored(stored);
+        }
+        interpreter
+    }
+
+    fn get_stored_calls(contract: &Edcas, path: &str) -> Vec<StoredCall> {
+        let status = Arc::new(Mutex::new(TransactionQueueStatus::default()));
+        TransactionStore::from_file(path.to_string(), contract, status).1
+    }
+
+    fn get_call(contract: &Edcas) -> ContractCall {
+        contract.set_body_count(10477373803, 12, 100.into())
+    }
+
+    #[test]
+    fn repeatable_errors_stay_queued() {
+        let contract = get_offline_contract();
+        let path = get_temp_path("interpreter-repeatable");
+        let mut interpreter = get_interpreter(contract.clone(), &path);
+        let record = Record::System(10477373803);
+        interpreter.enqueue_record(get_call(&contract), record, 100.into());
+        let (id, function_call) = interpreter.queue.pop_front().unwrap();
+        let nonce = interpreter.nonces.next_nonce();
+
+        let result = Err(RepeatableError(function_call, "HTTPError".to_string()));
+        assert!(!interpreter.handle_result(id, nonce, result));
+        assert_eq!(interpreter.queue.len(), 1);
+        assert_eq!(interpreter.queue[0].0, id);
+        assert_eq!(interpreter.records.get(&id), Some(&(record, 100.into())));
+        assert_eq!(interpreter.nonces.next_nonce(), nonce);
+        assert_eq!(get_stored_calls(&contract, &path).len(), 1);
+
+        //A call which can never succeed is given up
+        let (id, _) = interpreter.queue.pop_front().unwrap();
+        let result = Err(NonRepeatableError("Revert".to_string()));
+        assert!(!interpreter.handle_result(id, nonce, result));
+        assert!(interpreter.queue.is_empty());
+        assert!(interpreter.records.is_empty());
+        assert!(get_stored_calls(&contract, &path).is_empty());
+    }
+
+    #[test]
+    fn delayed_calls_survive_a_restart() {
+        let contract = get_offline_contract();
+        let path = get_temp_path("interpreter-delayed");
+        let mut interpreter = get_interpreter(contract.clone(), &path);
+        interpreter.release_time = Utc::now().timestamp() + 3600;
+        interpreter.enqueue(get_call(&contract));
+        interpreter.release_delayed();
+        assert!(interpreter.queue.is_empty());
+        assert_eq!(interpreter.delayed.len(), 1);
+        let id = interpreter.delayed[0].1;
+        drop(interpreter);
+
+        let mut interpreter = get_interpreter(contract.clone(), &path);
+        assert!(interpreter.queue.is_empty());
+        assert_eq!(interpreter.delayed.len(), 1);
+        assert_eq!(interpreter.delayed[0].1, id);
+
+        //Released calls are queued with their id, not stored a second time
+        interpreter.delayed[0].0 = 0;
+        interpreter.release_delayed();
+        assert!(interpreter.delayed.is_empty());
+        assert_eq!(interpreter.queue.len(), 1);
+        assert_eq!(interpreter.queue[0].0, id);
+        assert_eq!(get_stored_calls(&contract, &path).len(), 1);
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn mined_transactions_are_audited_with_their_hash() {
+        let contract = get_offline_contract();
+        let path = get_temp_path("interpreter-audit");
+        let mut interpreter = get_interpreter(contract.clone(), &path);
+        let description = describe_call(&contract, &get_call(&contract));
+        assert_eq!(description, "set_body_count(27080096b, c, 64)");
+
+        interpreter.enqueue(get_call(&contract));
+        let (id, _) = interpreter.queue.pop_front().unwrap();
+        let nonce = interpreter.nonces.next_nonce();
+        let worker: Worker = thread::spawn(|| Err(NonRepeatableError("Revert".to_string())));
+        assert!(!interpreter.finish_worker(id, nonce, &description, worker));
+
+        interpreter.enqueue(get_call(&contract));
+        let (id, _) = interpreter.queue.pop_front().unwrap();
+        let nonce = interpreter.nonces.next_nonce();
+        let receipt = TransactionReceipt {
+            transaction_hash: TxHash::repeat_byte(0xab),
+            ..TransactionReceipt::default()
+        };
+        let worker: Worker = thread::spawn(move || Ok(receipt));
+        assert!(interpreter.finish_worker(id, nonce, &description, worker));
+
+        let audit = std::fs::read_to_string(format!("{}-audit", path)).unwrap();
+        let lines: Vec<&str> = audit.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let line = json::parse(lines[0]).unwrap();
+        assert_eq!(line["hash"], format!("{:?}", TxHash::repeat_byte(0xab)));
+        assert_eq!(line["nonce"].to_string(), nonce.to_string());
+        assert_eq!(line["call"], description);
+    }
+
+    #[test]
+    fn stuck_calls_keep_their_nonce_and_stay_stored() {
+        let contract = get_offline_contract();
+        let path = get_temp_path("interpreter-stuck");
+        let mut interpreter = get_interpreter(contract.clone(), &path);
+        let record = Record::System(10477373803);
+        interpreter.enqueue_record(get_call(&contract), record, 100.into());
+        let (id, function_call) = interpreter.queue.pop_front().unwrap();
+        let nonce = interpreter.nonces.next_nonce();
+
+        let result = Err(Stuck(function_call, "Transaction is stuck".to_string()));
+        assert!(!interpreter.handle_result(id, nonce, result));
+        assert!(interpreter.queue.is_empty());
+        assert_eq!(interpreter.stuck.len(), 1);
+        assert!(interpreter.nonces.is_stuck(nonce));
+        assert_ne!(interpreter.nonces.next_nonce(), nonce);
+        assert_eq!(interpreter.records.get(&id), Some(&(record, 100.into())));
+        assert_eq!(get_stored_calls(&contract, &path).len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a local anvil binary"]
+    async fn stuck_transactions_are_replaced_with_a_higher_gas_price() {
+        let anvil = start_anvil();
+        let contract = get_anvil_contract(&anvil);
+        let client = contract.client();
+        let gas_price = client.get_gas_price().await.unwrap();
+
+        let timeout = Duration::from_secs(1);
+        let result = execute_send(get_call(&contract), U256::zero(), client.clone(), timeout).await;
+        assert!(matches!(result, Err(Stuck(_, _))));
+
+        //Only the last replacement is left in the mempool
+        mine(&contract).await;
+        let block = client.get_block_with_txs(1u64).await.unwrap().unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        let transaction = &block.transactions[0];
+        assert_eq!(transaction.nonce, U256::zero());
+        assert!(transaction.gas_price.unwrap() > gas_price);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a local anvil binary"]
+    async fn replaced_transactions_are_mined() {
+        let anvil = start_anvil();
+        let contract = get_anvil_contract(&anvil);
+        let client = contract.client();
+
+        let timeout = Duration::from_secs(1);
+        let send = tokio::spawn(execute_send(get_call(&contract), U256::zero(), client, timeout));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        mine(&contract).await;
+        let receipt = match send.await.unwrap() {
+            Ok(receipt) => receipt,
+            Err(_) => panic!("The replacement wasn't mined"),
+        };
+        assert_eq!(receipt.block_number, Some(1.into()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a local anvil binary"]
+    async fn stuck_transactions_found_mined_are_audited() {
+        let anvil = start_anvil();
+        let contract = get_anvil_contract(&anvil);
+        let path = get_temp_path("interpreter-stuck-audit");
+        let interpreter_contract = contract.clone();
+        let interpreter_path = path.clone();
+        let mut interpreter = tokio::task::spawn_blocking(move || {
+            get_interpreter(interpreter_contract, &interpreter_path)
+        })
+        .await
+        .unwrap();
+
+        interpreter.enqueue(get_call(&contract));
+        let (id, function_call) = interpreter.queue.pop_front().unwrap();
+        let nonce = interpreter.nonces.next_nonce();
+        let timeout = Duration::from_secs(1);
+        let result = execute_send(function_call, nonce, contract.client(), timeout).await;
+        assert!(matches!(result, Err(Stuck(_, _))));
+        assert!(!interpreter.handle_result(id, nonce, result));
+
+        mine(&contract).await;
+        let interpreter = tokio::task::spawn_blocking(move || {
+            interpreter.resync();
+            interpreter
+        })
+        .await
+        .unwrap();
+        assert!(interpreter.stuck.is_empty());
+
+        let audit = std::fs::read_to_string(format!("{}-audit", path)).unwrap();
+        let line = json::parse(audit.lines().next().unwrap()).unwrap();
+        assert_eq!(line["nonce"].to_string(), nonce.to_string());
+        assert_eq!(line["call"], describe_call(&contract, &get_call(&contract)));
+        assert!(line["hash"].is_null());
+    }
+}
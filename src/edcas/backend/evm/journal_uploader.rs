@@ -1,71 +1,385 @@
+use crate::cli::get_arg;
 use crate::edcas;
-use crate::edcas::backend::journal_reader::{get_journal_log_by_index, get_log_file_list};
-use crate::edcas::settings::EvmSettings;
+use crate::edcas::backend::journal_reader::get_log_file_list;
+use crate::edcas::settings::{get_data_path, EvmSettings};
 use bus::{Bus, BusReader};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use json::JsonValue;
-use log::error;
-use std::io::BufRead;
+use log::{error, info, warn};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::thread;
 
+/// Lines which are broadcast but possibly not yet taken by the interpreter
+const JOURNAL_BUS_SIZE: usize = 10;
+/// Lines between two checkpoint writes
+const CHECKPOINT_INTERVAL: usize = 100;
+
+/// Filters and modes of an upload, read from the arguments after `--upload-journal`
+#[derive(Default, Clone)]
+pub struct UploadOptions {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Event names to upload, all if empty
+    pub events: Vec<String>,
+    pub dry_run: bool,
+    /// Ignores the checkpoint and starts with the oldest journal
+    pub restart: bool,
+}
+
+impl UploadOptions {
+    pub fn from_args(args: &[String]) -> Result<UploadOptions, String> {
+        let mut options = UploadOptions::default();
+        if let Some(from) = get_arg(args, "--from")? {
+            options.from = Some(parse_date(&from)?);
+        }
+        if let Some(to) = get_arg(args, "--to")? {
+            options.to = Some(parse_date(&to)?);
+        }
+        if let Some(events) = get_arg(args, "--events")? {
+            options.events = events
+                .split(',')
+                .map(|event| event.trim().to_string())
+                .filter(|event| !event.is_empty())
+                .collect();
+        }
+        options.dry_run = args.iter().any(|arg| arg == "--dry-run");
+        options.restart = args.iter().any(|arg| arg == "--restart");
+        Ok(options)
+    }
+
+    fn matches(&self, json: &JsonValue) -> bool {
+        if !self.events.is_empty()
+            && !self
+                .events
+                .iter()
+                .any(|event| json["event"].as_str() == Some(event.as_str()))
+        {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Some(date) = json["timestamp"]
+            .as_str()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.date_naive())
+        else {
+            return false;
+        };
+        self.from.map_or(true, |from| date >= from) && self.to.map_or(true, |to| date <= to)
+    }
+
+    /// A checkpoint is only valid for the filters it was written with
+    fn get_filter(&self) -> String {
+        format!(
+            "from={};to={};events={}",
+            self.from.map(|date| date.to_string()).unwrap_or_default(),
+            self.to.map(|date| date.to_string()).unwrap_or_default(),
+            self.events.join(",")
+        )
+    }
+}
+
+/// Sent after each finished journal
+#[derive(Clone)]
+pub struct UploadProgress {
+    pub journal: String,
+    /// Finished journals of this run
+    pub done: usize,
+    pub total: usize,
+    /// Lines of the journal which passed the filters
+    pub lines: usize,
+}
+
+/// Next line to upload
+#[derive(Clone)]
+struct Position {
+    journal: String,
+    line: usize,
+}
+
+struct Checkpoint {
+    path: String,
+    filter: String,
+}
+
+impl Checkpoint {
+    fn new(filter: String) -> Checkpoint {
+        Checkpoint::from_file(get_data_path("upload-checkpoint.json"), filter)
+    }
+
+    fn from_file(path: String, filter: String) -> Checkpoint {
+        Checkpoint { path, filter }
+    }
+
+    fn load(&self) -> Option<Position> {
+        let json = json::parse(&fs::read_to_string(&self.path).ok()?).ok()?;
+        if json["filter"].as_str() != Some(self.filter.as_str()) {
+            info!("Upload filters changed, ignoring the checkpoint");
+            return None;
+        }
+        Some(Position {
+            journal: json["journal"].as_str()?.to_string(),
+            line: json["line"].as_usize()?,
+        })
+    }
+
+    fn save(&self, position: &Position) {
+        let content = json!({
+            "journal": position.journal,
+            "line": position.line,
+            "filter": self.filter,
+        });
+        if let Err(err) = fs::write(&self.path, content.to_string()) {
+            error!("Couldn't write upload checkpoint {}: {}", self.path, err);
+        }
+    }
+}
+
 /**
-    Initializes the uploader which uploads the journal events to the edcas network, going from the latest to the oldest.
-    It gives back a bus reader which gives the current index of the finished log and the total number of logs which has to be uploaded.
+    Initializes the uploader which uploads the journal events to the edcas network, going from the oldest to the latest.
+    It continues after the line of the last run, which is kept in a checkpoint file.
+    It gives back a bus reader with the progress after each journal and the number of journals which have to be uploaded.
+    The bus closes once the interpreter sent every call.
 */
-pub fn initialize(evm_settings: &EvmSettings, journal_directory: String) -> (BusReader<i64>, i64) {
-    let mut progress_bus: Bus<i64> = Bus::new(10);
+pub fn initialize(
+    evm_settings: &EvmSettings,
+    journal_directory: String,
+    options: UploadOptions,
+) -> (BusReader<UploadProgress>, usize) {
+    let mut progress_bus: Bus<UploadProgress> = Bus::new(10);
     let progress_bus_reader = progress_bus.add_rx();
 
-    let mut journal_bus: Bus<JsonValue> = Bus::new(10);
+    let mut journal_bus: Bus<JsonValue> = Bus::new(JOURNAL_BUS_SIZE);
     let journal_bus_reader = journal_bus.add_rx();
-    let mut evm_interpreter =
-        edcas::backend::evm::journal_interpreter::initialize(journal_bus_reader, evm_settings);
-    thread::Builder::new()
+    let mut evm_interpreter = edcas::backend::evm::journal_interpreter::initialize(
+        journal_bus_reader,
+        evm_settings,
+        "upload-transactions.log",
+    );
+    if options.dry_run {
+        evm_interpreter.set_dry_run();
+    }
+    let interpreter = thread::Builder::new()
         .name("edcas-evm-interpreter".into())
         .spawn(move || evm_interpreter.run_loop())
         .expect("Failed to create thread journal-reader-evm");
 
-    let path = journal_directory.clone();
-    let mut index: i64 = get_log_file_list(&path).len() as i64;
+    let checkpoint = Checkpoint::new(options.get_filter());
+    let start = if options.restart || options.dry_run {
+        None
+    } else {
+        checkpoint.load()
+    };
+
+    let journals = select_journals(
+        get_log_file_list(&journal_directory),
+        start.as_ref(),
+        options.to,
+    );
+    let total = journals.len();
+
     thread::Builder::new()
         .name("edcas-journal-uploader".into())
         .spawn(move || {
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async move {
-                    while index > 0 {
-                        index -= 1;
-                        let mut journal = get_journal_log_by_index(path.clone(), index as usize);
-                        let mut line = String::new();
-                        let mut flag: usize = 1;
-                        while flag != 0 {
-                            match journal.read_line(&mut line) {
-                                Ok(line_flag) => {
-                                    if line_flag == 0 {
-                                        flag = 0;
-                                    } else if !line.eq("") {
-                                        let json_result = json::parse(&line);
-                                        match json_result {
-                                            Ok(json) => {
-                                                journal_bus.broadcast(json);
-                                            }
-                                            Err(err) => {
-                                                error!("Couldn't parse json: {}", err)
-                                            }
-                                        }
-                                    }
-                                    line.clear();
-                                }
-                                Err(_err) => {
-                                    error!("Error reading journal file!");
+            //The checkpoint stays behind the lines in the bus, replayed lines are skipped by the state check
+            let mut in_flight: VecDeque<Position> = VecDeque::new();
+            let mut position = None;
+            for (done, journal) in journals.into_iter().enumerate() {
+                let path = format!("{}/Journal.{}.01.log", journal_directory, journal);
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(err) => {
+                        error!("Couldn't open {}: {}", path, err);
+                        continue;
+                    }
+                };
+                let skip = match &start {
+                    Some(start) if start.journal == journal => start.line,
+                    _ => 0,
+                };
+                let mut lines = 0;
+                for (index, line) in BufReader::new(file).lines().enumerate().skip(skip) {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(err) => {
+                            error!("Error reading journal file {}: {}", path, err);
+                            break;
+                        }
+                    };
+                    let current = Position {
+                        journal: journal.clone(),
+                        line: index,
+                    };
+                    if !line.is_empty() {
+                        match json::parse(&line) {
+                            Ok(json) if options.matches(&json) => {
+                                lines += 1;
+                                in_flight.push_back(current.clone());
+                                if in_flight.len() > JOURNAL_BUS_SIZE {
+                                    in_flight.pop_front();
                                 }
-                            };
+                                journal_bus.broadcast(json);
+                            }
+                            Ok(_) => {}
+                            Err(err) => error!("Couldn't parse json: {}", err),
                         }
-                        progress_bus.broadcast(index);
                     }
-                })
+                    if !options.dry_run && index % CHECKPOINT_INTERVAL == 0 {
+                        checkpoint.save(in_flight.front().unwrap_or(&current));
+                    }
+                    position = Some(Position {
+                        journal: journal.clone(),
+                        line: index + 1,
+                    });
+                }
+                progress_bus.broadcast(UploadProgress {
+                    journal,
+                    done: done + 1,
+                    total,
+                    lines,
+                });
+            }
+
+            //Closing the bus lets the interpreter finish the queue
+            drop(journal_bus);
+            if interpreter.join().is_err() {
+                error!("EVM interpreter stopped unexpectedly");
+                return;
+            }
+            if let (false, Some(position)) = (options.dry_run, position) {
+                checkpoint.save(&position);
+            }
         })
         .expect("Cannot spawn edcas-journal-uploader thread");
-    (progress_bus_reader, index)
+    (progress_bus_reader, total)
+}
+
+/// Journals to upload oldest first, starting with the journal of the checkpoint if it still exists
+fn select_journals(
+    mut journals: Vec<String>,
+    start: Option<&Position>,
+    to: Option<NaiveDate>,
+) -> Vec<String> {
+    journals.reverse();
+    if let Some(start) = start {
+        match journals
+            .iter()
+            .position(|journal| journal == &start.journal)
+        {
+            Some(index) => {
+                info!("Continuing at {} line {}", start.journal, start.line);
+                journals.drain(..index);
+            }
+            None => warn!(
+                "Journal {} of the checkpoint is gone, starting over",
+                start.journal
+            ),
+        }
+    }
+    if let Some(to) = to {
+        journals.retain(|journal| {
+            NaiveDateTime::parse_from_str(journal, "%Y-%m-%dT%H%M%S")
+                .map_or(true, |started| started.date() <= to)
+        });
+    }
+    journals
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Wrong date: {}, expected YYYY-MM-DD", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::get_temp_path;
+
+    const FSD_JUMP: &str = r#"{"timestamp":"2022-09-08T21:40:13Z","event":"FSDJump","Taxi":false,"Multicrew":false,"StarSystem":"Col 285 Sector KS-T d3-68","SystemAddress":2345873295747,"StarPos":[-65.40625,-70.68750,-89.53125],"SystemAllegiance":"","SystemEconomy":"$economy_None;","SystemEconomy_Localised":"n/v","SystemSecondEconomy":"$economy_None;","SystemSecondEconomy_Localised":"n/v","SystemGovernment":"$government_None;","SystemGovernment_Localised":"n/v","SystemSecurity":"$GAlAXY_MAP_INFO_state_anarchy;","SystemSecurity_Localised":"Anarchie","Population":0,"Body":"Col 285 Sector KS-T d3-68 A","BodyID":1,"BodyType":"Star","JumpDist":20.532,"FuelUsed":2.184843,"FuelLevel":29.815157}"#;
+    const SCAN: &str = r#"{"timestamp":"2022-09-10T08:12:55Z","event":"Scan","ScanType":"Detailed","BodyName":"Col 285 Sector KS-T d3-68 A 1","BodyID":3,"Parents":[{"Star":1}],"StarSystem":"Col 285 Sector KS-T d3-68","SystemAddress":2345873295747,"DistanceFromArrivalLS":12.46,"TidalLock":true,"TerraformState":"","PlanetClass":"Icy body","Atmosphere":"","AtmosphereType":"None","Volcanism":"","MassEM":0.0022,"Radius":1169431.875,"SurfaceGravity":0.64,"SurfaceTemperature":61.43,"SurfacePressure":0.0,"Landable":true,"Composition":{"Ice":0.68,"Rock":0.21,"Metal":0.1},"SemiMajorAxis":3694582223.8922,"Eccentricity":0.0,"OrbitalInclination":-0.01,"Periapsis":11.18,"OrbitalPeriod":1206218.659878,"AscendingNode":-81.02,"MeanAnomaly":243.04,"RotationPeriod":1206263.6036,"AxialTilt":-0.21,"WasDiscovered":false,"WasMapped":false}"#;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_filter_events_and_dates() {
+        let jump = json::parse(FSD_JUMP).unwrap();
+        let scan = json::parse(SCAN).unwrap();
+        assert!(UploadOptions::default().matches(&jump));
+
+        let options =
+            UploadOptions::from_args(&to_args(&["--events", "Scan, FSSDiscoveryScan"])).unwrap();
+        assert_eq!(options.events, vec!["Scan", "FSSDiscoveryScan"]);
+        assert!(!options.matches(&jump));
+        assert!(options.matches(&scan));
+
+        let options =
+            UploadOptions::from_args(&to_args(&["--from", "2022-09-09", "--dry-run"])).unwrap();
+        assert!(options.dry_run);
+        assert!(!options.matches(&jump));
+        assert!(options.matches(&scan));
+        let options = UploadOptions::from_args(&to_args(&["--to", "2022-09-08"])).unwrap();
+        assert!(options.matches(&jump));
+        assert!(!options.matches(&scan));
+        //Without a timestamp the date filter can't tell
+        assert!(!options.matches(&json::parse(r#"{"event":"FSDJump"}"#).unwrap()));
+
+        assert!(UploadOptions::from_args(&to_args(&["--from", "08.09.2022"])).is_err());
+        assert!(UploadOptions::from_args(&to_args(&["--to"])).is_err());
+    }
+
+    #[test]
+    fn checkpoint_only_resumes_the_same_filters() {
+        let path = get_temp_path("upload-checkpoint");
+        let options = UploadOptions::from_args(&to_args(&["--events", "Scan"])).unwrap();
+        let checkpoint = Checkpoint::from_file(path.clone(), options.get_filter());
+        assert!(checkpoint.load().is_none());
+        checkpoint.save(&Position {
+            journal: "2022-09-10T081022".to_string(),
+            line: 42,
+        });
+
+        let position = Checkpoint::from_file(path.clone(), options.get_filter())
+            .load()
+            .unwrap();
+        assert_eq!(position.journal, "2022-09-10T081022");
+        assert_eq!(position.line, 42);
+        let other = UploadOptions::default().get_filter();
+        assert!(Checkpoint::from_file(path.clone(), other).load().is_none());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn upload_resumes_at_the_checkpoint_journal() {
+        //Newest first, like the journal reader lists them
+        let journals = to_args(&[
+            "2022-09-12T193501",
+            "2022-09-10T081022",
+            "2022-09-08T212104",
+        ]);
+        let start = Position {
+            journal: "2022-09-10T081022".to_string(),
+            line: 42,
+        };
+        assert_eq!(
+            select_journals(journals.clone(), Some(&start), None),
+            vec!["2022-09-10T081022", "2022-09-12T193501"]
+        );
+        assert_eq!(
+            select_journals(journals.clone(), None, NaiveDate::from_ymd_opt(2022, 9, 10)),
+            vec!["2022-09-08T212104", "2022-09-10T081022"]
+        );
+
+        let gone = Position {
+            journal: "2022-09-01T120000".to_string(),
+            line: 7,
+        };
+        assert_eq!(select_journals(journals, Some(&gone), None).len(), 3);
+    }
 }
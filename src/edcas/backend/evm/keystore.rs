@@ -0,0 +1,256 @@
+use std::fs;
+use std::io::{stdin, IsTerminal, Write};
+use std::path::Path;
+
+use ethers::core::rand::thread_rng;
+use ethers::prelude::{LocalWallet, Signer};
+use ethers::utils::hex;
+use log::{error, info, warn};
+
+use crate::edcas::settings::{get_data_path, EvmSettings};
+
+/// Used instead of asking, for starts without a terminal
+pub const PASSPHRASE_VARIABLE: &str = "EDCAS_KEYSTORE_PASSPHRASE";
+
+pub fn get_default_path() -> String {
+    get_data_path("keystore.json")
+}
+
+/// Encrypts the key into a V3 keystore file and returns the address
+pub fn create(path: &str, private_key: &str, passphrase: &str) -> Result<String, String> {
+    let path = Path::new(path);
+    if path.exists() {
+        return Err(format!(
+            "Keystore {} exists already, export or move it first",
+            path.display()
+        ));
+    }
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(format!("Invalid keystore path: {}", path.display()))?;
+    if let Err(err) = fs::create_dir_all(directory) {
+        warn!("Couldn't create {}: {}", directory.display(), err);
+    }
+    let key = hex::decode(private_key.trim_start_matches("0x"))
+        .map_err(|err| format!("Invalid private key: {}", err))?;
+    let (wallet, _) =
+        LocalWallet::encrypt_keystore(directory, &mut thread_rng(), key, passphrase, Some(name))
+            .map_err(|err| format!("Couldn't write keystore {}: {}", path.display(), err))?;
+    Ok(format!("{:?}", wallet.address()))
+}
+
+/// Decrypts the keystore and returns the private key as hex
+pub fn unlock(path: &str, passphrase: &str) -> Result<String, String> {
+    LocalWallet::decrypt_keystore(path, passphrase)
+        .map(|wallet| hex::encode(wallet.signer().to_bytes()))
+        .map_err(|err| format!("Couldn't unlock keystore {}: {}", path, err))
+}
+
+pub fn generate_private_key() -> String {
+    hex::encode(LocalWallet::new(&mut thread_rng()).signer().to_bytes())
+}
+
+/// Startup step before the EVM interpreter: plain keys of older settings and generated keys
+/// are moved into a keystore. Returns true if the settings have to be saved.
+pub fn migrate_private_key(evm_settings: &mut EvmSettings) -> bool {
+    migrate_into(evm_settings, &get_default_path(), || {
+        read_new_passphrase("Passphrase to encrypt the EVM private key")
+    })
+}
+
+/// A generated key is only kept if it ends up in the keystore, it is never saved in plain text.
+/// Without a passphrase, e.g. started from the GUI without a terminal, the client stays read-only.
+fn migrate_into(
+    evm_settings: &mut EvmSettings,
+    path: &str,
+    read_passphrase: impl FnOnce() -> Option<String>,
+) -> bool {
+    if !evm_settings.keystore_path.is_empty() {
+        return false;
+    }
+    //Without sharing no key is needed, the client only reads from the contract
+    let generated = evm_settings.private_key.is_empty();
+    if generated && !evm_settings.allow_share_data {
+        return false;
+    }
+
+    let Some(passphrase) = read_passphrase() else {
+        if generated {
+            warn!(
+                "No passphrase given, no private key is generated. Set {} to share data",
+                PASSPHRASE_VARIABLE
+            );
+        } else {
+            warn!("No passphrase given, the private key stays in plain text");
+        }
+        return false;
+    };
+    if generated {
+        info!("Generating a new private key");
+        evm_settings.private_key = generate_private_key();
+    }
+    match create(path, &evm_settings.private_key, &passphrase) {
+        Ok(address) => {
+            info!("Moved the private key of {} into {}", address, path);
+            evm_settings.keystore_path = path.to_string();
+            true
+        }
+        Err(err) => {
+            error!("{}", err);
+            if generated {
+                evm_settings.private_key.clear();
+            }
+            false
+        }
+    }
+}
+
+/// Unlocks the keystore with the passphrase, the key is kept in the settings until the client exits
+pub fn unlock_settings(evm_settings: &mut EvmSettings, passphrase: &str) -> Result<(), String> {
+    evm_settings.private_key = unlock(&evm_settings.keystore_path, passphrase)?;
+    info!("Unlocked keystore {}", evm_settings.keystore_path);
+    Ok(())
+}
+
+/// Takes the passphrase from the environment or asks for it on the terminal
+pub fn read_passphrase(prompt: &str) -> Option<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VARIABLE) {
+        return Some(passphrase);
+    }
+    read_hidden(&format!("{}: ", prompt))
+}
+
+/// Like `read_passphrase`, but asks twice to catch typos
+pub fn read_new_passphrase(prompt: &str) -> Option<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VARIABLE) {
+        return Some(passphrase);
+    }
+    let passphrase = read_hidden(&format!("{}: ", prompt))?;
+    if read_hidden("Repeat passphrase: ")? != passphrase {
+        println!("Passphrases don't match");
+        return None;
+    }
+    Some(passphrase)
+}
+
+/// Reads a line without echo. Returns `None` without a terminal or if cancelled with esc.
+#[cfg(feature = "tui")]
+pub fn read_hidden(prompt: &str) -> Option<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+    if !stdin().is_terminal() {
+        return None;
+    }
+    print!("{}", prompt);
+    std::io::stdout().flush().ok()?;
+    enable_raw_mode().ok()?;
+    let mut input = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Some(input),
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(char) => input.push(char),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(_) => break None,
+        }
+    };
+    let _ = disable_raw_mode();
+    println!();
+    result
+}
+
+/// Without the tui there is no way to hide the input, so it is read as a normal line
+#[cfg(not(feature = "tui"))]
+pub fn read_hidden(prompt: &str) -> Option<String> {
+    if !stdin().is_terminal() {
+        return None;
+    }
+    print!("{}", prompt);
+    std::io::stdout().flush().ok()?;
+    let mut input = String::new();
+    stdin().read_line(&mut input).ok()?;
+    Some(input.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::Settings;
+
+    #[test]
+    fn keystore_round_trip() {
+        let directory = std::env::temp_dir().join(format!("edcas-keystore-{}", std::process::id()));
+        let path = directory.join("keystore.json").display().to_string();
+        let private_key = generate_private_key();
+
+        let address = create(&path, &private_key, "passphrase").unwrap();
+        let wallet = private_key.parse::<LocalWallet>().unwrap();
+        assert_eq!(address, format!("{:?}", wallet.address()));
+        assert!(create(&path, &private_key, "passphrase").is_err());
+
+        assert!(unlock(&path, "wrong").is_err());
+        assert_eq!(unlock(&path, "passphrase").unwrap(), private_key);
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    fn get_settings() -> Settings {
+        let mut settings = Settings::from_example();
+        settings.evm_settings.allow_share_data = true;
+        settings.evm_settings.private_key = String::new();
+        settings.evm_settings.keystore_path = String::new();
+        settings
+    }
+
+    #[test]
+    fn failed_migration_saves_no_generated_key() {
+        let directory =
+            std::env::temp_dir().join(format!("edcas-migration-{}", std::process::id()));
+        let path = directory.join("keystore.json").display().to_string();
+
+        let mut settings = get_settings();
+        assert!(!migrate_into(&mut settings.evm_settings, &path, || None));
+        assert!(settings.evm_settings.private_key.is_empty());
+        assert_eq!(settings.to_json()["evm"]["private-key"], "");
+
+        //An existing keystore makes the creation fail after the key was generated
+        create(&path, &generate_private_key(), "passphrase").unwrap();
+        let mut settings = get_settings();
+        assert!(!migrate_into(&mut settings.evm_settings, &path, || {
+            Some("passphrase".to_string())
+        }));
+        assert!(settings.evm_settings.private_key.is_empty());
+        assert_eq!(settings.to_json()["evm"]["private-key"], "");
+
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn generated_key_is_moved_into_the_keystore() {
+        let directory =
+            std::env::temp_dir().join(format!("edcas-generated-{}", std::process::id()));
+        let path = directory.join("keystore.json").display().to_string();
+
+        let mut settings = get_settings();
+        assert!(migrate_into(&mut settings.evm_settings, &path, || {
+            Some("passphrase".to_string())
+        }));
+        assert_eq!(settings.evm_settings.keystore_path, path);
+        assert_eq!(
+            unlock(&path, "passphrase").unwrap(),
+            settings.evm_settings.private_key
+        );
+        assert_eq!(settings.to_json()["evm"]["private-key"], "");
+
+        let _ = fs::remove_dir_all(directory);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use log::{debug, error, info};
+use tokio::runtime::Runtime;
+
+/// Hands out nonces for the interpreter. A nonce is pending from being handed out until its
+/// transaction is mined. Nonces of transactions which never reached the chain are released and
+/// handed out again first, so no gap blocks the following transactions. A stuck transaction keeps
+/// its nonce pending until a resync finds it mined or dropped from the mempool.
+pub struct NonceManager {
+    client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    runtime: Runtime,
+    /// Next nonce which was never handed out
+    next: U256,
+    pending: BTreeSet<U256>,
+    released: BTreeSet<U256>,
+    /// Pending nonces of transactions which are still in the mempool after all replacements
+    stuck: BTreeSet<U256>,
+    /// Transaction count of the latest block at the last resync, every nonce below is mined
+    mined: U256,
+}
+
+impl NonceManager {
+    pub fn new(client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>) -> NonceManager {
+        let mut nonce_manager = NonceManager {
+            client,
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap(),
+            next: U256::zero(),
+            pending: BTreeSet::new(),
+            released: BTreeSet::new(),
+            stuck: BTreeSet::new(),
+            mined: U256::zero(),
+        };
+        nonce_manager.resync();
+        nonce_manager
+    }
+
+    pub fn next_nonce(&mut self) -> U256 {
+        let nonce = match self.released.pop_first() {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = self.next;
+                self.next = nonce + 1;
+                nonce
+            }
+        };
+        self.pending.insert(nonce);
+        nonce
+    }
+
+    /// The transaction is mined
+    pub fn confirm(&mut self, nonce: U256) {
+        self.pending.remove(&nonce);
+    }
+
+    /// The transaction never made it to the chain, so the nonce is free again
+    pub fn release(&mut self, nonce: U256) {
+        if self.pending.remove(&nonce) {
+            self.released.insert(nonce);
+        }
+    }
+
+    /// The transaction is still in the mempool, so the nonce can't be handed out again
+    pub fn mark_stuck(&mut self, nonce: U256) {
+        if self.pending.contains(&nonce) {
+            self.stuck.insert(nonce);
+        }
+    }
+
+    pub fn is_stuck(&self, nonce: U256) -> bool {
+        self.stuck.contains(&nonce)
+    }
+
+    pub fn is_mined(&self, nonce: U256) -> bool {
+        nonce < self.mined
+    }
+
+    /// Asks the node for the transaction count including the mempool. Everything below is used,
+    /// everything above which is not pending anymore is a gap and gets filled first.
+    /// Stuck transactions which were mined or dropped from the mempool are cleaned up, the nonce
+    /// of a dropped one becomes a gap.
+    pub fn resync(&mut self) {
+        let address = self.client.address();
+        let counts = self.runtime.block_on(async {
+            let mined = self
+                .client
+                .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+                .await?;
+            let count = self
+                .client
+                .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+                .await?;
+            Ok::<_, <SignerMiddleware<Provider<Http>, LocalWallet> as Middleware>::Error>((
+                mined, count,
+            ))
+        });
+        let (mined, count) = match counts {
+            Ok(counts) => counts,
+            Err(err) => {
+                error!("Couldn't get transaction count: {}", err);
+                return;
+            }
+        };
+        self.mined = mined;
+        for nonce in self.stuck.iter().filter(|nonce| *nonce >= &count) {
+            self.pending.remove(nonce);
+        }
+        self.stuck.retain(|nonce| nonce >= &mined && nonce < &count);
+        let stuck = &self.stuck;
+        self.pending
+            .retain(|nonce| nonce >= &count || stuck.contains(nonce));
+        self.next = self.next.max(count);
+        if self.pending.is_empty() {
+            self.next = count;
+        }
+        self.released.clear();
+        let mut nonce = count;
+        while nonce < self.next {
+            if !self.pending.contains(&nonce) {
+                self.released.insert(nonce);
+            }
+            nonce += U256::one();
+        }
+        info!(
+            "Nonce resynced: {} (Pending: {}, stuck: {})",
+            count,
+            self.pending.len(),
+            self.stuck.len()
+        );
+        if !self.released.is_empty() {
+            debug!("Nonce gaps to fill: {:?}", self.released);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::journal_interpreter::Edcas;
+    use crate::edcas::backend::evm::test_utils::{get_anvil_contract, mine, start_anvil};
+
+    /// Sends a transaction with the nonce into the mempool of anvil
+    async fn send(contract: &Edcas, nonce: U256) -> TxHash {
+        let call = contract
+            .set_body_count(10477373803, 12, 1700000000.into())
+            .legacy()
+            .nonce(nonce);
+        let pending = call.send().await.unwrap();
+        pending.tx_hash()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a local anvil binary"]
+    async fn resync_cleans_up_stuck_nonces() {
+        let anvil = start_anvil();
+        let contract = get_anvil_contract(&anvil);
+        let client = contract.client();
+        let mut nonces = tokio::task::spawn_blocking(move || NonceManager::new(client))
+            .await
+            .unwrap();
+
+        //A stuck transaction in the mempool keeps its nonce until it is mined
+        let nonce = nonces.next_nonce();
+        assert_eq!(nonce, U256::zero());
+        send(&contract, nonce).await;
+        nonces.mark_stuck(nonce);
+        let mut nonces = tokio::task::spawn_blocking(move || {
+            nonces.resync();
+            nonces
+        })
+        .await
+        .unwrap();
+        assert!(nonces.is_stuck(nonce));
+        assert!(!nonces.is_mined(nonce));
+        assert_eq!(nonces.next_nonce(), U256::one());
+        nonces.release(U256::one());
+
+        mine(&contract).await;
+        let mut nonces = tokio::task::spawn_blocking(move || {
+            nonces.resync();
+            nonces
+        })
+        .await
+        .unwrap();
+        assert!(!nonces.is_stuck(nonce));
+        assert!(nonces.is_mined(nonce));
+
+        //The nonce of a dropped transaction is a gap and handed out again first,
+        //while the following nonce is still in use by a worker
+        let dropped = nonces.next_nonce();
+        let following = nonces.next_nonce();
+        assert_eq!(dropped, U256::one());
+        let hash = send(&contract, dropped).await;
+        nonces.mark_stuck(dropped);
+        contract
+            .client()
+            .provider()
+            .request::<_, ()>("anvil_dropTransaction", [hash])
+            .await
+            .unwrap();
+        let mut nonces = tokio::task::spawn_blocking(move || {
+            nonces.resync();
+            nonces
+        })
+        .await
+        .unwrap();
+        assert!(!nonces.is_stuck(dropped));
+        assert!(!nonces.is_mined(dropped));
+        assert_eq!(nonces.next_nonce(), dropped);
+        assert_eq!(nonces.next_nonce(), following + 1);
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use json::{object, JsonValue};
+use log::{error, info};
+
+use crate::edcas::settings::get_data_path;
+
+pub fn get_carrier_path() -> String {
+    get_data_path("own-carrier.json")
+}
+
+/// Kinds of data the interpreter can share with the EDCAS network
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataCategory {
+    Systems,
+    Bodies,
+    Stations,
+    Carriers,
+    OwnCarrier,
+}
+
+impl DataCategory {
+    pub const ALL: [DataCategory; 5] = [
+        DataCategory::Systems,
+        DataCategory::Bodies,
+        DataCategory::Stations,
+        DataCategory::Carriers,
+        DataCategory::OwnCarrier,
+    ];
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            DataCategory::Systems => "Visited systems and their body count",
+            DataCategory::Bodies => "Scanned stars and planets and their signals",
+            DataCategory::Stations => "Docked stations and their markets",
+            DataCategory::Carriers => "Carriers of other commanders you docked at",
+            DataCategory::OwnCarrier => {
+                "Name, services, docking access and jumps of your own carrier"
+            }
+        }
+    }
+}
+
+impl Display for DataCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DataCategory::Systems => "systems",
+            DataCategory::Bodies => "bodies",
+            DataCategory::Stations => "stations",
+            DataCategory::Carriers => "carriers",
+            DataCategory::OwnCarrier => "own-carrier",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DataCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DataCategory::ALL
+            .into_iter()
+            .find(|category| category.to_string() == s)
+            .ok_or(format!("Unknown data category: {}", s))
+    }
+}
+
+/// Decides which journal events are shared and when
+pub struct PrivacyFilter {
+    shared_categories: Vec<DataCategory>,
+    /// Seconds after the event before it is published
+    delay: i64,
+    /// Learned from the carrier events, so docking at it counts as own carrier
+    own_carrier: Option<OwnCarrier>,
+    /// The own carrier is kept here, its events might be in a journal of an earlier session
+    carrier_path: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct OwnCarrier {
+    market_id: u64,
+    callsign: String,
+}
+
+impl PrivacyFilter {
+    pub fn open(shared_categories: Vec<DataCategory>, publish_delay: u64) -> PrivacyFilter {
+        PrivacyFilter::from_file(shared_categories, publish_delay, get_carrier_path())
+    }
+
+    pub fn from_file(
+        shared_categories: Vec<DataCategory>,
+        publish_delay: u64,
+        carrier_path: String,
+    ) -> PrivacyFilter {
+        let own_carrier = fs::read_to_string(&carrier_path)
+            .ok()
+            .and_then(|content| json::parse(&content).ok())
+            .and_then(|json| {
+                Some(OwnCarrier {
+                    market_id: json["market-id"].as_u64()?,
+                    callsign: json["callsign"].as_str().unwrap_or_default().to_string(),
+                })
+            });
+        PrivacyFilter {
+            shared_categories,
+            delay: publish_delay as i64 * 60,
+            own_carrier,
+            carrier_path,
+        }
+    }
+
+    /// Category of the data the event would be shared as, `None` for events which are not shared at all
+    pub fn get_category(&mut self, json: &JsonValue) -> Option<DataCategory> {
+        match json["event"].as_str().unwrap_or("") {
+            "FSDJump" | "FSSDiscoveryScan" => Some(DataCategory::Systems),
+            "Scan" | "FSSBodySignals" | "SAASignalsFound" => Some(DataCategory::Bodies),
+            "CarrierJumpRequest" | "CarrierJumpCancelled" | "CarrierBuy" | "CarrierStats" => {
+                self.learn_own_carrier(json);
+                Some(DataCategory::OwnCarrier)
+            }
+            "Docked" => {
+                if json["StationType"].as_str() != Some("FleetCarrier") {
+                    Some(DataCategory::Stations)
+                } else if self.is_own_carrier(json) {
+                    Some(DataCategory::OwnCarrier)
+                } else {
+                    Some(DataCategory::Carriers)
+                }
+            }
+            //Markets without an event come from the eddn adapter
+            "" if !json["commodities"].is_empty() => Some(DataCategory::Stations),
+            _ => None,
+        }
+    }
+
+    fn is_own_carrier(&self, json: &JsonValue) -> bool {
+        let Some(own_carrier) = &self.own_carrier else {
+            return false;
+        };
+        json["MarketID"].as_u64() == Some(own_carrier.market_id)
+            || (!own_carrier.callsign.is_empty()
+                && json["StationName"].as_str() == Some(own_carrier.callsign.as_str()))
+    }
+
+    /// Only CarrierBuy and CarrierStats name the callsign, the jump events keep the known one
+    fn learn_own_carrier(&mut self, json: &JsonValue) {
+        let Some(market_id) = json["CarrierID"].as_u64() else {
+            return;
+        };
+        let callsign = match json["Callsign"].as_str() {
+            Some(callsign) => callsign.to_string(),
+            None => match &self.own_carrier {
+                Some(own_carrier) if own_carrier.market_id == market_id => {
+                    own_carrier.callsign.clone()
+                }
+                _ => String::new(),
+            },
+        };
+        let own_carrier = OwnCarrier {
+            market_id,
+            callsign,
+        };
+        if self.own_carrier.as_ref() == Some(&own_carrier) {
+            return;
+        }
+        info!(
+            "Own carrier is {} ({})",
+            own_carrier.callsign, own_carrier.market_id
+        );
+        let content = object! {
+            "market-id": own_carrier.market_id,
+            "callsign": own_carrier.callsign.as_str(),
+        };
+        if let Err(err) = fs::write(&self.carrier_path, content.dump()) {
+            error!("Couldn't write {}: {}", self.carrier_path, err);
+        }
+        self.own_carrier = Some(own_carrier);
+    }
+
+    pub fn is_shared(&self, category: DataCategory) -> bool {
+        self.shared_categories.contains(&category)
+    }
+
+    /// Unix time at which the event may be published
+    pub fn get_release_time(&self, json: &JsonValue) -> i64 {
+        if self.delay == 0 {
+            return 0;
+        }
+        json["timestamp"]
+            .as_str()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.timestamp() + self.delay)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::get_temp_path;
+
+    const CARRIER_STATS: &str = r#"{"timestamp":"2022-09-08T21:27:21Z","event":"CarrierStats","CarrierID":3704402432,"Callsign":"Q2K-BHB","Name":"FUXBAU","DockingAccess":"squadron","AllowNotorious":false,"FuelLevel":529,"JumpRangeCurr":500.000000,"JumpRangeMax":500.000000,"PendingDecommission":false,"SpaceUsage":{"TotalCapacity":25000,"Crew":6170,"Cargo":0,"CargoSpaceReserved":0,"ShipPacks":0,"ModulePacks":0,"FreeSpace":18830},"Finance":{"CarrierBalance":0,"ReserveBalance":0,"AvailableBalance":0,"ReservePercent":0},"Crew":[],"ShipPacks":[],"ModulePacks":[]}"#;
+    const DOCKED_OWN: &str = r#"{"timestamp":"2022-09-08T21:30:02Z","event":"Docked","StationName":"Q2K-BHB","StationType":"FleetCarrier","Taxi":false,"Multicrew":false,"StarSystem":"Col 285 Sector KS-T d3-68","SystemAddress":2345873295747,"MarketID":3704402432,"StationFaction":{"Name":"FleetCarrier"},"StationGovernment":"$government_Carrier;","StationGovernment_Localised":"Privater Besitz","StationServices":["dock","autodock","commodities","contacts","crewlounge","rearm","refuel","repair","engineer","flightcontroller","stationoperations","stationMenu","carriermanagement","carrierfuel","socialspace"],"StationEconomy":"$economy_Carrier;","StationEconomy_Localised":"Privatunternehmen","StationEconomies":[{"Name":"$economy_Carrier;","Name_Localised":"Privatunternehmen","Proportion":1.000000}],"DistFromStarLS":432.478495,"LandingPads":{"Small":4,"Medium":4,"Large":8}}"#;
+    const DOCKED_OTHER: &str = r#"{"timestamp":"2022-09-08T22:11:45Z","event":"Docked","StationName":"K7Q-BQL","StationType":"FleetCarrier","Taxi":false,"Multicrew":false,"StarSystem":"Col 285 Sector KS-T d3-68","SystemAddress":2345873295747,"MarketID":3709409280,"StationFaction":{"Name":"FleetCarrier"},"StationGovernment":"$government_Carrier;","StationGovernment_Localised":"Privater Besitz","StationServices":["dock","autodock","commodities","contacts","crewlounge","rearm","refuel","repair","flightcontroller","stationoperations","stationMenu","carriermanagement","carrierfuel","socialspace"],"StationEconomy":"$economy_Carrier;","StationEconomy_Localised":"Privatunternehmen","StationEconomies":[{"Name":"$economy_Carrier;","Name_Localised":"Privatunternehmen","Proportion":1.000000}],"DistFromStarLS":432.478495,"LandingPads":{"Small":4,"Medium":4,"Large":8}}"#;
+    const DOCKED_STATION: &str = r#"{"timestamp":"2022-09-09T18:01:17Z","event":"Docked","StationName":"Jameson Memorial","StationType":"Orbis","Taxi":false,"Multicrew":false,"StarSystem":"Shinrarta Dezhra","SystemAddress":3932277478106,"MarketID":128666762,"StationFaction":{"Name":"Pilots' Federation Local Branch"},"StationGovernment":"$government_Democracy;","StationGovernment_Localised":"Demokratie","StationAllegiance":"PilotsFederation","StationServices":["dock","autodock","commodities","contacts","exploration","missions","outfitting","crewlounge","rearm","refuel","repair","shipyard","tuning","engineer","missionsgenerated","flightcontroller","stationoperations","powerplay","searchrescue","stationMenu","shop","livery","socialspace","bartender","vistagenomics","pioneersupplies","apexinterstellar","frontlinesolutions"],"StationEconomy":"$economy_HighTech;","StationEconomy_Localised":"Hightech","StationEconomies":[{"Name":"$economy_HighTech;","Name_Localised":"Hightech","Proportion":0.800000},{"Name":"$economy_Industrial;","Name_Localised":"Industrie","Proportion":0.200000}],"DistFromStarLS":346.260538,"LandingPads":{"Small":17,"Medium":18,"Large":9}}"#;
+    const FSD_JUMP: &str = r#"{"timestamp":"2022-09-08T21:40:13Z","event":"FSDJump","Taxi":false,"Multicrew":false,"StarSystem":"Col 285 Sector KS-T d3-68","SystemAddress":2345873295747,"StarPos":[-65.40625,-70.68750,-89.53125],"SystemAllegiance":"","SystemEconomy":"$economy_None;","SystemEconomy_Localised":"n/v","SystemSecondEconomy":"$economy_None;","SystemSecondEconomy_Localised":"n/v","SystemGovernment":"$government_None;","SystemGovernment_Localised":"n/v","SystemSecurity":"$GAlAXY_MAP_INFO_state_anarchy;","SystemSecurity_Localised":"Anarchie","Population":0,"Body":"Col 285 Sector KS-T d3-68 A","BodyID":1,"BodyType":"Star","JumpDist":20.532,"FuelUsed":2.184843,"FuelLevel":29.815157}"#;
+
+    #[test]
+    fn events_are_categorised() {
+        let path = get_temp_path("privacy-categories");
+        let mut filter = PrivacyFilter::from_file(vec![DataCategory::Systems], 0, path);
+        let parse = |line: &str| json::parse(line).unwrap();
+
+        assert_eq!(
+            filter.get_category(&parse(FSD_JUMP)),
+            Some(DataCategory::Systems)
+        );
+        assert_eq!(
+            filter.get_category(&parse(DOCKED_STATION)),
+            Some(DataCategory::Stations)
+        );
+        assert_eq!(
+            filter.get_category(&parse(DOCKED_OWN)),
+            Some(DataCategory::Carriers)
+        );
+        assert_eq!(
+            filter.get_category(&parse(
+                r#"{"timestamp":"2022-09-08T21:40:13Z","event":"Music","MusicTrack":"Exploration"}"#
+            )),
+            None
+        );
+        assert!(filter.is_shared(DataCategory::Systems));
+        assert!(!filter.is_shared(DataCategory::Stations));
+
+        //Once the carrier is known, docking at it is own carrier data
+        assert_eq!(
+            filter.get_category(&parse(CARRIER_STATS)),
+            Some(DataCategory::OwnCarrier)
+        );
+        assert_eq!(
+            filter.get_category(&parse(DOCKED_OWN)),
+            Some(DataCategory::OwnCarrier)
+        );
+        assert_eq!(
+            filter.get_category(&parse(DOCKED_OTHER)),
+            Some(DataCategory::Carriers)
+        );
+    }
+
+    #[test]
+    fn own_carrier_is_remembered() {
+        let path = get_temp_path("privacy-carrier");
+        let mut filter = PrivacyFilter::from_file(vec![], 0, path.clone());
+        filter.get_category(&json::parse(CARRIER_STATS).unwrap());
+
+        let mut filter = PrivacyFilter::from_file(vec![], 0, path.clone());
+        let docked = json::parse(DOCKED_OWN).unwrap();
+        assert_eq!(filter.get_category(&docked), Some(DataCategory::OwnCarrier));
+
+        //The callsign is enough, e.g. for an event without MarketID
+        let mut docked = docked;
+        docked.remove("MarketID");
+        assert_eq!(filter.get_category(&docked), Some(DataCategory::OwnCarrier));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn events_are_released_after_the_delay() {
+        let path = get_temp_path("privacy-delay");
+        let jump = json::parse(FSD_JUMP).unwrap();
+        let timestamp = DateTime::parse_from_rfc3339("2022-09-08T21:40:13Z")
+            .unwrap()
+            .timestamp();
+
+        let filter = PrivacyFilter::from_file(vec![], 0, path.clone());
+        assert_eq!(filter.get_release_time(&jump), 0);
+        let filter = PrivacyFilter::from_file(vec![], 30, path);
+        assert_eq!(filter.get_release_time(&jump), timestamp + 30 * 60);
+        assert_eq!(filter.get_release_time(&json::parse("{}").unwrap()), 0);
+    }
+}
@@ -1,545 +1,613 @@
 use crate::edcas::backend;
+use crate::edcas::backend::data_backend::{DataBackend, DataUpdate, SystemMetaData};
 use crate::edcas::backend::evm::edcas_contract::*;
-use crate::edcas::backend::evm::journal_interpreter::Edcas;
 use crate::edcas::carrier::Carrier;
 use crate::edcas::explorer::body::{BodyType, Parent, Signal};
 use crate::edcas::explorer::planet::Planet;
 use crate::edcas::explorer::star::Star;
-use crate::edcas::request_handler::EvmUpdate::{CarrierList, StationList};
 use crate::edcas::settings::Settings;
 use crate::edcas::station::{CommodityListening, StationMetaData};
-use bus::Bus;
 use chrono::DateTime;
 use ethers::contract::ContractCall;
-use ethers::middleware::SignerMiddleware;
-use ethers::prelude::{Http, LocalWallet, Provider, U256};
+use ethers::prelude::{Address, Http, Middleware, Provider, U256, U64};
 use log::{debug, error};
-use std::sync::mpsc::Receiver;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
 
-#[derive(Clone)]
-pub enum EvmUpdate {
-    CarrierList(Vec<Carrier>),
-    StationList(Vec<StationIdentity>),
-    StationMetaData(u64, StationMetaData),
-    StationCommodityListening(u64, Vec<CommodityListening>),
-    SystemMetaData(u64, SystemMetaData),
-    PlanetList(u64, Vec<BodyType>),
-    //System address, body id, signal
-}
-/**
-+ StationMetaData(u64): MarketId from station
-* StationCommodityListener(u64): MarketId from station
-* SystemMetaData(u64): SystemAddress from system
-*/
-#[derive(Clone)]
-pub enum EvmRequest {
-    StationMetaData(u64),
-    StationCommodityListener(u64),
-    SystemMetaData(u64),
-    SystemPlanetData(u64),
-}
-
-pub struct EvmUpdater {
-    writer: Bus<EvmUpdate>,
-    receiver: Receiver<EvmRequest>,
+/// Reads the galaxy data from the EDCAS smart contract
+pub struct EvmBackend {
     settings: Arc<Mutex<Settings>>,
-    contract: Edcas,
+    contract: EdcasReader,
+    runtime: Runtime,
+    /// Last block whose events are handled, `None` until the lists are loaded
+    last_block: Option<U64>,
+    /// Commodity names of each market. The listenings are stored per name in the contract,
+    /// the names are only known from the update events.
+    commodity_names: HashMap<u64, BTreeSet<String>>,
 }
 
-#[derive(Clone)]
-pub struct SystemMetaData {
-    pub name: String,
-    pub address: u64,
-    pub allegiance: String,
-    pub economy: String,
-    pub second_economy: String,
-    pub government: String,
-    pub security: String,
-    pub population: String,
-    pub body_count: u8,
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-}
+/// Largest block range asked for in one event query, nodes reject bigger ones
+const MAX_BLOCK_RANGE: u64 = 10_000;
 
-pub fn initialize(
-    writer: Bus<EvmUpdate>,
-    receiver: Receiver<EvmRequest>,
-    settings: Arc<Mutex<Settings>>,
-) -> EvmUpdater {
-    let contract = settings
-        .lock()
-        .unwrap()
-        .evm_settings
-        .contract
-        .clone()
-        .unwrap();
-    EvmUpdater {
-        writer,
-        receiver,
+/// Reading needs no signer, so a plain provider is enough
+pub type EdcasReader = EDCAS<Provider<Http>>;
+
+pub fn initialize(settings: Arc<Mutex<Settings>>) -> Result<EvmBackend, String> {
+    let (url, address) = {
+        let settings = settings.lock().unwrap();
+        (
+            settings.evm_settings.url.clone(),
+            settings.evm_settings.smart_contract_address.clone(),
+        )
+    };
+    let provider = Provider::<Http>::try_from(url.as_str())
+        .map_err(|err| format!("Invalid EVM url {}: {}", url, err))?;
+    let address = address
+        .parse::<Address>()
+        .map_err(|err| format!("Invalid smart contract address {}: {}", address, err))?;
+    Ok(EvmBackend {
         settings,
-        contract,
-    }
+        contract: EDCAS::new(address, Arc::new(provider)),
+        runtime: tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+        last_block: None,
+        commodity_names: HashMap::new(),
+    })
 }
 
-impl EvmUpdater {
-    pub fn run_update(&mut self) {
-        let contract = &self.contract;
-        tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async {
-                    //Receive Requests
-                    {
-                        if let Ok(request) = self.receiver.try_recv() {
-                            match request {
-                                EvmRequest::StationMetaData(market_id) => {
-                                    let function_call: ContractCall<
-                                        SignerMiddleware<Provider<Http>, LocalWallet>,
-                                        (
-                                            bool,
-                                            U256,
-                                            u64,
-                                            String,
-                                            Faction,
-                                            String,
-                                            String,
-                                            String,
-                                            Floating,
-                                            String,
-                                        ),
-                                    > = contract.station_map(market_id);
-                                    match function_call.legacy().call().await {
-                                        Ok(result) => {
-                                            self.writer.broadcast(EvmUpdate::StationMetaData(
-                                                market_id,
-                                                StationMetaData {
-                                                    timestamp: DateTime::from_timestamp(
-                                                        result.1.as_u64() as i64,
-                                                        0,
-                                                    )
-                                                    .unwrap(),
-                                                    services: result.7,
-                                                    system_name: result.3,
-                                                    faction: result.4,
-                                                    government: result.5,
-                                                    economy: result.6,
-                                                    distance: result.8,
-                                                    landingpads: result.9,
-                                                },
-                                            ))
-                                        }
-                                        Err(err) => {
-                                            error!("Error getting station metadata: {err}");
-                                        }
-                                    }
-                                }
-                                EvmRequest::StationCommodityListener(_market_id) => {
-                                    todo!("Implement");
-                                    let function_call: ContractCall<
-                                        SignerMiddleware<Provider<Http>, LocalWallet>,
-                                        (u32, u32, u32, u32, u32, u32, u32),
-                                    > = contract
-                                        .commodity_listening_map(_market_id, "".to_string());
-                                    match function_call.legacy().call().await {
-                                        Ok(_result) => {}
-                                        Err(err) => {
-                                            error!("Error getting station metadata: {err}");
-                                        }
-                                    }
-                                }
-                                EvmRequest::SystemMetaData(system_address) => {
-                                    let function_call: ContractCall<
-                                        SignerMiddleware<Provider<Http>, LocalWallet>,
-                                        (
-                                            U256,
-                                            String,
-                                            String,
-                                            String,
-                                            String,
-                                            String,
-                                            String,
-                                            u64,
-                                            u8,
-                                            Floating,
-                                            Floating,
-                                            Floating,
-                                        ),
-                                    > = contract.system_map(system_address);
-                                    match function_call.legacy().call().await {
-                                        Ok(result) => {
-                                            self.writer.broadcast(EvmUpdate::SystemMetaData(
-                                                system_address,
-                                                SystemMetaData {
-                                                    name: result.1,
-                                                    address: system_address,
-                                                    allegiance: result.2,
-                                                    economy: result.4,
-                                                    second_economy: result.5,
-                                                    government: result.3,
-                                                    security: result.6,
-                                                    population: result.7.to_string(),
-                                                    body_count: result.8,
-                                                    x: backend::floating::floating_to_f64(
-                                                        result.9.decimal,
-                                                        result.9.floating_point,
-                                                    ),
-                                                    y: backend::floating::floating_to_f64(
-                                                        result.10.decimal,
-                                                        result.10.floating_point,
-                                                    ),
-                                                    z: backend::floating::floating_to_f64(
-                                                        result.11.decimal,
-                                                        result.11.floating_point,
-                                                    ),
-                                                },
-                                            ))
-                                        }
-                                        Err(err) => {
-                                            error!("Error getting system metadata: {err}");
-                                        }
-                                    }
-                                }
-                                EvmRequest::SystemPlanetData(system_address) => {
-                                    //TODO Stars and Belt Cluster
-                                    debug!("Call get_highest_body_id_from_system: {system_address}");
-                                    let function_call: ContractCall<
-                                        SignerMiddleware<Provider<Http>, LocalWallet>,
-                                        u8,
-                                    > = contract.get_highest_body_id_from_system(system_address);
-                                    let highest_id = match function_call.legacy().call().await {
-                                        Ok(result) => {
-                                            debug!("highest_id received: {}", result);
-                                            result
-                                        }
-                                        Err(_) => 0,
-                                    };
+impl DataBackend for EvmBackend {
+    fn get_system_meta_data(&self, system_address: u64) -> Result<Option<SystemMetaData>, String> {
+        self.runtime
+            .block_on(get_system_meta_data(&self.contract, system_address))
+            .map(Some)
+    }
 
-                                    let mut planet_list = vec![];
+    fn get_bodies(&self, system_address: u64) -> Result<Vec<BodyType>, String> {
+        Ok(self.runtime.block_on(get_bodies(
+            &self.contract,
+            system_address,
+            self.settings.clone(),
+        )))
+    }
 
-                                    let mut timestamp: U256 = U256::max_value();
-                                    let mut index = 0;
-                                    while index <= highest_id
-                                        || (highest_id == 0 && timestamp != U256::from(0)) {
-                                        debug!("Call star_map: {system_address}-{index}");
-                                        let function_call: ContractCall<
-                                            SignerMiddleware<Provider<Http>, LocalWallet>,
-                                            (
-                                                U256,
-                                                u8,
-                                                String,
-                                                bool,
-                                                bool,
-                                                StarProperties,
-                                                BodyProperties,
-                                            ),
-                                        > = contract.star_map(system_address, index.into());
-                                        match function_call.legacy().call().await {
-                                            Ok(result) => {
-                                                planet_list.push(BodyType::Star(Star {
-                                                    timestamp: result.0.to_string(),
-                                                    event: "EVM".to_string(),
-                                                    scan_type: "n/v".to_string(),
-                                                    body_name: result.2,
-                                                    body_id: result.1 as u64,
-                                                    parents: vec![], //TODO Implement Parents
-                                                    star_system: "".to_string(), //TODO Insert Star System
-                                                    system_address: system_address as i64, //TODO Convert to u64
-                                                    distance_from_arrival_ls:
-                                                    backend::floating::floating_to_f64(
-                                                        result
-                                                            .6
-                                                            .distance_from_arrival_ls
-                                                            .decimal,
-                                                        result
-                                                            .6
-                                                            .distance_from_arrival_ls
-                                                            .floating_point,
-                                                    ),
-                                                    star_type: result.5.type_,
-                                                    subclass: result.5.subclass as i64,
-                                                    stellar_mass: result.5.age_my as f64,
-                                                    radius: backend::floating::floating_to_f64(
-                                                        result.6.radius.decimal,
-                                                        result.6.radius.floating_point,
-                                                    ),
-                                                    absolute_magnitude:
-                                                    backend::floating::floating_to_f64(
-                                                        result.5.absolute_magnitude.decimal,
-                                                        result
-                                                            .5
-                                                            .absolute_magnitude
-                                                            .floating_point,
-                                                    ),
-                                                    age_my: result.5.age_my as i64,
-                                                    surface_temperature:
-                                                    backend::floating::floating_to_f64(
-                                                        result.6.surface_temperature.decimal,
-                                                        result
-                                                            .6
-                                                            .surface_temperature
-                                                            .floating_point,
-                                                    ),
-                                                    luminosity: result.5.luminosity,
-                                                    semi_major_axis: None, //TODO
-                                                    eccentricity: None,    //TODO
-                                                    orbital_inclination: None, //TODO
-                                                    periapsis: None,       //TODO
-                                                    orbital_period: None,  //TODO
-                                                    ascending_node: None,  //TODO
-                                                    mean_anomaly: None,    //TODO
-                                                    rotation_period:
-                                                    backend::floating::floating_to_f64(
-                                                        result.6.rotation_period.decimal,
-                                                        result.6.rotation_period.floating_point,
-                                                    ),
-                                                    axial_tilt: backend::floating::floating_to_f64(
-                                                        result.6.axial_tilt.decimal,
-                                                        result.6.axial_tilt.floating_point,
-                                                    ),
-                                                    was_discovered: result.3,
-                                                    was_mapped: result.4,
-                                                    asteroid_rings: vec![], //TODO
-                                                    settings: self.settings.clone(),
-                                                }))
-                                            }
-                                            Err(err) => {
-                                                error!("Error getting star data: {err} -> Trying to get planet...");
+    fn get_stations(&self) -> Result<Vec<StationIdentity>, String> {
+        self.runtime.block_on(get_station_list(&self.contract))
+    }
 
-                                                debug!("Call get_planet_signals: {system_address}-{index}");
-                                                let function_call: ContractCall<
-                                                    SignerMiddleware<Provider<Http>, LocalWallet>,
-                                                    Vec<PlanetSignal>,
-                                                > = contract.get_planet_signals(system_address, index);
-                                                let planet_signals: Vec<Signal> = function_call
-                                                    .legacy()
-                                                    .call()
-                                                    .await
-                                                    .unwrap_or_else(|err| {
-                                                        error!("Error getting planet signal data: {err}");
-                                                        vec![]
-                                                    })
-                                                    .iter()
-                                                    .map(|planet_signal| {
-                                                        let r#type: String = match planet_signal.type_ {
-                                                            4 => "$SAA_SignalType_Human;".into(),
-                                                            3 => "$SAA_SignalType_Biological;".into(),
-                                                            2 => "$SAA_SignalType_Xenological;".into(),
-                                                            1 => "$SAA_SignalType_Geological;".into(),
-                                                            _ => "Unknown".into(),
-                                                        };
-                                                        let type_localised = r#type
-                                                            .split('_')
-                                                            .last()
-                                                            .unwrap_or("Unknown;")
-                                                            .replace(";", "")
-                                                            .to_string();
-                                                        Signal {
-                                                            r#type,
-                                                            type_localised,
-                                                            count: planet_signal.count as u64,
-                                                        }
-                                                    })
-                                                    .collect();
-                                                debug!("Call planet_map: {system_address}-{index}");
-                                                let function_call: ContractCall<
-                                                    SignerMiddleware<Provider<Http>, LocalWallet>,
-                                                    (
-                                                        U256,
-                                                        u8,
-                                                        String,
-                                                        bool,
-                                                        bool,
-                                                        PlanetProperties,
-                                                        BodyProperties,
-                                                    ),
-                                                > = contract.planet_map(system_address, index.into());
-                                                match function_call.legacy().call().await {
-                                                    Ok(result) => {
-                                                        planet_list.push(BodyType::Planet(Planet {
-                                                            timestamp: result.0.to_string(),
-                                                            event: "EVM".to_string(),
-                                                            scan_type: "n/v".to_string(),
-                                                            body_name: result.2,
-                                                            body_id: result.1 as u64,
-                                                            parents: vec![Parent {
-                                                                name: "Unknown".into(),
-                                                                id: result.5.parent_id.into(),
-                                                            }], //TODO Implement parents
-                                                            star_system: "".to_string(), //TODO Insert Star System
-                                                            system_address: system_address as i64, //TODO Convert to u64
-                                                            distance_from_arrival_ls:
-                                                            backend::floating::floating_to_f64(
-                                                                result
-                                                                    .6
-                                                                    .distance_from_arrival_ls
-                                                                    .decimal,
-                                                                result
-                                                                    .6
-                                                                    .distance_from_arrival_ls
-                                                                    .floating_point,
-                                                            ),
-                                                            tidal_lock: result.5.tidal_lock,
-                                                            terraform_state: result.5.terraform_state,
-                                                            planet_class: result.5.class,
-                                                            atmosphere: result.5.atmosphere,
-                                                            atmosphere_type: "".to_string(), //TODO Implement Atmosphere Type
-                                                            atmosphere_composition: vec![], //TODO Implement Atmosphere Composition
-                                                            volcanism: result.5.volcanism,
-                                                            mass_em: backend::floating::floating_to_f64(
-                                                                result.5.mass_em.decimal,
-                                                                result.5.mass_em.floating_point,
-                                                            ),
-                                                            radius: backend::floating::floating_to_f64(
-                                                                result.6.radius.decimal,
-                                                                result.6.radius.floating_point,
-                                                            ),
-                                                            surface_gravity:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.surface_gravity.decimal,
-                                                                result.5.surface_gravity.floating_point,
-                                                            ),
-                                                            surface_temperature:
-                                                            backend::floating::floating_to_f64(
-                                                                result.6.surface_temperature.decimal,
-                                                                result
-                                                                    .6
-                                                                    .surface_temperature
-                                                                    .floating_point,
-                                                            ),
-                                                            surface_pressure:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.surface_pressure.decimal,
-                                                                result
-                                                                    .5
-                                                                    .surface_pressure
-                                                                    .floating_point,
-                                                            ),
-                                                            landable: result.5.landable,
-                                                            materials: vec![], //TODO Implement Materials
-                                                            composition: vec![], //TODO Implement Composition
-                                                            semi_major_axis:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.semi_major_axis.decimal,
-                                                                result.5.semi_major_axis.floating_point,
-                                                            ),
-                                                            eccentricity:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.eccentricity.decimal,
-                                                                result.5.eccentricity.floating_point,
-                                                            ),
-                                                            orbital_inclination:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.orbital_inclination.decimal,
-                                                                result
-                                                                    .5
-                                                                    .orbital_inclination
-                                                                    .floating_point,
-                                                            ),
-                                                            periapsis: backend::floating::floating_to_f64(
-                                                                result.5.periapsis.decimal,
-                                                                result.5.periapsis.floating_point,
-                                                            ),
-                                                            orbital_period:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.orbital_period.decimal,
-                                                                result.5.orbital_period.floating_point,
-                                                            ),
-                                                            ascending_node:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.ascending_node.decimal,
-                                                                result.5.ascending_node.floating_point,
-                                                            ),
-                                                            mean_anomaly:
-                                                            backend::floating::floating_to_f64(
-                                                                result.5.mean_anomaly.decimal,
-                                                                result.5.mean_anomaly.floating_point,
-                                                            ),
-                                                            rotation_period:
-                                                            backend::floating::floating_to_f64(
-                                                                result.6.rotation_period.decimal,
-                                                                result.6.rotation_period.floating_point,
-                                                            ),
-                                                            axial_tilt: backend::floating::floating_to_f64(
-                                                                result.6.axial_tilt.decimal,
-                                                                result.6.axial_tilt.floating_point,
-                                                            ),
-                                                            was_discovered: result.3,
-                                                            was_mapped: result.4,
-                                                            reserve_level: "".to_string(), //What?
-                                                            asteroid_rings: vec![], //TODO Implement Asteroid Rings
-                                                            planet_signals,
-                                                            settings: self.settings.clone(),
-                                                        }))
-                                                    }
-                                                    Err(err) => {
-                                                        timestamp = U256::from(0);
-                                                        error!("Error getting planet data: {err}");
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        index += 1;
-                                    }
-                                    planet_list.sort_by_key(|planet| planet.get_id());
-                                    self.writer.broadcast(EvmUpdate::PlanetList(
-                                        system_address,
-                                        planet_list,
-                                    ))
-                                }
-                            }
+    fn get_station_meta_data(&self, market_id: u64) -> Result<Option<StationMetaData>, String> {
+        self.runtime
+            .block_on(get_station_meta_data(&self.contract, market_id))
+            .map(Some)
+    }
+
+    fn get_market(&self, market_id: u64) -> Result<Vec<CommodityListening>, String> {
+        if self.last_block.is_none() {
+            return Err("Commodity names are not loaded yet".to_string());
+        }
+        let names = self
+            .commodity_names
+            .get(&market_id)
+            .cloned()
+            .unwrap_or_default();
+        Ok(self
+            .runtime
+            .block_on(get_market(&self.contract, market_id, names)))
+    }
+
+    fn get_carriers(&self) -> Result<Vec<Carrier>, String> {
+        self.runtime.block_on(get_carrier_list(&self.contract))
+    }
+
+    /// The lists are loaded once, afterwards only what the contract events name is fetched again
+    fn get_changes(&mut self) -> Result<Vec<DataUpdate>, String> {
+        let contract = &self.contract;
+        let last_block = self.last_block;
+        let commodity_names = &mut self.commodity_names;
+        let (updates, current_block) = self.runtime.block_on(async {
+            let current_block = contract
+                .client()
+                .get_block_number()
+                .await
+                .map_err(|err| format!("Error getting block number: {err}"))?;
+            let mut updates = vec![];
+            match last_block {
+                None => {
+                    let carriers = get_carrier_list(contract)
+                        .await
+                        .map_err(|err| format!("Error getting carriers: {err}"))?;
+                    let stations = get_station_list(contract)
+                        .await
+                        .map_err(|err| format!("Error getting stations: {err}"))?;
+                    if !carriers.is_empty() {
+                        updates.push(DataUpdate::CarrierList(carriers));
+                    }
+                    if !stations.is_empty() {
+                        updates.push(DataUpdate::StationList(stations));
+                    }
+                    for (from, to) in block_ranges(U64::zero(), current_block) {
+                        let commodity_updates = contract
+                            .commodity_listening_update_filter()
+                            .from_block(from)
+                            .to_block(to)
+                            .query()
+                            .await
+                            .map_err(|err| format!("Error getting commodity updates: {err}"))?;
+                        for update in commodity_updates {
+                            add_commodity_name(commodity_names, update);
                         }
                     }
-
-                    //Update carrier list
-                    {
-                        let function_call: ContractCall<
-                            SignerMiddleware<Provider<Http>, LocalWallet>,
-                            Vec<u64>,
-                        > = contract.get_carrier_ids();
-                        let mut carriers = Vec::new();
-                        match function_call.legacy().call().await {
-                            Ok(results) => {
-                                for carrier_id in results {
-                                    if let Ok(carrier) = get_carrier(contract, carrier_id).await {
-                                        carriers.push(carrier);
-                                    }
-                                }
+                }
+                Some(last_block) if current_block > last_block => {
+                    //The same blocks are asked for again in the next run if this fails
+                    let mut events = vec![];
+                    for (from, to) in block_ranges(last_block + 1, current_block) {
+                        events.extend(
+                            contract
+                                .events()
+                                .from_block(from)
+                                .to_block(to)
+                                .query()
+                                .await
+                                .map_err(|err| format!("Error getting contract events: {err}"))?,
+                        );
+                    }
+                    let mut carrier_ids = vec![];
+                    let mut market_ids = vec![];
+                    let mut stations_registered = false;
+                    for event in events {
+                        match event {
+                            EDCASEvents::CarrierJumpFilter(CarrierJumpFilter { carrier_id })
+                            | EDCASEvents::CarrierJumpCancelFilter(CarrierJumpCancelFilter {
+                                carrier_id,
+                            })
+                            | EDCASEvents::CarrierLocationFilter(CarrierLocationFilter {
+                                carrier_id,
+                            })
+                            | EDCASEvents::CarrierRegistrationFilter(CarrierRegistrationFilter {
+                                carrier_id,
+                            })
+                            | EDCASEvents::CarrierUpdateFilter(CarrierUpdateFilter {
+                                carrier_id,
+                            }) => carrier_ids.push(carrier_id),
+                            EDCASEvents::StationRegistrationFilter(_) => stations_registered = true,
+                            EDCASEvents::StationUpdateFilter(StationUpdateFilter { market_id }) => {
+                                market_ids.push(market_id)
                             }
-                            Err(err) => {
-                                error!("Error getting carriers ids: {err}");
+                            EDCASEvents::CommodityListeningUpdateFilter(update) => {
+                                add_commodity_name(commodity_names, update)
                             }
-                        }
-                        if !carriers.is_empty() {
-                            self.writer.broadcast(CarrierList(carriers));
+                            _ => {}
                         }
                     }
+                    carrier_ids.sort();
+                    carrier_ids.dedup();
+                    market_ids.sort();
+                    market_ids.dedup();
+                    debug!(
+                        "Blocks {} to {}: {} carriers and {} stations changed",
+                        last_block + 1,
+                        current_block,
+                        carrier_ids.len(),
+                        market_ids.len()
+                    );
 
-                    //Update station list
-                    {
-                        let function_call: ContractCall<
-                            SignerMiddleware<Provider<Http>, LocalWallet>,
-                            Vec<StationIdentity>,
-                        > = contract.get_stations();
-                        match function_call.legacy().call().await {
-                            Ok(mut results) => {
-                                if !results.is_empty() {
-                                    results.sort_by_key(|a| a.name.clone());
-                                    self.writer.broadcast(StationList(results));
-                                }
-                            }
-                            Err(err) => {
-                                error!("Error getting station ids: {err}");
+                    for carrier_id in carrier_ids {
+                        if let Ok(carrier) = get_carrier(contract, carrier_id).await {
+                            updates.push(DataUpdate::Carrier(carrier));
+                        }
+                    }
+                    if stations_registered {
+                        match get_station_list(contract).await {
+                            Ok(stations) => updates.push(DataUpdate::StationList(stations)),
+                            Err(err) => error!("Error getting station ids: {err}"),
+                        }
+                    }
+                    for market_id in market_ids {
+                        match get_station_meta_data(contract, market_id).await {
+                            Ok(meta_data) => {
+                                updates.push(DataUpdate::StationMetaData(market_id, meta_data))
                             }
+                            Err(err) => error!("Error getting station metadata: {err}"),
                         }
                     }
-                });
+                }
+                Some(_) => {}
+            }
+            Ok::<_, String>((updates, current_block))
+        })?;
+        self.last_block = Some(current_block);
+        Ok(updates)
+    }
+}
+
+/// Splits the blocks into ranges of at most `MAX_BLOCK_RANGE` blocks
+fn block_ranges(from: U64, to: U64) -> Vec<(U64, U64)> {
+    let mut ranges = vec![];
+    let mut start = from;
+    while start <= to {
+        let end = to.min(start + MAX_BLOCK_RANGE - 1);
+        ranges.push((start, end));
+        start = end + 1;
     }
+    ranges
+}
+
+fn add_commodity_name(
+    commodity_names: &mut HashMap<u64, BTreeSet<String>>,
+    update: CommodityListeningUpdateFilter,
+) {
+    commodity_names
+        .entry(update.market_id)
+        .or_default()
+        .insert(update.commodity);
 }
 
-pub async fn get_carrier(contract: &Edcas, carrier_id: u64) -> Result<Carrier, ()> {
+async fn get_market(
+    contract: &EdcasReader,
+    market_id: u64,
+    names: BTreeSet<String>,
+) -> Vec<CommodityListening> {
+    let mut listenings = vec![];
+    for name in names {
+        let function_call: ContractCall<Provider<Http>, (u32, u32, u32, u32, u32, u32, u32)> =
+            contract.commodity_listening_map(market_id, name.clone());
+        match function_call.legacy().call().await {
+            Ok(result) => listenings.push(CommodityListening {
+                name,
+                buy_price: result.0,
+                sell_price: result.1,
+                mean_price: result.2,
+                demand: result.3,
+                demand_bracket: result.4,
+                stock: result.5,
+                stock_bracket: result.6,
+            }),
+            Err(err) => {
+                error!("Error getting commodity listening {name}: {err}");
+            }
+        }
+    }
+    listenings
+}
+
+async fn get_system_meta_data(
+    contract: &EdcasReader,
+    system_address: u64,
+) -> Result<SystemMetaData, String> {
+    let function_call: ContractCall<
+        Provider<Http>,
+        (
+            U256,
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+            u64,
+            u8,
+            Floating,
+            Floating,
+            Floating,
+        ),
+    > = contract.system_map(system_address);
+    let result = function_call
+        .legacy()
+        .call()
+        .await
+        .map_err(|err| format!("Error getting system metadata: {err}"))?;
+    Ok(SystemMetaData {
+        name: result.1,
+        body_count: result.8,
+        x: backend::floating::floating_to_f64(result.9.decimal, result.9.floating_point),
+        y: backend::floating::floating_to_f64(result.10.decimal, result.10.floating_point),
+        z: backend::floating::floating_to_f64(result.11.decimal, result.11.floating_point),
+    })
+}
+
+async fn get_bodies(
+    contract: &EdcasReader,
+    system_address: u64,
+    settings: Arc<Mutex<Settings>>,
+) -> Vec<BodyType> {
+    debug!("Call get_highest_body_id_from_system: {system_address}");
+    let function_call: ContractCall<Provider<Http>, u8> =
+        contract.get_highest_body_id_from_system(system_address);
+    let highest_id = match function_call.legacy().call().await {
+        Ok(result) => {
+            debug!("highest_id received: {}", result);
+            result
+        }
+        Err(_) => 0,
+    };
+
+    let mut planet_list = vec![];
+
+    let mut timestamp: U256 = U256::max_value();
+    let mut index = 0;
+    while index <= highest_id || (highest_id == 0 && timestamp != U256::from(0)) {
+        debug!("Call star_map: {system_address}-{index}");
+        let function_call: ContractCall<
+            Provider<Http>,
+            (U256, u8, String, bool, bool, StarProperties, BodyProperties),
+        > = contract.star_map(system_address, index.into());
+        match function_call.legacy().call().await {
+            Ok(result) => {
+                planet_list.push(BodyType::Star(Star {
+                    timestamp: result.0.to_string(),
+                    event: "EVM".to_string(),
+                    scan_type: "n/v".to_string(),
+                    body_name: result.2,
+                    body_id: result.1 as u64,
+                    parents: vec![],                       //TODO Implement Parents
+                    star_system: "".to_string(),           //TODO Insert Star System
+                    system_address: system_address as i64, //TODO Convert to u64
+                    distance_from_arrival_ls: backend::floating::floating_to_f64(
+                        result.6.distance_from_arrival_ls.decimal,
+                        result.6.distance_from_arrival_ls.floating_point,
+                    ),
+                    star_type: result.5.type_,
+                    subclass: result.5.subclass as i64,
+                    stellar_mass: result.5.age_my as f64,
+                    radius: backend::floating::floating_to_f64(
+                        result.6.radius.decimal,
+                        result.6.radius.floating_point,
+                    ),
+                    absolute_magnitude: backend::floating::floating_to_f64(
+                        result.5.absolute_magnitude.decimal,
+                        result.5.absolute_magnitude.floating_point,
+                    ),
+                    age_my: result.5.age_my as i64,
+                    surface_temperature: backend::floating::floating_to_f64(
+                        result.6.surface_temperature.decimal,
+                        result.6.surface_temperature.floating_point,
+                    ),
+                    luminosity: result.5.luminosity,
+                    semi_major_axis: None,     //TODO
+                    eccentricity: None,        //TODO
+                    orbital_inclination: None, //TODO
+                    periapsis: None,           //TODO
+                    orbital_period: None,      //TODO
+                    ascending_node: None,      //TODO
+                    mean_anomaly: None,        //TODO
+                    rotation_period: backend::floating::floating_to_f64(
+                        result.6.rotation_period.decimal,
+                        result.6.rotation_period.floating_point,
+                    ),
+                    axial_tilt: backend::floating::floating_to_f64(
+                        result.6.axial_tilt.decimal,
+                        result.6.axial_tilt.floating_point,
+                    ),
+                    was_discovered: result.3,
+                    was_mapped: result.4,
+                    asteroid_rings: vec![], //TODO
+                    highlights: vec![],
+                    settings: settings.clone(),
+                }))
+            }
+            Err(err) => {
+                error!("Error getting star data: {err} -> Trying to get planet...");
+
+                debug!("Call get_planet_signals: {system_address}-{index}");
+                let function_call: ContractCall<Provider<Http>, Vec<PlanetSignal>> =
+                    contract.get_planet_signals(system_address, index);
+                let planet_signals: Vec<Signal> = function_call
+                    .legacy()
+                    .call()
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!("Error getting planet signal data: {err}");
+                        vec![]
+                    })
+                    .iter()
+                    .map(|planet_signal| {
+                        let r#type: String = match planet_signal.type_ {
+                            4 => "$SAA_SignalType_Human;".into(),
+                            3 => "$SAA_SignalType_Biological;".into(),
+                            2 => "$SAA_SignalType_Xenological;".into(),
+                            1 => "$SAA_SignalType_Geological;".into(),
+                            _ => "Unknown".into(),
+                        };
+                        let type_localised = r#type
+                            .split('_')
+                            .last()
+                            .unwrap_or("Unknown;")
+                            .replace(";", "")
+                            .to_string();
+                        Signal {
+                            r#type,
+                            type_localised,
+                            count: planet_signal.count as u64,
+                        }
+                    })
+                    .collect();
+                debug!("Call planet_map: {system_address}-{index}");
+                let function_call: ContractCall<
+                    Provider<Http>,
+                    (
+                        U256,
+                        u8,
+                        String,
+                        bool,
+                        bool,
+                        PlanetProperties,
+                        BodyProperties,
+                    ),
+                > = contract.planet_map(system_address, index.into());
+                match function_call.legacy().call().await {
+                    Ok(result) => {
+                        planet_list.push(BodyType::Planet(Planet {
+                            timestamp: result.0.to_string(),
+                            event: "EVM".to_string(),
+                            scan_type: "n/v".to_string(),
+                            body_name: result.2,
+                            body_id: result.1 as u64,
+                            parents: vec![Parent {
+                                name: "Unknown".into(),
+                                id: result.5.parent_id.into(),
+                            }], //TODO Implement parents
+                            star_system: "".to_string(), //TODO Insert Star System
+                            system_address: system_address as i64, //TODO Convert to u64
+                            distance_from_arrival_ls: backend::floating::floating_to_f64(
+                                result.6.distance_from_arrival_ls.decimal,
+                                result.6.distance_from_arrival_ls.floating_point,
+                            ),
+                            tidal_lock: result.5.tidal_lock,
+                            terraform_state: result.5.terraform_state,
+                            planet_class: result.5.class,
+                            atmosphere: result.5.atmosphere,
+                            atmosphere_type: "".to_string(), //TODO Implement Atmosphere Type
+                            atmosphere_composition: vec![],  //TODO Implement Atmosphere Composition
+                            volcanism: result.5.volcanism,
+                            mass_em: backend::floating::floating_to_f64(
+                                result.5.mass_em.decimal,
+                                result.5.mass_em.floating_point,
+                            ),
+                            radius: backend::floating::floating_to_f64(
+                                result.6.radius.decimal,
+                                result.6.radius.floating_point,
+                            ),
+                            surface_gravity: backend::floating::floating_to_f64(
+                                result.5.surface_gravity.decimal,
+                                result.5.surface_gravity.floating_point,
+                            ),
+                            surface_temperature: backend::floating::floating_to_f64(
+                                result.6.surface_temperature.decimal,
+                                result.6.surface_temperature.floating_point,
+                            ),
+                            surface_pressure: backend::floating::floating_to_f64(
+                                result.5.surface_pressure.decimal,
+                                result.5.surface_pressure.floating_point,
+                            ),
+                            landable: result.5.landable,
+                            materials: vec![],   //TODO Implement Materials
+                            composition: vec![], //TODO Implement Composition
+                            semi_major_axis: backend::floating::floating_to_f64(
+                                result.5.semi_major_axis.decimal,
+                                result.5.semi_major_axis.floating_point,
+                            ),
+                            eccentricity: backend::floating::floating_to_f64(
+                                result.5.eccentricity.decimal,
+                                result.5.eccentricity.floating_point,
+                            ),
+                            orbital_inclination: backend::floating::floating_to_f64(
+                                result.5.orbital_inclination.decimal,
+                                result.5.orbital_inclination.floating_point,
+                            ),
+                            periapsis: backend::floating::floating_to_f64(
+                                result.5.periapsis.decimal,
+                                result.5.periapsis.floating_point,
+                            ),
+                            orbital_period: backend::floating::floating_to_f64(
+                                result.5.orbital_period.decimal,
+                                result.5.orbital_period.floating_point,
+                            ),
+                            ascending_node: backend::floating::floating_to_f64(
+                                result.5.ascending_node.decimal,
+                                result.5.ascending_node.floating_point,
+                            ),
+                            mean_anomaly: backend::floating::floating_to_f64(
+                                result.5.mean_anomaly.decimal,
+                                result.5.mean_anomaly.floating_point,
+                            ),
+                            rotation_period: backend::floating::floating_to_f64(
+                                result.6.rotation_period.decimal,
+                                result.6.rotation_period.floating_point,
+                            ),
+                            axial_tilt: backend::floating::floating_to_f64(
+                                result.6.axial_tilt.decimal,
+                                result.6.axial_tilt.floating_point,
+                            ),
+                            was_discovered: result.3,
+                            was_mapped: result.4,
+                            reserve_level: "".to_string(), //What?
+                            asteroid_rings: vec![],        //TODO Implement Asteroid Rings
+                            planet_signals,
+                            highlights: vec![],
+                            settings: settings.clone(),
+                        }))
+                    }
+                    Err(err) => {
+                        timestamp = U256::from(0);
+                        error!("Error getting planet data: {err}");
+                    }
+                }
+            }
+        }
+        index += 1;
+    }
+    planet_list.sort_by_key(|planet| planet.get_id());
+    planet_list
+}
+
+async fn get_carrier_list(contract: &EdcasReader) -> Result<Vec<Carrier>, String> {
+    let function_call: ContractCall<Provider<Http>, Vec<u64>> = contract.get_carrier_ids();
+    let carrier_ids = function_call
+        .legacy()
+        .call()
+        .await
+        .map_err(|err| err.to_string())?;
+    let mut carriers = Vec::new();
+    for carrier_id in carrier_ids {
+        if let Ok(carrier) = get_carrier(contract, carrier_id).await {
+            carriers.push(carrier);
+        }
+    }
+    Ok(carriers)
+}
+
+async fn get_station_list(contract: &EdcasReader) -> Result<Vec<StationIdentity>, String> {
+    let function_call: ContractCall<Provider<Http>, Vec<StationIdentity>> = contract.get_stations();
+    let mut stations = function_call
+        .legacy()
+        .call()
+        .await
+        .map_err(|err| err.to_string())?;
+    stations.sort_by_key(|a| a.name.clone());
+    Ok(stations)
+}
+
+async fn get_station_meta_data(
+    contract: &EdcasReader,
+    market_id: u64,
+) -> Result<StationMetaData, String> {
+    let function_call: ContractCall<
+        Provider<Http>,
+        (
+            bool,
+            U256,
+            u64,
+            String,
+            Faction,
+            String,
+            String,
+            String,
+            Floating,
+            String,
+        ),
+    > = contract.station_map(market_id);
+    let result = function_call
+        .legacy()
+        .call()
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(StationMetaData {
+        timestamp: DateTime::from_timestamp(result.1.as_u64() as i64, 0).unwrap(),
+        services: result.7,
+        system_name: result.3,
+        faction: result.4,
+        government: result.5,
+        economy: result.6,
+        distance: result.8,
+        landingpads: result.9,
+        system_address: result.2,
+    })
+}
+
+pub async fn get_carrier(contract: &EdcasReader, carrier_id: u64) -> Result<Carrier, ()> {
     let function_call = contract.carrier_map(carrier_id);
     match function_call.call().await {
         Ok(result) => {
@@ -564,3 +632,46 @@ pub async fn get_carrier(contract: &Edcas, carrier_id: u64) -> Result<Carrier, (
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_ranges_are_capped() {
+        assert_eq!(
+            block_ranges(U64::zero(), U64::from(MAX_BLOCK_RANGE)),
+            vec![
+                (U64::zero(), U64::from(MAX_BLOCK_RANGE - 1)),
+                (U64::from(MAX_BLOCK_RANGE), U64::from(MAX_BLOCK_RANGE)),
+            ]
+        );
+        assert_eq!(
+            block_ranges(U64::from(5), U64::from(5)),
+            vec![(U64::from(5), U64::from(5))]
+        );
+        assert!(block_ranges(U64::from(6), U64::from(5)).is_empty());
+    }
+
+    #[test]
+    fn commodity_names_are_kept_per_market() {
+        let mut commodity_names = HashMap::new();
+        for (market_id, commodity) in [(1, "gold"), (2, "silver"), (1, "gold"), (1, "tea")] {
+            add_commodity_name(
+                &mut commodity_names,
+                CommodityListeningUpdateFilter {
+                    market_id,
+                    commodity: commodity.to_string(),
+                },
+            );
+        }
+        assert_eq!(
+            commodity_names[&1].iter().collect::<Vec<_>>(),
+            vec!["gold", "tea"]
+        );
+        assert_eq!(
+            commodity_names[&2].iter().collect::<Vec<_>>(),
+            vec!["silver"]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ethers::prelude::*;
+use log::debug;
+use tokio::runtime::Runtime;
+
+use crate::edcas::backend::evm::journal_interpreter::Edcas;
+use crate::edcas::backend::evm::transaction_store::TransactionQueueStatus;
+
+/// Entries of the contract which are only written if the journal has newer data
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Record {
+    System(u64),
+    Star(u64, u8),
+    Planet(u64, u8),
+    Station(u64),
+}
+
+/// Reads the timestamp of a record from the contract before it is written, so data which is
+/// already stored costs neither gas nor a nonce. Known timestamps are cached.
+pub struct StateCheck {
+    contract: Edcas,
+    runtime: Runtime,
+    /// Timestamps the contract has, read from it or written by a mined transaction
+    timestamps: HashMap<Record, U256>,
+    /// Timestamps of queued writes, so the same data isn't queued twice
+    pending: HashMap<Record, U256>,
+    status: Arc<Mutex<TransactionQueueStatus>>,
+}
+
+impl StateCheck {
+    pub fn new(contract: Edcas, status: Arc<Mutex<TransactionQueueStatus>>) -> StateCheck {
+        StateCheck {
+            contract,
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap(),
+            timestamps: HashMap::new(),
+            pending: HashMap::new(),
+            status,
+        }
+    }
+
+    /// True if neither the contract nor a queued write has the same or newer data for the record.
+    /// The write is pending until it is confirmed or released. If the contract can't be read,
+    /// the record is written anyway.
+    pub fn is_newer(&mut self, record: Record, timestamp: U256) -> bool {
+        let stored = match self.timestamps.get(&record) {
+            Some(stored) => *stored,
+            None => match self.get_stored_timestamp(record) {
+                Ok(stored) => {
+                    self.timestamps.insert(record, stored);
+                    stored
+                }
+                Err(err) => {
+                    debug!("Couldn't read {:?}: {}", record, err);
+                    U256::zero()
+                }
+            },
+        };
+        let known = self
+            .pending
+            .get(&record)
+            .map_or(stored, |pending| stored.max(*pending));
+        if timestamp > known {
+            self.pending.insert(record, timestamp);
+            true
+        } else {
+            self.status.lock().unwrap().skipped += 1;
+            debug!("Skipping {:?}, the contract is up to date", record);
+            false
+        }
+    }
+
+    /// The write of the record is mined, so the contract has its timestamp now
+    pub fn confirm(&mut self, record: Record, timestamp: U256) {
+        self.release(record, timestamp);
+        let stored = self.timestamps.entry(record).or_default();
+        *stored = (*stored).max(timestamp);
+    }
+
+    /// The write of the record failed, the same data may be queued again
+    pub fn release(&mut self, record: Record, timestamp: U256) {
+        if self.pending.get(&record) == Some(&timestamp) {
+            self.pending.remove(&record);
+        }
+    }
+
+    /// Unregistered records have a timestamp of 0
+    fn get_stored_timestamp(&self, record: Record) -> Result<U256, String> {
+        let contract = &self.contract;
+        self.runtime
+            .block_on(async {
+                match record {
+                    Record::System(address) => contract
+                        .system_map(address)
+                        .call()
+                        .await
+                        .map(|system| system.0),
+                    Record::Star(address, body_id) => contract
+                        .star_map(address, body_id.into())
+                        .call()
+                        .await
+                        .map(|star| star.0),
+                    Record::Planet(address, body_id) => contract
+                        .planet_map(address, body_id.into())
+                        .call()
+                        .await
+                        .map(|planet| planet.0),
+                    Record::Station(market_id) => contract
+                        .station_map(market_id)
+                        .call()
+                        .await
+                        .map(|station| station.1),
+                }
+            })
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::get_offline_contract;
+
+    /// Every contract read fails, so all records count as unregistered
+    fn get_state_check() -> StateCheck {
+        StateCheck::new(
+            get_offline_contract(),
+            Arc::new(Mutex::new(TransactionQueueStatus::default())),
+        )
+    }
+
+    #[test]
+    fn timestamps_are_cached_once_confirmed() {
+        let mut state_check = get_state_check();
+        let record = Record::System(10477373803);
+
+        assert!(state_check.is_newer(record, 100.into()));
+        //Queued, but not mined yet
+        assert!(!state_check.is_newer(record, 100.into()));
+        state_check.release(record, 100.into());
+        assert!(state_check.is_newer(record, 100.into()));
+
+        state_check.confirm(record, 100.into());
+        assert!(!state_check.is_newer(record, 90.into()));
+        assert!(state_check.is_newer(record, 110.into()));
+        //A failed newer write doesn't hide the confirmed one
+        state_check.release(record, 110.into());
+        assert!(!state_check.is_newer(record, 100.into()));
+        assert_eq!(state_check.status.lock().unwrap().skipped, 3);
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ethers::core::rand::thread_rng;
+use ethers::prelude::*;
+use ethers::utils::{Anvil, AnvilInstance};
+
+use crate::edcas::backend::evm::journal_interpreter::Edcas;
+
+/// Contract at a node nobody listens on, every call to it fails
+pub fn get_offline_contract() -> Edcas {
+    let provider = Provider::<Http>::try_from("http://127.0.0.1:9").unwrap();
+    let client = SignerMiddleware::new(provider, LocalWallet::new(&mut thread_rng()));
+    Edcas::new(Address::zero(), Arc::new(client))
+}
+
+/// Local chain which only mines on request, so transactions stay pending until `mine` is called
+pub fn start_anvil() -> AnvilInstance {
+    Anvil::new().args(["--no-mining"]).spawn()
+}
+
+/**
+Contract at an address without code on the anvil chain, signed by its first account.
+Calls to it are mined like to the real contract, which is enough to test nonces and replacements.
+*/
+pub fn get_anvil_contract(anvil: &AnvilInstance) -> Edcas {
+    let provider = Provider::<Http>::try_from(anvil.endpoint())
+        .unwrap()
+        .interval(Duration::from_millis(100));
+    let wallet = LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id());
+    let client = SignerMiddleware::new(provider, wallet);
+    Edcas::new(Address::repeat_byte(0xed), Arc::new(client))
+}
+
+/// Mines a block with everything in the mempool of anvil
+pub async fn mine(contract: &Edcas) {
+    contract
+        .client()
+        .provider()
+        .request::<_, U256>("evm_mine", ())
+        .await
+        .unwrap();
+}
+
+/// Fresh file in the temp directory, unique per test process
+pub fn get_temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("edcas-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.display().to_string()
+}
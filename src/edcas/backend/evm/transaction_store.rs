@@ -0,0 +1,289 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use ethers::abi::Token;
+use ethers::utils::hex;
+use log::{error, info, warn};
+use serde_json::json;
+
+use crate::edcas::backend::evm::journal_interpreter::{ContractCall, Edcas};
+use crate::edcas::settings::get_data_path;
+
+/// Shared between the interpreters and the UI, which shows how many transactions are not mined yet
+#[derive(Default)]
+pub struct TransactionQueueStatus {
+    pub pending: usize,
+    pub last_error: Option<String>,
+    /// Queued calls, writes of data the contract already had and calls which were given up
+    pub submitted: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+/// Call of the store which is not confirmed yet
+pub struct StoredCall {
+    pub id: u64,
+    /// Unix time the publish delay of the call ends, 0 if it has none
+    pub release_time: i64,
+    pub call: ContractCall,
+}
+
+/// Append-only log of queued contract calls. A call is written as `add` when it is queued or
+/// delayed and as `done` once its receipt is confirmed, so everything without `done` is replayed
+/// after a restart.
+pub struct TransactionStore {
+    path: String,
+    file: Option<File>,
+    next_id: u64,
+    status: Arc<Mutex<TransactionQueueStatus>>,
+}
+
+impl TransactionStore {
+    /// Opens the store and returns the calls which were not confirmed in the last run.
+    /// The file is compacted to those calls.
+    pub fn load(
+        name: &str,
+        contract: &Edcas,
+        status: Arc<Mutex<TransactionQueueStatus>>,
+    ) -> (TransactionStore, Vec<StoredCall>) {
+        TransactionStore::from_file(get_data_path(name), contract, status)
+    }
+
+    pub fn from_file(
+        path: String,
+        contract: &Edcas,
+        status: Arc<Mutex<TransactionQueueStatus>>,
+    ) -> (TransactionStore, Vec<StoredCall>) {
+        let mut entries: Vec<(u64, String, String, i64)> = vec![];
+        if let Ok(content) = fs::read_to_string(&path) {
+            for line in content.lines() {
+                //A crash while writing can leave a broken last line
+                let Ok(json) = json::parse(line) else {
+                    warn!("Skipping broken line in {}: {}", path, line);
+                    continue;
+                };
+                let id = json["id"].as_u64().unwrap_or(0);
+                match json["op"].as_str().unwrap_or("") {
+                    "add" => entries.push((
+                        id,
+                        json["selector"].to_string(),
+                        json["arguments"].to_string(),
+                        json["release"].as_i64().unwrap_or(0),
+                    )),
+                    "done" => entries.retain(|(other, _, _, _)| other != &id),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut calls = vec![];
+        for (id, selector, arguments, release_time) in &entries {
+            match decode_call(contract, selector, arguments) {
+                Ok(call) => calls.push(StoredCall {
+                    id: *id,
+                    release_time: *release_time,
+                    call,
+                }),
+                Err(err) => error!("Dropping stored transaction {}: {}", id, err),
+            }
+        }
+        if !calls.is_empty() {
+            info!(
+                "Replaying {} stored transactions from {}",
+                calls.len(),
+                path
+            );
+        }
+
+        let mut store = TransactionStore {
+            path,
+            file: None,
+            next_id: entries
+                .iter()
+                .map(|(id, _, _, _)| id + 1)
+                .max()
+                .unwrap_or(0),
+            status,
+        };
+        store.compact(&entries);
+        store.status.lock().unwrap().pending += calls.len();
+        (store, calls)
+    }
+
+    fn compact(&mut self, entries: &[(u64, String, String, i64)]) {
+        let content: String = entries
+            .iter()
+            .map(|(id, selector, arguments, release_time)| {
+                format!(
+                    "{}\n",
+                    json!({
+                        "op": "add",
+                        "id": id,
+                        "selector": selector,
+                        "arguments": arguments,
+                        "release": release_time,
+                    })
+                )
+            })
+            .collect();
+        if let Err(err) = fs::write(&self.path, content) {
+            error!("Couldn't write transaction store {}: {}", self.path, err);
+        }
+        self.file = match OpenOptions::new().append(true).open(&self.path) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                error!("Couldn't open transaction store {}: {}", self.path, err);
+                None
+            }
+        };
+    }
+
+    /// Stores the call with the unix time it may be sent at and returns its id, which is needed to confirm it
+    pub fn add(&mut self, call: &ContractCall, release_time: i64) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let calldata = call.calldata().unwrap_or_default();
+        let (selector, arguments) = calldata.split_at(4.min(calldata.len()));
+        self.append(json!({
+            "op": "add",
+            "id": id,
+            "selector": hex::encode(selector),
+            "arguments": hex::encode(arguments),
+            "release": release_time,
+        }));
+        self.status.lock().unwrap().pending += 1;
+        id
+    }
+
+    /// Removes the call from the store, either because it got mined or because it can never succeed
+    pub fn remove(&mut self, id: u64) {
+        self.append(json!({"op": "done", "id": id}));
+        let mut status = self.status.lock().unwrap();
+        status.pending = status.pending.saturating_sub(1);
+    }
+
+    /// Removes a call which can't succeed anymore
+    pub fn fail(&mut self, id: u64, error: String) {
+        self.remove(id);
+        let mut status = self.status.lock().unwrap();
+        status.failed += 1;
+        status.last_error = Some(error);
+    }
+
+    pub fn set_error(&self, error: String) {
+        self.status.lock().unwrap().last_error = Some(error);
+    }
+
+    fn append(&mut self, line: serde_json::Value) {
+        if let Some(file) = &mut self.file {
+            if let Err(err) = writeln!(file, "{}", line) {
+                error!("Couldn't write transaction store {}: {}", self.path, err);
+            }
+        }
+    }
+}
+
+/// Rebuilds the call from the contract abi, so it is sent like a freshly queued one
+fn decode_call(contract: &Edcas, selector: &str, arguments: &str) -> Result<ContractCall, String> {
+    let selector: [u8; 4] = hex::decode(selector)
+        .map_err(|err| err.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid selector".to_string())?;
+    let arguments = hex::decode(arguments).map_err(|err| err.to_string())?;
+    let function = contract
+        .abi()
+        .functions()
+        .find(|function| function.short_signature() == selector)
+        .ok_or(format!("Unknown function {}", hex::encode(selector)))?;
+    let tokens: Vec<Token> = function
+        .decode_input(&arguments)
+        .map_err(|err| err.to_string())?;
+    contract
+        .method_hash(selector, tokens.as_slice())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::{
+        get_anvil_contract, get_offline_contract, get_temp_path, mine, start_anvil,
+    };
+    use ethers::prelude::Middleware;
+
+    fn get_status() -> Arc<Mutex<TransactionQueueStatus>> {
+        Arc::new(Mutex::new(TransactionQueueStatus::default()))
+    }
+
+    fn get_call(contract: &Edcas, body_count: u8) -> ContractCall {
+        contract.set_body_count(10477373803, body_count, 1700000000.into())
+    }
+
+    #[test]
+    fn replays_unconfirmed_calls() {
+        let contract = get_offline_contract();
+        let path = get_temp_path("store-replay");
+        let (mut store, calls) = TransactionStore::from_file(path.clone(), &contract, get_status());
+        assert!(calls.is_empty());
+        let first = store.add(&get_call(&contract, 1), 0);
+        let second = store.add(&get_call(&contract, 2), 1700003600);
+        store.remove(first);
+        drop(store);
+
+        let status = get_status();
+        let (mut store, calls) =
+            TransactionStore::from_file(path.clone(), &contract, status.clone());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, second);
+        assert_eq!(calls[0].release_time, 1700003600);
+        assert_eq!(calls[0].call.calldata(), get_call(&contract, 2).calldata());
+        assert_eq!(status.lock().unwrap().pending, 1);
+        //Ids of the replayed calls are not handed out again
+        assert_eq!(store.add(&get_call(&contract, 3), 0), second + 1);
+
+        //A failed call is not replayed
+        store.fail(second, "Revert".to_string());
+        drop(store);
+        let (_, calls) = TransactionStore::from_file(path, &contract, get_status());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, second + 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a local anvil binary"]
+    async fn replayed_calls_are_mined() {
+        let anvil = start_anvil();
+        let contract = get_anvil_contract(&anvil);
+        let path = get_temp_path("store-anvil");
+        let (mut store, _) = TransactionStore::from_file(path.clone(), &contract, get_status());
+        store.add(&get_call(&contract, 7), 0);
+        drop(store);
+
+        let (mut store, calls) = TransactionStore::from_file(path, &contract, get_status());
+        assert_eq!(calls.len(), 1);
+        let StoredCall { id, call, .. } = calls.into_iter().next().unwrap();
+        let call = call.legacy();
+        let hash = call.send().await.unwrap().tx_hash();
+        mine(&contract).await;
+        let receipt = contract
+            .client()
+            .get_transaction_receipt(hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.status, Some(1.into()));
+        let transaction = contract
+            .client()
+            .get_transaction(hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            transaction.input,
+            get_call(&contract, 7).calldata().unwrap()
+        );
+        store.remove(id);
+    }
+}
@@ -2,23 +2,27 @@ use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use crate::edcas::backend::evm::request_handler::EvmRequest;
+use crate::edcas::backend::data_backend::DataRequest;
 use json::{JsonValue, Null};
 use log::{debug, error, info, warn};
 
+use crate::edcas::explorer::barycentre::Barycentre;
 use crate::edcas::explorer::body::Signal;
-use crate::edcas::explorer::system::{PlanetSignal, System, SystemSignal};
+use crate::edcas::explorer::signal::SystemSignal;
+use crate::edcas::explorer::system::{PlanetSignal, System};
 use crate::edcas::explorer::{body, Explorer};
 use crate::edcas::materials::{Material, MaterialState};
 use crate::edcas::mining::{Mining, MiningMaterial, Prospector};
 use crate::edcas::settings::Settings;
+use crate::edcas::trade::TradeState;
 
 pub fn interpret_json(
     json: JsonValue,
-    evm_request_writer: &Sender<EvmRequest>,
+    data_request_writer: &Sender<DataRequest>,
     explorer: &mut Explorer,
     materials: &mut MaterialState,
     mining: &mut Mining,
+    trade: &mut TradeState,
     settings: Arc<Mutex<Settings>>,
 ) {
     let event = json["event"].as_str().unwrap();
@@ -26,11 +30,14 @@ pub fn interpret_json(
     let now = Instant::now();
     //println!("{}",&json);
 
+    trade.ledger.interpret_json(&json);
+
     match event {
         //Navigation
         //{ "timestamp":"2022-10-16T20:54:45Z", "event":"Location", "DistFromStarLS":1007.705243, "Docked":true, "StationName":"Q2K-BHB", "StationType":"FleetCarrier", "MarketID":3704402432, "StationFaction":{ "Name":"FleetCarrier" }, "StationGovernment":"$government_Carrier;", "StationGovernment_Localised":"Privateigentum", "StationServices":[ "dock", "autodock", "commodities", "contacts", "exploration", "outfitting", "crewlounge", "rearm", "refuel", "repair", "shipyard", "engineer", "flightcontroller", "stationoperations", "stationMenu", "carriermanagement", "carrierfuel", "livery", "voucherredemption", "socialspace", "bartender", "vistagenomics" ], "StationEconomy":"$economy_Carrier;", "StationEconomy_Localised":"Privatunternehmen", "StationEconomies":[ { "Name":"$economy_Carrier;", "Name_Localised":"Privatunternehmen", "Proportion":1.000000 } ], "Taxi":false, "Multicrew":false, "StarSystem":"Colonia", "SystemAddress":3238296097059, "StarPos":[-9530.50000,-910.28125,19808.12500], "SystemAllegiance":"Independent", "SystemEconomy":"$economy_Tourism;", "SystemEconomy_Localised":"Tourismus", "SystemSecondEconomy":"$economy_HighTech;", "SystemSecondEconomy_Localised":"Hightech", "SystemGovernment":"$government_Cooperative;", "SystemGovernment_Localised":"Kooperative", "SystemSecurity":"$SYSTEM_SECURITY_low;", "SystemSecurity_Localised":"Geringe Sicherheit", "Population":583869, "Body":"Colonia 2 c", "BodyID":18, "BodyType":"Planet", "Factions":[ { "Name":"Jaques", "FactionState":"Investment", "Government":"Cooperative", "Influence":0.454092, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand1;", "Happiness_Localised":"In Hochstimmung", "MyReputation":100.000000, "RecoveringStates":[ { "State":"PublicHoliday", "Trend":0 } ], "ActiveStates":[ { "State":"Investment" }, { "State":"CivilLiberty" } ] }, { "Name":"Colonia Council", "FactionState":"Boom", "Government":"Cooperative", "Influence":0.331337, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":100.000000, "ActiveStates":[ { "State":"Boom" } ] }, { "Name":"People of Colonia", "FactionState":"None", "Government":"Cooperative", "Influence":0.090818, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":27.956400 }, { "Name":"Holloway Bioscience Institute", "FactionState":"None", "Government":"Corporate", "Influence":0.123752, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":-9.420000, "RecoveringStates":[ { "State":"PirateAttack", "Trend":0 } ] } ], "SystemFaction":{ "Name":"Jaques", "FactionState":"Investment" } }
         //{ "timestamp":"2022-10-16T23:25:31Z", "event":"FSDJump", "Taxi":false, "Multicrew":false, "StarSystem":"Ogmar", "SystemAddress":84180519395914, "StarPos":[-9534.00000,-905.28125,19802.03125], "SystemAllegiance":"Independent", "SystemEconomy":"$economy_HighTech;", "SystemEconomy_Localised":"Hightech", "SystemSecondEconomy":"$economy_Military;", "SystemSecondEconomy_Localised":"Militär", "SystemGovernment":"$government_Confederacy;", "SystemGovernment_Localised":"Konföderation", "SystemSecurity":"$SYSTEM_SECURITY_medium;", "SystemSecurity_Localised":"Mittlere Sicherheit", "Population":151752, "Body":"Ogmar A", "BodyID":1, "BodyType":"Star", "JumpDist":8.625, "FuelUsed":0.024493, "FuelLevel":31.975506, "Factions":[ { "Name":"Jaques", "FactionState":"Election", "Government":"Cooperative", "Influence":0.138384, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand1;", "Happiness_Localised":"In Hochstimmung", "MyReputation":100.000000, "PendingStates":[ { "State":"Outbreak", "Trend":0 } ], "ActiveStates":[ { "State":"Election" } ] }, { "Name":"ICU Colonial Corps", "FactionState":"War", "Government":"Communism", "Influence":0.119192, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":96.402496, "PendingStates":[ { "State":"Expansion", "Trend":0 } ], "ActiveStates":[ { "State":"War" } ] }, { "Name":"Societas Eruditorum de Civitas Dei", "FactionState":"War", "Government":"Dictatorship", "Influence":0.119192, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":46.414799, "ActiveStates":[ { "State":"War" } ] }, { "Name":"GalCop Colonial Defence Commission", "FactionState":"Boom", "Government":"Confederacy", "Influence":0.406061, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":-75.000000, "ActiveStates":[ { "State":"Boom" } ] }, { "Name":"Likedeeler of Colonia", "FactionState":"None", "Government":"Democracy", "Influence":0.068687, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":4.002500 }, { "Name":"Colonia Tech Combine", "FactionState":"Election", "Government":"Cooperative", "Influence":0.138384, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":4.850000, "ActiveStates":[ { "State":"Election" } ] }, { "Name":"Milanov's Reavers", "FactionState":"Bust", "Government":"Anarchy", "Influence":0.010101, "Allegiance":"Independent", "Happiness":"$Faction_HappinessBand2;", "Happiness_Localised":"Glücklich", "MyReputation":0.000000, "RecoveringStates":[ { "State":"Terrorism", "Trend":0 } ], "ActiveStates":[ { "State":"Bust" } ] } ], "SystemFaction":{ "Name":"GalCop Colonial Defence Commission", "FactionState":"Boom" }, "Conflicts":[ { "WarType":"election", "Status":"active", "Faction1":{ "Name":"Jaques", "Stake":"Guerrero Military Base", "WonDays":1 }, "Faction2":{ "Name":"Colonia Tech Combine", "Stake":"", "WonDays":0 } }, { "WarType":"war", "Status":"active", "Faction1":{ "Name":"ICU Colonial Corps", "Stake":"Boulaid Command Facility", "WonDays":1 }, "Faction2":{ "Name":"Societas Eruditorum de Civitas Dei", "Stake":"Chatterjee's Respite", "WonDays":0 } } ] }
         "FSDJump" | "Location" | "CarrierJump" => {
+            trade.record_location(&json);
             let system = System {
                 name: json["StarSystem"].to_string(),
                 address: json["SystemAddress"].as_u64().unwrap(),
@@ -42,7 +49,9 @@ pub fn interpret_json(
                 population: json["Population"].to_string(),
                 body_count: "n/v".to_string(),
                 non_body_count: "n/v".to_string(),
-                signal_list: vec![],
+                signal_list: explorer
+                    .signal_store
+                    .get_signals(json["SystemAddress"].as_u64().unwrap_or(0)),
                 body_list: vec![],
                 planet_signals: vec![],
                 index: 0,
@@ -50,19 +59,22 @@ pub fn interpret_json(
                 x: json["StarPos"][0].as_f64().unwrap_or(0.0),
                 y: json["StarPos"][1].as_f64().unwrap_or(0.0),
                 z: json["StarPos"][2].as_f64().unwrap_or(0.0),
+                unsold_data: false,
             };
 
-            evm_request_writer
-                .send(EvmRequest::SystemMetaData(system.address))
+            data_request_writer
+                .send(DataRequest::SystemMetaData(system.address))
                 .unwrap();
 
-            evm_request_writer
-                .send(EvmRequest::SystemPlanetData(system.address))
+            data_request_writer
+                .send(DataRequest::SystemPlanetData(system.address))
                 .unwrap();
             //TODO Planet Signals
             //TODO Signal List
 
             explorer.systems.push(system);
+            //Signals of the system just left are final until the next visit
+            explorer.signal_store.save();
 
             explorer.index = explorer.systems.len() - 1;
 
@@ -141,6 +153,7 @@ pub fn interpret_json(
                         found = true;
                     }
                 }
+                explorer.systems[len].set_body_signals(planet_signals.body_id, &signals);
                 if !found {
                     explorer.systems[len].planet_signals.push(planet_signals);
                     explorer.systems[len]
@@ -154,35 +167,11 @@ pub fn interpret_json(
             // { "timestamp":"2023-05-29T22:40:26Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"THE GENERAL MELCHETT X5W-0XL", "IsStation":true }
             //{ "timestamp":"2023-05-29T22:40:42Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"$USS_HighGradeEmissions;", "SignalName_Localised":"Unidentifizierte Signalquelle",
             // "USSType":"$USS_Type_ValuableSalvage;", "USSType_Localised":"Verschlüsselte Emissionen", "SpawningState":"", "SpawningFaction":"Murus Major Industry", "ThreatLevel":0, "TimeRemaining":707.545837 }
-            if !explorer.systems.is_empty() {
-                let mut name = json["SignalName_Localised"].to_string();
-                if name == *"null" {
-                    name = json["SignalName"].to_string();
-                    if name == *"null" {
-                        name = json["USSType_Localised"].to_string();
-                    }
-                }
-
-                let mut thread = json["ThreatLevel"].to_string();
-                if thread == *"null" {
-                    thread = "".to_string();
-                }
-
-                let system_signal = SystemSignal {
-                    timestamp: json["timestamp"].to_string(),
-                    event: json["event"].to_string(),
-                    name,
-                    threat: thread,
-                };
-                let len = explorer.systems.len() - 1;
-                explorer.systems[len].signal_list.push(system_signal);
-                explorer.systems[len].signal_list.sort_by(|a, b| {
-                    if a.name == b.name {
-                        a.threat.cmp(&b.threat)
-                    } else {
-                        a.name.cmp(&b.name)
-                    }
-                });
+            if let Some(system) = explorer.systems.last_mut() {
+                system.add_signal(SystemSignal::from_json(&json));
+                explorer
+                    .signal_store
+                    .update(system.address, &system.signal_list);
             }
         }
         "SAAScanComplete" => {}
@@ -208,11 +197,18 @@ pub fn interpret_json(
                 if !explorer.systems[len].body_list.contains(&body) {
                     let index = explorer.systems[len].insert_body(body);
                     explorer.systems[len].index = index;
+                    explorer.systems[len].unsold_data = true;
                 }
             }
         }
         //Planet scan with fss
-        "ScanBaryCentre" => {}
+        "ScanBaryCentre" => {
+            if let Some(system) = explorer.systems.last_mut() {
+                if system.address == json["SystemAddress"].as_u64().unwrap_or(0) {
+                    system.insert_barycentre(Barycentre::from_json(&json, settings.clone()));
+                }
+            }
+        }
 
         //Maintenance
         "RefuelAll" => {}
@@ -231,7 +227,8 @@ pub fn interpret_json(
         "DockingRequested" => {}
         "DockingGranted" => {}
         "Docked" => {
-            //{ "timestamp":"2023-09-09T23:59:09Z", "event":"CarrierJump", "Docked":true, "StationName":"Q2K-BHB", "StationType":"FleetCarrier", "MarketID":3704402432, "StationFaction":{ "Name":"FleetCarrier" }, "StationGovernment":"$government_Carrier;", "StationGovernment_Localised":"Privateigentum", "StationServices":[ "dock", "autodock", "commodities", "contacts", "exploration", "outfitting", "crewlounge", "rearm", "refuel", "repair", "shipyard", "engineer", "flightcontroller", "stationoperations", "stationMenu", "carriermanagement", "carrierfuel", "livery", "voucherredemption", "socialspace", "bartender", "vistagenomics" ], "StationEconomy":"$economy_Carrier;", "StationEconomy_Localised":"Privatunternehmen", "StationEconomies":[ { "Name":"$economy_Carrier;", "Name_Localised":"Privatunternehmen", "Proportion":1.000000 } ], "Taxi":false, "Multicrew":false, "StarSystem":"Plio Broae ML-D c2", "SystemAddress":637165713922, "StarPos":[2112.75000,719.12500,50162.93750], "SystemAllegiance":"", "SystemEconomy":"$economy_None;", "SystemEconomy_Localised":"n/v", "SystemSecondEconomy":"$economy_None;", "SystemSecondEconomy_Localised":"n/v", "SystemGovernment":"$government_None;", "SystemGovernment_Localised":"n/v", "SystemSecurity":"$GAlAXY_MAP_INFO_state_anarchy;", "SystemSecurity_Localised":"Anarchie", "Population":0, "Body":"Plio Broae ML-D c2", "BodyID":0, "BodyType":"Star" }
+            //{ "timestamp":"2023-09-10T18:09:40Z", "event":"Docked", "StationName":"Ray Gateway", "StationType":"Coriolis", "Taxi":false, "Multicrew":false, "StarSystem":"Diaguandri", "SystemAddress":670417429889, "MarketID":3223343616, "StationFaction":{ "Name":"EXO", "FactionState":"Boom" }, "StationGovernment":"$government_Democracy;", "StationGovernment_Localised":"Democracy", "StationServices":[ "dock", "autodock", "commodities", "contacts" ], "StationEconomy":"$economy_HighTech;", "StationEconomy_Localised":"High Tech", "StationEconomies":[ { "Name":"$economy_HighTech;", "Name_Localised":"High Tech", "Proportion":0.800000 } ], "DistFromStarLS":1432.525823, "LandingPads":{ "Small":4, "Medium":4, "Large":2 } }
+            trade.record_docked(&json);
         }
         "Undocked" => {
             //{ "timestamp":"2023-09-09T18:29:17Z", "event":"Undocked", "StationName":"Q2K-BHB", "StationType":"FleetCarrier", "MarketID":3704402432, "Taxi":false, "Multicrew":false }
@@ -340,7 +337,11 @@ pub fn interpret_json(
         "BackpackChange" => {}
         "CollectItems" => {}
         "UpgradeSuit" => {}
-        "Loadout" => {}
+        "Loadout" => {
+            if let Some(cargo_capacity) = json["CargoCapacity"].as_u32() {
+                trade.set_cargo_capacity(cargo_capacity);
+            }
+        }
         "LoadoutEquipModule" => {}
         "SuitLoadout" => {}
         "UseConsumable" => {}
@@ -349,7 +350,7 @@ pub fn interpret_json(
 
         //Market
         "MarketBuy" => {}
-        "Market" => {}
+        "Market" => trade.record_market(&json),
         "MarketSell" => {}
 
         //SRV
@@ -682,7 +683,11 @@ pub fn interpret_json(
         "ReceiveText" => {}
         "Friends" => {}
         "Scanned" => {}
-        "LoadGame" => {}
+        "LoadGame" => {
+            if let Some(credits) = json["Credits"].as_u64() {
+                trade.set_credits(credits);
+            }
+        }
         "SquadronStartup" => {}
         "Music" => {}
         "CodexEntry" => {}
@@ -697,7 +702,18 @@ pub fn interpret_json(
         "DockingDenied" => {}
         "HeatWarning" => {}
         "FactionKillBond" => {}
-        "MultiSellExplorationData" => {}
+        //{ "timestamp":"2023-12-05T19:40:12Z", "event":"MultiSellExplorationData", "Discovered":[ { "SystemName":"Ogmar", "NumBodies":12 } ], "BaseValue":1430254, "Bonus":0, "TotalEarnings":1430254 }
+        "MultiSellExplorationData" => {
+            for discovered in json["Discovered"].members() {
+                mark_data_sold(explorer, discovered["SystemName"].to_string());
+            }
+        }
+        //{ "timestamp":"2018-01-13T16:32:00Z", "event":"SellExplorationData", "Systems":[ "HIP 78085", "Praea Euq NW-W b1-3" ], "Discovered":[ "HIP 78085 A 1" ], "BaseValue":10822, "Bonus":3959, "TotalEarnings":14781 }
+        "SellExplorationData" => {
+            for system_name in json["Systems"].members() {
+                mark_data_sold(explorer, system_name.to_string());
+            }
+        }
         "SwitchSuitLoadout" => {}
         "MaterialTrade" => {
             //{ "timestamp":"2023-12-05T19:23:23Z", "event":"MaterialTrade", "MarketID":3223208960, "TraderType":"manufactured",
@@ -847,7 +863,12 @@ pub fn interpret_json(
         "NavBeaconDetail" => {}
 
         //Jesus
-        "Died" => {}
+        "Died" => {
+            //Unsold exploration data is lost
+            for system in &mut explorer.systems {
+                system.unsold_data = false;
+            }
+        }
         "Resurrect" => {}
         "SelfDestruct" => {}
 
@@ -856,7 +877,9 @@ pub fn interpret_json(
         "ShipRedeemed" => {}
 
         "Fileheader" => {}
-        "Shutdown" => {}
+        "Shutdown" => {
+            explorer.signal_store.save();
+        }
         "" => {}
         _ => {
             warn!("Unknown event: {}", &event);
@@ -871,3 +894,11 @@ pub fn interpret_json(
         );
     }
 }
+
+fn mark_data_sold(explorer: &mut Explorer, system_name: String) {
+    for system in &mut explorer.systems {
+        if system.name == system_name {
+            system.unsold_data = false;
+        }
+    }
+}
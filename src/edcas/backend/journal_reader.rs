@@ -1,9 +1,9 @@
+use std::fs;
 use std::fs::{DirEntry, File};
 use std::io::{BufRead, BufReader};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
-use std::{fs, process};
 
 use bus::Bus;
 use chrono::NaiveDateTime;
@@ -16,9 +16,46 @@ pub struct JournalReader {
     pub reader: BufReader<File>,
     pub index: usize,
     pub settings: Arc<Mutex<Settings>>,
+    /// Number of events broadcast so far
+    pub count: u64,
+    pub live_events: LiveEvents,
 }
 
-pub fn initialize(settings: Arc<Mutex<Settings>>) -> JournalReader {
+/**
+Tells the readers of the journal bus which events are live, i.e. written while the client runs.
+Events are numbered in the order of the bus, starting with 1. The ones before the reader caught up
+with the newest journal and after it went on to older journals are replayed.
+*/
+#[derive(Clone, Default)]
+pub struct LiveEvents {
+    range: Arc<Mutex<(Option<u64>, Option<u64>)>>,
+}
+
+impl LiveEvents {
+    pub fn is_live(&self, number: u64) -> bool {
+        match *self.range.lock().unwrap() {
+            (Some(after), None) => number > after,
+            (Some(after), Some(until)) => number > after && number <= until,
+            (None, _) => false,
+        }
+    }
+
+    fn start(&self, after: u64) {
+        let mut range = self.range.lock().unwrap();
+        if range.0.is_none() {
+            range.0 = Some(after);
+        }
+    }
+
+    fn stop(&self, until: u64) {
+        let mut range = self.range.lock().unwrap();
+        if range.0.is_some() && range.1.is_none() {
+            range.1 = Some(until);
+        }
+    }
+}
+
+pub fn initialize(settings: Arc<Mutex<Settings>>, live_events: LiveEvents) -> JournalReader {
     let mut reader = get_journal_log_by_index(
         settings
             .lock()
@@ -78,6 +115,8 @@ pub fn initialize(settings: Arc<Mutex<Settings>>) -> JournalReader {
         reader: get_journal_log_by_index(journal_directory, 0),
         index: 0,
         settings,
+        count: 0,
+        live_events,
     }
 }
 
@@ -88,6 +127,10 @@ impl JournalReader {
         match self.reader.read_line(&mut line) {
             Ok(flag) => {
                 if flag == 0 {
+                    //Everything after the end of the newest journal is written while the client runs
+                    if self.index == 0 {
+                        self.live_events.start(self.count);
+                    }
                     //TODO Detect when file is ending ( has to detect "crashes" )
                     //Reached EOF -> does not mean new data wont come in
                     //debug!("\n\nReached EOF -> increasing index and reading older journals\n");
@@ -106,12 +149,12 @@ impl JournalReader {
                                     .journal_reader_settings
                                     .action_at_shutdown_signal
                                 {
-                                    ActionAtShutdownSignal::Exit => {
-                                        process::exit(0);
-                                    }
-                                    ActionAtShutdownSignal::Nothing => {}
+                                    //The client exits after the session report of the shutdown is written
+                                    ActionAtShutdownSignal::Exit
+                                    | ActionAtShutdownSignal::Nothing => {}
                                     ActionAtShutdownSignal::Continue => {
                                         debug!("\n\nReached Shutdown -> increasing index and reading older journals\n");
+                                        self.live_events.stop(self.count + 1);
                                         self.index += 1;
                                         self.reader = get_journal_log_by_index(
                                             self.settings
@@ -126,6 +169,7 @@ impl JournalReader {
                                 }
                             }
                             journal_bus.broadcast(json);
+                            self.count += 1;
                         }
                         Err(err) => {
                             error!("Couldn't parse json: {}", err)
@@ -179,3 +223,26 @@ pub fn get_log_file_list(directory_path: &str) -> Vec<String> {
     });
     log_name_date_list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_events_follow_the_reader() {
+        let live_events = LiveEvents::default();
+        assert!(!live_events.is_live(1));
+
+        //Replayed 3 events of the newest journal, then caught up
+        live_events.start(3);
+        live_events.start(5);
+        assert!(!live_events.is_live(3));
+        assert!(live_events.is_live(4));
+        assert!(live_events.is_live(100));
+
+        //The live shutdown is the 7th event, the ones after it come from older journals
+        live_events.stop(7);
+        assert!(live_events.is_live(7));
+        assert!(!live_events.is_live(8));
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::DateTime;
+use json::JsonValue;
+use log::info;
+use reqwest::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
+use reqwest::StatusCode;
+use tokio::runtime::Runtime;
+
+use crate::edcas::backend::data_backend::{DataBackend, DataUpdate, SystemMetaData};
+use crate::edcas::backend::evm::edcas_contract::{Faction, StationIdentity};
+use crate::edcas::backend::floating;
+use crate::edcas::carrier::Carrier;
+use crate::edcas::explorer::body;
+use crate::edcas::explorer::body::BodyType;
+use crate::edcas::settings::Settings;
+use crate::edcas::station::{CommodityListening, StationMetaData};
+
+/**
+Reads the galaxy data from a http server. The fields are named like the columns of the local
+database, timestamps are unix seconds and a missing system or station is answered with 404.
+
+* GET /systems/{address}: name, body_count, x, y, z
+* GET /systems/{address}/bodies: list of the journal scan events of the bodies
+* GET /stations: list of name, market_id, type
+* GET /stations/{market_id}: timestamp, services, system_name, faction_name, faction_state, government, economy, distance, landing_pads, system_address
+* GET /stations/{market_id}/market: list of name, buy_price, sell_price, mean_price, demand, demand_bracket, stock, stock_bracket
+* GET /carriers: list of timestamp, name, callsign, services, docking_access, allow_notorious, current_system, current_body, next_system, next_body, departure
+
+The station and carrier lists are polled with If-Modified-Since, a server which sends Last-Modified
+can answer 304 while nothing changed.
+*/
+pub struct RestBackend {
+    url: String,
+    settings: Arc<Mutex<Settings>>,
+    client: reqwest::Client,
+    runtime: Runtime,
+    /// Last-Modified of the polled lists by their path
+    last_modified: HashMap<String, String>,
+}
+
+pub fn initialize(url: &str, settings: Arc<Mutex<Settings>>) -> Result<RestBackend, String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {}
+        _ => return Err(format!("Invalid REST url: '{}'", url)),
+    }
+    info!("Using REST backend {}", url);
+    Ok(RestBackend {
+        url: url.trim_end_matches('/').to_string(),
+        settings,
+        client: reqwest::Client::new(),
+        runtime: tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+        last_modified: HashMap::new(),
+    })
+}
+
+impl RestBackend {
+    /// `None` if the server doesn't know the path
+    fn get(&self, path: &str) -> Result<Option<JsonValue>, String> {
+        let url = format!("{}{}", self.url, path);
+        self.runtime.block_on(async {
+            let response = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .map_err(|err| format!("Request to {} failed: {}", url, err))?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !response.status().is_success() {
+                return Err(format!("{} answered {}", url, response.status()));
+            }
+            let text = response
+                .text()
+                .await
+                .map_err(|err| format!("Couldn't read {}: {}", url, err))?;
+            json::parse(&text)
+                .map(Some)
+                .map_err(|err| format!("Invalid json from {}: {}", url, err))
+        })
+    }
+
+    fn get_list(&self, path: &str) -> Result<Vec<JsonValue>, String> {
+        Ok(self
+            .get(path)?
+            .map(|json| json.members().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// `None` if the list didn't change since the last poll
+    fn get_changed_list(&mut self, path: &str) -> Result<Option<Vec<JsonValue>>, String> {
+        let url = format!("{}{}", self.url, path);
+        let mut request = self.client.get(&url);
+        if let Some(last_modified) = self.last_modified.get(path) {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let (list, last_modified) = self.runtime.block_on(async {
+            let response = request
+                .send()
+                .await
+                .map_err(|err| format!("Request to {} failed: {}", url, err))?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok((None, None));
+            }
+            if !response.status().is_success() {
+                return Err(format!("{} answered {}", url, response.status()));
+            }
+            let last_modified = response
+                .headers()
+                .get(LAST_MODIFIED)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+            let text = response
+                .text()
+                .await
+                .map_err(|err| format!("Couldn't read {}: {}", url, err))?;
+            let json =
+                json::parse(&text).map_err(|err| format!("Invalid json from {}: {}", url, err))?;
+            Ok((Some(json.members().cloned().collect()), last_modified))
+        })?;
+        if let Some(last_modified) = last_modified {
+            self.last_modified.insert(path.to_string(), last_modified);
+        }
+        Ok(list)
+    }
+}
+
+/// Missing text fields are empty, `to_string` would turn them into "null"
+fn get_string(json: &JsonValue, key: &str) -> String {
+    json[key].as_str().unwrap_or_default().to_string()
+}
+
+fn parse_stations(list: Vec<JsonValue>) -> Vec<StationIdentity> {
+    let mut stations: Vec<StationIdentity> = list
+        .into_iter()
+        .filter_map(|json| {
+            Some(StationIdentity {
+                name: json["name"].as_str()?.to_string(),
+                market_id: json["market_id"].as_u64()?,
+                type_: get_string(&json, "type"),
+            })
+        })
+        .collect();
+    stations.sort_by_key(|station| station.name.clone());
+    stations
+}
+
+fn parse_carrier(json: JsonValue) -> Carrier {
+    Carrier {
+        timestamp: DateTime::from_timestamp(json["timestamp"].as_i64().unwrap_or(0), 0)
+            .unwrap_or_default(),
+        name: get_string(&json, "name"),
+        callsign: get_string(&json, "callsign"),
+        services: get_string(&json, "services"),
+        docking_access: get_string(&json, "docking_access"),
+        allow_notorious: json["allow_notorious"].as_bool().unwrap_or(false),
+        current_system: get_string(&json, "current_system"),
+        current_body: get_string(&json, "current_body"),
+        next_system: get_string(&json, "next_system"),
+        next_body: get_string(&json, "next_body"),
+        departure: DateTime::from_timestamp(json["departure"].as_i64().unwrap_or(0), 0)
+            .unwrap_or_default(),
+    }
+}
+
+impl DataBackend for RestBackend {
+    fn get_system_meta_data(&self, system_address: u64) -> Result<Option<SystemMetaData>, String> {
+        Ok(self
+            .get(&format!("/systems/{}", system_address))?
+            .map(|json| SystemMetaData {
+                name: get_string(&json, "name"),
+                body_count: json["body_count"].as_u8().unwrap_or(0),
+                x: json["x"].as_f64().unwrap_or(0.0),
+                y: json["y"].as_f64().unwrap_or(0.0),
+                z: json["z"].as_f64().unwrap_or(0.0),
+            }))
+    }
+
+    /// Bodies are sent as their scan event, so they are built like the ones from the journal
+    fn get_bodies(&self, system_address: u64) -> Result<Vec<BodyType>, String> {
+        let mut bodies = self
+            .get_list(&format!("/systems/{}/bodies", system_address))?
+            .into_iter()
+            .map(|scan| body::try_generate_from_json(scan, self.settings.clone()))
+            .collect::<Result<Vec<BodyType>, String>>()?;
+        bodies.sort_by_key(|body| body.get_id());
+        Ok(bodies)
+    }
+
+    fn get_stations(&self) -> Result<Vec<StationIdentity>, String> {
+        Ok(parse_stations(self.get_list("/stations")?))
+    }
+
+    fn get_station_meta_data(&self, market_id: u64) -> Result<Option<StationMetaData>, String> {
+        Ok(self
+            .get(&format!("/stations/{}", market_id))?
+            .map(|json| StationMetaData {
+                timestamp: DateTime::from_timestamp(json["timestamp"].as_i64().unwrap_or(0), 0)
+                    .unwrap_or_default(),
+                services: get_string(&json, "services"),
+                system_name: get_string(&json, "system_name"),
+                faction: Faction {
+                    name: get_string(&json, "faction_name"),
+                    state: get_string(&json, "faction_state"),
+                },
+                government: get_string(&json, "government"),
+                economy: get_string(&json, "economy"),
+                distance: floating::generate_floating_from_string(
+                    json["distance"]
+                        .as_f64()
+                        .map(|distance| distance.to_string())
+                        .unwrap_or_default(),
+                ),
+                landingpads: get_string(&json, "landing_pads"),
+                system_address: json["system_address"].as_u64().unwrap_or(0),
+            }))
+    }
+
+    fn get_market(&self, market_id: u64) -> Result<Vec<CommodityListening>, String> {
+        Ok(self
+            .get_list(&format!("/stations/{}/market", market_id))?
+            .into_iter()
+            .map(|json| CommodityListening {
+                name: get_string(&json, "name"),
+                buy_price: json["buy_price"].as_u32().unwrap_or(0),
+                sell_price: json["sell_price"].as_u32().unwrap_or(0),
+                mean_price: json["mean_price"].as_u32().unwrap_or(0),
+                demand: json["demand"].as_u32().unwrap_or(0),
+                demand_bracket: json["demand_bracket"].as_u32().unwrap_or(0),
+                stock: json["stock"].as_u32().unwrap_or(0),
+                stock_bracket: json["stock_bracket"].as_u32().unwrap_or(0),
+            })
+            .collect())
+    }
+
+    fn get_carriers(&self) -> Result<Vec<Carrier>, String> {
+        Ok(self
+            .get_list("/carriers")?
+            .into_iter()
+            .map(parse_carrier)
+            .collect())
+    }
+
+    /// Only the lists the server changed since the last poll are sent
+    fn get_changes(&mut self) -> Result<Vec<DataUpdate>, String> {
+        let mut updates = vec![];
+        if let Some(carriers) = self.get_changed_list("/carriers")? {
+            updates.push(DataUpdate::CarrierList(
+                carriers.into_iter().map(parse_carrier).collect(),
+            ));
+        }
+        if let Some(stations) = self.get_changed_list("/stations")? {
+            updates.push(DataUpdate::StationList(parse_stations(stations)));
+        }
+        Ok(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers one request per response in order and returns the request heads it got
+    fn serve(responses: Vec<String>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                requests.push(request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    #[test]
+    fn reads_systems() {
+        let (url, server) = serve(vec![
+            response(
+                "200 OK",
+                "",
+                r#"{"name":"Sol","body_count":40,"x":0.0,"y":1.5,"z":-2.25}"#,
+            ),
+            response("404 Not Found", "", ""),
+        ]);
+        let backend = initialize(&url, get_test_settings()).unwrap();
+
+        let system = backend.get_system_meta_data(10477373803).unwrap().unwrap();
+        assert_eq!(system.name, "Sol");
+        assert_eq!(system.body_count, 40);
+        assert_eq!([system.x, system.y, system.z], [0.0, 1.5, -2.25]);
+        assert!(backend.get_system_meta_data(1).unwrap().is_none());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /systems/10477373803 "));
+        assert!(requests[1].starts_with("GET /systems/1 "));
+    }
+
+    #[test]
+    fn partial_bodies_are_an_error() {
+        let (url, server) = serve(vec![
+            response(
+                "200 OK",
+                "",
+                r#"[{"event":"Scan","BodyName":"Sol 3","StarSystem":"Sol","SystemAddress":10477373803}]"#,
+            ),
+            response(
+                "200 OK",
+                "",
+                r#"[{"name":"Ray Gateway","market_id":3223343616}]"#,
+            ),
+        ]);
+        let backend = initialize(&url, get_test_settings()).unwrap();
+
+        let err = backend.get_bodies(10477373803).err().unwrap();
+        assert_eq!(err, "Scan of 'Sol 3' misses BodyID");
+        //Missing text is empty instead of "null"
+        let stations = backend.get_stations().unwrap();
+        assert_eq!(stations[0].type_, "");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn changes_are_only_sent_when_modified() {
+        let modified = "Last-Modified: Mon, 19 Oct 2026 10:00:00 GMT\r\n";
+        let (url, server) = serve(vec![
+            response(
+                "200 OK",
+                modified,
+                r#"[{"name":"Carrier","callsign":"ABC-123","departure":0}]"#,
+            ),
+            response(
+                "200 OK",
+                modified,
+                r#"[{"name":"Jameson Memorial","market_id":128666762,"type":"Orbis"}]"#,
+            ),
+            response("304 Not Modified", "", ""),
+            response("304 Not Modified", "", ""),
+        ]);
+        let mut backend = initialize(&url, get_test_settings()).unwrap();
+
+        let updates = backend.get_changes().unwrap();
+        assert_eq!(updates.len(), 2);
+        match &updates[0] {
+            DataUpdate::CarrierList(carriers) => assert_eq!(carriers[0].callsign, "ABC-123"),
+            _ => panic!("Expected the carrier list"),
+        }
+        match &updates[1] {
+            DataUpdate::StationList(stations) => assert_eq!(stations[0].market_id, 128666762),
+            _ => panic!("Expected the station list"),
+        }
+        assert!(backend.get_changes().unwrap().is_empty());
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].to_lowercase().contains("if-modified-since"));
+        for request in &requests[2..] {
+            assert!(request
+                .to_lowercase()
+                .contains("if-modified-since: mon, 19 oct 2026 10:00:00 gmt"));
+        }
+    }
+}
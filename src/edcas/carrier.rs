@@ -1,12 +1,113 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use crate::edcas::backend::data_backend::DataRequest;
 use crate::edcas::settings::Settings;
-use chrono::{DateTime, Utc};
+use crate::edcas::station::{
+    compare_distances, distance, StationService, FILTER_REQUESTS_PER_INTERVAL,
+    FILTER_REQUEST_INTERVAL,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::error;
 
 pub struct CarrierState {
     pub carriers: Vec<Carrier>,
     pub search: String,
     pub settings: Arc<Mutex<Settings>>,
+    pub filter: CarrierFilter,
+    pub data_request_writer: Sender<DataRequest>,
+    /// Coordinates of the systems by system name
+    pub system_coordinates: HashMap<String, [f64; 3]>,
+    pub requested_systems: HashSet<String>,
+    /// Star position of the commander's current system
+    pub position: Option<[f64; 3]>,
+    pub filter_requested_at: Option<Instant>,
+}
+
+impl CarrierState {
+    /**
+    Requests the coordinates of the systems the carriers are in, which the filter needs for the distances.
+    Does nothing while the filter is empty. Like the station filter it sends a batch of requests per interval.
+    */
+    pub fn request_filter_data(&mut self) {
+        if self.filter.is_empty()
+            || self
+                .filter_requested_at
+                .is_some_and(|requested_at| requested_at.elapsed() < FILTER_REQUEST_INTERVAL)
+        {
+            return;
+        }
+        self.filter_requested_at = Some(Instant::now());
+        let mut sent = 0;
+        for carrier in &self.carriers {
+            if sent == FILTER_REQUESTS_PER_INTERVAL {
+                break;
+            }
+            if carrier.current_system.is_empty()
+                || self
+                    .system_coordinates
+                    .contains_key(&carrier.current_system)
+                || !self
+                    .requested_systems
+                    .insert(carrier.current_system.clone())
+            {
+                continue;
+            }
+            if let Err(err) = self
+                .data_request_writer
+                .send(DataRequest::SystemCoordinates(
+                    carrier.current_system.clone(),
+                ))
+            {
+                error!("Error sending SystemCoordinatesRequest: {err}");
+            }
+            sent += 1;
+        }
+    }
+
+    /// Light years from the current system, `None` if one of the positions is unknown
+    pub fn get_distance(&self, carrier: &Carrier) -> Option<f64> {
+        let system = self.system_coordinates.get(&carrier.current_system)?;
+        Some(distance(self.position?, *system))
+    }
+
+    /**
+    Indices of the carriers matching the search and the filter.
+    With an active filter they are sorted by the distance to the current system, carriers with an unknown distance last.
+    */
+    pub fn get_matching_carriers(&self, search: &str) -> Vec<usize> {
+        let search = search.to_lowercase();
+        let mut indices: Vec<usize> = self
+            .carriers
+            .iter()
+            .enumerate()
+            .filter(|(_, carrier)| {
+                [
+                    &carrier.name,
+                    &carrier.callsign,
+                    &carrier.current_system,
+                    &carrier.current_body,
+                    &carrier.next_system,
+                    &carrier.next_body,
+                ]
+                .iter()
+                .any(|field| field.to_lowercase().contains(&search))
+            })
+            .filter(|(_, carrier)| self.filter.matches(carrier, self.get_distance(carrier)))
+            .map(|(index, _)| index)
+            .collect();
+        if !self.filter.is_empty() {
+            indices.sort_by(|a, b| {
+                compare_distances(
+                    self.get_distance(&self.carriers[*a]),
+                    self.get_distance(&self.carriers[*b]),
+                )
+            });
+        }
+        indices
+    }
 }
 
 #[derive(Clone)]
@@ -23,3 +124,194 @@ pub struct Carrier {
     pub next_body: String,
     pub departure: DateTime<Utc>,
 }
+
+impl Carrier {
+    /// Only the owner's `CarrierStats` tells the access, carriers seen over EDDN leave it empty
+    pub fn get_docking_access(&self) -> &str {
+        if self.docking_access.is_empty() {
+            "unknown"
+        } else {
+            &self.docking_access
+        }
+    }
+
+    /// Time left until the scheduled jump, `None` if no jump is pending
+    pub fn get_time_to_departure(&self) -> Option<Duration> {
+        let left = self.departure - Utc::now();
+        (left > Duration::zero()).then_some(left)
+    }
+
+    /// Countdown like "00:42:10", "-" if no jump is pending
+    pub fn get_countdown(&self) -> String {
+        match self.get_time_to_departure() {
+            Some(left) => format!(
+                "{:02}:{:02}:{:02}",
+                left.num_hours(),
+                left.num_minutes() % 60,
+                left.num_seconds() % 60
+            ),
+            None => "-".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct CarrierFilter {
+    pub services: Vec<StationService>,
+    /// Only carriers which let everyone dock, carriers with an unknown access are kept
+    pub open_access: bool,
+    /// Maximum distance from the current system in ly
+    pub max_distance: Option<f64>,
+    /// Only carriers which jump within the next hour
+    pub departing_soon: bool,
+}
+
+impl CarrierFilter {
+    /// Services explorers look for on a carrier
+    pub const SERVICES: [StationService; 4] = [
+        StationService::Refuel,
+        StationService::Repair,
+        StationService::Shipyard,
+        StationService::UniversalCartographics,
+    ];
+
+    pub fn is_empty(&self) -> bool {
+        self == &CarrierFilter::default()
+    }
+
+    pub fn toggle_service(&mut self, service: StationService) {
+        match self.services.iter().position(|other| other == &service) {
+            Some(index) => {
+                self.services.remove(index);
+            }
+            None => self.services.push(service),
+        }
+    }
+
+    /// `distance` is the distance from the current system, carriers with an unknown distance don't match a maximum distance
+    pub fn matches(&self, carrier: &Carrier, distance: Option<f64>) -> bool {
+        if !self
+            .services
+            .iter()
+            .all(|service| service.is_offered(&carrier.services))
+        {
+            return false;
+        }
+        if self.open_access && !carrier.docking_access.is_empty() && carrier.docking_access != "all"
+        {
+            return false;
+        }
+        if let Some(max_distance) = self.max_distance {
+            if distance.map_or(true, |distance| distance > max_distance) {
+                return false;
+            }
+        }
+        if self.departing_soon
+            && carrier
+                .get_time_to_departure()
+                .map_or(true, |left| left > Duration::hours(1))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+    use std::cmp::Ordering;
+    use std::sync::mpsc;
+
+    fn get_carrier(callsign: &str, current_system: &str) -> Carrier {
+        Carrier {
+            timestamp: Utc::now(),
+            name: callsign.to_string(),
+            callsign: callsign.to_string(),
+            services: "refuel".to_string(),
+            docking_access: "all".to_string(),
+            allow_notorious: false,
+            current_system: current_system.to_string(),
+            current_body: String::new(),
+            next_system: String::new(),
+            next_body: String::new(),
+            departure: DateTime::default(),
+        }
+    }
+
+    #[test]
+    fn carriers_are_sorted_by_the_distance_of_their_system() {
+        let (data_request_writer, receiver) = mpsc::channel();
+        let mut state = CarrierState {
+            carriers: vec![
+                get_carrier("AAA-001", "Colonia"),
+                get_carrier("AAA-002", "Achenar"),
+                get_carrier("AAA-003", "Colonia"),
+                get_carrier("AAA-004", "Sol"),
+            ],
+            search: String::new(),
+            settings: get_test_settings(),
+            filter: CarrierFilter::default(),
+            data_request_writer,
+            system_coordinates: HashMap::from([("Sol".to_string(), [0.0, 0.0, 0.0])]),
+            requested_systems: HashSet::new(),
+            position: Some([0.0, 0.0, 0.0]),
+            filter_requested_at: None,
+        };
+        state.filter.toggle_service(StationService::Refuel);
+
+        //Every unknown system is requested once
+        state.request_filter_data();
+        let requested: Vec<String> = receiver
+            .try_iter()
+            .map(|request| match request {
+                DataRequest::SystemCoordinates(name) => name,
+                _ => panic!("Unexpected request"),
+            })
+            .collect();
+        assert_eq!(requested, vec!["Colonia", "Achenar"]);
+
+        state
+            .system_coordinates
+            .insert("Achenar".to_string(), [67.5, -119.46875, 24.84375]);
+        assert_eq!(state.get_matching_carriers(""), vec![3, 1, 0, 2]);
+
+        state.filter_requested_at = None;
+        state.request_filter_data();
+        assert_eq!(receiver.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn unknown_docking_access_is_kept_by_the_open_access_filter() {
+        let filter = CarrierFilter {
+            open_access: true,
+            ..CarrierFilter::default()
+        };
+        let mut carrier = get_carrier("AAA-001", "Colonia");
+        assert!(filter.matches(&carrier, None));
+        carrier.docking_access = "squadronfriends".to_string();
+        assert!(!filter.matches(&carrier, None));
+        //Docked events shared over EDDN don't tell the access
+        carrier.docking_access = String::new();
+        assert!(filter.matches(&carrier, None));
+        assert_eq!(carrier.get_docking_access(), "unknown");
+
+        let filter = CarrierFilter {
+            max_distance: Some(100.0),
+            ..CarrierFilter::default()
+        };
+        assert!(filter.matches(&carrier, Some(50.0)));
+        assert!(!filter.matches(&carrier, Some(150.0)));
+        assert!(!filter.matches(&carrier, None));
+    }
+
+    #[test]
+    fn unknown_distances_are_sorted_last() {
+        assert_eq!(compare_distances(Some(1.0), Some(2.0)), Ordering::Less);
+        assert_eq!(compare_distances(Some(5.0), None), Ordering::Less);
+        assert_eq!(compare_distances(None, Some(5.0)), Ordering::Greater);
+        assert_eq!(compare_distances(None, None), Ordering::Equal);
+        assert_eq!(distance([0.0, 0.0, 0.0], [3.0, 4.0, 12.0]), 13.0);
+    }
+}
@@ -1,12 +1,21 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+use chrono::Utc;
+use eframe::egui;
+
+use crate::edcas::explorer::signal::{SignalClass, SignalStore};
 use crate::edcas::explorer::system::System;
 use crate::edcas::settings::Settings;
 
+pub mod barycentre;
 pub mod belt_cluster;
 pub mod body;
+pub mod highlight;
+pub mod orbit;
 pub mod planet;
 pub(crate) mod ring;
+pub mod signal;
 pub mod star;
 pub mod system;
 
@@ -15,4 +24,81 @@ pub struct Explorer {
     pub index: usize,
     pub body_list_index: Option<usize>,
     pub settings: Arc<Mutex<Settings>>,
+    pub system_map: SystemMapView,
+    pub galaxy_map: GalaxyMapView,
+    pub signal_store: SignalStore,
+    pub signal_filter: Option<SignalClass>,
+    /// Revision of the highlight rules the bodies were checked with
+    pub highlight_rules_revision: u64,
+    /// Shared with the settings, so checking it doesn't lock them every frame
+    pub saved_highlight_rules_revision: Arc<AtomicU64>,
+}
+
+impl Explorer {
+    /// Drops despawned USSes from all systems and their stored copies
+    pub fn remove_expired_signals(&mut self) {
+        let now = Utc::now();
+        for system in &mut self.systems {
+            if system.remove_expired_signals(now) {
+                self.signal_store
+                    .update(system.address, &system.signal_list);
+            }
+        }
+    }
+
+    /// Checks all bodies again after the highlight rules were saved
+    pub fn update_highlights(&mut self) {
+        let revision = self.saved_highlight_rules_revision.load(Ordering::Relaxed);
+        if revision == self.highlight_rules_revision {
+            return;
+        }
+        self.highlight_rules_revision = revision;
+        let settings = self.settings.lock().unwrap();
+        for system in &mut self.systems {
+            system.update_highlights(&settings.highlight_rules);
+        }
+    }
+}
+
+pub struct SystemMapView {
+    pub show: bool,
+    pub zoom: f32,
+    pub offset: egui::Vec2,
+}
+
+impl Default for SystemMapView {
+    fn default() -> Self {
+        Self {
+            show: false,
+            zoom: 1.0,
+            offset: egui::Vec2::ZERO,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub enum GalaxyProjection {
+    /// X/Z plane, looking down onto the galactic plane
+    TopDown,
+    /// X/Y plane, looking at the galactic plane from the side
+    Side,
+}
+
+pub struct GalaxyMapView {
+    pub projection: GalaxyProjection,
+    pub show_trail: bool,
+    /// Pixels per light year
+    pub zoom: f32,
+    pub offset: egui::Vec2,
+}
+
+impl Default for GalaxyMapView {
+    fn default() -> Self {
+        Self {
+            projection: GalaxyProjection::TopDown,
+            show_trail: true,
+            zoom: 2.0,
+            offset: egui::Vec2::ZERO,
+        }
+    }
 }
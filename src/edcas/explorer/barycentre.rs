@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use json::JsonValue;
+
+use crate::edcas::explorer::body::Parent;
+use crate::edcas::settings::Settings;
+
+/// Common centre of mass of binary stars or planets, listed as `Null` in the `Parents` of a body.
+/// The orbital data is only known after a `ScanBaryCentre` event, until then it is `None`.
+#[derive(Clone)]
+pub struct Barycentre {
+    pub timestamp: String,
+    pub event: String,
+    pub body_name: String,
+    pub body_id: u64,
+    pub parents: Vec<Parent>,
+    pub star_system: String,
+    pub semi_major_axis: Option<f64>,
+    pub eccentricity: Option<f64>,
+    pub orbital_inclination: Option<f64>,
+    pub periapsis: Option<f64>,
+    pub orbital_period: Option<f64>,
+    pub ascending_node: Option<f64>,
+    pub mean_anomaly: Option<f64>,
+    pub settings: Arc<Mutex<Settings>>,
+}
+
+impl Barycentre {
+    /// Placeholder for a barycentre that only showed up in the `Parents` of another body
+    pub fn from_parents(
+        body_id: u64,
+        parents: Vec<Parent>,
+        star_system: String,
+        settings: Arc<Mutex<Settings>>,
+    ) -> Self {
+        Self {
+            timestamp: "".to_string(),
+            event: "".to_string(),
+            body_name: get_barycentre_name(&star_system, body_id),
+            body_id,
+            parents,
+            star_system,
+            semi_major_axis: None,
+            eccentricity: None,
+            orbital_inclination: None,
+            periapsis: None,
+            orbital_period: None,
+            ascending_node: None,
+            mean_anomaly: None,
+            settings,
+        }
+    }
+
+    //{ "timestamp":"2023-07-19T17:02:42Z", "event":"ScanBaryCentre", "StarSystem":"Phaa Chroa YL-B b5-4", "SystemAddress":9544091982377, "BodyID":5, "SemiMajorAxis":10452453732.490540, "Eccentricity":0.016339, "OrbitalInclination":-14.617426, "Periapsis":73.271569, "OrbitalPeriod":11393706.798553, "AscendingNode":-77.528137, "MeanAnomaly":210.744437 }
+    pub fn from_json(json: &JsonValue, settings: Arc<Mutex<Settings>>) -> Self {
+        let star_system = json["StarSystem"].to_string();
+        let body_id = json["BodyID"].as_u64().unwrap_or(0);
+        Self {
+            timestamp: json["timestamp"].to_string(),
+            event: json["event"].to_string(),
+            body_name: get_barycentre_name(&star_system, body_id),
+            body_id,
+            //The event does not list parents, they are filled in from the bodies orbiting it
+            parents: vec![],
+            star_system,
+            semi_major_axis: json["SemiMajorAxis"].as_f64(),
+            eccentricity: json["Eccentricity"].as_f64(),
+            orbital_inclination: json["OrbitalInclination"].as_f64(),
+            periapsis: json["Periapsis"].as_f64(),
+            orbital_period: json["OrbitalPeriod"].as_f64(),
+            ascending_node: json["AscendingNode"].as_f64(),
+            mean_anomaly: json["MeanAnomaly"].as_f64(),
+            settings,
+        }
+    }
+
+    /// Takes over whatever the other barycentre knows and this one doesn't
+    pub fn merge(&mut self, other: Barycentre) {
+        if self.parents.is_empty() {
+            self.parents = other.parents;
+        }
+        if other.semi_major_axis.is_some() {
+            self.timestamp = other.timestamp;
+            self.event = other.event;
+            self.semi_major_axis = other.semi_major_axis;
+            self.eccentricity = other.eccentricity;
+            self.orbital_inclination = other.orbital_inclination;
+            self.periapsis = other.periapsis;
+            self.orbital_period = other.orbital_period;
+            self.ascending_node = other.ascending_node;
+            self.mean_anomaly = other.mean_anomaly;
+        }
+    }
+}
+
+fn get_barycentre_name(star_system: &str, body_id: u64) -> String {
+    format!("{} Barycentre {}", star_system, body_id)
+}
@@ -41,7 +41,7 @@ pub fn generate_from_json(json: JsonValue, settings: Arc<Mutex<Settings>>) -> Bo
     if json["StarType"].is_null() {
         if json["BodyName"].to_string().contains("Belt Cluster") {
             BodyType::BeltCluster(crate::edcas::explorer::belt_cluster::BeltCluster {
-                timestamp: json["Timestamp"].to_string(),
+                timestamp: json["timestamp"].to_string(),
                 event: json["event"].to_string(),
                 scan_type: json["ScanType"].to_string(),
                 body_name: json["BodyName"].to_string(),
@@ -57,7 +57,7 @@ pub fn generate_from_json(json: JsonValue, settings: Arc<Mutex<Settings>>) -> Bo
         } else if json["BodyName"].to_string().contains("Ring") {
             //{"timestamp":"2023-07-17T18:18:37Z","event":"Scan","ScanType":"AutoScan","BodyName":"Scheau Blao BB-W b57-45 1 B Ring","BodyID":7,"Parents":[{"Planet":5},{"Star":0}],"StarSystem":"Scheau Blao BB-W b57-45","SystemAddress":99684979268081,"DistanceFromArrivalLS":535.932803,"SemiMajorAxis":208998668.193817,"Eccentricity":0,"OrbitalInclination":0,"Periapsis":0,"OrbitalPeriod":44426.782131,"AscendingNode":0,"MeanAnomaly":190.333503,"WasDiscovered":false,"WasMapped":false}
             BodyType::Ring(crate::edcas::explorer::ring::Ring {
-                timestamp: json["Timestamp"].to_string(),
+                timestamp: json["timestamp"].to_string(),
                 event: json["event"].to_string(),
                 scan_type: json["ScanType"].to_string(),
                 body_name: json["BodyName"].to_string(),
@@ -94,7 +94,7 @@ pub fn generate_from_json(json: JsonValue, settings: Arc<Mutex<Settings>>) -> Bo
             // "Rings":[ { "Name":"Phaa Chroa YL-B b5-4 A 9 A Ring", "RingClass":"eRingClass_Rocky", "MassMT":2.501e+09, "InnerRad":2.166e+07, "OuterRad":2.3866e+07 }, { "Name":"Phaa Chroa YL-B b5-4 A 9 B Ring", "RingClass":"eRingClass_Icy", "MassMT":9.0988e+10, "InnerRad":2.3966e+07, "OuterRad":6.2742e+07 } ],
             // "ReserveLevel":"PristineResources", "WasDiscovered":false, "WasMapped":false }
             BodyType::Planet(crate::edcas::explorer::planet::Planet {
-                timestamp: json["Timestamp"].to_string(),
+                timestamp: json["timestamp"].to_string(),
                 event: json["event"].to_string(),
                 scan_type: json["ScanType"].to_string(),
                 body_name: json["BodyName"].to_string(),
@@ -129,16 +129,17 @@ pub fn generate_from_json(json: JsonValue, settings: Arc<Mutex<Settings>>) -> Bo
                 axial_tilt: json["AxialTilt"].as_f64().unwrap(),
                 was_discovered: json["WasDiscovered"].to_string().parse().unwrap(),
                 was_mapped: json["WasMapped"].to_string().parse().unwrap(),
-                reserve_level: json["PristineResources"].to_string(),
+                reserve_level: json["ReserveLevel"].to_string(),
                 asteroid_rings,
                 planet_signals: vec![],
+                highlights: vec![],
                 settings: settings.clone(),
             })
         }
     } else {
         //{ "timestamp":"2023-07-12T21:52:23Z", "event":"Scan", "ScanType":"AutoScan", "BodyName":"Lasao DX-Z b43-37 A", "BodyID":1, "Parents":[ {"Null":0} ], "StarSystem":"Lasao DX-Z b43-37", "SystemAddress":82108367853945, "DistanceFromArrivalLS":0.000000, "StarType":"M", "Subclass":7, "StellarMass":0.285156, "Radius":307783360.000000, "AbsoluteMagnitude":10.356186, "Age_MY":3076, "SurfaceTemperature":2434.000000, "Luminosity":"Va", "SemiMajorAxis":514860939979.553223, "Eccentricity":0.153621, "OrbitalInclination":2.176175, "Periapsis":6.939240, "OrbitalPeriod":4139431655.406952, "AscendingNode":-133.798577, "MeanAnomaly":169.548183, "RotationPeriod":118438.397553, "AxialTilt":0.000000, "Rings":[ { "Name":"Lasao DX-Z b43-37 A A Belt", "RingClass":"eRingClass_Rocky", "MassMT":7.2313e+13, "InnerRad":5.0784e+08, "OuterRad":1.6453e+09 } ], "WasDiscovered":false, "WasMapped":false }
         BodyType::Star(crate::edcas::explorer::star::Star {
-            timestamp: json["Timestamp"].to_string(),
+            timestamp: json["timestamp"].to_string(),
             event: json["event"].to_string(),
             scan_type: json["ScanType"].to_string(),
             body_name: json["BodyName"].to_string(),
@@ -167,17 +168,113 @@ pub fn generate_from_json(json: JsonValue, settings: Arc<Mutex<Settings>>) -> Bo
             was_discovered: json["WasDiscovered"].as_bool().unwrap(),
             was_mapped: json["WasMapped"].as_bool().unwrap(),
             asteroid_rings,
+            highlights: vec![],
             settings: settings.clone(),
         })
     }
 }
 
+/// Like `generate_from_json`, but fails instead of panicking on a scan with missing fields.
+/// The journal always writes them, scans from a backend are not checked by the game.
+pub fn try_generate_from_json(
+    json: JsonValue,
+    settings: Arc<Mutex<Settings>>,
+) -> Result<BodyType, String> {
+    validate_scan(&json)?;
+    Ok(generate_from_json(json, settings))
+}
+
+/// Checks the fields `generate_from_json` unwraps for the kind of body the scan describes
+fn validate_scan(json: &JsonValue) -> Result<(), String> {
+    let body_name = json["BodyName"].as_str().unwrap_or_default();
+    let missing = |key: &str| format!("Scan of '{}' misses {}", body_name, key);
+
+    for parent in json["Parents"].members() {
+        for (kind, id) in parent.entries() {
+            if id.as_u64().is_none() {
+                return Err(missing(&format!("the id of the {} parent", kind)));
+            }
+        }
+    }
+    for ring in json["Rings"].members() {
+        for key in ["MassMT", "InnerRad", "OuterRad"] {
+            if ring[key].as_f64().is_none() {
+                return Err(missing(&format!("{} of a ring", key)));
+            }
+        }
+    }
+    if json["BodyID"].as_u64().is_none() {
+        return Err(missing("BodyID"));
+    }
+    if json["SystemAddress"].as_i64().is_none() {
+        return Err(missing("SystemAddress"));
+    }
+
+    let orbit = [
+        "SemiMajorAxis",
+        "Eccentricity",
+        "OrbitalInclination",
+        "Periapsis",
+        "OrbitalPeriod",
+        "AscendingNode",
+        "MeanAnomaly",
+    ];
+    let (numbers, integers, flags): (Vec<&str>, Vec<&str>, Vec<&str>) =
+        if !json["StarType"].is_null() {
+            (
+                vec![
+                    "StellarMass",
+                    "Radius",
+                    "AbsoluteMagnitude",
+                    "SurfaceTemperature",
+                    "RotationPeriod",
+                    "AxialTilt",
+                ],
+                vec!["Subclass", "Age_MY"],
+                vec![],
+            )
+        } else if body_name.contains("Belt Cluster") {
+            (vec![], vec![], vec![])
+        } else if body_name.contains("Ring") {
+            (orbit.to_vec(), vec![], vec![])
+        } else {
+            let mut numbers = orbit.to_vec();
+            numbers.extend([
+                "MassEM",
+                "Radius",
+                "SurfaceGravity",
+                "SurfaceTemperature",
+                "SurfacePressure",
+                "RotationPeriod",
+                "AxialTilt",
+            ]);
+            (numbers, vec![], vec!["TidalLock", "Landable"])
+        };
+    for key in numbers.into_iter().chain(["DistanceFromArrivalLS"]) {
+        if json[key].as_f64().is_none() {
+            return Err(missing(key));
+        }
+    }
+    for key in integers {
+        if json[key].as_i64().is_none() {
+            return Err(missing(key));
+        }
+    }
+    for key in flags.into_iter().chain(["WasDiscovered", "WasMapped"]) {
+        if json[key].as_bool().is_none() {
+            return Err(missing(key));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 pub(crate) enum BodyType {
     Star(crate::edcas::explorer::star::Star),
     Planet(crate::edcas::explorer::planet::Planet),
     Ring(crate::edcas::explorer::ring::Ring),
     BeltCluster(crate::edcas::explorer::belt_cluster::BeltCluster),
+    Barycentre(crate::edcas::explorer::barycentre::Barycentre),
 }
 
 impl BodyType {
@@ -187,6 +284,7 @@ impl BodyType {
             BodyType::Planet(body) => body.body_id,
             BodyType::Ring(body) => body.body_id,
             BodyType::BeltCluster(body) => body.body_id,
+            BodyType::Barycentre(body) => body.body_id,
         }
     }
 
@@ -196,6 +294,7 @@ impl BodyType {
             BodyType::Planet(body) => &body.parents,
             BodyType::Ring(body) => &body.parents,
             BodyType::BeltCluster(body) => &body.parents,
+            BodyType::Barycentre(body) => &body.parents,
         }
     }
 
@@ -206,6 +305,7 @@ impl BodyType {
             BodyType::Planet(body) => body.body_name.clone(),
             BodyType::Ring(body) => body.body_name.clone(),
             BodyType::BeltCluster(body) => body.body_name.clone(),
+            BodyType::Barycentre(body) => body.body_name.clone(),
         }
     }
 
@@ -219,6 +319,9 @@ impl BodyType {
             BodyType::BeltCluster(_) => {
                 unreachable!("BeltClusters cannot have signals")
             }
+            BodyType::Barycentre(_) => {
+                unreachable!("Barycentres cannot have signals")
+            }
         }
     }
 
@@ -232,6 +335,25 @@ impl BodyType {
             BodyType::BeltCluster(_) => {
                 unreachable!("BeltClusters cannot have signals")
             }
+            BodyType::Barycentre(_) => {
+                unreachable!("Barycentres cannot have signals")
+            }
+        }
+    }
+
+    pub(crate) fn get_highlights(&self) -> Vec<String> {
+        match self {
+            BodyType::Star(body) => body.highlights.clone(),
+            BodyType::Planet(body) => body.highlights.clone(),
+            BodyType::Ring(_) | BodyType::BeltCluster(_) | BodyType::Barycentre(_) => vec![],
+        }
+    }
+
+    pub(crate) fn set_highlights(&mut self, highlights: Vec<String>) {
+        match self {
+            BodyType::Star(body) => body.highlights = highlights,
+            BodyType::Planet(body) => body.highlights = highlights,
+            BodyType::Ring(_) | BodyType::BeltCluster(_) | BodyType::Barycentre(_) => {}
         }
     }
 
@@ -243,7 +365,7 @@ impl BodyType {
             }
             BodyType::Planet(body) => body.planet_signals.clone(),
             BodyType::Ring(body) => body.ring_signals.clone(),
-            BodyType::BeltCluster(_) => {
+            BodyType::BeltCluster(_) | BodyType::Barycentre(_) => {
                 vec![]
             }
         }
@@ -262,6 +384,13 @@ pub struct Parent {
     pub id: u64,
 }
 
+impl Parent {
+    /// Barycentres are listed as `Null` in the journal
+    pub fn is_barycentre(&self) -> bool {
+        self.name == "Null"
+    }
+}
+
 #[derive(Clone)]
 pub struct Signal {
     pub r#type: String,
@@ -278,3 +407,35 @@ impl Default for Signal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+
+    #[test]
+    fn journal_scans_are_valid() {
+        let star = json::parse(r#"{ "timestamp":"2023-07-12T21:52:23Z", "event":"Scan", "ScanType":"AutoScan", "BodyName":"Lasao DX-Z b43-37 A", "BodyID":1, "Parents":[ {"Null":0} ], "StarSystem":"Lasao DX-Z b43-37", "SystemAddress":82108367853945, "DistanceFromArrivalLS":0.000000, "StarType":"M", "Subclass":7, "StellarMass":0.285156, "Radius":307783360.000000, "AbsoluteMagnitude":10.356186, "Age_MY":3076, "SurfaceTemperature":2434.000000, "Luminosity":"Va", "SemiMajorAxis":514860939979.553223, "Eccentricity":0.153621, "OrbitalInclination":2.176175, "Periapsis":6.939240, "OrbitalPeriod":4139431655.406952, "AscendingNode":-133.798577, "MeanAnomaly":169.548183, "RotationPeriod":118438.397553, "AxialTilt":0.000000, "Rings":[ { "Name":"Lasao DX-Z b43-37 A A Belt", "RingClass":"eRingClass_Rocky", "MassMT":7.2313e+13, "InnerRad":5.0784e+08, "OuterRad":1.6453e+09 } ], "WasDiscovered":false, "WasMapped":false }"#).unwrap();
+        let ring = json::parse(r#"{"timestamp":"2023-07-17T18:18:37Z","event":"Scan","ScanType":"AutoScan","BodyName":"Scheau Blao BB-W b57-45 1 B Ring","BodyID":7,"Parents":[{"Planet":5},{"Star":0}],"StarSystem":"Scheau Blao BB-W b57-45","SystemAddress":99684979268081,"DistanceFromArrivalLS":535.932803,"SemiMajorAxis":208998668.193817,"Eccentricity":0,"OrbitalInclination":0,"Periapsis":0,"OrbitalPeriod":44426.782131,"AscendingNode":0,"MeanAnomaly":190.333503,"WasDiscovered":false,"WasMapped":false}"#).unwrap();
+        for scan in [star, ring] {
+            let id = scan["BodyID"].as_u64().unwrap();
+            let body = try_generate_from_json(scan, get_test_settings()).unwrap();
+            assert_eq!(body.get_id(), id);
+        }
+    }
+
+    #[test]
+    fn partial_scans_are_an_error() {
+        let planet = json::parse(r#"{ "timestamp":"2022-10-16T23:51:17Z", "event":"Scan", "ScanType":"Detailed", "BodyName":"Ogmar A 6", "BodyID":40, "Parents":[ {"Star":1}, {"Null":0} ], "StarSystem":"Ogmar", "SystemAddress":84180519395914, "DistanceFromArrivalLS":3376.246435, "TidalLock":false, "PlanetClass":"Sudarsky class I gas giant", "MassEM":24.477320, "Radius":22773508.000000, "WasDiscovered":true, "WasMapped":true }"#).unwrap();
+        let err = try_generate_from_json(planet, get_test_settings())
+            .err()
+            .unwrap();
+        assert_eq!(err, "Scan of 'Ogmar A 6' misses SemiMajorAxis");
+
+        let star = json::parse(r#"{ "event":"Scan", "BodyName":"Sol", "BodyID":0, "Parents":[ {"Null":"zero"} ], "SystemAddress":10477373803, "StarType":"G" }"#).unwrap();
+        let err = try_generate_from_json(star, get_test_settings())
+            .err()
+            .unwrap();
+        assert_eq!(err, "Scan of 'Sol' misses the id of the Null parent");
+    }
+}
@@ -0,0 +1,607 @@
+use std::fmt::Display;
+
+use eframe::egui::{Color32, RichText};
+
+use crate::edcas::explorer::body::BodyType;
+use crate::edcas::explorer::planet::{get_body_class_from_body, get_profit_from_body, Planet};
+use crate::edcas::explorer::star::Star;
+
+/// A user defined rule, e.g. `landable && surface_gravity < 0.3 && atmosphere != ''`.
+/// Bodies matching the expression get the rule's char and color shown next to their name.
+#[derive(Clone)]
+pub struct HighlightRule {
+    pub name: String,
+    pub expression: String,
+    pub char: String,
+    pub color: Color32,
+    pub enabled: bool,
+    /// The expression parsed once, see [`HighlightRule::parse`]
+    pub parsed: Result<Expression, String>,
+}
+
+impl HighlightRule {
+    pub fn new(
+        name: String,
+        expression: String,
+        char: String,
+        color: Color32,
+        enabled: bool,
+    ) -> Self {
+        let parsed = parse(&expression);
+        HighlightRule {
+            name,
+            expression,
+            char,
+            color,
+            enabled,
+            parsed,
+        }
+    }
+
+    /// Parses the expression again after it was edited
+    pub fn parse(&mut self) {
+        self.parsed = parse(&self.expression);
+    }
+
+    pub fn get_richtext(&self) -> RichText {
+        RichText::new(&self.char).color(self.color)
+    }
+
+    pub fn matches(&self, body: &BodyType) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match &self.parsed {
+            Ok(expression) => expression.evaluate(body),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Fields a rule can refer to. Planet-only fields never match stars and vice versa.
+pub const FIELDS: [&str; 30] = [
+    "name",
+    "distance",
+    "discovered",
+    "mapped",
+    "rings",
+    "star",
+    "planet",
+    "planet_class",
+    "terraform_state",
+    "terraformable",
+    "atmosphere",
+    "atmosphere_type",
+    "volcanism",
+    "landable",
+    "tidal_lock",
+    "surface_gravity",
+    "surface_temperature",
+    "surface_pressure",
+    "mass_em",
+    "radius",
+    "reserve_level",
+    "signals",
+    "bio_signals",
+    "geo_signals",
+    "value",
+    "star_type",
+    "subclass",
+    "stellar_mass",
+    "luminosity",
+    "age_my",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            Value::Number(value) => *value != 0.0,
+            Value::Text(value) => !value.is_empty() && value != "null",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Contains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Field(String),
+    Compare(String, Operator, Value),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn evaluate(&self, body: &BodyType) -> bool {
+        match self {
+            Expression::Field(field) => match field_value(body, field) {
+                None => false,
+                Some(value) => value.is_truthy(),
+            },
+            Expression::Compare(field, operator, expected) => match field_value(body, field) {
+                None => false,
+                Some(value) => compare(&value, operator, expected),
+            },
+            Expression::Not(expression) => !expression.evaluate(body),
+            Expression::And(left, right) => left.evaluate(body) && right.evaluate(body),
+            Expression::Or(left, right) => left.evaluate(body) || right.evaluate(body),
+        }
+    }
+}
+
+fn compare(value: &Value, operator: &Operator, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::Number(value), Value::Number(expected)) => match operator {
+            Operator::Equal => value == expected,
+            Operator::NotEqual => value != expected,
+            Operator::Less => value < expected,
+            Operator::LessEqual => value <= expected,
+            Operator::Greater => value > expected,
+            Operator::GreaterEqual => value >= expected,
+            Operator::Contains => false,
+        },
+        (Value::Bool(value), Value::Bool(expected)) => match operator {
+            Operator::Equal => value == expected,
+            Operator::NotEqual => value != expected,
+            _ => false,
+        },
+        (Value::Text(value), Value::Text(expected)) => {
+            //Journal strings are null if absent, which should compare like an empty string
+            let value = if value == "null" { "" } else { value.as_str() };
+            let value = value.to_lowercase();
+            let expected = expected.to_lowercase();
+            match operator {
+                Operator::Equal => value == expected,
+                Operator::NotEqual => value != expected,
+                Operator::Contains => value.contains(&expected),
+                Operator::Less => value < expected,
+                Operator::LessEqual => value <= expected,
+                Operator::Greater => value > expected,
+                Operator::GreaterEqual => value >= expected,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn field_value(body: &BodyType, field: &str) -> Option<Value> {
+    match body {
+        BodyType::Planet(planet) => planet_field_value(planet, field),
+        BodyType::Star(star) => star_field_value(star, field),
+        BodyType::Ring(_) | BodyType::BeltCluster(_) | BodyType::Barycentre(_) => None,
+    }
+}
+
+fn planet_field_value(planet: &Planet, field: &str) -> Option<Value> {
+    let signal_count = |signal_type: &str| {
+        planet
+            .planet_signals
+            .iter()
+            .filter(|signal| signal.r#type == signal_type)
+            .map(|signal| signal.count)
+            .sum::<u64>() as f64
+    };
+    let value = match field {
+        "name" => Value::Text(planet.body_name.clone()),
+        "distance" => Value::Number(planet.distance_from_arrival_ls),
+        "discovered" => Value::Bool(planet.was_discovered),
+        "mapped" => Value::Bool(planet.was_mapped),
+        "rings" => Value::Number(planet.asteroid_rings.len() as f64),
+        "star" => Value::Bool(false),
+        "planet" => Value::Bool(true),
+        "planet_class" => Value::Text(planet.planet_class.clone()),
+        "terraform_state" => Value::Text(planet.terraform_state.clone()),
+        "terraformable" => Value::Bool(planet.terraform_state == "Terraformable"),
+        "atmosphere" => Value::Text(planet.atmosphere.clone()),
+        "atmosphere_type" => Value::Text(planet.atmosphere_type.clone()),
+        "volcanism" => Value::Text(planet.volcanism.clone()),
+        "landable" => Value::Bool(planet.landable),
+        "tidal_lock" => Value::Bool(planet.tidal_lock),
+        //Journal reports m/s², rules are written in G like in game
+        "surface_gravity" => Value::Number(planet.surface_gravity / 9.80665),
+        "surface_temperature" => Value::Number(planet.surface_temperature),
+        "surface_pressure" => Value::Number(planet.surface_pressure),
+        "mass_em" => Value::Number(planet.mass_em),
+        "radius" => Value::Number(planet.radius / 1000.0),
+        "reserve_level" => Value::Text(planet.reserve_level.clone()),
+        "signals" => Value::Number(
+            planet
+                .planet_signals
+                .iter()
+                .map(|signal| signal.count)
+                .sum::<u64>() as f64,
+        ),
+        "bio_signals" => Value::Number(signal_count("$SAA_SignalType_Biological;")),
+        "geo_signals" => Value::Number(signal_count("$SAA_SignalType_Geological;")),
+        "value" => Value::Number(
+            get_profit_from_body(get_body_class_from_body(planet), planet.was_discovered).1 as f64,
+        ),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn star_field_value(star: &Star, field: &str) -> Option<Value> {
+    let value = match field {
+        "name" => Value::Text(star.body_name.clone()),
+        "distance" => Value::Number(star.distance_from_arrival_ls),
+        "discovered" => Value::Bool(star.was_discovered),
+        "mapped" => Value::Bool(star.was_mapped),
+        "rings" => Value::Number(star.asteroid_rings.len() as f64),
+        "star" => Value::Bool(true),
+        "planet" => Value::Bool(false),
+        "surface_temperature" => Value::Number(star.surface_temperature),
+        "radius" => Value::Number(star.radius / 1000.0),
+        "star_type" => Value::Text(star.star_type.clone()),
+        "subclass" => Value::Number(star.subclass as f64),
+        "stellar_mass" => Value::Number(star.stellar_mass),
+        "luminosity" => Value::Text(star.luminosity.clone()),
+        "age_my" => Value::Number(star.age_my as f64),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Value),
+    Operator(Operator),
+    And,
+    Or,
+    Not,
+    OpenParenthesis,
+    CloseParenthesis,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Identifier(identifier) => write!(f, "{}", identifier),
+            Token::Literal(value) => write!(f, "{:?}", value),
+            Token::Operator(operator) => write!(f, "{:?}", operator),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Not => write!(f, "!"),
+            Token::OpenParenthesis => write!(f, "("),
+            Token::CloseParenthesis => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            ' ' | '\t' | '\n' => {
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::OpenParenthesis);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::CloseParenthesis);
+                i += 1;
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '|' if next == Some('|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '=' if next == Some('=') => {
+                tokens.push(Token::Operator(Operator::Equal));
+                i += 2;
+            }
+            '!' if next == Some('=') => {
+                tokens.push(Token::Operator(Operator::NotEqual));
+                i += 2;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '<' if next == Some('=') => {
+                tokens.push(Token::Operator(Operator::LessEqual));
+                i += 2;
+            }
+            '<' => {
+                tokens.push(Token::Operator(Operator::Less));
+                i += 1;
+            }
+            '>' if next == Some('=') => {
+                tokens.push(Token::Operator(Operator::GreaterEqual));
+                i += 2;
+            }
+            '>' => {
+                tokens.push(Token::Operator(Operator::Greater));
+                i += 1;
+            }
+            '~' => {
+                tokens.push(Token::Operator(Operator::Contains));
+                i += 1;
+            }
+            '\'' | '"' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|other| *other == c)
+                    .ok_or(format!("Unterminated string at position {}", i))?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                tokens.push(Token::Literal(Value::Text(text)));
+                i += end + 2;
+            }
+            _ if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                let number = number
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid number: {}", number))?;
+                tokens.push(Token::Literal(Value::Number(number)));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.as_str() {
+                    "true" => tokens.push(Token::Literal(Value::Bool(true))),
+                    "false" => tokens.push(Token::Literal(Value::Bool(false))),
+                    "and" => tokens.push(Token::And),
+                    "or" => tokens.push(Token::Or),
+                    "not" => tokens.push(Token::Not),
+                    _ => tokens.push(Token::Identifier(word)),
+                }
+            }
+            _ => {
+                return Err(format!("Unexpected character '{}' at position {}", c, i));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_unary()?;
+        while let Some(Token::And) = self.peek() {
+            self.next();
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expression::Not(Box::new(self.parse_unary()?))),
+            Some(Token::OpenParenthesis) => {
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some(Token::CloseParenthesis) => Ok(expression),
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(Token::Identifier(field)) => {
+                if !FIELDS.contains(&field.as_str()) {
+                    return Err(format!("Unknown field: {}", field));
+                }
+                if let Some(Token::Operator(operator)) = self.peek().cloned() {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Literal(value)) => {
+                            Ok(Expression::Compare(field, operator, value))
+                        }
+                        Some(token) => {
+                            Err(format!("Expected value after {}, found {}", field, token))
+                        }
+                        None => Err(format!("Expected value after {}", field)),
+                    }
+                } else {
+                    Ok(Expression::Field(field))
+                }
+            }
+            Some(token) => Err(format!("Unexpected {}", token)),
+            None => Err("Unexpected end of rule".to_string()),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expression, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+    let expression = parser.parse_or()?;
+    match parser.next() {
+        None => Ok(expression),
+        Some(token) => Err(format!("Unexpected {}", token)),
+    }
+}
+
+/// Names of all enabled rules that match the body
+pub fn get_matching_rules(rules: &[HighlightRule], body: &BodyType) -> Vec<String> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(body))
+        .map(|rule| rule.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare_expression(field: &str, operator: Operator, value: Value) -> Expression {
+        Expression::Compare(field.to_string(), operator, value)
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        assert_eq!(
+            parse("surface_gravity < 0.3").unwrap(),
+            compare_expression("surface_gravity", Operator::Less, Value::Number(0.3))
+        );
+        assert_eq!(
+            parse("atmosphere != ''").unwrap(),
+            compare_expression("atmosphere", Operator::NotEqual, Value::Text(String::new()))
+        );
+        assert_eq!(
+            parse("planet_class ~ \"water\"").unwrap(),
+            compare_expression(
+                "planet_class",
+                Operator::Contains,
+                Value::Text("water".to_string())
+            )
+        );
+        assert_eq!(
+            parse("distance >= -12.5").unwrap(),
+            compare_expression("distance", Operator::GreaterEqual, Value::Number(-12.5))
+        );
+        assert_eq!(
+            parse("landable == true").unwrap(),
+            compare_expression("landable", Operator::Equal, Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn and_binds_stronger_than_or() {
+        assert_eq!(
+            parse("landable || terraformable && !mapped").unwrap(),
+            Expression::Or(
+                Box::new(Expression::Field("landable".to_string())),
+                Box::new(Expression::And(
+                    Box::new(Expression::Field("terraformable".to_string())),
+                    Box::new(Expression::Not(Box::new(Expression::Field(
+                        "mapped".to_string()
+                    )))),
+                )),
+            )
+        );
+        assert_eq!(
+            parse("(landable or terraformable) and not mapped").unwrap(),
+            Expression::And(
+                Box::new(Expression::Or(
+                    Box::new(Expression::Field("landable".to_string())),
+                    Box::new(Expression::Field("terraformable".to_string())),
+                )),
+                Box::new(Expression::Not(Box::new(Expression::Field(
+                    "mapped".to_string()
+                )))),
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert_eq!(parse("gravity < 1").unwrap_err(), "Unknown field: gravity");
+        assert_eq!(
+            parse("(landable").unwrap_err(),
+            "Missing closing parenthesis"
+        );
+        assert_eq!(parse("").unwrap_err(), "Unexpected end of rule");
+        assert_eq!(
+            parse("landable landable").unwrap_err(),
+            "Unexpected landable"
+        );
+        assert_eq!(
+            parse("atmosphere == 'thin").unwrap_err(),
+            "Unterminated string at position 14"
+        );
+        assert_eq!(
+            parse("landable & mapped").unwrap_err(),
+            "Unexpected character '&' at position 9"
+        );
+        assert_eq!(
+            parse("radius <").unwrap_err(),
+            "Expected value after radius"
+        );
+    }
+
+    #[test]
+    fn compares_text_case_insensitive() {
+        let value = Value::Text("Thin Ammonia atmosphere".to_string());
+        assert!(compare(
+            &value,
+            &Operator::Contains,
+            &Value::Text("ammonia".to_string())
+        ));
+        assert!(compare(
+            &Value::Text("null".to_string()),
+            &Operator::Equal,
+            &Value::Text(String::new())
+        ));
+        assert!(!compare(
+            &Value::Number(1.0),
+            &Operator::Equal,
+            &Value::Text("1".to_string())
+        ));
+    }
+
+    #[test]
+    fn rule_keeps_parsed_expression() {
+        let mut rule = HighlightRule::new(
+            "Rule".to_string(),
+            "landable &&".to_string(),
+            "★".to_string(),
+            Color32::WHITE,
+            true,
+        );
+        assert!(rule.parsed.is_err());
+        rule.expression = "landable".to_string();
+        rule.parse();
+        assert_eq!(rule.parsed, Ok(Expression::Field("landable".to_string())));
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Utc};
+
+use crate::edcas::explorer::body::{BodyType, Parent};
+
+/// Keplerian elements as written by the journal `Scan` event.
+/// Distances are in meters, angles in degrees and the period in seconds.
+#[derive(Clone)]
+pub struct OrbitalElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub orbital_inclination: f64,
+    pub periapsis: f64,
+    pub ascending_node: f64,
+    pub mean_anomaly: f64,
+    pub orbital_period: f64,
+    pub epoch: Option<DateTime<Utc>>,
+}
+
+impl OrbitalElements {
+    /// Mean anomaly in radians at the given time, advanced from the scan timestamp
+    pub fn mean_anomaly_at(&self, time: DateTime<Utc>) -> f64 {
+        let mut mean_anomaly = self.mean_anomaly.to_radians();
+        if let Some(epoch) = self.epoch {
+            if self.orbital_period > 0.0 {
+                let elapsed = (time - epoch).num_milliseconds() as f64 / 1000.0;
+                mean_anomaly += 2.0 * PI * elapsed / self.orbital_period;
+            }
+        }
+        mean_anomaly.rem_euclid(2.0 * PI)
+    }
+
+    /// Top-down (x, y) position in meters relative to the parent at the given time
+    pub fn position_at(&self, time: DateTime<Utc>) -> (f64, f64) {
+        let eccentric_anomaly = solve_kepler(self.mean_anomaly_at(time), self.eccentricity);
+        self.position_from_eccentric_anomaly(eccentric_anomaly)
+    }
+
+    /// Points along the full orbit, used to draw the ellipse
+    pub fn orbit_points(&self, count: usize) -> Vec<(f64, f64)> {
+        (0..=count)
+            .map(|i| self.position_from_eccentric_anomaly(2.0 * PI * i as f64 / count as f64))
+            .collect()
+    }
+
+    fn position_from_eccentric_anomaly(&self, eccentric_anomaly: f64) -> (f64, f64) {
+        let e = self.eccentricity.clamp(0.0, 0.99);
+        let true_anomaly = 2.0
+            * f64::atan2(
+                (1.0 + e).sqrt() * (eccentric_anomaly / 2.0).sin(),
+                (1.0 - e).sqrt() * (eccentric_anomaly / 2.0).cos(),
+            );
+        let distance = self.semi_major_axis * (1.0 - e * eccentric_anomaly.cos());
+
+        let ascending_node = self.ascending_node.to_radians();
+        let argument = self.periapsis.to_radians() + true_anomaly;
+        let inclination = self.orbital_inclination.to_radians();
+
+        let x = distance
+            * (ascending_node.cos() * argument.cos()
+                - ascending_node.sin() * argument.sin() * inclination.cos());
+        let y = distance
+            * (ascending_node.sin() * argument.cos()
+                + ascending_node.cos() * argument.sin() * inclination.cos());
+        (x, y)
+    }
+}
+
+/// Solves Kepler's equation M = E - e*sin(E) for the eccentric anomaly E with Newton's method
+pub fn solve_kepler(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let e = eccentricity.clamp(0.0, 0.99);
+    let mut eccentric_anomaly = if e > 0.8 { PI } else { mean_anomaly };
+    for _ in 0..50 {
+        let delta = (eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly)
+            / (1.0 - e * eccentric_anomaly.cos());
+        eccentric_anomaly -= delta;
+        if delta.abs() < 1e-10 {
+            break;
+        }
+    }
+    eccentric_anomaly
+}
+
+/// Parses the journal timestamp or, for bodies from the EVM, the unix timestamp
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => Some(time.with_timezone(&Utc)),
+        Err(_) => timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+    }
+}
+
+/// The nearest parent listed in `Parents`, i.e. the body or barycentre this body orbits
+pub fn get_direct_parent(body: &BodyType) -> Option<&Parent> {
+    body.get_parents().first()
+}
+
+/// Indices of the bodies orbiting each parent, `None` being the root of the system.
+/// Bodies whose parents are not scanned yet are attached to the nearest known one.
+pub fn get_children(body_list: &[BodyType]) -> HashMap<Option<u64>, Vec<usize>> {
+    let ids: HashSet<u64> = body_list.iter().map(|body| body.get_id()).collect();
+    let mut children: HashMap<Option<u64>, Vec<usize>> = HashMap::new();
+    for (i, body) in body_list.iter().enumerate() {
+        let parent_id = body
+            .get_parents()
+            .iter()
+            .map(|parent| parent.id)
+            .find(|id| ids.contains(id));
+        children.entry(parent_id).or_default().push(i);
+    }
+    children
+}
+
+impl BodyType {
+    pub(crate) fn get_orbital_elements(&self) -> Option<OrbitalElements> {
+        match self {
+            BodyType::Planet(planet) => Some(OrbitalElements {
+                semi_major_axis: planet.semi_major_axis,
+                eccentricity: planet.eccentricity,
+                orbital_inclination: planet.orbital_inclination,
+                periapsis: planet.periapsis,
+                ascending_node: planet.ascending_node,
+                mean_anomaly: planet.mean_anomaly,
+                orbital_period: planet.orbital_period,
+                epoch: parse_timestamp(&planet.timestamp),
+            }),
+            BodyType::Star(star) => Some(OrbitalElements {
+                semi_major_axis: star.semi_major_axis?,
+                eccentricity: star.eccentricity.unwrap_or(0.0),
+                orbital_inclination: star.orbital_inclination.unwrap_or(0.0),
+                periapsis: star.periapsis.unwrap_or(0.0),
+                ascending_node: star.ascending_node.unwrap_or(0.0),
+                mean_anomaly: star.mean_anomaly.unwrap_or(0.0),
+                orbital_period: star.orbital_period.unwrap_or(0.0),
+                epoch: parse_timestamp(&star.timestamp),
+            }),
+            BodyType::Barycentre(barycentre) => Some(OrbitalElements {
+                semi_major_axis: barycentre.semi_major_axis?,
+                eccentricity: barycentre.eccentricity.unwrap_or(0.0),
+                orbital_inclination: barycentre.orbital_inclination.unwrap_or(0.0),
+                periapsis: barycentre.periapsis.unwrap_or(0.0),
+                ascending_node: barycentre.ascending_node.unwrap_or(0.0),
+                mean_anomaly: barycentre.mean_anomaly.unwrap_or(0.0),
+                orbital_period: barycentre.orbital_period.unwrap_or(0.0),
+                epoch: parse_timestamp(&barycentre.timestamp),
+            }),
+            BodyType::Ring(_) | BodyType::BeltCluster(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_elements(eccentricity: f64) -> OrbitalElements {
+        OrbitalElements {
+            semi_major_axis: 1000.0,
+            eccentricity,
+            orbital_inclination: 0.0,
+            periapsis: 0.0,
+            ascending_node: 0.0,
+            mean_anomaly: 0.0,
+            orbital_period: 400.0,
+            epoch: parse_timestamp("2024-03-02T17:40:12Z"),
+        }
+    }
+
+    #[test]
+    fn circular_orbits_have_no_correction() {
+        for mean_anomaly in [0.0, 0.5, PI / 2.0, PI, 5.0] {
+            assert_eq!(solve_kepler(mean_anomaly, 0.0), mean_anomaly);
+        }
+
+        //A quarter period later the body is a quarter around its parent
+        let elements = get_elements(0.0);
+        let time = parse_timestamp("2024-03-02T17:41:52Z").unwrap();
+        assert!((elements.mean_anomaly_at(time) - PI / 2.0).abs() < 1e-12);
+        let (x, y) = elements.position_at(time);
+        assert!(x.abs() < 1e-9);
+        assert!((y - 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn eccentric_orbits_solve_keplers_equation() {
+        //Textbook value for M = 1 rad and e = 0.5
+        assert!((solve_kepler(1.0, 0.5) - 1.498701133517848).abs() < 1e-9);
+
+        //High eccentricities start at PI, the result still fulfills the equation
+        let eccentric_anomaly = solve_kepler(2.0, 0.9);
+        assert!((eccentric_anomaly - 0.9 * eccentric_anomaly.sin() - 2.0).abs() < 1e-9);
+
+        //At the periapsis the body is closest, at the apoapsis farthest from the parent
+        let elements = get_elements(0.5);
+        let (x, y) = elements.position_from_eccentric_anomaly(0.0);
+        assert!((x - 500.0).abs() < 1e-9 && y.abs() < 1e-9);
+        let (x, y) = elements.position_from_eccentric_anomaly(PI);
+        assert!((x + 1500.0).abs() < 1e-9 && y.abs() < 1e-6);
+    }
+}
@@ -82,6 +82,7 @@ pub struct Planet {
     pub reserve_level: String,
     pub asteroid_rings: Vec<AsteroidRing>,
     pub planet_signals: Vec<Signal>,
+    pub highlights: Vec<String>,
     pub settings: Arc<Mutex<Settings>>,
 }
 
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+use chrono::{DateTime, Duration, Utc};
+use json::JsonValue;
+use log::{error, info};
+use serde_json::json;
+
+use crate::edcas::explorer::orbit::parse_timestamp;
+use crate::edcas::settings::get_data_path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignalClass {
+    Station,
+    FleetCarrier,
+    ResourceExtraction,
+    ConflictZone,
+    UnidentifiedSignal,
+    Thargoid,
+    Guardian,
+    Megaship,
+    Other,
+}
+
+impl SignalClass {
+    pub const ALL: [SignalClass; 9] = [
+        SignalClass::Station,
+        SignalClass::FleetCarrier,
+        SignalClass::ResourceExtraction,
+        SignalClass::ConflictZone,
+        SignalClass::UnidentifiedSignal,
+        SignalClass::Thargoid,
+        SignalClass::Guardian,
+        SignalClass::Megaship,
+        SignalClass::Other,
+    ];
+
+    fn from_name(name: &str) -> SignalClass {
+        SignalClass::ALL
+            .into_iter()
+            .find(|class| class.to_string() == name)
+            .unwrap_or(SignalClass::Other)
+    }
+
+    /// Steps through all classes, `None` being no filter
+    pub fn next_filter(filter: Option<SignalClass>) -> Option<SignalClass> {
+        match filter {
+            None => Some(SignalClass::ALL[0]),
+            Some(class) => SignalClass::ALL
+                .iter()
+                .position(|other| other == &class)
+                .and_then(|index| SignalClass::ALL.get(index + 1).copied()),
+        }
+    }
+}
+
+impl Display for SignalClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SignalClass::Station => "Station",
+            SignalClass::FleetCarrier => "Fleet Carrier",
+            SignalClass::ResourceExtraction => "Resource Extraction",
+            SignalClass::ConflictZone => "Conflict Zone",
+            SignalClass::UnidentifiedSignal => "USS",
+            SignalClass::Thargoid => "Thargoid",
+            SignalClass::Guardian => "Guardian",
+            SignalClass::Megaship => "Megaship",
+            SignalClass::Other => "Other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone)]
+pub struct SystemSignal {
+    pub name: String,
+    pub class: SignalClass,
+    /// Callsign, site grade, zone intensity or USS type, depending on the class
+    pub detail: String,
+    pub threat: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Only USSes despawn
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl SystemSignal {
+    pub fn from_json(json: &JsonValue) -> Self {
+        let mut name = json["SignalName_Localised"].to_string();
+        if name == *"null" {
+            name = json["SignalName"].to_string();
+            if name == *"null" {
+                name = json["USSType_Localised"].to_string();
+            }
+        }
+
+        let mut threat = json["ThreatLevel"].to_string();
+        if threat == *"null" {
+            threat = "".to_string();
+        }
+
+        let seen = parse_timestamp(&json["timestamp"].to_string()).unwrap_or_else(Utc::now);
+        let expires = json["TimeRemaining"]
+            .as_f64()
+            .map(|seconds| seen + Duration::milliseconds((seconds * 1000.0) as i64));
+
+        let (class, detail) = classify(json);
+        SystemSignal {
+            name,
+            class,
+            detail,
+            threat,
+            first_seen: seen,
+            last_seen: seen,
+            expires,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Scanning the same signal again only updates the times
+    pub fn is_same_signal(&self, other: &SystemSignal) -> bool {
+        self.class == other.class
+            && self.name == other.name
+            && self.detail == other.detail
+            && self.threat == other.threat
+    }
+
+    fn from_store(json: &JsonValue) -> Option<Self> {
+        Some(SystemSignal {
+            name: json["name"].as_str()?.to_string(),
+            class: SignalClass::from_name(json["class"].as_str().unwrap_or("")),
+            detail: json["detail"].as_str().unwrap_or("").to_string(),
+            threat: json["threat"].as_str().unwrap_or("").to_string(),
+            first_seen: parse_timestamp(json["first-seen"].as_str()?)?,
+            last_seen: parse_timestamp(json["last-seen"].as_str()?)?,
+            expires: json["expires"].as_str().and_then(parse_timestamp),
+        })
+    }
+
+    fn to_store(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "class": self.class.to_string(),
+            "detail": self.detail,
+            "threat": self.threat,
+            "first-seen": self.first_seen.to_rfc3339(),
+            "last-seen": self.last_seen.to_rfc3339(),
+            "expires": self.expires.map(|expires| expires.to_rfc3339()),
+        })
+    }
+}
+
+/// Sorts the signal into its class. Newer journals tell the class in `SignalType`,
+/// older ones only through the name.
+pub fn classify(json: &JsonValue) -> (SignalClass, String) {
+    let signal_name = json["SignalName"].to_string();
+    let signal_type = json["SignalType"].as_str().unwrap_or("");
+
+    if !json["USSType"].is_null() || signal_type == "USS" || signal_name.starts_with("$USS") {
+        let mut uss_type = json["USSType_Localised"].to_string();
+        if uss_type == *"null" {
+            uss_type = json["USSType"].to_string();
+        }
+        return (SignalClass::UnidentifiedSignal, uss_type);
+    }
+    if signal_type == "Megaship" || signal_type == "StationMegaShip" {
+        return (SignalClass::Megaship, "".to_string());
+    }
+    if let Some(callsign) = get_carrier_callsign(&signal_name) {
+        if signal_type == "FleetCarrier"
+            || signal_type == "SquadronCarrier"
+            || json["IsStation"].as_bool().unwrap_or(false)
+        {
+            return (SignalClass::FleetCarrier, callsign);
+        }
+    }
+    if json["IsStation"].as_bool().unwrap_or(false)
+        || signal_type.starts_with("Station")
+        || signal_type == "Outpost"
+    {
+        return (SignalClass::Station, "".to_string());
+    }
+    if signal_type == "Titan" || signal_name.contains("_TG_") || signal_name.contains("Thargoid") {
+        return (SignalClass::Thargoid, "".to_string());
+    }
+    if signal_name.contains("Guardian") || signal_name.contains("$Ancient") {
+        return (SignalClass::Guardian, "".to_string());
+    }
+    let grade = match signal_name.as_str() {
+        "$MULTIPLAYER_SCENARIO14_TITLE;" => Some("Normal"),
+        "$MULTIPLAYER_SCENARIO77_TITLE;" => Some("Low"),
+        "$MULTIPLAYER_SCENARIO78_TITLE;" => Some("High"),
+        "$MULTIPLAYER_SCENARIO79_TITLE;" => Some("Hazardous"),
+        _ => None,
+    };
+    if signal_type == "ResourceExtraction" || grade.is_some() {
+        return (
+            SignalClass::ResourceExtraction,
+            grade.unwrap_or("").to_string(),
+        );
+    }
+    if signal_type == "Combat" || signal_name.starts_with("$Warzone_PointRace") {
+        let intensity = if signal_name.contains("_Low") {
+            "Low"
+        } else if signal_name.contains("_Med") {
+            "Medium"
+        } else if signal_name.contains("_High") {
+            "High"
+        } else {
+            ""
+        };
+        return (SignalClass::ConflictZone, intensity.to_string());
+    }
+    (SignalClass::Other, "".to_string())
+}
+
+/// Carrier names end with a callsign like X5W-0XL
+fn get_carrier_callsign(signal_name: &str) -> Option<String> {
+    let callsign = signal_name.split_whitespace().last()?;
+    let (left, right) = callsign.split_once('-')?;
+    let is_part = |part: &str| {
+        part.len() == 3
+            && part
+                .chars()
+                .all(|char| char.is_ascii_uppercase() || char.is_ascii_digit())
+    };
+    if is_part(left) && is_part(right) {
+        Some(callsign.to_string())
+    } else {
+        None
+    }
+}
+
+/// Signals of all visited systems, kept on disk so they survive restarts
+pub struct SignalStore {
+    path: String,
+    systems: HashMap<u64, Vec<SystemSignal>>,
+    /// Signals changed since the file was written, replaying a journal writes it only once
+    dirty: bool,
+}
+
+impl SignalStore {
+    pub fn load() -> Self {
+        SignalStore::from_file(get_data_path("signals.json"))
+    }
+
+    pub fn from_file(path: String) -> Self {
+        let mut systems = HashMap::new();
+        if let Ok(content) = fs::read_to_string(&path) {
+            match json::parse(&content) {
+                Ok(json) => {
+                    let now = Utc::now();
+                    for (address, signals) in json.entries() {
+                        if let Ok(address) = address.parse::<u64>() {
+                            let signals: Vec<SystemSignal> = signals
+                                .members()
+                                .filter_map(SystemSignal::from_store)
+                                .filter(|signal| !signal.is_expired(now))
+                                .collect();
+                            systems.insert(address, signals);
+                        }
+                    }
+                    info!("Loaded signals of {} systems", systems.len());
+                }
+                Err(err) => error!("Couldn't parse signal file {}: {}", path, err),
+            }
+        }
+        SignalStore {
+            path,
+            systems,
+            dirty: false,
+        }
+    }
+
+    pub fn get_signals(&self, address: u64) -> Vec<SystemSignal> {
+        let now = Utc::now();
+        self.systems
+            .get(&address)
+            .map(|signals| {
+                signals
+                    .iter()
+                    .filter(|signal| !signal.is_expired(now))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn update(&mut self, address: u64, signals: &[SystemSignal]) {
+        self.systems.insert(address, signals.to_vec());
+        self.dirty = true;
+    }
+
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let now = Utc::now();
+        let mut json = serde_json::Map::new();
+        for (address, signals) in &self.systems {
+            let signals: Vec<serde_json::Value> = signals
+                .iter()
+                .filter(|signal| !signal.is_expired(now))
+                .map(|signal| signal.to_store())
+                .collect();
+            if !signals.is_empty() {
+                json.insert(address.to_string(), serde_json::Value::Array(signals));
+            }
+        }
+        if let Err(err) = fs::write(
+            &self.path,
+            serde_json::Value::Object(json).to_string().as_bytes(),
+        ) {
+            error!("Couldn't write signal file {}: {}", self.path, err);
+            return;
+        }
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const CARRIER: &str = r#"{ "timestamp":"2023-05-29T22:40:26Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"THE GENERAL MELCHETT X5W-0XL", "IsStation":true }"#;
+    const USS: &str = r#"{ "timestamp":"2023-05-29T22:40:42Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"$USS_HighGradeEmissions;", "SignalName_Localised":"Unidentifizierte Signalquelle", "USSType":"$USS_Type_ValuableSalvage;", "USSType_Localised":"Verschlüsselte Emissionen", "SpawningState":"", "SpawningFaction":"Murus Major Industry", "ThreatLevel":0, "TimeRemaining":707.545837 }"#;
+
+    fn get_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("edcas-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.display().to_string()
+    }
+
+    #[test]
+    fn signals_survive_a_restart() {
+        let path = get_path("signals");
+        let carrier = SystemSignal::from_json(&json::parse(CARRIER).unwrap());
+        assert!(carrier.class == SignalClass::FleetCarrier);
+        assert_eq!(carrier.detail, "X5W-0XL");
+        let mut uss = SystemSignal::from_json(&json::parse(USS).unwrap());
+        assert!(uss.class == SignalClass::UnidentifiedSignal);
+        //Despawned long ago
+        assert!(uss.is_expired(Utc::now()));
+        let mut active = uss.clone();
+        active.expires = Some(Utc::now() + Duration::hours(1));
+        uss.name = "Despawned".to_string();
+
+        let mut store = SignalStore::from_file(path.clone());
+        store.update(672296347049, &[carrier, uss, active]);
+        assert_eq!(store.get_signals(672296347049).len(), 2);
+        store.save();
+
+        let store = SignalStore::from_file(path.clone());
+        let signals = store.get_signals(672296347049);
+        assert_eq!(signals.len(), 2);
+        assert_eq!(signals[0].name, "THE GENERAL MELCHETT X5W-0XL");
+        assert_eq!(signals[1].name, "Unidentifizierte Signalquelle");
+        assert!(signals[1].expires.is_some());
+        assert!(store.get_signals(1).is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn unchanged_signals_are_not_written() {
+        let path = get_path("signals-unchanged");
+        let mut store = SignalStore::from_file(path.clone());
+        store.save();
+        assert!(!Path::new(&path).exists());
+
+        let carrier = SystemSignal::from_json(&json::parse(CARRIER).unwrap());
+        store.update(672296347049, &[carrier]);
+        store.save();
+        assert!(Path::new(&path).exists());
+
+        fs::remove_file(&path).unwrap();
+        store.save();
+        assert!(!Path::new(&path).exists());
+    }
+}
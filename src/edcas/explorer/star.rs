@@ -34,5 +34,6 @@ pub struct Star {
     pub was_discovered: bool,
     pub was_mapped: bool,
     pub asteroid_rings: Vec<AsteroidRing>,
+    pub highlights: Vec<String>,
     pub settings: Arc<Mutex<Settings>>,
 }
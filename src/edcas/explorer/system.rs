@@ -1,10 +1,15 @@
 use std::ops::Add;
 use std::sync::{Arc, Mutex};
 
+use chrono::{DateTime, Utc};
 use eframe::egui;
 use eframe::emath::Numeric;
 
-use crate::edcas::explorer::body::{BodyType, Signal};
+use crate::edcas::explorer::barycentre::Barycentre;
+use crate::edcas::explorer::body::{BodyType, Parent, Signal};
+use crate::edcas::explorer::highlight::{self, HighlightRule};
+use crate::edcas::explorer::planet::{get_body_class_from_body, get_profit_from_body};
+use crate::edcas::explorer::signal::{SignalClass, SystemSignal};
 use crate::edcas::settings::Settings;
 
 #[derive(Clone)]
@@ -27,6 +32,7 @@ pub struct System {
     pub x: f64,
     pub y: f64,
     pub z: f64,
+    pub unsold_data: bool,
 }
 
 #[derive(Clone)]
@@ -174,7 +180,61 @@ impl System {
         ui.end_row();
     }
 
-    pub fn draw_system_info(&self, ui: &mut egui::Ui) {
+    /// Compact overview of the system, e.g. for the galaxy map tooltip
+    pub fn draw_system_summary(&self, ui: &mut egui::Ui) {
+        ui.heading(&self.name);
+        egui::Grid::new("system_summary_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Coordinates");
+                ui.label(format!("{:.2} | {:.2} | {:.2}", self.x, self.y, self.z));
+                ui.end_row();
+                self.draw_system_details(ui);
+
+                ui.label("Bodies");
+                ui.label(format!(
+                    "{}/{}",
+                    self.get_scanned_body_count(),
+                    self.body_count
+                ));
+                ui.end_row();
+
+                ui.label("Est. value");
+                ui.label(format!("{} Cr", self.get_estimated_value()));
+                ui.end_row();
+
+                ui.label("Unsold data");
+                ui.label(if self.unsold_data { "yes" } else { "no" });
+                ui.end_row();
+
+                ui.label("Bookmarked");
+                ui.label(if self.is_bookmarked() { "yes" } else { "no" });
+                ui.end_row();
+            });
+
+        let high_value_bodies = self.get_high_value_bodies();
+        if !high_value_bodies.is_empty() {
+            ui.separator();
+            ui.label("High value bodies");
+            for body in high_value_bodies {
+                ui.label(body.get_name().trim_start_matches(&self.name));
+            }
+        }
+    }
+
+    pub fn draw_system_info(&self, ui: &mut egui::Ui, signal_filter: &mut Option<SignalClass>) {
+        let bookmark_label = if self.is_bookmarked() {
+            "★ Bookmarked"
+        } else {
+            "☆ Bookmark"
+        };
+        if ui
+            .selectable_label(self.is_bookmarked(), bookmark_label)
+            .clicked()
+        {
+            self.toggle_bookmark();
+        }
         egui::Grid::new("system_data_grid")
             .num_columns(2)
             .striped(true)
@@ -200,13 +260,13 @@ impl System {
         if !self.body_count.eq("n/v") {
             ui.add(
                 egui::ProgressBar::new(
-                    (self.body_list.len().to_f64()
+                    (self.get_scanned_body_count().to_f64()
                         / (self.body_count.parse::<f64>().unwrap_or(0.0)
                             + self.non_body_count.parse::<f64>().unwrap_or(0.0)))
                         as f32,
                 )
                 .text(
-                    self.body_list.len().to_string().add("/").add(
+                    self.get_scanned_body_count().to_string().add("/").add(
                         (self.body_count.parse::<f64>().unwrap_or(0.0)
                             + self.non_body_count.parse::<f64>().unwrap_or(0.0))
                         .to_string()
@@ -217,34 +277,174 @@ impl System {
         }
         ui.end_row();
         ui.separator();
-        ui.heading("System Signals");
+        ui.horizontal(|ui| {
+            ui.heading("System Signals");
+            egui::ComboBox::from_id_source("system_signal_filter")
+                .selected_text(match signal_filter {
+                    None => "All".to_string(),
+                    Some(class) => class.to_string(),
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(signal_filter, None, "All");
+                    for class in SignalClass::ALL {
+                        ui.selectable_value(signal_filter, Some(class), class.to_string());
+                    }
+                });
+        });
         egui::ScrollArea::vertical()
             .max_height(200.0)
             .show(ui, |ui| {
-                self.draw_system_signal_list(ui);
+                self.draw_system_signal_list(ui, *signal_filter);
             });
     }
 
-    fn draw_system_signal_list(&self, ui: &mut egui::Ui) {
+    fn draw_system_signal_list(&self, ui: &mut egui::Ui, signal_filter: Option<SignalClass>) {
+        let now = Utc::now();
         egui::Grid::new("system_signal_grid")
-            .num_columns(2)
+            .num_columns(4)
             .striped(true)
-            .min_col_width(130.0)
+            .min_col_width(60.0)
             .show(ui, |ui| {
+                ui.label("Class");
                 ui.label("Name");
                 ui.label("Thread");
+                ui.label("Expires");
                 ui.end_row();
-                for system_signal in &self.signal_list {
-                    ui.label(&system_signal.name);
+                for system_signal in self.get_signals(signal_filter) {
+                    ui.label(system_signal.class.to_string());
+                    let mut name = system_signal.name.clone();
+                    if !system_signal.detail.is_empty() {
+                        name = format!("{} ({})", name, system_signal.detail);
+                    }
+                    ui.label(name).on_hover_text(format!(
+                        "First seen: {}\nLast seen: {}",
+                        system_signal.first_seen.format("%Y-%m-%d %H:%M"),
+                        system_signal.last_seen.format("%Y-%m-%d %H:%M")
+                    ));
                     ui.label(&system_signal.threat);
+                    match system_signal.expires {
+                        Some(expires) => {
+                            let remaining = (expires - now).num_seconds().max(0);
+                            ui.label(format!("{}:{:02}", remaining / 60, remaining % 60))
+                        }
+                        None => ui.label(""),
+                    };
 
                     ui.end_row();
                 }
             });
     }
 
-    pub fn insert_body(&mut self, body: BodyType) -> usize {
+    pub fn get_signals(&self, signal_filter: Option<SignalClass>) -> Vec<&SystemSignal> {
+        self.signal_list
+            .iter()
+            .filter(|signal| signal_filter.is_none() || signal_filter == Some(signal.class))
+            .collect()
+    }
+
+    /// Adds the signal or refreshes the times if it was already seen
+    pub fn add_signal(&mut self, signal: SystemSignal) {
+        match self
+            .signal_list
+            .iter_mut()
+            .find(|known| known.is_same_signal(&signal))
+        {
+            Some(known) => {
+                known.last_seen = known.last_seen.max(signal.last_seen);
+                known.first_seen = known.first_seen.min(signal.first_seen);
+                if signal.expires > known.expires {
+                    known.expires = signal.expires;
+                }
+            }
+            None => self.signal_list.push(signal),
+        }
+        self.signal_list.sort_by(|a, b| {
+            a.class
+                .to_string()
+                .cmp(&b.class.to_string())
+                .then(a.name.cmp(&b.name))
+                .then(a.threat.cmp(&b.threat))
+        });
+    }
+
+    /// Removes despawned USSes, returns if anything changed
+    pub fn remove_expired_signals(&mut self, now: DateTime<Utc>) -> bool {
+        let count = self.signal_list.len();
+        self.signal_list.retain(|signal| !signal.is_expired(now));
+        count != self.signal_list.len()
+    }
+
+    pub fn is_bookmarked(&self) -> bool {
+        self.settings
+            .lock()
+            .unwrap()
+            .explorer_settings
+            .bookmarks
+            .contains(&self.address)
+    }
+
+    /// Adds or removes the system from the bookmarks and saves them to the settings file
+    pub fn toggle_bookmark(&self) {
+        let mut settings = self.settings.lock().unwrap();
+        let bookmarks = &mut settings.explorer_settings.bookmarks;
+        match bookmarks
+            .iter()
+            .position(|address| address == &self.address)
+        {
+            Some(index) => {
+                bookmarks.remove(index);
+            }
+            None => bookmarks.push(self.address),
+        }
+        settings.save_settings_to_file();
+    }
+
+    /// Planets whose estimated mapping value reaches the threshold from the explorer settings
+    pub fn get_high_value_bodies(&self) -> Vec<&BodyType> {
+        let threshold = self
+            .settings
+            .lock()
+            .unwrap()
+            .explorer_settings
+            .high_value_threshold;
+        self.body_list
+            .iter()
+            .filter(|body| match body {
+                BodyType::Planet(planet) => {
+                    get_profit_from_body(get_body_class_from_body(planet), planet.was_discovered).1
+                        >= threshold
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Estimated earnings for discovery and mapping of all planets
+    pub fn get_estimated_value(&self) -> i64 {
+        self.body_list
+            .iter()
+            .map(|body| match body {
+                BodyType::Planet(planet) => {
+                    get_profit_from_body(get_body_class_from_body(planet), planet.was_discovered).1
+                        as i64
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn distance_to(&self, other: &System) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2))
+            .sqrt()
+    }
+
+    pub fn insert_body(&mut self, mut body: BodyType) -> usize {
         let id = body.get_id();
+        self.insert_parent_barycentres(&body.get_parents().clone());
+        body.set_highlights(highlight::get_matching_rules(
+            &self.settings.lock().unwrap().highlight_rules,
+            &body,
+        ));
         self.body_list.push(body);
 
         self.body_list.sort_by_key(|body_a| body_a.get_id());
@@ -256,12 +456,275 @@ impl System {
         }
         0
     }
+
+    /// Gives the signals to the scanned body and checks its highlights again,
+    /// the signals of a body are often found after its scan
+    pub fn set_body_signals(&mut self, body_id: u64, signals: &[Signal]) {
+        let settings = self.settings.clone();
+        let rules = &settings.lock().unwrap().highlight_rules;
+        for body in &mut self.body_list {
+            if body.get_id() == body_id && matches!(body, BodyType::Planet(_) | BodyType::Ring(_)) {
+                body.set_signals(signals.to_vec());
+                body.set_highlights(highlight::get_matching_rules(rules, body));
+            }
+        }
+    }
+
+    pub fn update_highlights(&mut self, rules: &[HighlightRule]) {
+        for body in &mut self.body_list {
+            body.set_highlights(highlight::get_matching_rules(rules, body));
+        }
+    }
+
+    /// Inserts the barycentre or, if it is already known, completes the existing one.
+    /// The selected body stays selected.
+    pub fn insert_barycentre(&mut self, barycentre: Barycentre) {
+        for body in &mut self.body_list {
+            if body.get_id() == barycentre.body_id {
+                if let BodyType::Barycentre(existing) = body {
+                    existing.merge(barycentre);
+                }
+                return;
+            }
+        }
+
+        let selected_id = self.body_list.get(self.index).map(|body| body.get_id());
+        self.body_list.push(BodyType::Barycentre(barycentre));
+        self.body_list.sort_by_key(|body_a| body_a.get_id());
+        if let Some(selected_id) = selected_id {
+            self.index = self
+                .body_list
+                .iter()
+                .position(|body| body.get_id() == selected_id)
+                .unwrap_or(0);
+        }
+    }
+
+    /// Every `Null` entry in the parents is a barycentre, orbiting the parents listed after it
+    fn insert_parent_barycentres(&mut self, parents: &[Parent]) {
+        for (level, parent) in parents.iter().enumerate() {
+            if parent.is_barycentre() {
+                self.insert_barycentre(Barycentre::from_parents(
+                    parent.id,
+                    parents[level + 1..].to_vec(),
+                    self.name.clone(),
+                    self.settings.clone(),
+                ));
+            }
+        }
+    }
+
+    /// Scanned bodies without the barycentres, which are not part of the body count
+    pub fn get_scanned_body_count(&self) -> usize {
+        self.body_list
+            .iter()
+            .filter(|body| !matches!(body, BodyType::Barycentre(_)))
+            .count()
+    }
 }
 
-#[derive(Clone)]
-pub struct SystemSignal {
-    pub timestamp: String,
-    pub event: String,
-    pub name: String,
-    pub threat: String,
+#[cfg(test)]
+mod tests {
+    use json::{object, JsonValue};
+
+    use super::*;
+    use crate::edcas::explorer::body;
+    use crate::edcas::explorer::orbit::get_children;
+    use crate::edcas::settings::get_test_settings;
+
+    fn get_system(settings: Arc<Mutex<Settings>>) -> System {
+        System {
+            name: "Phaa Chroa YL-B b5-4".to_string(),
+            address: 9544091982377,
+            allegiance: String::new(),
+            economy_localised: String::new(),
+            second_economy_localised: String::new(),
+            government_localised: String::new(),
+            security_localised: String::new(),
+            population: String::new(),
+            body_count: String::new(),
+            non_body_count: String::new(),
+            signal_list: vec![],
+            body_list: vec![],
+            planet_signals: vec![],
+            index: 0,
+            settings,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            unsold_data: false,
+        }
+    }
+
+    fn get_parents(parents: &[(&str, u64)]) -> JsonValue {
+        let mut json = JsonValue::new_array();
+        for (name, id) in parents {
+            let mut parent = JsonValue::new_object();
+            parent[*name] = (*id).into();
+            json.push(parent).unwrap();
+        }
+        json
+    }
+
+    fn scan_star(name: &str, body_id: u64, parents: &[(&str, u64)]) -> JsonValue {
+        object! {
+            "timestamp": "2023-07-19T17:02:42Z", "event": "Scan", "ScanType": "AutoScan",
+            "BodyName": name, "BodyID": body_id, "Parents": get_parents(parents),
+            "StarSystem": "Phaa Chroa YL-B b5-4", "SystemAddress": 9544091982377_i64,
+            "DistanceFromArrivalLS": 0.0, "StarType": "M", "Subclass": 3, "StellarMass": 0.4,
+            "Radius": 300000000.0, "AbsoluteMagnitude": 9.1, "Age_MY": 5000,
+            "SurfaceTemperature": 3000.0, "Luminosity": "Va", "SemiMajorAxis": 10452453732.4,
+            "Eccentricity": 0.01, "OrbitalInclination": -14.6, "Periapsis": 73.2,
+            "OrbitalPeriod": 11393706.7, "AscendingNode": -77.5, "MeanAnomaly": 210.7,
+            "RotationPeriod": 200000.0, "AxialTilt": 0.0, "WasDiscovered": true,
+            "WasMapped": false
+        }
+    }
+
+    fn scan_planet(name: &str, body_id: u64, parents: &[(&str, u64)]) -> JsonValue {
+        object! {
+            "timestamp": "2023-07-19T17:05:42Z", "event": "Scan", "ScanType": "Detailed",
+            "BodyName": name, "BodyID": body_id, "Parents": get_parents(parents),
+            "StarSystem": "Phaa Chroa YL-B b5-4", "SystemAddress": 9544091982377_i64,
+            "DistanceFromArrivalLS": 512.3, "TidalLock": true, "TerraformState": "",
+            "PlanetClass": "Icy body", "Atmosphere": "", "AtmosphereType": "None",
+            "Volcanism": "", "MassEM": 0.02, "Radius": 1800000.0, "SurfaceGravity": 2.4,
+            "SurfaceTemperature": 40.0, "SurfacePressure": 0.0, "Landable": true,
+            "SemiMajorAxis": 50000000.0, "Eccentricity": 0.0, "OrbitalInclination": 0.1,
+            "Periapsis": 12.0, "OrbitalPeriod": 90000.0, "AscendingNode": 3.0,
+            "MeanAnomaly": 20.0, "RotationPeriod": 90000.0, "AxialTilt": 0.2,
+            "WasDiscovered": false, "WasMapped": false
+        }
+    }
+
+    fn insert(system: &mut System, json: JsonValue) {
+        let body = body::generate_from_json(json, system.settings.clone());
+        system.insert_body(body);
+    }
+
+    fn get_ids(system: &System) -> Vec<u64> {
+        system.body_list.iter().map(|body| body.get_id()).collect()
+    }
+
+    fn get_child_ids(system: &System, parent_id: Option<u64>) -> Vec<u64> {
+        get_children(&system.body_list)
+            .get(&parent_id)
+            .into_iter()
+            .flatten()
+            .map(|&i| system.body_list[i].get_id())
+            .collect()
+    }
+
+    #[test]
+    fn binary_stars_share_one_barycentre() {
+        let mut system = get_system(get_test_settings());
+        insert(
+            &mut system,
+            scan_star("Phaa Chroa YL-B b5-4 A", 1, &[("Null", 0)]),
+        );
+        insert(
+            &mut system,
+            scan_star("Phaa Chroa YL-B b5-4 B", 2, &[("Null", 0)]),
+        );
+        insert(
+            &mut system,
+            scan_planet("Phaa Chroa YL-B b5-4 A 1", 3, &[("Star", 1), ("Null", 0)]),
+        );
+
+        assert_eq!(get_ids(&system), vec![0, 1, 2, 3]);
+        assert_eq!(system.get_scanned_body_count(), 3);
+        match &system.body_list[0] {
+            BodyType::Barycentre(barycentre) => {
+                assert_eq!(barycentre.body_name, "Phaa Chroa YL-B b5-4 Barycentre 0");
+                assert!(barycentre.parents.is_empty());
+                assert!(barycentre.semi_major_axis.is_none());
+            }
+            _ => panic!("Body 0 should be a barycentre"),
+        }
+        assert_eq!(get_child_ids(&system, None), vec![0]);
+        assert_eq!(get_child_ids(&system, Some(0)), vec![1, 2]);
+        assert_eq!(get_child_ids(&system, Some(1)), vec![3]);
+    }
+
+    #[test]
+    fn nested_barycentres_of_a_multi_star_system() {
+        //A and B orbit barycentre 1, C orbits barycentre 0 together with them
+        let mut system = get_system(get_test_settings());
+        insert(
+            &mut system,
+            scan_star("Phaa Chroa YL-B b5-4 A", 2, &[("Null", 1), ("Null", 0)]),
+        );
+        insert(
+            &mut system,
+            scan_star("Phaa Chroa YL-B b5-4 C", 4, &[("Null", 0)]),
+        );
+        insert(
+            &mut system,
+            scan_star("Phaa Chroa YL-B b5-4 B", 3, &[("Null", 1), ("Null", 0)]),
+        );
+
+        assert_eq!(get_ids(&system), vec![0, 1, 2, 3, 4]);
+        match &system.body_list[1] {
+            BodyType::Barycentre(barycentre) => {
+                assert_eq!(barycentre.parents.len(), 1);
+                assert_eq!(barycentre.parents[0].id, 0);
+            }
+            _ => panic!("Body 1 should be a barycentre"),
+        }
+        assert_eq!(get_child_ids(&system, None), vec![0]);
+        assert_eq!(get_child_ids(&system, Some(0)), vec![1, 4]);
+        assert_eq!(get_child_ids(&system, Some(1)), vec![2, 3]);
+    }
+
+    #[test]
+    fn binary_planets_get_the_scanned_barycentre_orbit() {
+        let settings = get_test_settings();
+        let mut system = get_system(settings.clone());
+        insert(&mut system, scan_star("Phaa Chroa YL-B b5-4", 0, &[]));
+        insert(
+            &mut system,
+            scan_planet("Phaa Chroa YL-B b5-4 1 a", 6, &[("Null", 5), ("Star", 0)]),
+        );
+        system.insert_barycentre(Barycentre::from_json(
+            &object! {
+                "timestamp": "2023-07-19T17:02:42Z", "event": "ScanBaryCentre",
+                "StarSystem": "Phaa Chroa YL-B b5-4", "SystemAddress": 9544091982377_i64,
+                "BodyID": 5, "SemiMajorAxis": 10452453732.49, "Eccentricity": 0.016,
+                "OrbitalInclination": -14.6, "Periapsis": 73.2, "OrbitalPeriod": 11393706.7,
+                "AscendingNode": -77.5, "MeanAnomaly": 210.7
+            },
+            settings,
+        ));
+        insert(
+            &mut system,
+            scan_planet("Phaa Chroa YL-B b5-4 1 b", 7, &[("Null", 5), ("Star", 0)]),
+        );
+
+        assert_eq!(get_ids(&system), vec![0, 5, 6, 7]);
+        match &system.body_list[1] {
+            BodyType::Barycentre(barycentre) => {
+                //The parents come from the planets, the orbit from the ScanBaryCentre event
+                assert_eq!(barycentre.parents.len(), 1);
+                assert_eq!(barycentre.parents[0].id, 0);
+                assert_eq!(barycentre.semi_major_axis, Some(10452453732.49));
+            }
+            _ => panic!("Body 5 should be a barycentre"),
+        }
+        assert_eq!(get_child_ids(&system, Some(0)), vec![5]);
+        assert_eq!(get_child_ids(&system, Some(5)), vec![6, 7]);
+    }
+
+    #[test]
+    fn moon_of_unscanned_planet_attaches_to_star() {
+        let mut system = get_system(get_test_settings());
+        insert(&mut system, scan_star("Phaa Chroa YL-B b5-4", 0, &[]));
+        insert(
+            &mut system,
+            scan_planet("Phaa Chroa YL-B b5-4 2 a", 9, &[("Planet", 8), ("Star", 0)]),
+        );
+
+        assert_eq!(get_child_ids(&system, None), vec![0]);
+        assert_eq!(get_child_ids(&system, Some(0)), vec![9]);
+        assert!(get_child_ids(&system, Some(8)).is_empty());
+    }
 }
@@ -0,0 +1,646 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use json::JsonValue;
+use log::{error, info, warn};
+use serde_json::json;
+
+use crate::edcas::ledger::normalize_name;
+use crate::edcas::settings::get_data_path;
+
+/// Where credits came from or went to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Activity {
+    Trading,
+    Mining,
+    Exploration,
+    Combat,
+    Missions,
+    Ship,
+    Carrier,
+    Other,
+}
+
+impl Activity {
+    pub const ALL: [Activity; 8] = [
+        Activity::Trading,
+        Activity::Mining,
+        Activity::Exploration,
+        Activity::Combat,
+        Activity::Missions,
+        Activity::Ship,
+        Activity::Carrier,
+        Activity::Other,
+    ];
+
+    fn get_index(&self) -> usize {
+        Activity::ALL
+            .iter()
+            .position(|activity| activity == self)
+            .unwrap_or(0)
+    }
+}
+
+impl Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Activity::Trading => write!(f, "Trading"),
+            Activity::Mining => write!(f, "Mining"),
+            Activity::Exploration => write!(f, "Exploration"),
+            Activity::Combat => write!(f, "Combat"),
+            Activity::Missions => write!(f, "Missions"),
+            Activity::Ship => write!(f, "Ship"),
+            Activity::Carrier => write!(f, "Carrier"),
+            Activity::Other => write!(f, "Other"),
+        }
+    }
+}
+
+impl std::str::FromStr for Activity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Activity::ALL
+            .into_iter()
+            .find(|activity| activity.to_string() == s)
+            .ok_or(format!("Unknown activity: {}", s))
+    }
+}
+
+/// Time span shown in the history
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistoryRange {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl HistoryRange {
+    pub const ALL: [HistoryRange; 4] = [
+        HistoryRange::Day,
+        HistoryRange::Week,
+        HistoryRange::Month,
+        HistoryRange::All,
+    ];
+
+    /// Oldest point in time of the range
+    pub fn get_start(&self) -> DateTime<Utc> {
+        match self {
+            HistoryRange::Day => Utc::now() - Duration::days(1),
+            HistoryRange::Week => Utc::now() - Duration::weeks(1),
+            HistoryRange::Month => Utc::now() - Duration::days(30),
+            HistoryRange::All => DateTime::default(),
+        }
+    }
+}
+
+impl Display for HistoryRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryRange::Day => write!(f, "Day"),
+            HistoryRange::Week => write!(f, "Week"),
+            HistoryRange::Month => write!(f, "Month"),
+            HistoryRange::All => write!(f, "All"),
+        }
+    }
+}
+
+/// A change of the credit balance caused by a journal event
+#[derive(Clone, PartialEq)]
+pub struct FinanceEntry {
+    pub timestamp: DateTime<Utc>,
+    pub event: String,
+    pub activity: Activity,
+    pub amount: i64,
+}
+
+/// Balance the game reported with LoadGame
+#[derive(Clone, PartialEq)]
+pub struct BalanceSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub balance: i64,
+    /// Reported minus the balance expected from the previous snapshot and the entries since, 0 for the first one
+    pub difference: i64,
+}
+
+/**
+Position in the journal up to which events are recorded. Events of the same second are counted,
+so a replayed second is skipped exactly as far as it was recorded before.
+*/
+#[derive(Default)]
+struct RecordedUntil {
+    timestamp: Option<DateTime<Utc>>,
+    /// Recorded events of the last second
+    counts: HashMap<String, u64>,
+    /// Events of the last second read since the start
+    seen: HashMap<String, u64>,
+}
+
+impl RecordedUntil {
+    /// Marks the event as recorded, false if it was recorded already
+    fn record(&mut self, timestamp: DateTime<Utc>, event: &str) -> bool {
+        match self.timestamp {
+            Some(last) if timestamp < last => false,
+            Some(last) if timestamp == last => {
+                let seen = self.seen.entry(event.to_string()).or_default();
+                *seen += 1;
+                let count = self.counts.entry(event.to_string()).or_default();
+                if *seen <= *count {
+                    return false;
+                }
+                *count += 1;
+                true
+            }
+            _ => {
+                self.timestamp = Some(timestamp);
+                self.counts = HashMap::from([(event.to_string(), 1)]);
+                self.seen = self.counts.clone();
+                true
+            }
+        }
+    }
+}
+
+/**
+Credit history built from the journal and kept across sessions.
+The journal is read from the start again after a restart, events which were recorded already are skipped.
+Changes are saved at most every [`SAVE_INTERVAL`] and at the shutdown of the game or the client.
+*/
+pub struct FinanceState {
+    pub entries: Vec<FinanceEntry>,
+    pub snapshots: Vec<BalanceSnapshot>,
+    /// Balance after the last snapshot and the entries since
+    pub balance: Option<i64>,
+    pub range: HistoryRange,
+    /// Refined tons per commodity which haven't been sold yet, their sale counts as mining
+    mined: HashMap<String, u64>,
+    recorded_until: RecordedUntil,
+    changed: bool,
+    saved: Instant,
+    path: String,
+}
+
+pub const SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+impl FinanceState {
+    pub fn load() -> Self {
+        Self::from_file(get_data_path("finance.json"))
+    }
+
+    pub(crate) fn from_file(path: String) -> Self {
+        let mut finance = FinanceState {
+            entries: vec![],
+            snapshots: vec![],
+            balance: None,
+            range: HistoryRange::Week,
+            mined: HashMap::new(),
+            recorded_until: RecordedUntil::default(),
+            changed: false,
+            saved: Instant::now(),
+            path,
+        };
+        if let Ok(content) = fs::read_to_string(&finance.path) {
+            match json::parse(&content) {
+                Ok(json) => {
+                    finance.snapshots = json["snapshots"]
+                        .members()
+                        .filter_map(|snapshot| {
+                            Some(BalanceSnapshot {
+                                timestamp: DateTime::from_timestamp(
+                                    snapshot["timestamp"].as_i64()?,
+                                    0,
+                                )?,
+                                balance: snapshot["balance"].as_i64()?,
+                                difference: snapshot["difference"].as_i64().unwrap_or(0),
+                            })
+                        })
+                        .collect();
+                    finance.entries = json["entries"]
+                        .members()
+                        .filter_map(|entry| {
+                            Some(FinanceEntry {
+                                timestamp: DateTime::from_timestamp(
+                                    entry["timestamp"].as_i64()?,
+                                    0,
+                                )?,
+                                event: entry["event"].as_str()?.to_string(),
+                                activity: entry["activity"].as_str()?.parse().ok()?,
+                                amount: entry["amount"].as_i64()?,
+                            })
+                        })
+                        .collect();
+                    finance.mined = json["mined"]
+                        .entries()
+                        .filter_map(|(name, count)| Some((name.to_string(), count.as_u64()?)))
+                        .collect();
+                    finance.recorded_until = match json["recorded-until"]["timestamp"]
+                        .as_i64()
+                        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                    {
+                        Some(timestamp) => RecordedUntil {
+                            timestamp: Some(timestamp),
+                            counts: json["recorded-until"]["events"]
+                                .entries()
+                                .filter_map(|(event, count)| {
+                                    Some((event.to_string(), count.as_u64()?))
+                                })
+                                .collect(),
+                            seen: HashMap::new(),
+                        },
+                        None => finance.get_recorded_until(),
+                    };
+                    finance.balance = finance.get_expected_balance();
+                    info!(
+                        "Loaded {} finance entries and {} balance snapshots",
+                        finance.entries.len(),
+                        finance.snapshots.len()
+                    );
+                }
+                Err(err) => error!("Couldn't parse finance file {}: {}", finance.path, err),
+            }
+        }
+        finance
+    }
+
+    /// Files written before the position was saved end with the last entry or snapshot
+    fn get_recorded_until(&self) -> RecordedUntil {
+        let entry = self.entries.last().map(|entry| entry.timestamp);
+        let snapshot = self.snapshots.last().map(|snapshot| snapshot.timestamp);
+        let Some(timestamp) = entry.max(snapshot) else {
+            return RecordedUntil::default();
+        };
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.timestamp == timestamp)
+        {
+            counts.insert(entry.event.clone(), 1);
+        }
+        if snapshot == Some(timestamp) {
+            counts.insert("LoadGame".to_string(), 1);
+        }
+        RecordedUntil {
+            timestamp: Some(timestamp),
+            counts,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Writes the changes right away
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        let json = json!({
+            "snapshots": self.snapshots.iter().map(|snapshot| json!({
+                "timestamp": snapshot.timestamp.timestamp(),
+                "balance": snapshot.balance,
+                "difference": snapshot.difference,
+            })).collect::<Vec<serde_json::Value>>(),
+            "entries": self.entries.iter().map(|entry| json!({
+                "timestamp": entry.timestamp.timestamp(),
+                "event": entry.event,
+                "activity": entry.activity.to_string(),
+                "amount": entry.amount,
+            })).collect::<Vec<serde_json::Value>>(),
+            "mined": self.mined,
+            "recorded-until": {
+                "timestamp": self.recorded_until.timestamp.map(|timestamp| timestamp.timestamp()),
+                "events": self.recorded_until.counts,
+            },
+        });
+        if let Err(err) = fs::write(&self.path, json.to_string().as_bytes()) {
+            error!("Couldn't write finance file {}: {}", self.path, err);
+        }
+        self.changed = false;
+        self.saved = Instant::now();
+    }
+
+    /// Writes the changes if the last save is at least [`SAVE_INTERVAL`] ago
+    pub fn save_if_due(&mut self) {
+        if self.saved.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+    }
+
+    /// Last snapshot plus the entries recorded after it
+    fn get_expected_balance(&self) -> Option<i64> {
+        let snapshot = self.snapshots.last()?;
+        Some(
+            snapshot.balance
+                + self
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.timestamp >= snapshot.timestamp)
+                    .map(|entry| entry.amount)
+                    .sum::<i64>(),
+        )
+    }
+
+    pub fn interpret_json(&mut self, json: &JsonValue) {
+        let Some(timestamp) = json["timestamp"]
+            .as_str()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+        else {
+            return;
+        };
+        let event = json["event"].to_string();
+        if !self.recorded_until.record(timestamp, &event) {
+            return;
+        }
+
+        if event == "LoadGame" {
+            if let Some(balance) = json["Credits"].as_i64() {
+                self.record_snapshot(timestamp, balance);
+            }
+            return;
+        }
+
+        for (activity, amount) in self.get_credit_changes(&event, json) {
+            if amount == 0 {
+                continue;
+            }
+            self.balance = self.balance.map(|balance| balance + amount);
+            self.entries.push(FinanceEntry {
+                timestamp,
+                event: event.clone(),
+                activity,
+                amount,
+            });
+            self.changed = true;
+        }
+    }
+
+    /// Compares the balance the game reports with the one expected from the recorded events
+    fn record_snapshot(&mut self, timestamp: DateTime<Utc>, balance: i64) {
+        let difference = match self.get_expected_balance() {
+            Some(expected) => balance - expected,
+            None => 0,
+        };
+        if difference != 0 {
+            warn!(
+                "Balance of {} Cr differs by {} Cr from the recorded events",
+                balance, difference
+            );
+        }
+        self.snapshots.push(BalanceSnapshot {
+            timestamp,
+            balance,
+            difference,
+        });
+        self.balance = Some(balance);
+        self.changed = true;
+    }
+
+    /// Credit changes of an event, most events change the balance by one amount
+    fn get_credit_changes(&mut self, event: &str, json: &JsonValue) -> Vec<(Activity, i64)> {
+        let amount = |key: &str| json[key].as_i64().unwrap_or(0);
+        match event {
+            "MiningRefined" => {
+                *self
+                    .mined
+                    .entry(normalize_name(&json["Type"].to_string()))
+                    .or_default() += 1;
+                self.changed = true;
+                vec![]
+            }
+            "MarketSell" => {
+                let count = json["Count"].as_u64().unwrap_or(0);
+                let total_sale = amount("TotalSale");
+                let mined = match self
+                    .mined
+                    .get_mut(&normalize_name(&json["Type"].to_string()))
+                {
+                    Some(mined) => {
+                        let sold = (*mined).min(count);
+                        *mined -= sold;
+                        self.changed = true;
+                        sold
+                    }
+                    None => 0,
+                };
+                if count == 0 || mined == 0 {
+                    return vec![(Activity::Trading, total_sale)];
+                }
+                let mining = total_sale * mined as i64 / count as i64;
+                vec![
+                    (Activity::Mining, mining),
+                    (Activity::Trading, total_sale - mining),
+                ]
+            }
+            "MarketBuy" => vec![(Activity::Trading, -amount("TotalCost"))],
+            "BuyTradeData" => vec![(Activity::Trading, -amount("Cost"))],
+            "BuyDrones" => vec![(Activity::Ship, -amount("TotalCost"))],
+            "SellDrones" => vec![(Activity::Ship, amount("TotalSale"))],
+            "BuyAmmo" | "RefuelAll" | "RefuelPartial" | "Repair" | "RepairAll"
+            | "RestockVehicle" | "Resurrect" => vec![(Activity::Ship, -amount("Cost"))],
+            "ModuleBuy" => vec![(Activity::Ship, amount("SellPrice") - amount("BuyPrice"))],
+            "ModuleSell" | "ModuleSellRemote" => vec![(Activity::Ship, amount("SellPrice"))],
+            "FetchRemoteModule" => vec![(Activity::Ship, -amount("TransferCost"))],
+            "ShipyardBuy" => vec![(Activity::Ship, amount("SellPrice") - amount("ShipPrice"))],
+            "ShipyardSell" => vec![(Activity::Ship, amount("ShipPrice"))],
+            "ShipyardTransfer" => vec![(Activity::Ship, -amount("TransferPrice"))],
+            "PayFines" | "PayBounties" => vec![(Activity::Other, -amount("Amount"))],
+            "CrewHire" => vec![(Activity::Other, -amount("HireCost"))],
+            "MissionCompleted" => vec![(
+                Activity::Missions,
+                amount("Reward") - json["Donated"].as_i64().unwrap_or(0),
+            )],
+            "CommunityGoalReward" => vec![(Activity::Missions, amount("Reward"))],
+            "SearchAndRescue" => vec![(Activity::Missions, amount("Reward"))],
+            "RedeemVoucher" => {
+                let activity = match json["Type"].as_str().unwrap_or("") {
+                    "bounty" | "CombatBond" => Activity::Combat,
+                    "codex" => Activity::Exploration,
+                    "trade" => Activity::Trading,
+                    _ => Activity::Other,
+                };
+                vec![(activity, amount("Amount"))]
+            }
+            "SellExplorationData" | "MultiSellExplorationData" => {
+                vec![(Activity::Exploration, amount("TotalEarnings"))]
+            }
+            "SellOrganicData" => vec![(
+                Activity::Exploration,
+                json["BioData"]
+                    .members()
+                    .map(|data| {
+                        data["Value"].as_i64().unwrap_or(0) + data["Bonus"].as_i64().unwrap_or(0)
+                    })
+                    .sum(),
+            )],
+            "CarrierBankTransfer" => {
+                vec![(Activity::Carrier, amount("Withdraw") - amount("Deposit"))]
+            }
+            "CarrierBuy" => vec![(Activity::Carrier, -amount("Price"))],
+            _ => vec![],
+        }
+    }
+
+    /// Balance after every snapshot and entry within the range, starts with the first snapshot
+    pub fn get_balance_series(&self, range: HistoryRange) -> Vec<(DateTime<Utc>, i64)> {
+        let start = range.get_start();
+        let mut series = vec![];
+        let mut balance: Option<i64> = None;
+        let mut entries = self.entries.iter().peekable();
+        let mut snapshots = self.snapshots.iter().peekable();
+        loop {
+            let next_entry = entries.peek().map(|entry| entry.timestamp);
+            let next_snapshot = snapshots.peek().map(|snapshot| snapshot.timestamp);
+            let timestamp = match (next_entry, next_snapshot) {
+                (Some(entry), Some(snapshot)) if snapshot <= entry => {
+                    let snapshot = snapshots.next().unwrap();
+                    balance = Some(snapshot.balance);
+                    snapshot.timestamp
+                }
+                (Some(_), _) => {
+                    let entry = entries.next().unwrap();
+                    balance = balance.map(|balance| balance + entry.amount);
+                    entry.timestamp
+                }
+                (None, Some(_)) => {
+                    let snapshot = snapshots.next().unwrap();
+                    balance = Some(snapshot.balance);
+                    snapshot.timestamp
+                }
+                (None, None) => break,
+            };
+            if let Some(balance) = balance {
+                if timestamp >= start {
+                    series.push((timestamp, balance));
+                }
+            }
+        }
+        series
+    }
+
+    /// Income minus expenses per activity within the range
+    pub fn get_activity_totals(&self, range: HistoryRange) -> [i64; Activity::ALL.len()] {
+        self.get_activity_totals_since(range.get_start())
+    }
+
+    pub fn get_activity_totals_since(&self, start: DateTime<Utc>) -> [i64; Activity::ALL.len()] {
+        let mut totals = [0; Activity::ALL.len()];
+        for entry in self.entries.iter().filter(|entry| entry.timestamp >= start) {
+            totals[entry.activity.get_index()] += entry.amount;
+        }
+        totals
+    }
+
+    /// Income minus expenses per activity for every day of the range with entries
+    pub fn get_daily_totals(
+        &self,
+        range: HistoryRange,
+    ) -> Vec<(NaiveDate, [i64; Activity::ALL.len()])> {
+        let start = range.get_start();
+        let mut days: Vec<(NaiveDate, [i64; Activity::ALL.len()])> = vec![];
+        for entry in self.entries.iter().filter(|entry| entry.timestamp >= start) {
+            let day = entry.timestamp.date_naive();
+            if days.last().map(|(last, _)| *last) != Some(day) {
+                days.push((day, [0; Activity::ALL.len()]));
+            }
+            if let Some((_, totals)) = days.last_mut() {
+                totals[entry.activity.get_index()] += entry.amount;
+            }
+        }
+        days
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_events() -> Vec<JsonValue> {
+        [
+            r#"{ "timestamp":"2024-03-01T10:00:00Z", "event":"LoadGame", "Credits":1000000 }"#,
+            r#"{ "timestamp":"2024-03-01T10:05:00Z", "event":"MiningRefined", "Type":"$painite_name;" }"#,
+            r#"{ "timestamp":"2024-03-01T10:05:00Z", "event":"MiningRefined", "Type":"$painite_name;" }"#,
+            r#"{ "timestamp":"2024-03-01T10:20:00Z", "event":"MarketBuy", "Type":"gold", "Count":1, "TotalCost":9000 }"#,
+            r#"{ "timestamp":"2024-03-01T10:30:00Z", "event":"MarketSell", "Type":"painite", "Count":1, "TotalSale":300000 }"#,
+        ]
+        .iter()
+        .map(|line| json::parse(line).unwrap())
+        .collect()
+    }
+
+    fn get_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!(
+                "edcas-finance-{}-{}.json",
+                name,
+                std::process::id()
+            ))
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn replayed_journal_is_skipped() {
+        let path = get_path("replay");
+        let events = get_events();
+        let mut finance = FinanceState::from_file(path.clone());
+        for event in &events {
+            finance.interpret_json(event);
+        }
+        assert_eq!(finance.entries.len(), 2);
+        assert_eq!(finance.balance, Some(1291000));
+        finance.save();
+
+        //After a restart the same journal is read again
+        let mut finance = FinanceState::from_file(path.clone());
+        for event in &events {
+            finance.interpret_json(event);
+        }
+        assert_eq!(finance.entries.len(), 2);
+        assert_eq!(finance.snapshots.len(), 1);
+        assert_eq!(finance.balance, Some(1291000));
+        assert!(!finance.changed);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn mined_tons_are_kept_across_restarts() {
+        let path = get_path("mined");
+        let events = get_events();
+        let mut finance = FinanceState::from_file(path.clone());
+        for event in &events[..3] {
+            finance.interpret_json(event);
+        }
+        finance.save();
+
+        let mut finance = FinanceState::from_file(path.clone());
+        assert_eq!(finance.mined.get("painite"), Some(&2));
+        //The second refined ton of the same second was recorded before the restart
+        for event in &events {
+            finance.interpret_json(event);
+        }
+        assert_eq!(finance.mined.get("painite"), Some(&1));
+        assert_eq!(
+            finance.get_activity_totals_since(DateTime::UNIX_EPOCH),
+            [-9000, 300000, 0, 0, 0, 0, 0, 0]
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn events_of_the_same_second_are_counted() {
+        let mut recorded_until = RecordedUntil::default();
+        let timestamp = DateTime::from_timestamp(1709287200, 0).unwrap();
+        assert!(recorded_until.record(timestamp, "MarketSell"));
+        assert!(recorded_until.record(timestamp, "MarketSell"));
+        assert!(!recorded_until.record(timestamp - Duration::seconds(1), "MarketSell"));
+
+        //Read again after a restart, only a third sale of that second is new
+        recorded_until.seen.clear();
+        assert!(!recorded_until.record(timestamp, "MarketSell"));
+        assert!(!recorded_until.record(timestamp, "MarketSell"));
+        assert!(recorded_until.record(timestamp, "MarketSell"));
+    }
+}
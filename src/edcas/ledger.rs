@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use json::JsonValue;
+use log::debug;
+
+/// Commodity names differ between the events, e.g. "$Gold_Name;", "Gold" and "gold"
+pub fn normalize_name(name: &str) -> String {
+    name.to_lowercase()
+        .trim_start_matches('$')
+        .trim_end_matches("_name;")
+        .to_string()
+}
+
+/// Tons of a commodity in the hold and what was paid for them
+#[derive(Clone, Default)]
+pub struct CargoPosition {
+    pub name_localised: String,
+    pub count: u64,
+    /// Total cost of the tons, mined and mission cargo cost nothing
+    pub cost: u64,
+}
+
+impl CargoPosition {
+    pub fn get_average_cost(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.cost as f64 / self.count as f64
+    }
+
+    /// Takes tons out of the position and gives back their cost
+    fn remove(&mut self, count: u64) -> u64 {
+        let count = count.min(self.count);
+        let cost = (self.get_average_cost() * count as f64).round() as u64;
+        self.count -= count;
+        self.cost = self.cost.saturating_sub(cost);
+        cost
+    }
+}
+
+/// Sales and losses between two undockings
+#[derive(Clone, Default)]
+pub struct Trip {
+    pub station: String,
+    pub system: String,
+    pub timestamp: String,
+    pub tons_sold: u64,
+    pub revenue: u64,
+    /// Revenue minus the cost of the sold tons, lost cargo counts with its cost
+    pub profit: i64,
+}
+
+/**
+Follows the cost of the cargo through the journal to tell the real profit of a sale.
+Cargo loaded before the journal was read has no known cost, sales of it use the average price the game reports.
+*/
+#[derive(Default)]
+pub struct CargoLedger {
+    pub positions: HashMap<String, CargoPosition>,
+    /// Finished trips of this session, oldest first
+    pub trips: Vec<Trip>,
+    pub current_trip: Trip,
+    station: String,
+    system: String,
+}
+
+impl CargoLedger {
+    fn add(&mut self, name: &str, name_localised: &JsonValue, count: u64, cost: u64) {
+        let position = self.positions.entry(normalize_name(name)).or_default();
+        if let Some(name_localised) = name_localised.as_str() {
+            position.name_localised = name_localised.to_string();
+        } else if position.name_localised.is_empty() {
+            position.name_localised = name.to_string();
+        }
+        position.count += count;
+        position.cost += cost;
+    }
+
+    /// Removes tons without selling them, gives back their cost
+    fn remove(&mut self, name: &str, count: u64) -> u64 {
+        let name = normalize_name(name);
+        let cost = match self.positions.get_mut(&name) {
+            Some(position) => position.remove(count),
+            None => 0,
+        };
+        if self
+            .positions
+            .get(&name)
+            .is_some_and(|position| position.count == 0)
+        {
+            self.positions.remove(&name);
+        }
+        cost
+    }
+
+    fn record_loss(&mut self, cost: u64) {
+        self.current_trip.profit -= cost as i64;
+    }
+
+    pub fn interpret_json(&mut self, json: &JsonValue) {
+        let count = json["Count"].as_u64().unwrap_or(1);
+        match json["event"].as_str().unwrap_or("") {
+            "Docked" => {
+                self.station = json["StationName"].to_string();
+                self.system = json["StarSystem"].to_string();
+            }
+            "Undocked" => self.close_trip(),
+            "MarketBuy" => {
+                let cost = json["TotalCost"].as_u64().unwrap_or(0);
+                self.add(
+                    &json["Type"].to_string(),
+                    &json["Type_Localised"],
+                    count,
+                    cost,
+                );
+            }
+            "MarketSell" => {
+                let name = json["Type"].to_string();
+                let known = self
+                    .positions
+                    .get(&normalize_name(&name))
+                    .map(|position| position.count.min(count))
+                    .unwrap_or(0);
+                //Tons the ledger doesn't know are valued with the average price the game tracked
+                let unknown_cost = json["AvgPricePaid"].as_u64().unwrap_or(0) * (count - known);
+                let cost = self.remove(&name, known) + unknown_cost;
+                let revenue = json["TotalSale"].as_u64().unwrap_or(0);
+                self.current_trip.tons_sold += count;
+                self.current_trip.revenue += revenue;
+                self.current_trip.profit += revenue as i64 - cost as i64;
+                self.current_trip.station = self.station.clone();
+                self.current_trip.system = self.system.clone();
+                self.current_trip.timestamp = json["timestamp"].to_string();
+            }
+            "EjectCargo" => {
+                let cost = self.remove(&json["Type"].to_string(), count);
+                self.record_loss(cost);
+            }
+            "CollectCargo" => {
+                self.add(&json["Type"].to_string(), &json["Type_Localised"], 1, 0);
+            }
+            "MiningRefined" => {
+                self.add(&json["Type"].to_string(), &json["Type_Localised"], 1, 0);
+            }
+            "CargoDepot" => match json["UpdateType"].as_str() {
+                Some("Collect") => {
+                    self.add(
+                        &json["CargoType"].to_string(),
+                        &json["CargoType_Localised"],
+                        count,
+                        0,
+                    );
+                }
+                Some("Deliver") => {
+                    self.remove(&json["CargoType"].to_string(), count);
+                }
+                _ => {}
+            },
+            //Wing missions load and deliver their cargo with CargoDepot
+            "MissionAccepted" if !json["Commodity"].is_null() => {
+                let name = json["Name"].to_string();
+                if name.contains("Delivery") && !name.contains("Wing") {
+                    self.add(
+                        &json["Commodity"].to_string(),
+                        &json["Commodity_Localised"],
+                        count,
+                        0,
+                    );
+                }
+            }
+            "MissionCompleted" => {
+                if !json["Commodity"].is_null() && !json["Name"].to_string().contains("Wing") {
+                    self.remove(&json["Commodity"].to_string(), count);
+                }
+                for reward in json["CommodityReward"].members() {
+                    self.add(
+                        &reward["Name"].to_string(),
+                        &reward["Name_Localised"],
+                        reward["Count"].as_u64().unwrap_or(0),
+                        0,
+                    );
+                }
+            }
+            "Died" => {
+                let cost = self.positions.values().map(|position| position.cost).sum();
+                self.positions.clear();
+                self.record_loss(cost);
+            }
+            //The hold of the ship, tons which are gone without an event are dropped
+            "Cargo" if json["Vessel"].as_str() == Some("Ship") && json["Inventory"].is_array() => {
+                let mut hold: HashMap<String, u64> = HashMap::new();
+                for item in json["Inventory"].members() {
+                    *hold
+                        .entry(normalize_name(&item["Name"].to_string()))
+                        .or_default() += item["Count"].as_u64().unwrap_or(0);
+                }
+                for (name, position) in self.positions.iter_mut() {
+                    let count = hold.get(name).copied().unwrap_or(0);
+                    if position.count > count {
+                        debug!(
+                            "Ledger had {} t {}, hold has {} t",
+                            position.count, name, count
+                        );
+                        position.remove(position.count - count);
+                    }
+                }
+                self.positions.retain(|_, position| position.count > 0);
+            }
+            _ => {}
+        }
+    }
+
+    /// Ends the trip after the sales at a station
+    fn close_trip(&mut self) {
+        if self.current_trip.tons_sold > 0 || self.current_trip.profit != 0 {
+            self.trips.push(std::mem::take(&mut self.current_trip));
+        }
+    }
+
+    /// Trips of this session including the running one, newest first
+    pub fn get_trips(&self) -> impl Iterator<Item = &Trip> {
+        let current_trip = (self.current_trip.tons_sold > 0 || self.current_trip.profit != 0)
+            .then_some(&self.current_trip);
+        current_trip.into_iter().chain(self.trips.iter().rev())
+    }
+
+    pub fn get_session_profit(&self) -> i64 {
+        self.trips.iter().map(|trip| trip.profit).sum::<i64>() + self.current_trip.profit
+    }
+
+    /// Profit if the hold was sold for the given prices, commodities without a price are left out
+    pub fn get_unrealised_profit(&self, get_sell_price: impl Fn(&str) -> Option<u32>) -> i64 {
+        self.positions
+            .iter()
+            .filter_map(|(name, position)| {
+                let sell_price = get_sell_price(name)?;
+                Some(sell_price as i64 * position.count as i64 - position.cost as i64)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKED: &str = r#"{"timestamp":"2024-03-02T17:40:12Z","event":"Docked","StationName":"Hutton Orbital","StationType":"Outpost","Taxi":false,"Multicrew":false,"StarSystem":"Alpha Centauri","SystemAddress":3932277478114,"MarketID":128000000,"StationFaction":{"Name":"Hutton Orbital Truckers Co-Operative"},"StationGovernment":"$government_Cooperative;","StationGovernment_Localised":"Cooperative","StationServices":["dock","autodock","commodities","contacts","exploration","missions","rearm","refuel","repair","tuning","flightcontroller","stationoperations","stationMenu"],"StationEconomy":"$economy_Industrial;","StationEconomy_Localised":"Industrial","StationEconomies":[{"Name":"$economy_Industrial;","Name_Localised":"Industrial","Proportion":1.000000}],"DistFromStarLS":6784404.500000,"LandingPads":{"Small":2,"Medium":4,"Large":0}}"#;
+    const BUY_GOLD: &str = r#"{"timestamp":"2024-03-02T17:41:03Z","event":"MarketBuy","MarketID":128000000,"Type":"gold","Count":10,"BuyPrice":9000,"TotalCost":90000}"#;
+    const BUY_MORE_GOLD: &str = r#"{"timestamp":"2024-03-02T17:41:30Z","event":"MarketBuy","MarketID":128000000,"Type":"gold","Count":10,"BuyPrice":10000,"TotalCost":100000}"#;
+    const BUY_WINE: &str = r#"{"timestamp":"2024-03-02T17:42:11Z","event":"MarketBuy","MarketID":128000000,"Type":"wine","Count":4,"BuyPrice":250,"TotalCost":1000}"#;
+    const SELL_GOLD: &str = r#"{"timestamp":"2024-03-02T18:20:47Z","event":"MarketSell","MarketID":128000000,"Type":"gold","Count":5,"SellPrice":12000,"TotalSale":60000,"AvgPricePaid":9500}"#;
+    const SELL_PALLADIUM: &str = r#"{"timestamp":"2024-03-02T18:21:15Z","event":"MarketSell","MarketID":128000000,"Type":"palladium","Type_Localised":"Palladium","Count":3,"SellPrice":14000,"TotalSale":42000,"AvgPricePaid":11000}"#;
+    const UNDOCKED: &str = r#"{"timestamp":"2024-03-02T18:25:00Z","event":"Undocked","StationName":"Hutton Orbital","StationType":"Outpost","MarketID":128000000,"Taxi":false,"Multicrew":false}"#;
+    const DIED: &str = r#"{"timestamp":"2024-03-02T18:40:27Z","event":"Died","KillerName":"Cmdr Bob","KillerShip":"federation_corvette","KillerRank":"Elite"}"#;
+    const CARGO: &str = r#"{"timestamp":"2024-03-02T18:30:02Z","event":"Cargo","Vessel":"Ship","Count":12,"Inventory":[{"Name":"gold","Count":12,"Stolen":0}]}"#;
+
+    fn read(ledger: &mut CargoLedger, lines: &[&str]) {
+        for line in lines {
+            ledger.interpret_json(&json::parse(line).unwrap());
+        }
+    }
+
+    #[test]
+    fn purchases_average_the_cost() {
+        let mut ledger = CargoLedger::default();
+        read(&mut ledger, &[DOCKED, BUY_GOLD, BUY_MORE_GOLD]);
+        let gold = &ledger.positions["gold"];
+        assert_eq!(gold.count, 20);
+        assert_eq!(gold.cost, 190000);
+        assert_eq!(gold.get_average_cost(), 9500.0);
+
+        read(&mut ledger, &[SELL_GOLD]);
+        let gold = &ledger.positions["gold"];
+        assert_eq!(gold.count, 15);
+        assert_eq!(gold.cost, 142500);
+        assert_eq!(ledger.current_trip.profit, 60000 - 47500);
+        assert_eq!(ledger.current_trip.station, "Hutton Orbital");
+        assert_eq!(ledger.current_trip.system, "Alpha Centauri");
+    }
+
+    #[test]
+    fn unknown_cargo_is_sold_with_the_average_price_paid() {
+        let mut ledger = CargoLedger::default();
+        read(&mut ledger, &[DOCKED, SELL_PALLADIUM]);
+        assert_eq!(ledger.current_trip.tons_sold, 3);
+        assert_eq!(ledger.current_trip.revenue, 42000);
+        assert_eq!(ledger.current_trip.profit, 42000 - 3 * 11000);
+        assert!(ledger.positions.is_empty());
+
+        read(&mut ledger, &[UNDOCKED]);
+        assert_eq!(ledger.trips.len(), 1);
+        assert_eq!(ledger.get_trips().count(), 1);
+        assert_eq!(ledger.get_session_profit(), 9000);
+    }
+
+    #[test]
+    fn dying_loses_the_hold() {
+        let mut ledger = CargoLedger::default();
+        read(&mut ledger, &[DOCKED, BUY_GOLD, BUY_WINE, UNDOCKED, DIED]);
+        assert!(ledger.positions.is_empty());
+        assert_eq!(ledger.current_trip.profit, -91000);
+        assert_eq!(ledger.get_session_profit(), -91000);
+    }
+
+    #[test]
+    fn cargo_event_drops_tons_which_are_gone() {
+        let mut ledger = CargoLedger::default();
+        read(
+            &mut ledger,
+            &[DOCKED, BUY_GOLD, BUY_MORE_GOLD, BUY_WINE, CARGO],
+        );
+        assert!(!ledger.positions.contains_key("wine"));
+        let gold = &ledger.positions["gold"];
+        assert_eq!(gold.count, 12);
+        assert_eq!(gold.cost, 12 * 9500);
+        //Tons gone without an event are not counted as a loss
+        assert_eq!(ledger.current_trip.profit, 0);
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+use chrono::{DateTime, Utc};
+use json::JsonValue;
+use log::{error, info, warn};
+use serde_json::json;
+
+use crate::edcas::finance::{Activity, FinanceState};
+use crate::edcas::settings::get_data_path;
+
+/// What happened between the start of the journal and the shutdown of the game
+#[derive(Clone)]
+pub struct SessionReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub jumps: u64,
+    /// Jump distance in ly
+    pub distance: f64,
+    pub systems_discovered: usize,
+    pub bodies_scanned: usize,
+    pub bodies_mapped: usize,
+    pub credits: Vec<(Activity, i64)>,
+    pub materials: Vec<(String, u64)>,
+    /// Refined tons per commodity
+    pub mined: Vec<(String, u64)>,
+    pub missions: Vec<String>,
+}
+
+impl SessionReport {
+    pub fn get_duration(&self) -> String {
+        let minutes = (self.end - self.start).num_minutes();
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+
+    pub fn get_credits_total(&self) -> i64 {
+        self.credits.iter().map(|(_, amount)| amount).sum()
+    }
+
+    pub fn get_materials_total(&self) -> u64 {
+        self.materials.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn get_mined_total(&self) -> u64 {
+        self.mined.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "# Session {} - {}\n\nDuration: {}\n\n",
+            self.start.format("%Y-%m-%d %H:%M"),
+            self.end.format("%Y-%m-%d %H:%M"),
+            self.get_duration()
+        );
+        markdown.push_str("## Travel\n\n");
+        markdown.push_str(&format!("- Jumps: {}\n", self.jumps));
+        markdown.push_str(&format!("- Distance: {:.2} ly\n\n", self.distance));
+        markdown.push_str("## Exploration\n\n");
+        markdown.push_str(&format!(
+            "- Systems first discovered: {}\n",
+            self.systems_discovered
+        ));
+        markdown.push_str(&format!("- Bodies scanned: {}\n", self.bodies_scanned));
+        markdown.push_str(&format!("- Bodies mapped: {}\n\n", self.bodies_mapped));
+        markdown.push_str("## Credits\n\n| Activity | Credits |\n|---|---:|\n");
+        for (activity, amount) in &self.credits {
+            markdown.push_str(&format!("| {} | {} |\n", activity, amount));
+        }
+        markdown.push_str(&format!(
+            "| **Total** | **{}** |\n\n",
+            self.get_credits_total()
+        ));
+        markdown.push_str("## Materials\n\n");
+        if self.materials.is_empty() {
+            markdown.push_str("None\n");
+        }
+        for (name, count) in &self.materials {
+            markdown.push_str(&format!("- {}: {}\n", name, count));
+        }
+        markdown.push_str(&format!(
+            "\n## Mining\n\nRefined: {} t\n\n",
+            self.get_mined_total()
+        ));
+        for (name, count) in &self.mined {
+            markdown.push_str(&format!("- {}: {} t\n", name, count));
+        }
+        markdown.push_str(&format!(
+            "\n## Missions\n\nCompleted: {}\n\n",
+            self.missions.len()
+        ));
+        for mission in &self.missions {
+            markdown.push_str(&format!("- {}\n", mission));
+        }
+        markdown
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "start": self.start.to_rfc3339(),
+            "end": self.end.to_rfc3339(),
+            "jumps": self.jumps,
+            "distance": self.distance,
+            "systems_discovered": self.systems_discovered,
+            "bodies_scanned": self.bodies_scanned,
+            "bodies_mapped": self.bodies_mapped,
+            "credits": self.credits.iter().map(|(activity, amount)| (activity.to_string(), json!(amount))).collect::<serde_json::Map<String, serde_json::Value>>(),
+            "materials": self.materials.iter().map(|(name, count)| (name.clone(), json!(count))).collect::<serde_json::Map<String, serde_json::Value>>(),
+            "mined": self.mined.iter().map(|(name, count)| (name.clone(), json!(count))).collect::<serde_json::Map<String, serde_json::Value>>(),
+            "missions": self.missions,
+        })
+    }
+
+    /// Writes the report as Markdown and JSON, named after the start of the session
+    pub fn write(&self, directory: &str) -> Result<String, String> {
+        fs::create_dir_all(directory)
+            .map_err(|err| format!("Couldn't create {}: {}", directory, err))?;
+        let path = format!(
+            "{}/session-{}",
+            directory,
+            self.start.format("%Y-%m-%d-%H-%M")
+        );
+        fs::write(format!("{}.md", path), self.to_markdown())
+            .map_err(|err| format!("Couldn't write {}.md: {}", path, err))?;
+        fs::write(format!("{}.json", path), self.to_json().to_string())
+            .map_err(|err| format!("Couldn't write {}.json: {}", path, err))?;
+        Ok(path)
+    }
+}
+
+/**
+Collects the session from the journal events.
+A report is made when the game shuts down or on demand, the shutdown starts a new session.
+*/
+pub struct SessionState {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    jumps: u64,
+    distance: f64,
+    systems_discovered: HashSet<u64>,
+    bodies_scanned: HashSet<(u64, u64)>,
+    bodies_mapped: HashSet<(u64, u64)>,
+    materials: BTreeMap<String, u64>,
+    mined: BTreeMap<String, u64>,
+    missions: Vec<String>,
+    /// Last report, shown until it is closed
+    pub report: Option<SessionReport>,
+    /// Where the last report was written to or why it couldn't be
+    pub report_path: Result<String, String>,
+    directory: String,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        let directory = get_data_path("reports");
+        SessionState {
+            start: None,
+            end: None,
+            jumps: 0,
+            distance: 0.0,
+            systems_discovered: HashSet::new(),
+            bodies_scanned: HashSet::new(),
+            bodies_mapped: HashSet::new(),
+            materials: BTreeMap::new(),
+            mined: BTreeMap::new(),
+            missions: vec![],
+            report: None,
+            report_path: Ok(String::new()),
+            directory,
+        }
+    }
+}
+
+impl SessionState {
+    pub fn interpret_json(&mut self, json: &JsonValue) {
+        if let Some(timestamp) = json["timestamp"]
+            .as_str()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+        {
+            self.start.get_or_insert(timestamp);
+            self.end = Some(timestamp);
+        }
+
+        let system_address = json["SystemAddress"].as_u64().unwrap_or(0);
+        match json["event"].as_str().unwrap_or("") {
+            "FSDJump" => {
+                self.jumps += 1;
+                self.distance += json["JumpDist"].as_f64().unwrap_or(0.0);
+            }
+            "Scan" => {
+                if let Some(body_id) = json["BodyID"].as_u64() {
+                    self.bodies_scanned.insert((system_address, body_id));
+                }
+                //The arrival star tells if the system was known before
+                if json["DistanceFromArrivalLS"].as_f64() == Some(0.0)
+                    && json["WasDiscovered"].as_bool() == Some(false)
+                {
+                    self.systems_discovered.insert(system_address);
+                }
+            }
+            "SAAScanComplete" => {
+                if let Some(body_id) = json["BodyID"].as_u64() {
+                    self.bodies_mapped.insert((system_address, body_id));
+                }
+            }
+            "MaterialCollected" => {
+                self.add_material(json, json["Count"].as_u64().unwrap_or(1));
+            }
+            "MiningRefined" => {
+                let name = match json["Type_Localised"].as_str() {
+                    Some(name) => name.to_string(),
+                    None => json["Type"].to_string(),
+                };
+                *self.mined.entry(name).or_default() += 1;
+            }
+            "MissionCompleted" => {
+                self.missions.push(match json["LocalisedName"].as_str() {
+                    Some(name) => name.to_string(),
+                    None => json["Name"].to_string(),
+                });
+                for material in json["MaterialsReward"].members() {
+                    self.add_material(material, material["Count"].as_u64().unwrap_or(0));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_material(&mut self, json: &JsonValue, count: u64) {
+        let name = match json["Name_Localised"].as_str() {
+            Some(name) => name.to_string(),
+            None => json["Name"].to_string(),
+        };
+        *self.materials.entry(name).or_default() += count;
+    }
+
+    /// Makes a report of the session so far, writes it and keeps it to be shown
+    pub fn create_report(&mut self, finance: &FinanceState) {
+        let start = self.start.unwrap_or_else(Utc::now);
+        let totals = finance.get_activity_totals_since(start);
+        let report = SessionReport {
+            start,
+            end: self.end.unwrap_or(start),
+            jumps: self.jumps,
+            distance: self.distance,
+            systems_discovered: self.systems_discovered.len(),
+            bodies_scanned: self.bodies_scanned.len(),
+            bodies_mapped: self.bodies_mapped.len(),
+            credits: Activity::ALL
+                .into_iter()
+                .zip(totals)
+                .filter(|(_, amount)| *amount != 0)
+                .collect(),
+            materials: self
+                .materials
+                .iter()
+                .map(|(name, count)| (name.clone(), *count))
+                .collect(),
+            mined: self
+                .mined
+                .iter()
+                .map(|(name, count)| (name.clone(), *count))
+                .collect(),
+            missions: self.missions.clone(),
+        };
+        self.report_path = report.write(&self.directory);
+        match &self.report_path {
+            Ok(path) => info!("Session report written to {}", path),
+            Err(err) => error!("{}", err),
+        }
+        self.report = Some(report);
+    }
+
+    /// Reports the session at the shutdown of the game, the next events belong to a new session
+    pub fn finish(&mut self, finance: &FinanceState) {
+        if self.start.is_none() {
+            warn!("Shutdown without any events, no session report");
+            return;
+        }
+        self.create_report(finance);
+        self.restart();
+    }
+
+    /// Starts a new session without a report, the last report stays shown
+    pub fn restart(&mut self) {
+        let report = self.report.take();
+        let report_path = std::mem::replace(&mut self.report_path, Ok(String::new()));
+        *self = SessionState {
+            report,
+            report_path,
+            ..Default::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_events() -> Vec<JsonValue> {
+        [
+            r#"{ "timestamp":"2024-03-01T10:00:00Z", "event":"LoadGame", "Credits":1000000 }"#,
+            r#"{ "timestamp":"2024-03-01T10:02:10Z", "event":"FSDJump", "StarSystem":"Ogmar", "SystemAddress":84180519395914, "StarPos":[-9534.00000,-905.28125,19802.03125], "JumpDist":8.625, "FuelUsed":0.024493, "FuelLevel":31.975506 }"#,
+            r#"{ "timestamp":"2024-03-01T10:04:31Z", "event":"FSDJump", "StarSystem":"Col 285 Sector KS-T d3-68", "SystemAddress":2345873295747, "StarPos":[-65.40625,-70.68750,-89.53125], "JumpDist":20.532, "FuelUsed":2.184843, "FuelLevel":29.815157 }"#,
+            r#"{ "timestamp":"2024-03-01T10:04:40Z", "event":"Scan", "ScanType":"AutoScan", "BodyName":"Col 285 Sector KS-T d3-68 A", "BodyID":1, "StarSystem":"Col 285 Sector KS-T d3-68", "SystemAddress":2345873295747, "DistanceFromArrivalLS":0.000000, "StarType":"M", "Subclass":3, "WasDiscovered":false, "WasMapped":false }"#,
+            r#"{ "timestamp":"2024-03-01T10:06:02Z", "event":"Scan", "ScanType":"Detailed", "BodyName":"Col 285 Sector KS-T d3-68 A 1", "BodyID":3, "StarSystem":"Col 285 Sector KS-T d3-68", "SystemAddress":2345873295747, "DistanceFromArrivalLS":12.46, "PlanetClass":"Icy body", "WasDiscovered":false, "WasMapped":false }"#,
+            r#"{ "timestamp":"2024-03-01T10:09:15Z", "event":"Scan", "ScanType":"Detailed", "BodyName":"Col 285 Sector KS-T d3-68 A 1", "BodyID":3, "StarSystem":"Col 285 Sector KS-T d3-68", "SystemAddress":2345873295747, "DistanceFromArrivalLS":12.46, "PlanetClass":"Icy body", "WasDiscovered":false, "WasMapped":false }"#,
+            r#"{ "timestamp":"2024-03-01T10:09:10Z", "event":"SAAScanComplete", "BodyName":"Col 285 Sector KS-T d3-68 A 1", "SystemAddress":2345873295747, "BodyID":3, "ProbesUsed":5, "EfficiencyTarget":6 }"#,
+            r#"{ "timestamp":"2024-03-01T10:11:47Z", "event":"MaterialCollected", "Category":"Raw", "Name":"iron", "Count":3 }"#,
+            r#"{ "timestamp":"2024-03-01T10:20:03Z", "event":"MiningRefined", "Type":"$painite_name;", "Type_Localised":"Painite" }"#,
+            r#"{ "timestamp":"2024-03-01T10:21:26Z", "event":"MiningRefined", "Type":"$painite_name;", "Type_Localised":"Painite" }"#,
+            r#"{ "timestamp":"2024-03-01T11:05:00Z", "event":"MarketSell", "Type":"painite", "Count":2, "SellPrice":300000, "TotalSale":600000, "AvgPricePaid":0 }"#,
+            r#"{ "timestamp":"2024-03-01T11:07:19Z", "event":"MissionCompleted", "Faction":"Jaques", "Name":"Mission_Delivery_name", "LocalisedName":"Deliver 20 units of Gold", "MissionID":953462831, "Reward":250000, "MaterialsReward":[ { "Name":"iron", "Category":"$MICRORESOURCE_CATEGORY_Raw;", "Count":2 } ] }"#,
+            r#"{ "timestamp":"2024-03-01T11:30:00Z", "event":"Shutdown" }"#,
+        ]
+        .iter()
+        .map(|line| json::parse(line).unwrap())
+        .collect()
+    }
+
+    fn get_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("edcas-session-{}-{}", name, std::process::id()))
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn report_sums_up_the_session() {
+        let directory = get_path("report");
+        let mut finance = FinanceState::from_file(format!("{}-finance.json", directory));
+        let mut session = SessionState {
+            directory: directory.clone(),
+            ..Default::default()
+        };
+        for event in &get_events() {
+            finance.interpret_json(event);
+            session.interpret_json(event);
+        }
+        session.create_report(&finance);
+
+        let report = session.report.as_ref().unwrap();
+        assert_eq!(report.get_duration(), "1h 30m");
+        assert_eq!(report.jumps, 2);
+        assert!((report.distance - 29.157).abs() < 1e-9);
+        assert_eq!(report.systems_discovered, 1);
+        assert_eq!(report.bodies_scanned, 2);
+        assert_eq!(report.bodies_mapped, 1);
+        assert_eq!(report.materials, vec![("iron".to_string(), 5)]);
+        assert_eq!(report.mined, vec![("Painite".to_string(), 2)]);
+        assert_eq!(report.missions, vec!["Deliver 20 units of Gold"]);
+        assert_eq!(
+            report.credits,
+            vec![(Activity::Mining, 600000), (Activity::Missions, 250000)]
+        );
+        assert_eq!(report.get_credits_total(), 850000);
+
+        let path = session.report_path.clone().unwrap();
+        assert_eq!(path, format!("{}/session-2024-03-01-10-00", directory));
+        let markdown = fs::read_to_string(format!("{}.md", path)).unwrap();
+        assert!(markdown.contains("- Distance: 29.16 ly\n"));
+        assert!(markdown.contains("| **Total** | **850000** |\n"));
+        let json = json::parse(&fs::read_to_string(format!("{}.json", path)).unwrap()).unwrap();
+        assert_eq!(json["credits"]["Mining"], 600000);
+        assert_eq!(json["mined"]["Painite"], 2);
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn shutdown_starts_a_new_session() {
+        let directory = get_path("restart");
+        let finance = FinanceState::from_file(format!("{}-finance.json", directory));
+        let mut session = SessionState {
+            directory: directory.clone(),
+            ..Default::default()
+        };
+        //Nothing happened yet, so there is nothing to report
+        session.finish(&finance);
+        assert!(session.report.is_none());
+
+        let events = get_events();
+        for event in &events[..3] {
+            session.interpret_json(event);
+        }
+        session.finish(&finance);
+        assert_eq!(session.report.as_ref().unwrap().jumps, 2);
+        assert!(session.report_path.is_ok());
+
+        //The old report stays shown, the new session counts from zero
+        session.interpret_json(&events[8]);
+        assert_eq!(session.report.as_ref().unwrap().jumps, 2);
+        assert_eq!(session.jumps, 0);
+        assert_eq!(session.mined.get("Painite"), Some(&1));
+        assert_eq!(
+            session.start,
+            DateTime::parse_from_rfc3339("2024-03-01T10:20:03Z")
+                .ok()
+                .map(|start| start.with_timezone(&Utc))
+        );
+        let _ = fs::remove_dir_all(directory);
+    }
+}
@@ -6,19 +6,21 @@ use std::io::{Read, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{env, fs};
 
-use crate::edcas::backend::evm::edcas_contract;
-use crate::edcas::backend::evm::journal_interpreter::Edcas;
+use crate::edcas::backend::data_backend::BackendKind;
+use crate::edcas::backend::eddn_publisher::{EddnSchema, DEFAULT_UPLOAD_URL};
+use crate::edcas::backend::evm::journal_uploader::UploadProgress;
+use crate::edcas::backend::evm::privacy::DataCategory;
+use crate::edcas::backend::evm::transaction_store::TransactionQueueStatus;
+use crate::edcas::explorer::highlight::HighlightRule;
 use eframe::egui;
 use eframe::egui::{Color32, RichText};
 use eframe::epaint::ahash::HashMap;
-use ethers::addressbook::Address;
-use ethers::middleware::SignerMiddleware;
 use ethers::prelude::*;
-use ethers::utils::hex;
+use json::JsonValue;
 use log::{error, info, warn};
 use serde_json::json;
 
@@ -90,6 +92,8 @@ impl PartialEq for ActionAtShutdownSignal {
 
 pub struct ExplorerSettings {
     pub include_system_name: bool,
+    pub high_value_threshold: i32,
+    pub bookmarks: Vec<u64>,
 }
 
 pub struct EvmSettings {
@@ -97,17 +101,82 @@ pub struct EvmSettings {
     pub n_timeout: u64,
     pub n_attempts: u64,
     pub allow_share_data: bool,
+    pub shared_categories: Vec<DataCategory>,
+    /// Minutes an event is held back before it is published
+    pub publish_delay: u64,
+    /// Only held in memory if a keystore is used
     pub private_key: String,
+    /// Encrypted V3 keystore with the private key
+    pub keystore_path: String,
     pub smart_contract_address: String,
-    pub contract: Option<Edcas>,
     pub show_upload_data_window: bool,
     pub journal_read_status: Option<JournalReadStatus>,
+    pub queue_status: Arc<Mutex<TransactionQueueStatus>>,
+}
+
+/// Reading needs no key, signing is only used if sharing is allowed and the key is valid
+#[derive(Clone, Copy, PartialEq)]
+pub enum EvmMode {
+    ReadOnly,
+    Signing,
+}
+
+impl Display for EvmMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvmMode::ReadOnly => write!(f, "Read-only"),
+            EvmMode::Signing => write!(f, "Signing"),
+        }
+    }
+}
+
+impl EvmSettings {
+    /// Sharing needs the key of the keystore, which isn't unlocked yet
+    pub fn is_locked(&self) -> bool {
+        self.allow_share_data && self.private_key.is_empty() && !self.keystore_path.is_empty()
+    }
+
+    pub fn get_mode(&self) -> EvmMode {
+        if self.allow_share_data && self.private_key.parse::<LocalWallet>().is_ok() {
+            EvmMode::Signing
+        } else {
+            EvmMode::ReadOnly
+        }
+    }
+}
+
+pub struct EddnSettings {
+    pub upload_url: String,
+    /// Schemas the commander opted in to publish
+    pub shared_schemas: Vec<EddnSchema>,
+}
+
+impl EddnSettings {
+    pub fn is_enabled(&self) -> bool {
+        !self.shared_schemas.is_empty()
+    }
+}
+
+pub struct BackendSettings {
+    /// Where the station, carrier and explorer data is read from
+    pub kind: BackendKind,
+    pub rest_url: String,
+}
+
+pub struct DatabaseSettings {
+    /// Local database filled by the eddn adapter. If set, it is used instead of the EVM for stations and carriers
+    pub path: String,
+}
+
+pub struct TradeSettings {
+    /// Markets older than this many hours are left out of the trade routes
+    pub max_age: u64,
 }
 
 pub struct JournalReadStatus {
     pub current_log: u32,
     pub total_logs: u32,
-    pub log_index_updates: BusReader<i64>,
+    pub log_index_updates: BusReader<UploadProgress>,
 }
 
 pub struct GraphicEditorSettings {
@@ -121,10 +190,19 @@ pub struct Settings {
     pub journal_reader_settings: JournalReaderSettings,
     pub explorer_settings: ExplorerSettings,
     pub evm_settings: EvmSettings,
+    pub eddn_settings: EddnSettings,
+    pub database_settings: DatabaseSettings,
+    pub backend_settings: BackendSettings,
+    pub trade_settings: TradeSettings,
     pub graphic_editor_settings: GraphicEditorSettings,
     pub icons: HashMap<String, Icon>,
     pub stars: HashMap<String, Icon>,
     pub planets: HashMap<String, Icon>,
+    pub highlight_rules: Vec<HighlightRule>,
+    /// Counts the saved changes of the rules, the explorer highlights all bodies again when it changes
+    pub highlight_rules_revision: Arc<AtomicU64>,
+    /// Rules as last saved, to tell whether a save changed them
+    pub saved_highlight_rules: serde_json::Value,
     pub settings_path: String,
     pub log_path: String,
 }
@@ -247,7 +325,13 @@ impl Default for Settings {
         let mut json_string: String = String::from("");
         settings_file.read_to_string(&mut json_string).unwrap();
         let json = json::parse(&json_string).unwrap();
+        Self::from_json(&json, settings_path)
+    }
+}
 
+impl Settings {
+    /// Settings from the content of a settings file, missing values get their defaults
+    pub fn from_json(json: &JsonValue, settings_path: String) -> Self {
         //---------------------------
         // Journal reader
         //---------------------------
@@ -302,25 +386,16 @@ impl Default for Settings {
         // EVM
         //---------------------------
 
-        let mut private_key = json["evm"]["private-key"]
+        //Moving the key into a keystore and unlocking it is a separate step at the start of the client
+        let allow_share_data = json["evm"]["allow-share-data"].as_bool().unwrap_or(false);
+        let private_key = json["evm"]["private-key"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let keystore_path = json["evm"]["keystore-path"]
             .as_str()
             .unwrap_or("")
             .to_string();
-        if private_key.is_empty() {
-            let mut buffer = [0u8; 32];
-
-            // Open a file to read random bytes from the OS
-            #[cfg(target_os = "linux")]
-            let mut file = File::open("/dev/urandom").unwrap();
-
-            #[cfg(target_os = "windows")]
-            let mut file = File::open("C:\\Windows\\System32\\advapi32.dll").unwrap();
-
-            // Read random bytes into the buffer
-            file.read_exact(&mut buffer).unwrap();
-
-            private_key = hex::encode(buffer);
-        }
 
         let evm_url = json["evm"]["base-url"]
             .as_str()
@@ -332,42 +407,10 @@ impl Default for Settings {
             .unwrap_or("0xC28b89d570Fec3df45629212922b7F43090CF843")
             .to_string();
 
-        let middleware_result = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(async {
-                let provider = Provider::connect(evm_url.as_str()).await;
-
-                let wallet: LocalWallet = private_key.parse::<LocalWallet>().unwrap();
-
-                let mut result =
-                    SignerMiddleware::new_with_provider_chain(provider.clone(), wallet.clone())
-                        .await;
-                let mut retries = 0;
-                while result.is_err() && retries < 20 {
-                    retries += 1;
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    result =
-                        SignerMiddleware::new_with_provider_chain(provider.clone(), wallet.clone())
-                            .await;
-                }
-                result
-            });
-
-        let contract = match middleware_result {
-            Ok(middleware) => {
-                let edcas_address = smart_contract_address.parse::<Address>().unwrap();
-                Some(edcas_contract::EDCAS::new(
-                    edcas_address,
-                    Arc::new(middleware),
-                ))
-            }
-            Err(_) => {
-                error!("Couldn't get contract by settings initialization");
-                None
-            }
-        };
+        let database_path = json["local-database"]["path"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
 
         //---------------------------
         // Appearance
@@ -451,6 +494,25 @@ impl Default for Settings {
             );
         }
 
+        //---------------------------
+        // Highlight rules
+        //---------------------------
+        let mut highlight_rules: Vec<HighlightRule> = vec![];
+        for i in 0..json["highlight-rules"].len() {
+            let rule_json = &json["highlight-rules"][i];
+            highlight_rules.push(HighlightRule::new(
+                rule_json["name"].to_string(),
+                rule_json["rule"].as_str().unwrap_or("").to_string(),
+                rule_json["char"].as_str().unwrap_or("★").to_string(),
+                Color32::from_rgb(
+                    rule_json["r"].as_u8().unwrap_or(255),
+                    rule_json["g"].as_u8().unwrap_or(255),
+                    rule_json["b"].as_u8().unwrap_or(255),
+                ),
+                rule_json["enabled"].as_bool().unwrap_or(true),
+            ));
+        }
+
         Self {
             appearance_settings: AppearanceSettings {
                 font_size: json["appearance"]["font-size"].as_f32().unwrap_or(24.0),
@@ -474,17 +536,63 @@ impl Default for Settings {
                 include_system_name: json["explorer"]["include_system_name"]
                     .as_bool()
                     .unwrap_or(true),
+                high_value_threshold: json["explorer"]["high_value_threshold"]
+                    .as_i32()
+                    .unwrap_or(1_000_000),
+                bookmarks: json["explorer"]["bookmarks"]
+                    .members()
+                    .filter_map(|address| address.as_u64())
+                    .collect(),
             },
             evm_settings: EvmSettings {
                 url: evm_url,
                 n_timeout: json["evm"]["timeout"].as_u64().unwrap_or(5),
                 n_attempts: json["evm"]["attempts"].as_u64().unwrap_or(4),
-                allow_share_data: json["evm"]["allow-share-data"].as_bool().unwrap_or(false),
+                allow_share_data,
+                //Older settings shared everything
+                shared_categories: if json["evm"]["shared-categories"].is_null() {
+                    DataCategory::ALL.to_vec()
+                } else {
+                    json["evm"]["shared-categories"]
+                        .members()
+                        .filter_map(|category| DataCategory::from_str(category.as_str()?).ok())
+                        .collect()
+                },
+                publish_delay: json["evm"]["publish-delay"].as_u64().unwrap_or(0),
                 private_key,
+                keystore_path,
                 smart_contract_address,
-                contract,
                 show_upload_data_window: false,
                 journal_read_status: None,
+                queue_status: Arc::new(Mutex::new(TransactionQueueStatus::default())),
+            },
+            eddn_settings: EddnSettings {
+                upload_url: json["eddn"]["upload-url"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_UPLOAD_URL)
+                    .to_string(),
+                shared_schemas: json["eddn"]["shared-schemas"]
+                    .members()
+                    .filter_map(|schema| EddnSchema::from_str(schema.as_str()?).ok())
+                    .collect(),
+            },
+            database_settings: DatabaseSettings {
+                path: database_path.clone(),
+            },
+            backend_settings: BackendSettings {
+                //Older settings used the database as soon as a path was set
+                kind: match json["data-backend"]["kind"].as_str() {
+                    Some(kind) => BackendKind::from_str(kind).unwrap_or(BackendKind::Evm),
+                    None if !database_path.is_empty() => BackendKind::Database,
+                    None => BackendKind::Evm,
+                },
+                rest_url: json["data-backend"]["rest-url"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string(),
+            },
+            trade_settings: TradeSettings {
+                max_age: json["trade"]["max-age"].as_u64().unwrap_or(24),
             },
             graphic_editor_settings: GraphicEditorSettings {
                 graphics_directory: graphics_directory.clone(),
@@ -495,6 +603,9 @@ impl Default for Settings {
             icons,
             stars,
             planets,
+            saved_highlight_rules: highlight_rules_to_json(&highlight_rules),
+            highlight_rules,
+            highlight_rules_revision: Arc::new(AtomicU64::new(0)),
             settings_path,
             log_path: "".to_string(),
         }
@@ -503,6 +614,36 @@ impl Default for Settings {
 
 impl Settings {
     pub fn save_settings_to_file(&mut self) {
+        let highlight_rules = highlight_rules_to_json(&self.highlight_rules);
+        if highlight_rules != self.saved_highlight_rules {
+            self.saved_highlight_rules = highlight_rules;
+            self.highlight_rules_revision
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let json = self.to_json();
+        info!("Trying to write settings file to {}", &self.settings_path);
+        match File::create(&self.settings_path) {
+            Ok(mut settings_file) => {
+                match settings_file
+                    .write_all(serde_json::to_string_pretty(&json).unwrap().as_bytes())
+                {
+                    Ok(_) => {}
+                    Err(err) => {
+                        error!("Failed to save settings: {}", err);
+                        panic!("Failed to save settings: {}", err);
+                    }
+                };
+            }
+            Err(err) => {
+                error!("Failed to create settings: {}", err);
+                panic!("Failed to create settings: {}", err);
+            }
+        };
+        info!("Done writing to settings file");
+    }
+
+    /// The settings as they are written to the settings file
+    pub fn to_json(&self) -> serde_json::Value {
         let icon_array: serde_json::Value = self
             .icons
             .values()
@@ -551,8 +692,9 @@ impl Settings {
                 )
             })
             .collect();
+        let highlight_rule_array = highlight_rules_to_json(&self.highlight_rules);
 
-        let json = json!(
+        json!(
             {
                 "appearance": {
                     "font-size": self.appearance_settings.font_size,
@@ -563,42 +705,142 @@ impl Settings {
                     "action-at-shutdown-signal": self.journal_reader_settings.action_at_shutdown_signal.to_string()
                 },
                 "explorer": {
-                    "include_system_name": self.explorer_settings.include_system_name
+                    "include_system_name": self.explorer_settings.include_system_name,
+                    "high_value_threshold": self.explorer_settings.high_value_threshold,
+                    "bookmarks": self.explorer_settings.bookmarks
                 },
                 "evm": {
                     "base-url": self.evm_settings.url,
                     "timeout": self.evm_settings.n_timeout,
                     "attempts": self.evm_settings.n_attempts,
-                    "private-key": self.evm_settings.private_key,
+                    "private-key": if self.evm_settings.keystore_path.is_empty() {
+                        self.evm_settings.private_key.as_str()
+                    } else {
+                        ""
+                    },
+                    "keystore-path": self.evm_settings.keystore_path,
                     "smart-contract-address": self.evm_settings.smart_contract_address,
-                    "allow-share-data": self.evm_settings.allow_share_data
+                    "allow-share-data": self.evm_settings.allow_share_data,
+                    "shared-categories": self.evm_settings.shared_categories.iter().map(|category| category.to_string()).collect::<Vec<String>>(),
+                    "publish-delay": self.evm_settings.publish_delay
+                },
+                "eddn": {
+                    "upload-url": self.eddn_settings.upload_url,
+                    "shared-schemas": self.eddn_settings.shared_schemas.iter().map(|schema| schema.to_string()).collect::<Vec<String>>()
+                },
+                "local-database": {
+                    "path": self.database_settings.path
+                },
+                "data-backend": {
+                    "kind": self.backend_settings.kind.to_string(),
+                    "rest-url": self.backend_settings.rest_url
+                },
+                "trade": {
+                    "max-age": self.trade_settings.max_age
                 },
                 "icons": icon_array,
                 "stars": star_array,
                 "planets": planet_array,
+                "highlight-rules": highlight_rule_array,
                 "graphics-editor": {
                     "graphics-directory": self.graphic_editor_settings.graphics_directory
                 }
             }
-        );
-        info!("Trying to write settings file to {}", &self.settings_path);
-        match File::create(&self.settings_path) {
-            Ok(mut settings_file) => {
-                match settings_file
-                    .write_all(serde_json::to_string_pretty(&json).unwrap().as_bytes())
+        )
+    }
+}
+
+fn highlight_rules_to_json(rules: &[HighlightRule]) -> serde_json::Value {
+    rules
+        .iter()
+        .map(|rule| {
+            json!(
                 {
-                    Ok(_) => {}
-                    Err(err) => {
-                        error!("Failed to save settings: {}", err);
-                        panic!("Failed to save settings: {}", err);
-                    }
-                };
-            }
-            Err(err) => {
-                error!("Failed to create settings: {}", err);
-                panic!("Failed to create settings: {}", err);
-            }
-        };
-        info!("Done writing to settings file");
+                    "name": rule.name,
+                    "rule": rule.expression,
+                    "char": rule.char,
+                    "r": rule.color.r(),
+                    "g": rule.color.g(),
+                    "b": rule.color.b(),
+                    "enabled": rule.enabled
+                }
+            )
+        })
+        .collect()
+}
+
+/// Settings of `settings-example.json`, so the tests don't depend on the settings of the user
+#[cfg(test)]
+pub fn get_test_settings() -> Arc<Mutex<Settings>> {
+    Arc::new(Mutex::new(Settings::from_example()))
+}
+
+#[cfg(test)]
+impl Settings {
+    pub fn from_example() -> Settings {
+        let json = json::parse(include_str!("../../settings-example.json")).unwrap();
+        Settings::from_json(&json, String::new())
+    }
+}
+
+/**
+Directory for the files the client keeps between sessions, created if it is missing.
+Follows `XDG_DATA_HOME` and falls back to `~/.local/share`, on Windows to `%APPDATA%`.
+Only without any of them the files end up next to the client in `./edcas-client`.
+*/
+pub fn get_data_directory() -> std::path::PathBuf {
+    let base = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|directory| !directory.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|home| Path::new(&home).join(".local").join("share"))
+        })
+        .or_else(|| env::var("APPDATA").ok().map(std::path::PathBuf::from))
+        .unwrap_or_else(|| {
+            warn!("Neither XDG_DATA_HOME, HOME nor APPDATA is set, using the current directory");
+            std::path::PathBuf::from(".")
+        });
+    let directory = base.join("edcas-client");
+    if let Err(err) = fs::create_dir_all(&directory) {
+        warn!("Couldn't create {}: {}", directory.display(), err);
+    }
+    directory
+}
+
+/// Path of a file in the data directory
+pub fn get_data_path(name: &str) -> String {
+    get_data_directory().join(name).display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::backend::evm::test_utils::get_temp_path;
+
+    #[test]
+    fn highlight_rules_revision_changes_only_with_the_rules() {
+        let mut settings = Settings::from_example();
+        settings.settings_path = get_temp_path("highlight-settings.json");
+        let revision = settings.highlight_rules_revision.clone();
+
+        settings.appearance_settings.font_size += 1.0;
+        settings.save_settings_to_file();
+        assert_eq!(revision.load(Ordering::Relaxed), 0);
+
+        settings.highlight_rules.push(HighlightRule::new(
+            "Landable".to_string(),
+            "landable".to_string(),
+            "L".to_string(),
+            Color32::GREEN,
+            true,
+        ));
+        settings.save_settings_to_file();
+        assert_eq!(revision.load(Ordering::Relaxed), 1);
+        settings.save_settings_to_file();
+        assert_eq!(revision.load(Ordering::Relaxed), 1);
+        fs::remove_file(&settings.settings_path).unwrap();
     }
 }
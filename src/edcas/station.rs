@@ -1,17 +1,339 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::edcas::backend::data_backend::DataRequest;
 use crate::edcas::backend::evm::edcas_contract;
-use crate::edcas::request_handler::EvmRequest;
+use crate::edcas::backend::floating::floating_to_f64;
 use chrono::{DateTime, Utc};
+use log::error;
 
 use crate::edcas::settings::Settings;
 
+/// Requests sent for the filter at most once per interval, so the backend isn't flooded
+pub const FILTER_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+pub const FILTER_REQUESTS_PER_INTERVAL: usize = 50;
+
+/// Light years between two star positions
+pub fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Nearest first, unknown distances last
+pub fn compare_distances(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
 pub struct StationState {
     pub stations: Vec<Station>,
     pub search: String,
-    pub evm_request_writer: Sender<EvmRequest>,
+    pub data_request_writer: Sender<DataRequest>,
     pub settings: Arc<Mutex<Settings>>,
+    pub market_sort: MarketSortKey,
+    pub filter: StationFilter,
+    /// Coordinates of the systems of the stations by system address
+    pub system_coordinates: HashMap<u64, [f64; 3]>,
+    pub requested_systems: HashSet<u64>,
+    /// Star position of the commander's current system
+    pub position: Option<[f64; 3]>,
+    pub filter_requested_at: Option<Instant>,
+}
+
+impl StationState {
+    pub fn set_market_sort(&mut self, market_sort: MarketSortKey) {
+        self.market_sort = market_sort;
+        for station in &mut self.stations {
+            if let Some(market) = &mut station.market {
+                market_sort.sort(market);
+            }
+        }
+    }
+
+    /**
+    Requests what the filter needs: the meta data of every station and the coordinates of their systems.
+    Does nothing while the filter is empty, since the station list loads them lazily.
+    Called every frame, but only sends a batch of requests every [`FILTER_REQUEST_INTERVAL`].
+    */
+    pub fn request_filter_data(&mut self) {
+        if self.filter.is_empty()
+            || self
+                .filter_requested_at
+                .is_some_and(|requested_at| requested_at.elapsed() < FILTER_REQUEST_INTERVAL)
+        {
+            return;
+        }
+        self.filter_requested_at = Some(Instant::now());
+        let mut sent = 0;
+        for station in &mut self.stations {
+            if sent == FILTER_REQUESTS_PER_INTERVAL {
+                break;
+            }
+            match &station.meta_data {
+                None if !station.requested_meta_data => {
+                    if let Err(err) = self
+                        .data_request_writer
+                        .send(DataRequest::StationMetaData(station.market_id))
+                    {
+                        error!("Error sending StationMetaDataRequest: {err}");
+                    }
+                    station.requested_meta_data = true;
+                    sent += 1;
+                }
+                Some(meta_data)
+                    if meta_data.system_address != 0
+                        && !self
+                            .system_coordinates
+                            .contains_key(&meta_data.system_address)
+                        && self.requested_systems.insert(meta_data.system_address) =>
+                {
+                    if let Err(err) = self
+                        .data_request_writer
+                        .send(DataRequest::SystemMetaData(meta_data.system_address))
+                    {
+                        error!("Error sending SystemMetaDataRequest: {err}");
+                    }
+                    sent += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Light years from the current system, `None` if one of the positions is unknown
+    pub fn get_distance(&self, station: &Station) -> Option<f64> {
+        let system = self
+            .system_coordinates
+            .get(&station.meta_data.as_ref()?.system_address)?;
+        Some(distance(self.position?, *system))
+    }
+
+    /**
+    Indices of the stations matching the search and the filter.
+    With an active filter they are sorted by the distance to the current system, stations with an unknown distance last.
+    */
+    pub fn get_matching_stations(&self, search: &str) -> Vec<usize> {
+        let search = search.to_lowercase();
+        let mut indices: Vec<usize> = self
+            .stations
+            .iter()
+            .enumerate()
+            .filter(|(_, station)| station.name.to_lowercase().contains(&search))
+            .filter(|(_, station)| {
+                self.filter.is_empty()
+                    || station
+                        .meta_data
+                        .as_ref()
+                        .is_some_and(|meta_data| self.filter.matches(meta_data))
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if !self.filter.is_empty() {
+            indices.sort_by(|a, b| {
+                compare_distances(
+                    self.get_distance(&self.stations[*a]),
+                    self.get_distance(&self.stations[*b]),
+                )
+            });
+        }
+        indices
+    }
+}
+
+/// Services a station can be searched for, named like in the StationServices of the Docked event
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StationService {
+    InterstellarFactors,
+    MaterialTrader,
+    TechnologyBroker,
+    UniversalCartographics,
+    BlackMarket,
+    Shipyard,
+    Outfitting,
+    Refuel,
+    Repair,
+    Rearm,
+    SearchAndRescue,
+    Engineer,
+}
+
+impl StationService {
+    pub const ALL: [StationService; 12] = [
+        StationService::InterstellarFactors,
+        StationService::MaterialTrader,
+        StationService::TechnologyBroker,
+        StationService::UniversalCartographics,
+        StationService::BlackMarket,
+        StationService::Shipyard,
+        StationService::Outfitting,
+        StationService::Refuel,
+        StationService::Repair,
+        StationService::Rearm,
+        StationService::SearchAndRescue,
+        StationService::Engineer,
+    ];
+
+    pub fn get_journal_name(&self) -> &'static str {
+        match self {
+            StationService::InterstellarFactors => "facilitator",
+            StationService::MaterialTrader => "materialtrader",
+            StationService::TechnologyBroker => "techBroker",
+            StationService::UniversalCartographics => "exploration",
+            StationService::BlackMarket => "blackmarket",
+            StationService::Shipyard => "shipyard",
+            StationService::Outfitting => "outfitting",
+            StationService::Refuel => "refuel",
+            StationService::Repair => "repair",
+            StationService::Rearm => "rearm",
+            StationService::SearchAndRescue => "searchrescue",
+            StationService::Engineer => "engineer",
+        }
+    }
+
+    /// Whether the comma separated services of a station or carrier contain this one
+    pub fn is_offered(&self, services: &str) -> bool {
+        services
+            .split(',')
+            .any(|service| service.trim().eq_ignore_ascii_case(self.get_journal_name()))
+    }
+}
+
+impl Display for StationService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StationService::InterstellarFactors => "Interstellar Factors",
+            StationService::MaterialTrader => "Material Trader",
+            StationService::TechnologyBroker => "Technology Broker",
+            StationService::UniversalCartographics => "Universal Cartographics",
+            StationService::BlackMarket => "Black Market",
+            StationService::Shipyard => "Shipyard",
+            StationService::Outfitting => "Outfitting",
+            StationService::Refuel => "Refuel",
+            StationService::Repair => "Repair",
+            StationService::Rearm => "Rearm",
+            StationService::SearchAndRescue => "Search and Rescue",
+            StationService::Engineer => "Engineer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PadSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl PadSize {
+    pub const ALL: [PadSize; 3] = [PadSize::Small, PadSize::Medium, PadSize::Large];
+
+    pub fn next(&self) -> Option<PadSize> {
+        match self {
+            PadSize::Small => Some(PadSize::Medium),
+            PadSize::Medium => Some(PadSize::Large),
+            PadSize::Large => None,
+        }
+    }
+
+    /// Largest pad of the landing pads of the Docked event, e.g. `{ "Small":4, "Medium":4, "Large":8 }`
+    pub fn get_largest(landingpads: &str) -> Option<PadSize> {
+        let json = json::parse(landingpads).ok()?;
+        PadSize::ALL
+            .into_iter()
+            .rev()
+            .find(|size| json[size.to_string()].as_u32().unwrap_or(0) > 0)
+    }
+}
+
+impl Display for PadSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PadSize::Small => "Small",
+            PadSize::Medium => "Medium",
+            PadSize::Large => "Large",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct StationFilter {
+    pub services: Vec<StationService>,
+    pub min_pad: Option<PadSize>,
+    /// One of [`StationFilter::ECONOMIES`], any if empty
+    pub economy: String,
+    /// Maximum distance of the station from the arrival star in LS
+    pub max_arrival_distance: Option<f64>,
+}
+
+impl StationFilter {
+    /// Economies as named in the journal, e.g. "$economy_HighTech;"
+    pub const ECONOMIES: [&'static str; 10] = [
+        "Agri",
+        "Colony",
+        "Extraction",
+        "HighTech",
+        "Industrial",
+        "Military",
+        "Refinery",
+        "Service",
+        "Terraforming",
+        "Tourism",
+    ];
+
+    pub fn is_empty(&self) -> bool {
+        self == &StationFilter::default()
+    }
+
+    pub fn toggle_service(&mut self, service: StationService) {
+        match self.services.iter().position(|other| other == &service) {
+            Some(index) => {
+                self.services.remove(index);
+            }
+            None => self.services.push(service),
+        }
+    }
+
+    pub fn matches(&self, meta_data: &StationMetaData) -> bool {
+        if !self
+            .services
+            .iter()
+            .all(|service| service.is_offered(&meta_data.services))
+        {
+            return false;
+        }
+        if let Some(min_pad) = self.min_pad {
+            if PadSize::get_largest(&meta_data.landingpads).map_or(true, |pad| pad < min_pad) {
+                return false;
+            }
+        }
+        if !self.economy.is_empty()
+            && !meta_data
+                .economy
+                .to_lowercase()
+                .contains(&format!("_{};", self.economy.to_lowercase()))
+        {
+            return false;
+        }
+        if let Some(max_arrival_distance) = self.max_arrival_distance {
+            let distance = floating_to_f64(
+                meta_data.distance.decimal,
+                meta_data.distance.floating_point,
+            );
+            if distance > max_arrival_distance {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 #[derive(Clone)]
@@ -34,14 +356,124 @@ pub struct StationMetaData {
     pub economy: String,
     pub distance: edcas_contract::Floating,
     pub landingpads: String,
+    pub system_address: u64,
 }
 #[derive(Clone)]
 pub struct CommodityListening {
     pub name: String,
     pub buy_price: u32,
+    pub sell_price: u32,
     pub mean_price: u32,
     pub demand: u32,
     pub demand_bracket: u32,
     pub stock: u32,
     pub stock_bracket: u32,
 }
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MarketSortKey {
+    Name,
+    BuyPrice,
+    SellPrice,
+    Stock,
+    Demand,
+}
+
+impl MarketSortKey {
+    pub const ALL: [MarketSortKey; 5] = [
+        MarketSortKey::Name,
+        MarketSortKey::BuyPrice,
+        MarketSortKey::SellPrice,
+        MarketSortKey::Stock,
+        MarketSortKey::Demand,
+    ];
+
+    pub fn next(&self) -> MarketSortKey {
+        let index = MarketSortKey::ALL
+            .iter()
+            .position(|key| key == self)
+            .unwrap_or(0);
+        MarketSortKey::ALL[(index + 1) % MarketSortKey::ALL.len()]
+    }
+
+    /// Names are sorted alphabetically, numbers with the highest first
+    pub fn sort(&self, market: &mut [CommodityListening]) {
+        match self {
+            MarketSortKey::Name => market.sort_by(|a, b| a.name.cmp(&b.name)),
+            MarketSortKey::BuyPrice => market.sort_by_key(|listening| Reverse(listening.buy_price)),
+            MarketSortKey::SellPrice => {
+                market.sort_by_key(|listening| Reverse(listening.sell_price))
+            }
+            MarketSortKey::Stock => market.sort_by_key(|listening| Reverse(listening.stock)),
+            MarketSortKey::Demand => market.sort_by_key(|listening| Reverse(listening.demand)),
+        }
+    }
+}
+
+impl Display for MarketSortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MarketSortKey::Name => "Commodity",
+            MarketSortKey::BuyPrice => "Buy",
+            MarketSortKey::SellPrice => "Sell",
+            MarketSortKey::Stock => "Stock",
+            MarketSortKey::Demand => "Demand",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+    use std::sync::mpsc;
+
+    #[test]
+    fn filter_requests_are_sent_in_batches() {
+        let (data_request_writer, receiver) = mpsc::channel();
+        let mut state = StationState {
+            stations: (0..120)
+                .map(|market_id| Station {
+                    market_id,
+                    name: format!("Station {}", market_id),
+                    _type: String::new(),
+                    requested_meta_data: false,
+                    meta_data: None,
+                    requested_market: false,
+                    market: None,
+                })
+                .collect(),
+            search: String::new(),
+            data_request_writer,
+            settings: get_test_settings(),
+            market_sort: MarketSortKey::Name,
+            filter: StationFilter::default(),
+            system_coordinates: HashMap::new(),
+            requested_systems: HashSet::new(),
+            position: None,
+            filter_requested_at: None,
+        };
+
+        state.request_filter_data();
+        assert_eq!(receiver.try_iter().count(), 0);
+
+        state.filter.toggle_service(StationService::Refuel);
+        state.request_filter_data();
+        assert_eq!(receiver.try_iter().count(), FILTER_REQUESTS_PER_INTERVAL);
+        state.request_filter_data();
+        assert_eq!(receiver.try_iter().count(), 0);
+
+        state.filter_requested_at = Some(Instant::now() - FILTER_REQUEST_INTERVAL);
+        state.request_filter_data();
+        assert_eq!(receiver.try_iter().count(), FILTER_REQUESTS_PER_INTERVAL);
+        assert_eq!(
+            state
+                .stations
+                .iter()
+                .filter(|station| station.requested_meta_data)
+                .count(),
+            2 * FILTER_REQUESTS_PER_INTERVAL
+        );
+    }
+}
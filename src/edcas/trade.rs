@@ -0,0 +1,917 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Duration, Utc};
+use json::JsonValue;
+use log::{debug, error, info, warn};
+use serde_json::json;
+
+#[cfg(feature = "database")]
+use crate::edcas::backend::database;
+use crate::edcas::ledger::CargoLedger;
+use crate::edcas::settings::{get_data_path, Settings};
+use crate::edcas::station::{self, CommodityListening, PadSize};
+
+/// Seconds for undocking, docking and trading at a station
+const DOCKING_SECONDS: f64 = 120.0;
+/// Seconds for charging and jumping to another system
+const JUMP_SECONDS: f64 = 45.0;
+/// Supercruise to a station takes about this times the square root of its arrival distance in LS
+const SUPERCRUISE_FACTOR: f64 = 2.5;
+/// Best hops per station which are followed when searching loops
+const LOOP_CANDIDATES: usize = 5;
+/// Results kept per route kind
+const MAX_ROUTES: usize = 50;
+
+/// Market of a station at the time it was recorded
+#[derive(Clone)]
+pub struct MarketSnapshot {
+    pub market_id: u64,
+    pub station_name: String,
+    pub system_name: String,
+    pub position: Option<[f64; 3]>,
+    pub largest_pad: Option<PadSize>,
+    /// Distance from the arrival star in LS
+    pub arrival_distance: f64,
+    pub timestamp: DateTime<Utc>,
+    pub commodities: Vec<CommodityListening>,
+}
+
+impl MarketSnapshot {
+    fn from_store(market_id: u64, json: &JsonValue) -> Option<MarketSnapshot> {
+        Some(MarketSnapshot {
+            market_id,
+            station_name: json["station"].as_str()?.to_string(),
+            system_name: json["system"].as_str()?.to_string(),
+            position: match (json["x"].as_f64(), json["y"].as_f64(), json["z"].as_f64()) {
+                (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+                _ => None,
+            },
+            largest_pad: json["pad"].as_str().and_then(|pad| {
+                PadSize::ALL
+                    .into_iter()
+                    .find(|size| size.to_string() == pad)
+            }),
+            arrival_distance: json["arrival"].as_f64().unwrap_or(0.0),
+            timestamp: DateTime::from_timestamp(json["timestamp"].as_i64()?, 0)?,
+            commodities: json["commodities"]
+                .members()
+                .map(|commodity| CommodityListening {
+                    name: commodity["name"].to_string(),
+                    buy_price: commodity["buy"].as_u32().unwrap_or(0),
+                    sell_price: commodity["sell"].as_u32().unwrap_or(0),
+                    mean_price: commodity["mean"].as_u32().unwrap_or(0),
+                    demand: commodity["demand"].as_u32().unwrap_or(0),
+                    demand_bracket: commodity["demand_bracket"].as_u32().unwrap_or(0),
+                    stock: commodity["stock"].as_u32().unwrap_or(0),
+                    stock_bracket: commodity["stock_bracket"].as_u32().unwrap_or(0),
+                })
+                .collect(),
+        })
+    }
+
+    fn to_store(&self) -> serde_json::Value {
+        json!({
+            "station": self.station_name,
+            "system": self.system_name,
+            "x": self.position.map(|position| position[0]),
+            "y": self.position.map(|position| position[1]),
+            "z": self.position.map(|position| position[2]),
+            "pad": self.largest_pad.map(|pad| pad.to_string()),
+            "arrival": self.arrival_distance,
+            "timestamp": self.timestamp.timestamp(),
+            "commodities": self.commodities.iter().map(|commodity| json!({
+                "name": commodity.name,
+                "buy": commodity.buy_price,
+                "sell": commodity.sell_price,
+                "mean": commodity.mean_price,
+                "demand": commodity.demand,
+                "demand_bracket": commodity.demand_bracket,
+                "stock": commodity.stock,
+                "stock_bracket": commodity.stock_bracket,
+            })).collect::<Vec<serde_json::Value>>(),
+        })
+    }
+
+    fn get_distance(&self, other: &MarketSnapshot) -> Option<f64> {
+        Some(station::distance(self.position?, other.position?))
+    }
+}
+
+type Routes = (Vec<TradeRoute>, Vec<TradeRoute>);
+
+/// Ship and wallet the routes are calculated for
+#[derive(Clone)]
+pub struct TradeQuery {
+    pub cargo_capacity: u32,
+    pub credits: u64,
+    /// Pad the ship needs
+    pub pad_size: PadSize,
+    /// Maximum distance between two stations of a route in ly
+    pub max_hop_distance: f64,
+    /// Hops of a loop, 2 goes back and forth
+    pub loop_hops: usize,
+}
+
+impl Default for TradeQuery {
+    fn default() -> Self {
+        TradeQuery {
+            cargo_capacity: 0,
+            credits: 0,
+            pad_size: PadSize::Large,
+            max_hop_distance: 20.0,
+            loop_hops: 2,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TradeHop {
+    pub from_station: String,
+    pub from_system: String,
+    pub to_station: String,
+    pub to_system: String,
+    pub commodity: String,
+    pub buy_price: u32,
+    pub sell_price: u32,
+    /// Tons bought, limited by cargo, credits, stock and demand
+    pub amount: u32,
+    /// Distance between the systems in ly
+    pub distance: f64,
+    /// Estimated duration of the hop
+    pub seconds: f64,
+}
+
+impl TradeHop {
+    pub fn get_margin(&self) -> u64 {
+        (self.sell_price - self.buy_price) as u64
+    }
+
+    pub fn get_profit(&self) -> u64 {
+        self.get_margin() * self.amount as u64
+    }
+}
+
+#[derive(Clone)]
+pub struct TradeRoute {
+    pub hops: Vec<TradeHop>,
+}
+
+impl TradeRoute {
+    pub fn get_profit(&self) -> u64 {
+        self.hops.iter().map(|hop| hop.get_profit()).sum()
+    }
+
+    pub fn get_profit_per_ton(&self) -> f64 {
+        let tons: u32 = self.hops.iter().map(|hop| hop.amount).sum();
+        if tons == 0 {
+            return 0.0;
+        }
+        self.get_profit() as f64 / tons as f64
+    }
+
+    pub fn get_profit_per_hour(&self) -> f64 {
+        let seconds: f64 = self.hops.iter().map(|hop| hop.seconds).sum();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.get_profit() as f64 / seconds * 3600.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RouteSortKey {
+    ProfitPerTon,
+    ProfitPerHour,
+}
+
+impl RouteSortKey {
+    pub const ALL: [RouteSortKey; 2] = [RouteSortKey::ProfitPerTon, RouteSortKey::ProfitPerHour];
+
+    pub fn next(&self) -> RouteSortKey {
+        match self {
+            RouteSortKey::ProfitPerTon => RouteSortKey::ProfitPerHour,
+            RouteSortKey::ProfitPerHour => RouteSortKey::ProfitPerTon,
+        }
+    }
+
+    /// Best route first
+    pub fn sort(&self, routes: &mut [TradeRoute]) {
+        let value = |route: &TradeRoute| match self {
+            RouteSortKey::ProfitPerTon => route.get_profit_per_ton(),
+            RouteSortKey::ProfitPerHour => route.get_profit_per_hour(),
+        };
+        routes.sort_by(|a, b| value(b).partial_cmp(&value(a)).unwrap_or(Ordering::Equal));
+    }
+}
+
+impl Display for RouteSortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RouteSortKey::ProfitPerTon => "Profit/t",
+            RouteSortKey::ProfitPerHour => "Profit/h",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct TradeState {
+    pub settings: Arc<Mutex<Settings>>,
+    pub query: TradeQuery,
+    pub sort: RouteSortKey,
+    pub single_hop_routes: Vec<TradeRoute>,
+    pub loop_routes: Vec<TradeRoute>,
+    pub ledger: CargoLedger,
+    /// Markets recorded from Market.json, kept across sessions
+    markets: HashMap<u64, MarketSnapshot>,
+    /// Markets of the local database filled by the eddn adapter, shared with the route calculation
+    database_markets: Arc<Vec<MarketSnapshot>>,
+    /// Database markets being read in the background
+    database_loading: Option<Receiver<Result<Vec<MarketSnapshot>, String>>>,
+    /// Routes being calculated in the background
+    calculation: Option<Receiver<Routes>>,
+    /// Address and star position of the system the commander is in, Docked events lack the position
+    current_system: Option<(u64, [f64; 3])>,
+    path: String,
+}
+
+impl TradeState {
+    pub fn load(settings: Arc<Mutex<Settings>>) -> Self {
+        let path = get_data_path("markets.json");
+
+        let mut markets = HashMap::new();
+        if let Ok(content) = fs::read_to_string(&path) {
+            match json::parse(&content) {
+                Ok(json) => {
+                    for (market_id, market) in json.entries() {
+                        if let Some(snapshot) = market_id
+                            .parse::<u64>()
+                            .ok()
+                            .and_then(|market_id| MarketSnapshot::from_store(market_id, market))
+                        {
+                            markets.insert(snapshot.market_id, snapshot);
+                        }
+                    }
+                    info!("Loaded {} markets", markets.len());
+                }
+                Err(err) => error!("Couldn't parse market file {}: {}", path, err),
+            }
+        }
+
+        let mut trade = TradeState {
+            settings,
+            query: TradeQuery::default(),
+            sort: RouteSortKey::ProfitPerHour,
+            single_hop_routes: vec![],
+            loop_routes: vec![],
+            ledger: CargoLedger::default(),
+            markets,
+            database_markets: Arc::new(vec![]),
+            database_loading: None,
+            calculation: None,
+            current_system: None,
+            path,
+        };
+        trade.reload_database();
+        trade
+    }
+
+    /// Reads the markets of the local database again in the background, if one is set
+    pub fn reload_database(&mut self) {
+        let path = self.settings.lock().unwrap().database_settings.path.clone();
+        if path.is_empty() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("edcas-trade-database".into())
+            .spawn(move || {
+                #[cfg(feature = "database")]
+                let markets = database::load_market_snapshots(&path)
+                    .map_err(|err| format!("Couldn't load markets from {}: {}", path, err));
+                #[cfg(not(feature = "database"))]
+                let markets: Result<Vec<MarketSnapshot>, String> = Err(format!(
+                    "Built without the database feature, can't load markets from {}",
+                    path
+                ));
+                if let Ok(markets) = &markets {
+                    info!("Loaded {} markets from {}", markets.len(), path);
+                }
+                let _ = sender.send(markets);
+            });
+        match spawned {
+            Ok(_) => self.database_loading = Some(receiver),
+            Err(err) => error!("Failed to create thread trade-database: {}", err),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.database_loading.is_some()
+    }
+
+    pub fn is_calculating(&self) -> bool {
+        self.calculation.is_some()
+    }
+
+    /// Takes over the results of the background work once they are done
+    pub fn poll(&mut self) {
+        if let Some(receiver) = &self.database_loading {
+            match receiver.try_recv() {
+                Ok(Ok(markets)) => {
+                    self.database_markets = Arc::new(markets);
+                    self.database_loading = None;
+                }
+                Ok(Err(err)) => {
+                    error!("{}", err);
+                    self.database_loading = None;
+                }
+                Err(TryRecvError::Disconnected) => self.database_loading = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        if let Some(receiver) = &self.calculation {
+            match receiver.try_recv() {
+                Ok((single_hop_routes, loop_routes)) => {
+                    self.single_hop_routes = single_hop_routes;
+                    self.loop_routes = loop_routes;
+                    //The sort could have been changed while calculating
+                    self.sort.sort(&mut self.single_hop_routes);
+                    self.sort.sort(&mut self.loop_routes);
+                    self.calculation = None;
+                }
+                Err(TryRecvError::Disconnected) => self.calculation = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+    }
+
+    pub fn get_market_count(&self) -> usize {
+        self.markets.len() + self.database_markets.len()
+    }
+
+    /// Highest price any known market with demand pays for the commodity
+    pub fn get_best_sell_price(&self, name: &str) -> Option<u32> {
+        self.markets
+            .values()
+            .chain(self.database_markets.iter())
+            .flat_map(|market| market.commodities.iter())
+            .filter(|commodity| commodity.name == name && commodity.demand > 0)
+            .map(|commodity| commodity.sell_price)
+            .max()
+    }
+
+    /// Profit of the current hold at the best known prices
+    pub fn get_unrealised_profit(&self) -> i64 {
+        self.ledger
+            .get_unrealised_profit(|name| self.get_best_sell_price(name))
+    }
+
+    /// Ship and wallet are taken from the journal, so the query fits the current ship
+    pub fn set_cargo_capacity(&mut self, cargo_capacity: u32) {
+        self.query.cargo_capacity = cargo_capacity;
+    }
+
+    pub fn set_credits(&mut self, credits: u64) {
+        self.query.credits = credits;
+    }
+
+    /// Remembers the system of a FSDJump, Location or CarrierJump. Starting the game docked
+    /// only writes a Location, so it counts as docking as well.
+    pub fn record_location(&mut self, json: &JsonValue) {
+        self.current_system = match (
+            json["SystemAddress"].as_u64(),
+            json["StarPos"][0].as_f64(),
+            json["StarPos"][1].as_f64(),
+            json["StarPos"][2].as_f64(),
+        ) {
+            (Some(address), Some(x), Some(y), Some(z)) => Some((address, [x, y, z])),
+            _ => None,
+        };
+        if json["event"] == "Location" && json["Docked"].as_bool() == Some(true) {
+            self.record_docked(json);
+        }
+    }
+
+    /// Remembers the station the commander docked at, its market follows with the Market event
+    pub fn record_docked(&mut self, json: &JsonValue) {
+        let Some(market_id) = json["MarketID"].as_u64() else {
+            return;
+        };
+        let position = self
+            .current_system
+            .filter(|(address, _)| json["SystemAddress"].as_u64() == Some(*address))
+            .map(|(_, position)| position);
+        let snapshot = self
+            .markets
+            .entry(market_id)
+            .or_insert_with(|| MarketSnapshot {
+                market_id,
+                station_name: String::new(),
+                system_name: String::new(),
+                position: None,
+                largest_pad: None,
+                arrival_distance: 0.0,
+                timestamp: DateTime::default(),
+                commodities: vec![],
+            });
+        snapshot.station_name = json["StationName"].to_string();
+        snapshot.system_name = json["StarSystem"].to_string();
+        snapshot.position = position.or(snapshot.position);
+        //Location events have no landing pads
+        snapshot.largest_pad =
+            PadSize::get_largest(&json["LandingPads"].to_string()).or(snapshot.largest_pad);
+        snapshot.arrival_distance = json["DistFromStarLS"].as_f64().unwrap_or(0.0);
+    }
+
+    /// Reads the Market.json written together with the Market event
+    pub fn record_market(&mut self, event: &JsonValue) {
+        let directory = self
+            .settings
+            .lock()
+            .unwrap()
+            .journal_reader_settings
+            .journal_directory
+            .clone();
+        let path = format!("{}/Market.json", directory);
+        let market = match fs::read_to_string(&path).map(|content| json::parse(&content)) {
+            Ok(Ok(market)) if market["timestamp"] == event["timestamp"] => market,
+            Ok(Ok(_)) => {
+                debug!("{} does not match event from {}", path, event["timestamp"]);
+                return;
+            }
+            Ok(Err(err)) => {
+                warn!("Couldn't parse {}: {}", path, err);
+                return;
+            }
+            Err(err) => {
+                warn!("Couldn't read {}: {}", path, err);
+                return;
+            }
+        };
+        let (Some(market_id), Some(timestamp)) = (
+            market["MarketID"].as_u64(),
+            market["timestamp"]
+                .as_str()
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok()),
+        ) else {
+            return;
+        };
+        let commodities = market["Items"]
+            .members()
+            .filter(|item| {
+                item["Category"].as_str() != Some("$MARKET_category_nonmarketable;")
+                    && item["Legality"].as_str().unwrap_or("").is_empty()
+            })
+            .map(|item| CommodityListening {
+                //Named like in the eddn commodity messages, so both sources can be compared
+                name: item["Name"]
+                    .to_string()
+                    .trim_start_matches('$')
+                    .trim_end_matches("_name;")
+                    .to_lowercase(),
+                buy_price: item["BuyPrice"].as_u32().unwrap_or(0),
+                sell_price: item["SellPrice"].as_u32().unwrap_or(0),
+                mean_price: item["MeanPrice"].as_u32().unwrap_or(0),
+                demand: item["Demand"].as_u32().unwrap_or(0),
+                demand_bracket: item["DemandBracket"].as_u32().unwrap_or(0),
+                stock: item["Stock"].as_u32().unwrap_or(0),
+                stock_bracket: item["StockBracket"].as_u32().unwrap_or(0),
+            })
+            .collect();
+        let snapshot = self
+            .markets
+            .entry(market_id)
+            .or_insert_with(|| MarketSnapshot {
+                market_id,
+                station_name: market["StationName"].to_string(),
+                system_name: market["StarSystem"].to_string(),
+                position: None,
+                largest_pad: None,
+                arrival_distance: 0.0,
+                timestamp: DateTime::default(),
+                commodities: vec![],
+            });
+        snapshot.timestamp = timestamp.with_timezone(&Utc);
+        snapshot.commodities = commodities;
+        self.save();
+    }
+
+    pub fn save(&self) {
+        let mut json = serde_json::Map::new();
+        for (market_id, snapshot) in &self.markets {
+            if !snapshot.commodities.is_empty() {
+                json.insert(market_id.to_string(), snapshot.to_store());
+            }
+        }
+        if let Err(err) = fs::write(
+            &self.path,
+            serde_json::Value::Object(json).to_string().as_bytes(),
+        ) {
+            error!("Couldn't write market file {}: {}", self.path, err);
+        }
+    }
+
+    /**
+    Calculates the single hop and loop routes of the query with the markets newer than the maximum age.
+    The calculation runs in the background, its routes are taken over by `poll`.
+    */
+    pub fn calculate(&mut self) {
+        let max_age = self.settings.lock().unwrap().trade_settings.max_age;
+        let oldest = Utc::now() - Duration::hours(max_age as i64);
+        let database_markets = self.database_markets.clone();
+        let journal_markets: Vec<MarketSnapshot> = self.markets.values().cloned().collect();
+        let query = self.query.clone();
+        let sort = self.sort;
+        let (sender, receiver) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("edcas-trade-routes".into())
+            .spawn(move || {
+                //Journal markets are newer than the database if both have the station
+                let mut markets: HashMap<u64, &MarketSnapshot> = HashMap::new();
+                for snapshot in database_markets.iter().chain(journal_markets.iter()) {
+                    if snapshot.timestamp >= oldest && !snapshot.commodities.is_empty() {
+                        markets.insert(snapshot.market_id, snapshot);
+                    }
+                }
+                let markets: Vec<&MarketSnapshot> = markets
+                    .into_values()
+                    .filter(|snapshot| {
+                        snapshot.position.is_some()
+                            && snapshot
+                                .largest_pad
+                                .is_some_and(|pad| pad >= query.pad_size)
+                    })
+                    .collect();
+                info!("Calculating trade routes with {} markets", markets.len());
+
+                let (mut single_hop_routes, mut loop_routes) = find_routes(&markets, &query);
+                sort.sort(&mut single_hop_routes);
+                sort.sort(&mut loop_routes);
+                single_hop_routes.truncate(MAX_ROUTES);
+                loop_routes.truncate(MAX_ROUTES);
+                let _ = sender.send((single_hop_routes, loop_routes));
+            });
+        //A running calculation is replaced, its routes are dropped
+        match spawned {
+            Ok(_) => self.calculation = Some(receiver),
+            Err(err) => error!("Failed to create thread trade-routes: {}", err),
+        }
+    }
+
+    pub fn set_sort(&mut self, sort: RouteSortKey) {
+        self.sort = sort;
+        sort.sort(&mut self.single_hop_routes);
+        sort.sort(&mut self.loop_routes);
+    }
+}
+
+/// Rough duration of a hop: a jump if the system changes, supercruise to the station and docking
+fn estimate_hop_seconds(distance: f64, arrival_distance: f64) -> f64 {
+    let jump = if distance > 0.0 { JUMP_SECONDS } else { 0.0 };
+    DOCKING_SECONDS + jump + SUPERCRUISE_FACTOR * arrival_distance.max(0.0).sqrt()
+}
+
+/// Most profitable commodity from one market to the other, `None` if nothing makes a profit
+fn find_best_hop(
+    from: &MarketSnapshot,
+    to: &MarketSnapshot,
+    prices: &HashMap<&str, &CommodityListening>,
+    query: &TradeQuery,
+) -> Option<TradeHop> {
+    let distance = from.get_distance(to)?;
+    if distance > query.max_hop_distance {
+        return None;
+    }
+    from.commodities
+        .iter()
+        .filter(|offer| offer.buy_price > 0 && offer.stock > 0)
+        .filter_map(|offer| {
+            let demand = prices.get(offer.name.as_str())?;
+            if demand.sell_price <= offer.buy_price || demand.demand == 0 {
+                return None;
+            }
+            let affordable = (query.credits / offer.buy_price as u64).min(u32::MAX as u64) as u32;
+            let amount = query
+                .cargo_capacity
+                .min(affordable)
+                .min(offer.stock)
+                .min(demand.demand);
+            if amount == 0 {
+                return None;
+            }
+            Some(TradeHop {
+                from_station: from.station_name.clone(),
+                from_system: from.system_name.clone(),
+                to_station: to.station_name.clone(),
+                to_system: to.system_name.clone(),
+                commodity: offer.name.clone(),
+                buy_price: offer.buy_price,
+                sell_price: demand.sell_price,
+                amount,
+                distance,
+                seconds: estimate_hop_seconds(distance, to.arrival_distance),
+            })
+        })
+        .max_by_key(|hop| hop.get_profit())
+}
+
+/// Grid cell of a position, cells are as large as the maximum hop distance
+fn get_cell(position: [f64; 3], cell_size: f64) -> [i64; 3] {
+    position.map(|coordinate| (coordinate / cell_size).floor() as i64)
+}
+
+/**
+Indices of the markets which could be in reach of the given one.
+Markets are only compared with the markets in the same and the neighbouring grid cells,
+because every other market is further away than the maximum hop distance.
+*/
+fn get_neighbours<'a>(
+    cells: &'a HashMap<[i64; 3], Vec<usize>>,
+    cell: [i64; 3],
+) -> impl Iterator<Item = usize> + 'a {
+    (-1..=1)
+        .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| [x, y, z])))
+        .filter_map(move |offset| {
+            cells.get(&[
+                cell[0] + offset[0],
+                cell[1] + offset[1],
+                cell[2] + offset[2],
+            ])
+        })
+        .flatten()
+        .copied()
+}
+
+/**
+Gives back the single hop routes and the loops with `loop_hops` hops which end at their first station.
+Loops only follow the best hops of each station, so they are good but not always the best ones.
+*/
+fn find_routes(markets: &[&MarketSnapshot], query: &TradeQuery) -> Routes {
+    let prices: Vec<HashMap<&str, &CommodityListening>> = markets
+        .iter()
+        .map(|snapshot| {
+            snapshot
+                .commodities
+                .iter()
+                .map(|commodity| (commodity.name.as_str(), commodity))
+                .collect()
+        })
+        .collect();
+
+    let cell_size = query.max_hop_distance.max(1.0);
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (index, snapshot) in markets.iter().enumerate() {
+        if let Some(position) = snapshot.position {
+            cells
+                .entry(get_cell(position, cell_size))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut hops: Vec<Vec<(usize, TradeHop)>> = vec![vec![]; markets.len()];
+    for (from, from_hops) in hops.iter_mut().enumerate() {
+        let Some(position) = markets[from].position else {
+            continue;
+        };
+        for to in get_neighbours(&cells, get_cell(position, cell_size)) {
+            if from == to {
+                continue;
+            }
+            if let Some(hop) = find_best_hop(markets[from], markets[to], &prices[to], query) {
+                from_hops.push((to, hop));
+            }
+        }
+        from_hops.sort_by_key(|(_, hop)| std::cmp::Reverse(hop.get_profit()));
+    }
+
+    let single_hop_routes = hops
+        .iter()
+        .flatten()
+        .map(|(_, hop)| TradeRoute {
+            hops: vec![hop.clone()],
+        })
+        .collect();
+
+    let mut loop_routes = vec![];
+    if query.loop_hops >= 2 {
+        for start in 0..markets.len() {
+            let mut path = vec![start];
+            let mut route = vec![];
+            find_loops(
+                &hops,
+                query.loop_hops,
+                &mut path,
+                &mut route,
+                &mut loop_routes,
+            );
+        }
+    }
+    (single_hop_routes, loop_routes)
+}
+
+/// Depth first search over the best hops. A loop is only taken from its lowest station, so each loop is found once.
+fn find_loops(
+    hops: &[Vec<(usize, TradeHop)>],
+    loop_hops: usize,
+    path: &mut Vec<usize>,
+    route: &mut Vec<TradeHop>,
+    loop_routes: &mut Vec<TradeRoute>,
+) {
+    let start = path[0];
+    let current = *path.last().unwrap();
+    if path.len() == loop_hops {
+        if let Some((_, hop)) = hops[current].iter().find(|(to, _)| *to == start) {
+            let mut hops = route.clone();
+            hops.push(hop.clone());
+            loop_routes.push(TradeRoute { hops });
+        }
+        return;
+    }
+    for (to, hop) in hops[current].iter().take(LOOP_CANDIDATES) {
+        if *to <= start || path.contains(to) {
+            continue;
+        }
+        path.push(*to);
+        route.push(hop.clone());
+        find_loops(hops, loop_hops, path, route, loop_routes);
+        path.pop();
+        route.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edcas::settings::get_test_settings;
+    use json::object;
+    use std::thread::sleep;
+
+    fn trade_state() -> TradeState {
+        TradeState {
+            settings: get_test_settings(),
+            query: TradeQuery {
+                cargo_capacity: 100,
+                credits: 1_000_000,
+                ..TradeQuery::default()
+            },
+            sort: RouteSortKey::ProfitPerTon,
+            single_hop_routes: vec![],
+            loop_routes: vec![],
+            ledger: CargoLedger::default(),
+            markets: HashMap::new(),
+            database_markets: Arc::new(vec![]),
+            database_loading: None,
+            calculation: None,
+            current_system: None,
+            path: String::new(),
+        }
+    }
+
+    fn commodity(name: &str, buy_price: u32, sell_price: u32) -> CommodityListening {
+        CommodityListening {
+            name: name.to_string(),
+            buy_price,
+            sell_price,
+            mean_price: 0,
+            demand: 1000,
+            demand_bracket: 0,
+            stock: 1000,
+            stock_bracket: 0,
+        }
+    }
+
+    /// Sells gold cheap and buys it expensive, so every pair of markets has a route
+    fn market(market_id: u64, x: f64, buy_price: u32, sell_price: u32) -> MarketSnapshot {
+        MarketSnapshot {
+            market_id,
+            station_name: format!("Station {}", market_id),
+            system_name: format!("System {}", market_id),
+            position: Some([x, 0.0, 0.0]),
+            largest_pad: Some(PadSize::Large),
+            arrival_distance: 100.0,
+            timestamp: Utc::now(),
+            commodities: vec![commodity("gold", buy_price, sell_price)],
+        }
+    }
+
+    #[test]
+    fn routes_stay_within_hop_distance() {
+        //19 and 21 ly are in neighbouring cells of 20 ly, 100 ly is out of reach
+        let markets = [
+            market(1, 19.0, 1000, 1000),
+            market(2, 21.0, 2000, 2000),
+            market(3, 100.0, 2000, 3000),
+        ];
+        let markets: Vec<&MarketSnapshot> = markets.iter().collect();
+        let query = TradeQuery {
+            cargo_capacity: 10,
+            credits: 1_000_000,
+            ..TradeQuery::default()
+        };
+        let (single_hop_routes, _) = find_routes(&markets, &query);
+        assert_eq!(single_hop_routes.len(), 1);
+        let hop = &single_hop_routes[0].hops[0];
+        assert_eq!(hop.from_station, "Station 1");
+        assert_eq!(hop.to_station, "Station 2");
+        assert_eq!(hop.get_profit(), 10_000);
+    }
+
+    #[test]
+    fn bucketing_finds_the_same_routes_as_comparing_all() {
+        let markets: Vec<MarketSnapshot> = (0..40)
+            .map(|i| {
+                market(
+                    i,
+                    (i * 7 % 53) as f64 - 26.0,
+                    1000 + i as u32 * 10,
+                    1500 + i as u32 * 10,
+                )
+            })
+            .collect();
+        let markets: Vec<&MarketSnapshot> = markets.iter().collect();
+        let query = TradeQuery {
+            cargo_capacity: 10,
+            credits: 1_000_000,
+            max_hop_distance: 5.0,
+            loop_hops: 2,
+            ..TradeQuery::default()
+        };
+        let mut expected = 0;
+        for from in &markets {
+            for to in &markets {
+                if from.market_id != to.market_id
+                    && find_best_hop(
+                        from,
+                        to,
+                        &to.commodities
+                            .iter()
+                            .map(|c| (c.name.as_str(), c))
+                            .collect(),
+                        &query,
+                    )
+                    .is_some()
+                {
+                    expected += 1;
+                }
+            }
+        }
+        let (single_hop_routes, _) = find_routes(&markets, &query);
+        assert!(expected > 0);
+        assert_eq!(single_hop_routes.len(), expected);
+    }
+
+    #[test]
+    fn docked_uses_star_position_of_the_jump() {
+        let mut trade = trade_state();
+        trade.record_location(&json::parse(r#"{ "timestamp":"2023-09-10T18:02:11Z", "event":"FSDJump", "Taxi":false, "Multicrew":false, "StarSystem":"Diaguandri", "SystemAddress":670417429889, "StarPos":[-41.06250,-62.15625,-103.25000], "SystemAllegiance":"Independent", "SystemEconomy":"$economy_HighTech;", "SystemEconomy_Localised":"High Tech", "Population":10303479, "Body":"Diaguandri", "BodyID":0, "BodyType":"Star", "JumpDist":9.244, "FuelUsed":0.561093, "FuelLevel":31.438908 }"#).unwrap());
+        trade.record_docked(&json::parse(r#"{ "timestamp":"2023-09-10T18:09:40Z", "event":"Docked", "StationName":"Ray Gateway", "StationType":"Coriolis", "Taxi":false, "Multicrew":false, "StarSystem":"Diaguandri", "SystemAddress":670417429889, "MarketID":3223343616, "StationFaction":{ "Name":"EXO", "FactionState":"Boom" }, "StationGovernment":"$government_Democracy;", "StationGovernment_Localised":"Democracy", "StationServices":[ "dock", "autodock", "commodities", "contacts" ], "StationEconomy":"$economy_HighTech;", "StationEconomy_Localised":"High Tech", "StationEconomies":[ { "Name":"$economy_HighTech;", "Name_Localised":"High Tech", "Proportion":0.800000 } ], "DistFromStarLS":1432.525823, "LandingPads":{ "Small":4, "Medium":4, "Large":2 } }"#).unwrap());
+        let snapshot = &trade.markets[&3223343616];
+        assert_eq!(snapshot.station_name, "Ray Gateway");
+        assert_eq!(snapshot.position, Some([-41.0625, -62.15625, -103.25]));
+        assert!(snapshot.largest_pad == Some(PadSize::Large));
+
+        //Docked in another system than the last jump, the position is not known
+        trade.record_docked(&object! {
+            "event": "Docked",
+            "MarketID": 128666762,
+            "StationName": "Jameson Memorial",
+            "StarSystem": "Shinrarta Dezhra",
+            "SystemAddress": 3932277478106u64,
+        });
+        assert_eq!(trade.markets[&128666762].position, None);
+    }
+
+    #[test]
+    fn starting_docked_records_the_station() {
+        let mut trade = trade_state();
+        trade.record_location(&json::parse(r#"{ "timestamp":"2023-09-10T17:50:02Z", "event":"Location", "DistFromStarLS":1432.525823, "Docked":true, "StationName":"Ray Gateway", "StationType":"Coriolis", "MarketID":3223343616, "Taxi":false, "Multicrew":false, "StarSystem":"Diaguandri", "SystemAddress":670417429889, "StarPos":[-41.06250,-62.15625,-103.25000], "Body":"Ray Gateway", "BodyID":34, "BodyType":"Station" }"#).unwrap());
+        let snapshot = &trade.markets[&3223343616];
+        assert_eq!(snapshot.station_name, "Ray Gateway");
+        assert_eq!(snapshot.position, Some([-41.0625, -62.15625, -103.25]));
+    }
+
+    #[test]
+    fn calculates_in_background() {
+        let mut trade = trade_state();
+        trade.markets.insert(1, market(1, 0.0, 1000, 1000));
+        trade.database_markets = Arc::new(vec![market(2, 10.0, 3000, 3000)]);
+        trade.calculate();
+        assert!(trade.is_calculating());
+        for _ in 0..100 {
+            trade.poll();
+            if !trade.is_calculating() {
+                break;
+            }
+            sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!trade.is_calculating());
+        assert_eq!(trade.single_hop_routes.len(), 1);
+        assert_eq!(trade.single_hop_routes[0].get_profit(), 200_000);
+    }
+}
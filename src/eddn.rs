@@ -1,45 +1,69 @@
-use crate::edcas::settings::EvmSettings;
 use bus::Bus;
 use json::JsonValue;
-use log::LevelFilter;
-use std::{env, thread};
+use log::{error, info, LevelFilter};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 
-use crate::eddn::adapter::EddnAdapter;
+use crate::eddn::adapter::{AdapterStatistics, EddnAdapter};
+use crate::eddn::config::{AdapterConfig, SinkKind};
+use crate::eddn::sink::{DryRunSink, Sink};
 
 mod adapter;
+mod config;
+mod envelope;
+mod sink;
 
-pub fn initialize() {
+pub fn initialize(args: &[String]) {
     println!("Initializing eddn adapter...");
     env_logger::Builder::new()
         .filter_level(LevelFilter::Info)
         .init();
 
+    let config = match AdapterConfig::load(args) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid eddn adapter configuration:\n{}", err);
+            exit(1);
+        }
+    };
+
     let mut bus_writer: Bus<JsonValue> = Bus::new(1000);
     let bus_reader = bus_writer.add_rx();
+    let shutdown = Arc::new(AtomicBool::new(false));
 
-    let eddn = EddnAdapter { bus_writer };
-    let evm_settings = EvmSettings {
-        url: env::var("EVM_URL").unwrap(),
-        n_timeout: env::var("DURATION_TIMEOUT")
-            .unwrap_or("5".into())
-            .parse()
-            .unwrap_or(5),
-        n_attempts: env::var("RETRY_TIMEOUT")
-            .unwrap_or("100".into())
-            .parse()
-            .unwrap_or(100),
-        allow_share_data: true,
-        private_key: env::var("PRIVATE_KEY").unwrap(),
-        smart_contract_address: env::var("SC_ADDRESS").unwrap(),
-        contract: None,
-        show_upload_data_window: false,
-        journal_read_status: None,
+    let eddn = EddnAdapter {
+        bus_writer,
+        url: config.zeromq_url.clone(),
+        statistics: Arc::new(AdapterStatistics::default()),
+        shutdown: shutdown.clone(),
     };
-    let mut evm_interpreter =
-        crate::edcas::backend::evm::journal_interpreter::initialize(bus_reader, &evm_settings);
-    thread::Builder::new()
-        .name("edcas-evm-interpreter".into())
-        .spawn(move || evm_interpreter.run_loop())
+
+    let mut sink: Box<dyn Sink> = if config.dry_run {
+        info!("Dry run, nothing will be stored");
+        Box::new(DryRunSink { bus: bus_reader })
+    } else {
+        match config.sink {
+            SinkKind::Evm => Box::new(crate::edcas::backend::evm::journal_interpreter::initialize(
+                bus_reader,
+                &config.get_evm_settings(),
+                "eddn-transactions.log",
+            )),
+            SinkKind::Database => {
+                match sink::initialize_database_sink(bus_reader, &config.database_path) {
+                    Ok(sink) => Box::new(sink),
+                    Err(err) => {
+                        error!("{}", err);
+                        exit(1);
+                    }
+                }
+            }
+        }
+    };
+    let sink = thread::Builder::new()
+        .name("edcas-eddn-sink".into())
+        .spawn(move || sink.run_loop())
         .expect("Can't spawn eddn thread");
     println!("Ready!");
     tokio::runtime::Builder::new_multi_thread()
@@ -47,6 +71,42 @@ pub fn initialize() {
         .build()
         .unwrap()
         .block_on(async move {
+            tokio::spawn(wait_for_shutdown_signal(shutdown));
             eddn.subscribe_to_eddn().await;
         });
+
+    //The adapter dropped the bus, the sink stops after handling what is left
+    info!("Waiting for pending messages and transactions");
+    if sink.join().is_err() {
+        error!("Sink thread panicked");
+    }
+    info!("Shut down");
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown_signal(shutdown: Arc<AtomicBool>) {
+    let mut terminate =
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(err) => {
+                error!("Can't listen for SIGTERM: {}", err);
+                return;
+            }
+        };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down"),
+        _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+    }
+    shutdown.store(true, Ordering::Relaxed);
+}
+
+/// There is no SIGTERM outside of unix
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal(shutdown: Arc<AtomicBool>) {
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        error!("Can't listen for ctrl-c: {}", err);
+        return;
+    }
+    info!("Received ctrl-c, shutting down");
+    shutdown.store(true, Ordering::Relaxed);
 }
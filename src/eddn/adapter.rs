@@ -1,54 +1,213 @@
 use bus::Bus;
 use std::io;
 use std::io::Read;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use flate2::read::ZlibDecoder;
 use json::JsonValue;
-use log::error;
+use log::{debug, error, info, warn};
+
+use crate::edcas::backend::eddn_publisher::EddnSchema;
+use crate::eddn::envelope;
+use crate::eddn::envelope::Envelope;
+
+/// EDDN sends a message every few seconds, so a silent socket means a broken connection
+const SILENCE_TIMEOUT_SECONDS: u64 = 60;
+/// How often the shutdown flag is checked while waiting for messages
+const RECEIVE_TIMEOUT_MILLIS: i32 = 1000;
+const MAX_BACKOFF_SECONDS: u64 = 60;
+const STATISTICS_INTERVAL: u64 = 1000;
+
+#[derive(Default)]
+pub struct AdapterStatistics {
+    pub received: AtomicU64,
+    /// Not decodable, not json or missing fields the handlers need
+    pub invalid: AtomicU64,
+    /// Lost because the bus was full
+    pub dropped: AtomicU64,
+    /// Valid, but of a schema without handler
+    pub ignored: AtomicU64,
+}
+
+impl AdapterStatistics {
+    fn log(&self) {
+        info!(
+            "EDDN messages received: {} invalid: {} dropped: {} ignored: {}",
+            self.received.load(Ordering::Relaxed),
+            self.invalid.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+            self.ignored.load(Ordering::Relaxed)
+        );
+    }
+}
 
 pub struct EddnAdapter {
     pub bus_writer: Bus<JsonValue>,
+    /// ZeroMQ relay to subscribe to, e.g. tcp://eddn.edcd.io:9500
+    pub url: String,
+    pub statistics: Arc<AdapterStatistics>,
+    /// Set on SIGINT/SIGTERM, stops receiving
+    pub shutdown: Arc<AtomicBool>,
 }
 
 impl EddnAdapter {
+    /// Receives messages until the publisher sends `END` or a shutdown is requested.
+    /// Connection errors are retried with backoff. Dropping the adapter closes the bus.
     pub async fn subscribe_to_eddn(mut self) {
         let context = zmq::Context::new();
-        let subscriber = context.socket(zmq::SUB).unwrap();
-        subscriber
-            .connect(std::env::var("ZEROMQ_URL").unwrap().as_str())
-            .expect("Failed to connect to ZeroMQ Server");
-        subscriber
-            .set_subscribe(b"")
-            .expect("Failed to subscribe to channel");
+        let mut backoff = 1;
 
-        tokio::time::sleep(Duration::from_millis(1)).await;
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let subscriber = match self.connect(&context) {
+                Ok(subscriber) => subscriber,
+                Err(err) => {
+                    error!("Failed to connect to {}: {}", self.url, err);
+                    tokio::time::sleep(Duration::from_secs(backoff)).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF_SECONDS);
+                    continue;
+                }
+            };
+            info!("Subscribed to {}", self.url);
 
-        loop {
-            let data = subscriber.recv_bytes(0).expect("Failed receiving update");
+            let mut last_message = Instant::now();
+            loop {
+                if self.shutdown.load(Ordering::Relaxed) {
+                    self.statistics.log();
+                    return;
+                }
+                let data = match subscriber.recv_bytes(0) {
+                    Ok(data) => data,
+                    Err(zmq::Error::EAGAIN) => {
+                        if last_message.elapsed().as_secs() < SILENCE_TIMEOUT_SECONDS {
+                            continue;
+                        }
+                        warn!(
+                            "No message from {} for {} seconds, reconnecting",
+                            self.url, SILENCE_TIMEOUT_SECONDS
+                        );
+                        break;
+                    }
+                    Err(err) => {
+                        error!("Failed receiving update: {}", err);
+                        break;
+                    }
+                };
+                backoff = 1;
+                last_message = Instant::now();
 
-            let message = decode_reader(data).unwrap();
+                let message = match decode_reader(data) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        self.statistics.received.fetch_add(1, Ordering::Relaxed);
+                        self.statistics.invalid.fetch_add(1, Ordering::Relaxed);
+                        debug!("Failed decoding message: {}", err);
+                        continue;
+                    }
+                };
 
-            if message == "END" {
-                break;
+                if message == "END" {
+                    self.statistics.log();
+                    return;
+                }
+                self.handle_message(&message);
             }
 
-            match json::parse(message.as_str()) {
-                Ok(json) => {
-                    let result = self.bus_writer.try_broadcast(json["message"].clone());
-                    match result {
-                        Ok(_) => {}
-                        Err(_) => {
-                            error!("Channel is full");
-                        }
-                    }
-                }
-                Err(error) => {
-                    error!("Error parsing json: {}", error);
-                }
+            tokio::time::sleep(Duration::from_secs(backoff)).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF_SECONDS);
+        }
+        self.statistics.log();
+    }
+
+    fn connect(&self, context: &zmq::Context) -> Result<zmq::Socket, zmq::Error> {
+        let subscriber = context.socket(zmq::SUB)?;
+        subscriber.set_rcvtimeo(RECEIVE_TIMEOUT_MILLIS)?;
+        subscriber.connect(self.url.as_str())?;
+        subscriber.set_subscribe(b"")?;
+        Ok(subscriber)
+    }
+
+    fn handle_message(&mut self, message: &str) {
+        let received = self.statistics.received.fetch_add(1, Ordering::Relaxed) + 1;
+        if received % STATISTICS_INTERVAL == 0 {
+            self.statistics.log();
+        }
+
+        match envelope::parse(message) {
+            Ok(Some(envelope)) => {
+                debug!(
+                    "{} from {} {} ({}) at {}",
+                    envelope.schema,
+                    envelope.header.software_name,
+                    envelope.header.software_version,
+                    envelope.header.uploader_id,
+                    envelope.header.gateway_timestamp
+                );
+                self.route(envelope);
+            }
+            Ok(None) => {
+                self.statistics.ignored.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                self.statistics.invalid.fetch_add(1, Ordering::Relaxed);
+                debug!("Invalid message: {}", err);
             }
         }
     }
+
+    fn route(&mut self, envelope: Envelope) {
+        match envelope.schema {
+            EddnSchema::Journal | EddnSchema::FssDiscoveryScan => {
+                self.handle_journal(envelope.message)
+            }
+            EddnSchema::FssSignalDiscovered => self.handle_signals(envelope.message),
+            EddnSchema::ScanBaryCentre => {
+                let mut message = envelope.message;
+                message["event"] = "ScanBaryCentre".into();
+                self.handle_journal(message);
+            }
+            EddnSchema::Commodity => self.handle_commodity(envelope.message),
+            EddnSchema::Outfitting
+            | EddnSchema::Shipyard
+            | EddnSchema::FssAllBodiesFound
+            | EddnSchema::NavRoute => {
+                self.statistics.ignored.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Journal schemas carry the journal event unchanged, except for the removed personal data
+    fn handle_journal(&mut self, message: JsonValue) {
+        self.broadcast(message);
+    }
+
+    /// The interpreter registers a commodity listening per market for messages without event
+    fn handle_commodity(&mut self, message: JsonValue) {
+        if message["commodities"].is_empty() {
+            self.statistics.ignored.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.broadcast(message);
+    }
+
+    /// Signals of a system arrive batched, the interpreter expects one event per signal
+    fn handle_signals(&mut self, message: JsonValue) {
+        for signal in message["signals"].members() {
+            let mut event = signal.clone();
+            event["event"] = "FSSSignalDiscovered".into();
+            event["SystemAddress"] = message["SystemAddress"].clone();
+            event["StarSystem"] = message["StarSystem"].clone();
+            self.broadcast(event);
+        }
+    }
+
+    fn broadcast(&mut self, json: JsonValue) {
+        if self.bus_writer.try_broadcast(json).is_err() {
+            self.statistics.dropped.fetch_add(1, Ordering::Relaxed);
+            error!("Channel is full");
+        }
+    }
 }
 
 // Uncompresses a Zlib Encoded vector of bytes and returns a string or error
@@ -59,3 +218,122 @@ fn decode_reader(bytes: Vec<u8>) -> io::Result<String> {
     z.read_to_string(&mut s)?;
     Ok(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use json::object;
+    use std::io::Write;
+    use std::thread;
+
+    fn compress(message: &str) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(message.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn envelope(schema_ref: &str, message: JsonValue) -> String {
+        object! {
+            "$schemaRef": schema_ref,
+            header: {
+                uploaderID: "Jameson",
+                softwareName: "E:D Market Connector",
+                softwareVersion: "5.11.0",
+                gatewayTimestamp: "2026-10-19T10:00:00.000000Z",
+            },
+            message: message,
+        }
+        .dump()
+    }
+
+    #[test]
+    fn routes_published_messages() {
+        let context = zmq::Context::new();
+        let publisher = context.socket(zmq::PUB).unwrap();
+        publisher.bind("tcp://127.0.0.1:*").unwrap();
+        let url = publisher.get_last_endpoint().unwrap().unwrap();
+
+        let mut bus_writer = Bus::new(100);
+        let mut reader = bus_writer.add_rx();
+        let statistics = Arc::new(AdapterStatistics::default());
+        let adapter = EddnAdapter {
+            bus_writer,
+            url,
+            statistics: statistics.clone(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        };
+        let subscriber = thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(adapter.subscribe_to_eddn())
+        });
+
+        let jump = envelope(
+            "https://eddn.edcd.io/schemas/journal/1",
+            object! {
+                timestamp: "2026-10-19T10:00:00Z",
+                event: "FSDJump",
+                StarSystem: "Sol",
+                SystemAddress: 10477373803u64,
+                StarPos: [0.0, 0.0, 0.0],
+            },
+        );
+        //Messages sent before the subscription is established are lost
+        let first = loop {
+            publisher.send(compress(&jump), 0).unwrap();
+            if let Ok(event) = reader.recv_timeout(Duration::from_millis(100)) {
+                break event;
+            }
+        };
+        assert_eq!(first["event"], "FSDJump");
+        assert_eq!(first["StarSystem"], "Sol");
+
+        publisher.send(b"not compressed".to_vec(), 0).unwrap();
+        publisher
+            .send(
+                compress(&envelope(
+                    "https://eddn.edcd.io/schemas/journal/1/test",
+                    object! { timestamp: "2026-10-19T10:00:00Z" },
+                )),
+                0,
+            )
+            .unwrap();
+        publisher
+            .send(
+                compress(&envelope(
+                    "https://eddn.edcd.io/schemas/fsssignaldiscovered/1",
+                    object! {
+                        timestamp: "2026-10-19T10:00:00Z",
+                        StarSystem: "Sol",
+                        SystemAddress: 10477373803u64,
+                        StarPos: [0.0, 0.0, 0.0],
+                        signals: [
+                            { timestamp: "2026-10-19T10:00:00Z", SignalName: "Abraham Lincoln" },
+                            { timestamp: "2026-10-19T10:00:00Z", SignalName: "Li Qing Jao" },
+                        ],
+                    },
+                )),
+                0,
+            )
+            .unwrap();
+        publisher.send(compress("END"), 0).unwrap();
+        subscriber.join().unwrap();
+
+        //The bus is closed once the adapter stopped
+        let mut signals = vec![];
+        while let Ok(event) = reader.recv_timeout(Duration::from_secs(1)) {
+            if event["event"] == "FSSSignalDiscovered" {
+                signals.push(event);
+            }
+        }
+        assert_eq!(signals.len(), 2);
+        assert_eq!(signals[0]["SignalName"], "Abraham Lincoln");
+        assert_eq!(signals[1]["SystemAddress"], 10477373803u64);
+        assert_eq!(signals[1]["StarSystem"], "Sol");
+        assert_eq!(statistics.invalid.load(Ordering::Relaxed), 1);
+        assert_eq!(statistics.ignored.load(Ordering::Relaxed), 1);
+        assert_eq!(statistics.dropped.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,316 @@
+use std::env;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use ethers::prelude::{Address, LocalWallet};
+use json::JsonValue;
+
+use crate::cli::get_arg;
+use crate::edcas::backend::evm::privacy::DataCategory;
+use crate::edcas::backend::evm::transaction_store::TransactionQueueStatus;
+use crate::edcas::settings::EvmSettings;
+
+const DEFAULT_ZEROMQ_URL: &str = "tcp://eddn.edcd.io:9500";
+
+#[derive(PartialEq)]
+pub enum SinkKind {
+    Evm,
+    Database,
+}
+
+impl FromStr for SinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "evm" => Ok(SinkKind::Evm),
+            "database" => Ok(SinkKind::Database),
+            _ => Err(format!("Unknown sink: {}", s)),
+        }
+    }
+}
+
+/// Settings of the `--eddn` mode. Values are taken from the config file, then the environment,
+/// then the command line, each overriding the previous one.
+pub struct AdapterConfig {
+    pub zeromq_url: String,
+    pub evm_url: String,
+    pub private_key: String,
+    pub smart_contract_address: String,
+    pub timeout: u64,
+    pub attempts: u64,
+    pub sink: SinkKind,
+    pub database_path: String,
+    /// Log what would be sent instead of sending it
+    pub dry_run: bool,
+}
+
+impl Default for AdapterConfig {
+    fn default() -> Self {
+        AdapterConfig {
+            zeromq_url: DEFAULT_ZEROMQ_URL.to_string(),
+            evm_url: "".to_string(),
+            private_key: "".to_string(),
+            smart_contract_address: "".to_string(),
+            timeout: 5,
+            attempts: 100,
+            sink: SinkKind::Evm,
+            database_path: "".to_string(),
+            dry_run: false,
+        }
+    }
+}
+
+impl AdapterConfig {
+    pub fn load(args: &[String]) -> Result<AdapterConfig, String> {
+        let mut config = AdapterConfig::default();
+
+        if let Some(path) = get_arg(args, "--config")? {
+            let content =
+                fs::read_to_string(&path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+            let json =
+                json::parse(&content).map_err(|err| format!("Can't parse {}: {}", path, err))?;
+            config.apply_file(&json)?;
+        }
+        config.apply_env();
+        config.apply_args(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, json: &JsonValue) -> Result<(), String> {
+        if let Some(url) = json["zeromq-url"].as_str() {
+            self.zeromq_url = url.to_string();
+        }
+        if let Some(url) = json["evm-url"].as_str() {
+            self.evm_url = url.to_string();
+        }
+        if let Some(private_key) = json["private-key"].as_str() {
+            self.private_key = private_key.to_string();
+        }
+        if let Some(address) = json["smart-contract-address"].as_str() {
+            self.smart_contract_address = address.to_string();
+        }
+        self.timeout = json["timeout"].as_u64().unwrap_or(self.timeout);
+        self.attempts = json["attempts"].as_u64().unwrap_or(self.attempts);
+        if let Some(sink) = json["sink"].as_str() {
+            self.sink = SinkKind::from_str(sink)?;
+        }
+        if let Some(path) = json["database-path"].as_str() {
+            self.database_path = path.to_string();
+        }
+        self.dry_run = json["dry-run"].as_bool().unwrap_or(self.dry_run);
+        Ok(())
+    }
+
+    fn apply_env(&mut self) {
+        if let Ok(url) = env::var("ZEROMQ_URL") {
+            self.zeromq_url = url;
+        }
+        if let Ok(url) = env::var("EVM_URL") {
+            self.evm_url = url;
+        }
+        if let Ok(private_key) = env::var("PRIVATE_KEY") {
+            self.private_key = private_key;
+        }
+        if let Ok(address) = env::var("SC_ADDRESS") {
+            self.smart_contract_address = address;
+        }
+        if let Some(timeout) = env::var("DURATION_TIMEOUT")
+            .ok()
+            .and_then(|t| t.parse().ok())
+        {
+            self.timeout = timeout;
+        }
+        if let Some(attempts) = env::var("RETRY_TIMEOUT").ok().and_then(|a| a.parse().ok()) {
+            self.attempts = attempts;
+        }
+    }
+
+    /// The private key is deliberately not accepted as argument, it would show up in the process list
+    fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        if let Some(url) = get_arg(args, "--zeromq-url")? {
+            self.zeromq_url = url;
+        }
+        if let Some(url) = get_arg(args, "--evm-url")? {
+            self.evm_url = url;
+        }
+        if let Some(address) = get_arg(args, "--sc-address")? {
+            self.smart_contract_address = address;
+        }
+        if let Some(timeout) = get_arg(args, "--timeout")? {
+            self.timeout = timeout
+                .parse()
+                .map_err(|_| format!("Wrong argument for --timeout: {}", timeout))?;
+        }
+        if let Some(attempts) = get_arg(args, "--attempts")? {
+            self.attempts = attempts
+                .parse()
+                .map_err(|_| format!("Wrong argument for --attempts: {}", attempts))?;
+        }
+        if let Some(sink) = get_arg(args, "--sink")? {
+            self.sink = SinkKind::from_str(&sink)?;
+        }
+        if let Some(path) = get_arg(args, "--database")? {
+            self.database_path = path;
+        }
+        if args.iter().any(|arg| arg == "--dry-run") {
+            self.dry_run = true;
+        }
+        Ok(())
+    }
+
+    /// Collects all problems, so they can be fixed in one go
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+        if !["tcp://", "ipc://", "inproc://"]
+            .iter()
+            .any(|scheme| self.zeromq_url.starts_with(scheme))
+        {
+            errors.push(format!("Invalid ZeroMQ url: {}", self.zeromq_url));
+        }
+        if self.sink == SinkKind::Database && self.database_path.is_empty() {
+            errors.push("Missing database path for the database sink".to_string());
+        }
+        //Without a chain the rest is not needed
+        if self.sink == SinkKind::Evm && !self.dry_run {
+            match reqwest::Url::parse(&self.evm_url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => errors.push(format!("Invalid EVM url: '{}'", self.evm_url)),
+            }
+            if self.private_key.is_empty() {
+                errors.push("Missing private key".to_string());
+            } else if LocalWallet::from_str(&self.private_key).is_err() {
+                errors.push("Invalid private key".to_string());
+            }
+            if Address::from_str(&self.smart_contract_address).is_err() {
+                errors.push(format!(
+                    "Invalid smart contract address: '{}'",
+                    self.smart_contract_address
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    pub fn get_evm_settings(&self) -> EvmSettings {
+        EvmSettings {
+            url: self.evm_url.clone(),
+            n_timeout: self.timeout,
+            n_attempts: self.attempts,
+            allow_share_data: true,
+            shared_categories: DataCategory::ALL.to_vec(),
+            publish_delay: 0,
+            private_key: self.private_key.clone(),
+            keystore_path: "".to_string(),
+            smart_contract_address: self.smart_contract_address.clone(),
+            show_upload_data_window: false,
+            journal_read_status: None,
+            queue_status: Arc::new(Mutex::new(TransactionQueueStatus::default())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_override_the_environment_and_the_file() {
+        let path =
+            env::temp_dir().join(format!("edcas-adapter-config-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"zeromq-url":"tcp://file:9500","evm-url":"http://file:8545","timeout":7,"attempts":9,"sink":"evm","database-path":"file.db"}"#,
+        )
+        .unwrap();
+        env::set_var("ZEROMQ_URL", "tcp://env:9500");
+        env::set_var("RETRY_TIMEOUT", "11");
+
+        let args = to_args(&[
+            "--config",
+            &path.display().to_string(),
+            "--attempts",
+            "13",
+            "--sink",
+            "database",
+            "--dry-run",
+        ]);
+        let config = AdapterConfig::load(&args);
+        env::remove_var("ZEROMQ_URL");
+        env::remove_var("RETRY_TIMEOUT");
+        let _ = fs::remove_file(path);
+
+        let config = config.unwrap();
+        assert_eq!(config.zeromq_url, "tcp://env:9500");
+        assert_eq!(config.evm_url, "http://file:8545");
+        assert_eq!(config.timeout, 7);
+        assert_eq!(config.attempts, 13);
+        assert!(config.sink == SinkKind::Database);
+        assert_eq!(config.database_path, "file.db");
+        assert!(config.dry_run);
+    }
+
+    #[test]
+    fn wrong_arguments_are_errors() {
+        let mut config = AdapterConfig::default();
+        assert_eq!(
+            config.apply_args(&to_args(&["--timeout"])),
+            Err("Missing argument for --timeout".to_string())
+        );
+        assert!(config.apply_args(&to_args(&["--timeout", "soon"])).is_err());
+        assert!(config.apply_args(&to_args(&["--sink", "file"])).is_err());
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let config = AdapterConfig::default();
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.lines().collect::<Vec<&str>>(),
+            vec![
+                "Invalid EVM url: ''",
+                "Missing private key",
+                "Invalid smart contract address: ''"
+            ]
+        );
+
+        //Neither a database nor a dry run needs the chain
+        let config = AdapterConfig {
+            zeromq_url: "http://eddn.edcd.io:9500".to_string(),
+            sink: SinkKind::Database,
+            ..AdapterConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.lines().collect::<Vec<&str>>(),
+            vec![
+                "Invalid ZeroMQ url: http://eddn.edcd.io:9500",
+                "Missing database path for the database sink"
+            ]
+        );
+        let config = AdapterConfig {
+            dry_run: true,
+            ..AdapterConfig::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = AdapterConfig {
+            evm_url: "http://localhost:8545".to_string(),
+            private_key: "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+                .to_string(),
+            smart_contract_address: "0x5FbDB2315678afecb367f032d93F642f64180aa3".to_string(),
+            ..AdapterConfig::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+}
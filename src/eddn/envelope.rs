@@ -0,0 +1,139 @@
+use json::JsonValue;
+
+use crate::edcas::backend::eddn_publisher::EddnSchema;
+
+pub struct Header {
+    pub uploader_id: String,
+    pub software_name: String,
+    pub software_version: String,
+    pub gateway_timestamp: String,
+}
+
+/// A message as relayed by EDDN, with the schema it was validated against by the gateway
+pub struct Envelope {
+    pub schema: EddnSchema,
+    pub header: Header,
+    pub message: JsonValue,
+}
+
+/// Parses and validates a relayed message.
+/// Returns `Ok(None)` for schemas that are not handled, including the `/test` schemas.
+pub fn parse(content: &str) -> Result<Option<Envelope>, String> {
+    let json = json::parse(content).map_err(|err| format!("Error parsing json: {}", err))?;
+
+    let schema_ref = json["$schemaRef"]
+        .as_str()
+        .ok_or("Missing $schemaRef".to_string())?;
+    let schema = match EddnSchema::ALL
+        .into_iter()
+        .find(|schema| schema.get_schema_ref() == schema_ref)
+    {
+        Some(schema) => schema,
+        None => return Ok(None),
+    };
+
+    let header = &json["header"];
+    let header = Header {
+        uploader_id: get_string(header, "uploaderID")?,
+        software_name: get_string(header, "softwareName")?,
+        software_version: get_string(header, "softwareVersion")?,
+        gateway_timestamp: header["gatewayTimestamp"]
+            .as_str()
+            .unwrap_or("")
+            .to_string(),
+    };
+
+    let message = json["message"].clone();
+    if !message.is_object() {
+        return Err(format!("{}: message is not an object", schema));
+    }
+    validate(schema, &message).map_err(|err| format!("{}: {}", schema, err))?;
+
+    Ok(Some(Envelope {
+        schema,
+        header,
+        message,
+    }))
+}
+
+/// Checks the fields the handlers rely on. The full schema is already enforced by the EDDN gateway.
+fn validate(schema: EddnSchema, message: &JsonValue) -> Result<(), String> {
+    get_string(message, "timestamp")?;
+    match schema {
+        EddnSchema::Journal => {
+            get_string(message, "event")?;
+            get_string(message, "StarSystem")?;
+            get_u64(message, "SystemAddress")?;
+            get_star_pos(message)?;
+        }
+        EddnSchema::Commodity => {
+            get_string(message, "systemName")?;
+            get_string(message, "stationName")?;
+            get_u64(message, "marketId")?;
+            get_array(message, "commodities")?;
+        }
+        EddnSchema::Outfitting => {
+            get_string(message, "systemName")?;
+            get_u64(message, "marketId")?;
+            get_array(message, "modules")?;
+        }
+        EddnSchema::Shipyard => {
+            get_string(message, "systemName")?;
+            get_u64(message, "marketId")?;
+            get_array(message, "ships")?;
+        }
+        EddnSchema::FssDiscoveryScan => {
+            get_string(message, "SystemName")?;
+            get_u64(message, "SystemAddress")?;
+            get_u64(message, "BodyCount")?;
+            get_star_pos(message)?;
+        }
+        EddnSchema::FssSignalDiscovered => {
+            get_u64(message, "SystemAddress")?;
+            get_array(message, "signals")?;
+            get_star_pos(message)?;
+        }
+        EddnSchema::FssAllBodiesFound => {
+            get_string(message, "SystemName")?;
+            get_u64(message, "SystemAddress")?;
+            get_u64(message, "Count")?;
+        }
+        EddnSchema::ScanBaryCentre => {
+            get_string(message, "StarSystem")?;
+            get_u64(message, "SystemAddress")?;
+            get_u64(message, "BodyID")?;
+        }
+        EddnSchema::NavRoute => {
+            get_array(message, "Route")?;
+        }
+    }
+    Ok(())
+}
+
+fn get_string(json: &JsonValue, key: &str) -> Result<String, String> {
+    json[key]
+        .as_str()
+        .map(|value| value.to_string())
+        .ok_or(format!("Missing {}", key))
+}
+
+fn get_u64(json: &JsonValue, key: &str) -> Result<u64, String> {
+    json[key].as_u64().ok_or(format!("Missing {}", key))
+}
+
+fn get_array<'a>(json: &'a JsonValue, key: &str) -> Result<&'a JsonValue, String> {
+    if json[key].is_array() {
+        Ok(&json[key])
+    } else {
+        Err(format!("Missing {}", key))
+    }
+}
+
+fn get_star_pos(json: &JsonValue) -> Result<(), String> {
+    let star_pos = get_array(json, "StarPos")?;
+    if star_pos.len() == 3 && star_pos.members().all(|value| value.as_f64().is_some()) {
+        Ok(())
+    } else {
+        Err("Invalid StarPos".to_string())
+    }
+}
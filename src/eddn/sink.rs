@@ -0,0 +1,244 @@
+use bus::BusReader;
+use chrono::DateTime;
+use json::JsonValue;
+use log::{debug, error, info};
+use rusqlite::{params, Connection};
+
+use crate::edcas::backend::database;
+use crate::edcas::backend::evm::journal_interpreter::EvmInterpreter;
+
+/// Destination of the messages the adapter received. A sink consumes the bus until the adapter
+/// drops it and returns once everything is stored.
+pub trait Sink: Send {
+    fn run_loop(&mut self);
+}
+
+impl Sink for EvmInterpreter {
+    fn run_loop(&mut self) {
+        EvmInterpreter::run_loop(self);
+    }
+}
+
+/// Logs what would be sent, for `--dry-run`
+pub struct DryRunSink {
+    pub bus: BusReader<JsonValue>,
+}
+
+impl Sink for DryRunSink {
+    fn run_loop(&mut self) {
+        while let Ok(json) = self.bus.recv() {
+            let event = json["event"].as_str().unwrap_or("commodity");
+            info!("Would send {}: {}", event, json.dump());
+        }
+    }
+}
+
+/// Stores systems, bodies, stations, carriers and markets in a local database,
+/// which the desktop client can use instead of the EVM
+pub struct DatabaseSink {
+    bus: BusReader<JsonValue>,
+    connection: Connection,
+}
+
+pub fn initialize_database_sink(
+    bus: BusReader<JsonValue>,
+    path: &str,
+) -> Result<DatabaseSink, String> {
+    Ok(DatabaseSink {
+        bus,
+        connection: database::open(path)?,
+    })
+}
+
+impl Sink for DatabaseSink {
+    fn run_loop(&mut self) {
+        while let Ok(json) = self.bus.recv() {
+            let result = match json["event"].as_str().unwrap_or("") {
+                "FSDJump" | "Location" | "CarrierJump" => self
+                    .store_system(&json)
+                    .and_then(|_| self.store_dockable(&json)),
+                "Docked" => self.store_dockable(&json),
+                "FSSDiscoveryScan" => self.store_body_count(&json),
+                "Scan" => self.store_body(&json),
+                "" if json["commodities"].is_array() => self.store_market(&json),
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                error!("Error writing to database: {}", err);
+            }
+        }
+    }
+}
+
+impl DatabaseSink {
+    fn store_system(&self, json: &JsonValue) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO systems (address, name, x, y, z, allegiance, economy, second_economy, \
+             government, security, population, timestamp) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12) \
+             ON CONFLICT(address) DO UPDATE SET name = excluded.name, \
+             allegiance = excluded.allegiance, economy = excluded.economy, \
+             second_economy = excluded.second_economy, government = excluded.government, \
+             security = excluded.security, population = excluded.population, \
+             timestamp = excluded.timestamp WHERE excluded.timestamp >= systems.timestamp",
+            params![
+                json["SystemAddress"].as_u64().unwrap_or(0),
+                json["StarSystem"].to_string(),
+                json["StarPos"][0].as_f64().unwrap_or(0.0),
+                json["StarPos"][1].as_f64().unwrap_or(0.0),
+                json["StarPos"][2].as_f64().unwrap_or(0.0),
+                json["SystemAllegiance"].as_str().unwrap_or(""),
+                json["SystemEconomy"].as_str().unwrap_or(""),
+                json["SystemSecondEconomy"].as_str().unwrap_or(""),
+                json["SystemGovernment"].as_str().unwrap_or(""),
+                json["SystemSecurity"].as_str().unwrap_or(""),
+                json["Population"].as_u64().unwrap_or(0),
+                get_timestamp(json),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn store_body_count(&self, json: &JsonValue) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "UPDATE systems SET body_count = ?2 WHERE address = ?1",
+            params![
+                json["SystemAddress"].as_u64().unwrap_or(0),
+                json["BodyCount"].as_u64().unwrap_or(0),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn store_body(&self, json: &JsonValue) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO bodies (system_address, body_id, name, scan, timestamp) \
+             VALUES (?1, ?2, ?3, ?4, ?5) \
+             ON CONFLICT(system_address, body_id) DO UPDATE SET name = excluded.name, \
+             scan = excluded.scan, timestamp = excluded.timestamp \
+             WHERE excluded.timestamp >= bodies.timestamp",
+            params![
+                json["SystemAddress"].as_u64().unwrap_or(0),
+                json["BodyID"].as_u64().unwrap_or(0),
+                json["BodyName"].to_string(),
+                json.dump(),
+                get_timestamp(json),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Docked and jump events of stations and carriers. Jumps without a station are skipped.
+    fn store_dockable(&self, json: &JsonValue) -> rusqlite::Result<()> {
+        let market_id = match json["MarketID"].as_u64() {
+            Some(market_id) => market_id,
+            None => return Ok(()),
+        };
+        let services = json["StationServices"]
+            .members()
+            .filter_map(|service| service.as_str())
+            .collect::<Vec<&str>>()
+            .join(",");
+
+        if json["StationType"].as_str() == Some("FleetCarrier") {
+            debug!("Storing carrier {}", json["StationName"]);
+            self.connection.execute(
+                "INSERT INTO carriers (market_id, name, callsign, services, docking_access, \
+                 allow_notorious, current_system, current_body, next_system, next_body, departure, \
+                 timestamp) VALUES (?1, 'Fleet Carrier', ?2, ?3, '', 0, ?4, ?5, '', '', 0, ?6) \
+                 ON CONFLICT(market_id) DO UPDATE SET callsign = excluded.callsign, \
+                 services = CASE WHEN excluded.services = '' THEN carriers.services ELSE excluded.services END, \
+                 current_system = excluded.current_system, current_body = excluded.current_body, \
+                 timestamp = excluded.timestamp WHERE excluded.timestamp >= carriers.timestamp",
+                params![
+                    market_id,
+                    json["StationName"].to_string(),
+                    services,
+                    json["StarSystem"].to_string(),
+                    json["Body"].as_str().unwrap_or(""),
+                    get_timestamp(json),
+                ],
+            )?;
+        } else if json["event"].as_str() == Some("Docked") {
+            debug!("Storing station {}", json["StationName"]);
+            self.connection.execute(
+                "INSERT INTO stations (market_id, name, type, system_address, system_name, \
+                 faction_name, faction_state, government, economy, services, distance, \
+                 landing_pads, timestamp) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13) \
+                 ON CONFLICT(market_id) DO UPDATE SET name = excluded.name, type = excluded.type, \
+                 system_address = excluded.system_address, system_name = excluded.system_name, \
+                 faction_name = excluded.faction_name, faction_state = excluded.faction_state, \
+                 government = excluded.government, economy = excluded.economy, \
+                 services = excluded.services, distance = excluded.distance, \
+                 landing_pads = excluded.landing_pads, timestamp = excluded.timestamp \
+                 WHERE excluded.timestamp >= stations.timestamp",
+                params![
+                    market_id,
+                    json["StationName"].to_string(),
+                    json["StationType"].to_string(),
+                    json["SystemAddress"].as_u64().unwrap_or(0),
+                    json["StarSystem"].to_string(),
+                    json["StationFaction"]["Name"].as_str().unwrap_or(""),
+                    json["StationFaction"]["FactionState"]
+                        .as_str()
+                        .unwrap_or(""),
+                    json["StationGovernment"].as_str().unwrap_or(""),
+                    json["StationEconomy"].as_str().unwrap_or(""),
+                    services,
+                    json["DistFromStarLS"].to_string(),
+                    json["LandingPads"].to_string(),
+                    get_timestamp(json),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A commodity message always lists the complete market, so the old listings are replaced
+    fn store_market(&mut self, json: &JsonValue) -> rusqlite::Result<()> {
+        let market_id = json["marketId"].as_u64().unwrap_or(0);
+        let timestamp = get_timestamp(json);
+        let transaction = self.connection.transaction()?;
+        let newer: bool = transaction.query_row(
+            "SELECT NOT EXISTS (SELECT 1 FROM commodities WHERE market_id = ?1 AND timestamp > ?2)",
+            params![market_id, timestamp],
+            |row| row.get(0),
+        )?;
+        if !newer {
+            return Ok(());
+        }
+        transaction.execute(
+            "DELETE FROM commodities WHERE market_id = ?1",
+            params![market_id],
+        )?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO commodities (market_id, name, buy_price, sell_price, mean_price, \
+                 demand, demand_bracket, stock, stock_bracket, timestamp) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for commodity in json["commodities"].members() {
+                statement.execute(params![
+                    market_id,
+                    commodity["name"].to_string().to_ascii_lowercase(),
+                    commodity["buyPrice"].as_u32().unwrap_or(0),
+                    commodity["sellPrice"].as_u32().unwrap_or(0),
+                    commodity["meanPrice"].as_u32().unwrap_or(0),
+                    commodity["demand"].as_u32().unwrap_or(0),
+                    commodity["demandBracket"].as_u32().unwrap_or(0),
+                    commodity["stock"].as_u32().unwrap_or(0),
+                    commodity["stockBracket"].as_u32().unwrap_or(0),
+                    timestamp,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+}
+
+fn get_timestamp(json: &JsonValue) -> i64 {
+    DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap_or(""))
+        .map(|timestamp| timestamp.timestamp())
+        .unwrap_or(0)
+}
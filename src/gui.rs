@@ -1,6 +1,7 @@
 use crate::edcas::EliteRustClient;
 use crate::edcas::State::{
-    About, CarrierPage, Explorer, MaterialInventory, Mining, News, Settings, StationPage,
+    About, CarrierPage, Explorer, FinancePage, GalaxyMap, MaterialInventory, Mining, News,
+    Settings, StationPage, TradePage,
 };
 use eframe::egui::{Context, TextStyle};
 use eframe::{egui, App};
@@ -9,11 +10,16 @@ mod about;
 mod body;
 mod carrier;
 mod explorer;
+mod finance;
+mod galaxy;
+mod keystore;
 mod materials;
 mod mining;
 mod news;
+mod session;
 mod settings;
 mod station;
+mod trade;
 
 impl App for EliteRustClient {
     fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
@@ -112,6 +118,10 @@ impl App for EliteRustClient {
                 if explorer_button.clicked() {
                     self.state = Explorer;
                 }
+                let galaxy_button = menu_bar.button("Galaxy");
+                if galaxy_button.clicked() {
+                    self.state = GalaxyMap;
+                }
                 let mining_button = menu_bar.button("Mining");
                 if mining_button.clicked() {
                     self.state = Mining;
@@ -128,6 +138,14 @@ impl App for EliteRustClient {
                 if carrier_button.clicked() {
                     self.state = CarrierPage;
                 }
+                let trade_button = menu_bar.button("Trade");
+                if trade_button.clicked() {
+                    self.state = TradePage;
+                }
+                let finance_button = menu_bar.button("Finance");
+                if finance_button.clicked() {
+                    self.state = FinancePage;
+                }
                 let settings_button = menu_bar.button("Settings");
                 if settings_button.clicked() {
                     self.state = Settings;
@@ -139,6 +157,9 @@ impl App for EliteRustClient {
 
                 menu_bar.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
                     ui.label(self.timestamp.as_str());
+                    if ui.button("Session report").clicked() {
+                        self.session.create_report(&self.finance);
+                    }
                 });
                 match self.state {
                     News => {
@@ -153,12 +174,21 @@ impl App for EliteRustClient {
                     CarrierPage => {
                         carrier_button.highlight();
                     }
+                    TradePage => {
+                        trade_button.highlight();
+                    }
+                    FinancePage => {
+                        finance_button.highlight();
+                    }
                     Settings => {
                         settings_button.highlight();
                     }
                     Explorer => {
                         explorer_button.highlight();
                     }
+                    GalaxyMap => {
+                        galaxy_button.highlight();
+                    }
                     MaterialInventory => {
                         materials_button.highlight();
                     }
@@ -170,14 +200,19 @@ impl App for EliteRustClient {
         });
 
         self.update_values();
+        self.session.update(ctx, frame);
+        keystore::draw_unlock_window(self, ctx);
 
         egui::CentralPanel::default().show(ctx, |_ui| match self.state {
             News => self.news.update(ctx, frame),
             About => self.about.update(ctx, frame),
             StationPage => self.station.update(ctx, frame),
             CarrierPage => self.carrier.update(ctx, frame),
+            TradePage => self.trade.update(ctx, frame),
+            FinancePage => self.finance.update(ctx, frame),
             Settings => self.settings.lock().unwrap().update(ctx, frame),
             Explorer => self.explorer.update(ctx, frame),
+            GalaxyMap => self.explorer.draw_galaxy_map(ctx),
             MaterialInventory => self.materials.update(ctx, frame),
             Mining => self.mining.update(ctx, frame),
         });
@@ -185,4 +220,8 @@ impl App for EliteRustClient {
         //Low prio because performance is okay
         ctx.request_repaint();
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.finance.save();
+    }
 }
@@ -1,8 +1,10 @@
+use crate::edcas::explorer::barycentre::Barycentre;
 use crate::edcas::explorer::belt_cluster::BeltCluster;
 use crate::edcas::explorer::body::BodyType;
 use crate::edcas::explorer::planet::{get_body_class_from_body, get_profit_from_body, Planet};
 use crate::edcas::explorer::ring::Ring;
 use crate::edcas::explorer::star::Star;
+use crate::edcas::settings::Settings;
 use eframe::egui::Ui;
 use num_format::{Locale, ToFormattedString};
 
@@ -15,6 +17,9 @@ impl BodyType {
             BodyType::BeltCluster(cluster) => {
                 cluster.print_header_content(ui, system_index, body_index)
             }
+            BodyType::Barycentre(barycentre) => {
+                barycentre.print_header_content(ui, system_index, body_index)
+            }
         }
     }
     pub fn print_side_panel_information(&self, ui: &mut Ui) {
@@ -23,6 +28,7 @@ impl BodyType {
             BodyType::Planet(planet) => planet.print_side_panel_information(ui),
             BodyType::Ring(ring) => ring.print_side_panel_information(ui),
             BodyType::BeltCluster(cluster) => cluster.print_side_panel_information(ui),
+            BodyType::Barycentre(barycentre) => barycentre.print_side_panel_information(ui),
         }
     }
 }
@@ -58,10 +64,15 @@ impl Star {
         {
             body_name.replace_range(0..self.star_system.len(), "");
         }
-        if ui.selectable_label(false, &body_name).clicked() {
+        if ui
+            .selectable_label(*system_index == body_index, &body_name)
+            .clicked()
+        {
             *system_index = body_index;
         };
 
+        print_highlights(&self.highlights, ui, &self.settings.lock().unwrap());
+
         for ring in &self.asteroid_rings {
             if self
                 .settings
@@ -220,10 +231,15 @@ impl Planet {
         {
             body_name.replace_range(0..self.star_system.len(), "");
         }
-        if ui.selectable_label(false, &body_name).clicked() {
+        if ui
+            .selectable_label(*system_index == body_index, &body_name)
+            .clicked()
+        {
             *system_index = body_index;
         };
 
+        print_highlights(&self.highlights, ui, &self.settings.lock().unwrap());
+
         for ring in &self.asteroid_rings {
             if self
                 .settings
@@ -596,7 +612,10 @@ impl Ring {
             let system_name = self.star_system.clone();
             body_name.replace_range(0..system_name.len(), "");
         }
-        if ui.selectable_label(false, &body_name).clicked() {
+        if ui
+            .selectable_label(*system_index == body_index, &body_name)
+            .clicked()
+        {
             *system_index = body_index;
         };
 
@@ -727,7 +746,10 @@ impl BeltCluster {
             body_name.replace_range(0..system_name.len(), "");
         }
 
-        if ui.selectable_label(false, &body_name).clicked() {
+        if ui
+            .selectable_label(*system_index == body_index, &body_name)
+            .clicked()
+        {
             *system_index = body_index;
         };
 
@@ -785,3 +807,73 @@ impl BeltCluster {
         ui.label(&self.distance_from_arrival_ls.to_string());
     }
 }
+
+impl Barycentre {
+    fn print_header_content(&self, ui: &mut Ui, system_index: &mut usize, body_index: usize) {
+        let mut body_name = self.body_name.to_string();
+        if !self
+            .settings
+            .lock()
+            .unwrap()
+            .explorer_settings
+            .include_system_name
+        {
+            body_name.replace_range(0..self.star_system.len(), "");
+        }
+        ui.label("⊕");
+        if ui
+            .selectable_label(*system_index == body_index, &body_name)
+            .clicked()
+        {
+            *system_index = body_index;
+        };
+    }
+
+    fn print_side_panel_information(&self, ui: &mut Ui) {
+        ui.heading(&self.body_name);
+        ui.end_row();
+        #[cfg(debug_assertions)]
+        {
+            ui.label("ID");
+            ui.label(self.body_id.to_string());
+            ui.end_row();
+            ui.label("Parents");
+            ui.vertical(|ui| {
+                self.parents.iter().for_each(|parent| {
+                    ui.label(format!("{}-{}\n", parent.id, parent.name));
+                });
+            });
+            ui.end_row();
+        }
+        match (self.semi_major_axis, self.orbital_period) {
+            (Some(semi_major_axis), Some(orbital_period)) => {
+                ui.label("Semi-major axis in LS");
+                ui.label(format!("{:.2}", semi_major_axis / 299_792_458.0));
+                ui.end_row();
+                ui.label("Orbital period in D");
+                ui.label(format!("{:.2}", orbital_period / 86400.0));
+                ui.end_row();
+                ui.label("Eccentricity");
+                ui.label(format!("{:.4}", self.eccentricity.unwrap_or(0.0)));
+                ui.end_row();
+                ui.label("Inclination");
+                ui.label(format!("{:.2}°", self.orbital_inclination.unwrap_or(0.0)));
+                ui.end_row();
+            }
+            _ => {
+                ui.label("Orbit");
+                ui.label("not scanned");
+                ui.end_row();
+            }
+        }
+    }
+}
+
+fn print_highlights(highlights: &[String], ui: &mut Ui, settings: &Settings) {
+    for rule in &settings.highlight_rules {
+        if rule.enabled && highlights.contains(&rule.name) {
+            ui.label("|");
+            ui.label(rule.get_richtext()).on_hover_text(&rule.name);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::edcas::carrier::CarrierFilter;
 use eframe::egui::collapsing_header::CollapsingState;
 use eframe::egui::Context;
 use eframe::{egui, App, Frame};
@@ -11,60 +12,92 @@ impl App for crate::edcas::carrier::CarrierState {
             });
             ui.end_row();
 
-            let search = self.search.to_ascii_lowercase();
+            ui.collapsing("Filter", |ui| {
+                egui::Grid::new("carrier_filter")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Services:");
+                        ui.horizontal_wrapped(|ui| {
+                            for service in CarrierFilter::SERVICES {
+                                let mut checked = self.filter.services.contains(&service);
+                                if ui.checkbox(&mut checked, service.to_string()).changed() {
+                                    self.filter.toggle_service(service);
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Docking access:");
+                        ui.checkbox(&mut self.filter.open_access, "Open to everyone");
+                        ui.end_row();
+
+                        ui.label("Within:");
+                        ui.horizontal(|ui| {
+                            let mut limited = self.filter.max_distance.is_some();
+                            if ui.checkbox(&mut limited, "").changed() {
+                                self.filter.max_distance = limited.then_some(100.0);
+                            }
+                            if let Some(max_distance) = &mut self.filter.max_distance {
+                                ui.add(
+                                    egui::DragValue::new(max_distance)
+                                        .speed(10.0)
+                                        .clamp_range(0.0..=f64::MAX),
+                                );
+                                ui.label("ly");
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Departure:");
+                        ui.checkbox(&mut self.filter.departing_soon, "Within the next hour");
+                        ui.end_row();
+                    });
+                if self.position.is_none() && !self.filter.is_empty() {
+                    ui.label("Current system unknown, results are not sorted by distance");
+                }
+            });
+            self.request_filter_data();
+
+            let matching_carriers = self.get_matching_carriers(&self.search);
 
             egui::ScrollArea::vertical().show(ui, |ui| {
-                for carrier in &self.carriers {
-                    if carrier.name.contains(search.as_str())
-                        || carrier
-                            .callsign
-                            .to_ascii_lowercase()
-                            .contains(search.as_str())
-                        || carrier
-                            .next_system
-                            .to_ascii_lowercase()
-                            .contains(search.as_str())
-                        || carrier
-                            .next_body
-                            .to_ascii_lowercase()
-                            .contains(search.as_str())
-                        || carrier
-                            .current_system
-                            .to_ascii_lowercase()
-                            .contains(search.as_str())
-                        || carrier
-                            .current_body
-                            .to_ascii_lowercase()
-                            .contains(search.as_str())
-                    {
-                        let id = ui.make_persistent_id(carrier.callsign.clone());
-                        CollapsingState::load_with_default_open(ui.ctx(), id, false)
-                            .show_header(ui, |ui| {
-                                ui.label(format!("{} - {}", carrier.name, carrier.callsign));
-                            })
-                            .body(|ui| {
-                                egui::Grid::new("carrier_grid")
-                                    .num_columns(1)
-                                    .striped(true)
-                                    .show(ui, |ui| {
-                                        ui.label(format!("Last update: {}", carrier.timestamp));
-                                        ui.end_row();
-                                        ui.label(format!(
-                                            "Location: {} - {}",
-                                            carrier.current_system, carrier.current_body
-                                        ));
-                                        ui.end_row();
-                                        ui.label(format!(
-                                            "Next Jump: {} - {} at {}",
-                                            carrier.next_system,
-                                            carrier.next_body,
-                                            carrier.departure
-                                        ));
-                                        ui.end_row();
-                                        ui.label(carrier.services.clone());
-                                    });
-                            });
-                    }
+                for index in matching_carriers {
+                    let carrier = &self.carriers[index];
+                    let distance = self.get_distance(carrier);
+                    let id = ui.make_persistent_id(carrier.callsign.clone());
+                    CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                        .show_header(ui, |ui| {
+                            ui.label(format!("{} - {}", carrier.name, carrier.callsign));
+                            if let Some(distance) = distance {
+                                ui.label(format!("{:.2} ly", distance));
+                            }
+                            ui.label(format!("Departure: {}", carrier.get_countdown()));
+                        })
+                        .body(|ui| {
+                            egui::Grid::new("carrier_grid")
+                                .num_columns(1)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label(format!("Last update: {}", carrier.timestamp));
+                                    ui.end_row();
+                                    ui.label(format!(
+                                        "Location: {} - {}",
+                                        carrier.current_system, carrier.current_body
+                                    ));
+                                    ui.end_row();
+                                    ui.label(format!(
+                                        "Next Jump: {} - {} at {}",
+                                        carrier.next_system, carrier.next_body, carrier.departure
+                                    ));
+                                    ui.end_row();
+                                    ui.label(format!(
+                                        "Docking access: {}",
+                                        carrier.get_docking_access()
+                                    ));
+                                    ui.end_row();
+                                    ui.label(carrier.services.clone());
+                                });
+                        });
                 }
             });
         });
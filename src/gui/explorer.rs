@@ -1,14 +1,21 @@
-use crate::edcas::explorer::body::{BodyType, Parent};
-use crate::edcas::explorer::Explorer;
+use std::collections::HashMap;
+
+use crate::edcas::explorer::body::BodyType;
+use crate::edcas::explorer::orbit::get_children;
+use crate::edcas::explorer::system::System;
+use crate::edcas::explorer::{Explorer, SystemMapView};
+use chrono::Utc;
 use eframe::egui::collapsing_header::{CollapsingState, HeaderResponse};
-use eframe::egui::{Align, Context, Layout};
+use eframe::egui::{
+    Align, Align2, Color32, Context, FontId, Layout, Pos2, Sense, Shape, Stroke, Vec2,
+};
 use eframe::{egui, App, Frame};
 
 impl App for Explorer {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
         if self.systems.len() > self.index {
             egui::SidePanel::left("system_data").show(ctx, |ui| {
-                self.systems[self.index].draw_system_info(ui);
+                self.systems[self.index].draw_system_info(ui, &mut self.signal_filter);
                 ui.separator();
                 ui.heading("Body Signals");
                 egui::ScrollArea::vertical()
@@ -40,10 +47,26 @@ impl App for Explorer {
                                 self.index += 1;
                             }
                         });
+
+                        ui.with_layout(Layout::right_to_left(Align::LEFT), |ui| {
+                            let label = if self.system_map.show {
+                                "Body tree"
+                            } else {
+                                "System map"
+                            };
+                            if ui.button(label).clicked() {
+                                self.system_map.show = !self.system_map.show;
+                            }
+                        });
                         //ui.add_space(ui.available_size_before_wrap().x);
                         ui.end_row();
                     });
 
+                if self.system_map.show {
+                    draw_system_map(ui, &mut self.systems[self.index], &mut self.system_map);
+                    return;
+                }
+
                 egui::ScrollArea::vertical()
                     .id_source("body_scroll_area")
                     .stick_to_right(true)
@@ -52,37 +75,9 @@ impl App for Explorer {
                         ui.separator();
 
                         ui.with_layout(Layout::top_down_justified(Align::LEFT), |ui| {
-                            let current_parent = Parent {
-                                name: "Null".to_string(),
-                                id: 0,
-                            };
                             let system = &mut self.systems[self.index];
-                            let finished_bodies_ids = build_tree(
-                                &mut system.body_list,
-                                &mut system.index,
-                                current_parent,
-                                ui,
-                                vec![],
-                            );
-                            for i in 0..system.body_list.len() {
-                                let body = &system.body_list[i];
-                                let mut found = false;
-                                for id in &finished_bodies_ids {
-                                    if id == &body.get_id() {
-                                        found = true;
-                                        break;
-                                    }
-                                }
-                                if !found {
-                                    let id = ui.make_persistent_id(body.get_name());
-                                    let entry: CollapsingState =
-                                        CollapsingState::load_with_default_open(ui.ctx(), id, true);
-                                    let header: HeaderResponse<()> = entry.show_header(ui, |ui| {
-                                        body.print_header_content(ui, &mut system.index, i);
-                                    });
-                                    header.body(|_ui| {});
-                                }
-                            }
+                            let children = get_children(&system.body_list);
+                            build_tree(&system.body_list, &children, &mut system.index, None, ui);
                         });
                     });
             });
@@ -96,7 +91,7 @@ impl App for Explorer {
                             .striped(true)
                             .min_col_width(200.0)
                             .max_col_width(200.0)
-                            .show(ui, |ui| match self.systems.last() {
+                            .show(ui, |ui| match self.systems.get(self.index) {
                                 None => {}
                                 Some(system) => match system.body_list.get(system.index) {
                                     None => {}
@@ -111,55 +106,170 @@ impl App for Explorer {
     }
 }
 
+/// Draws all bodies orbiting the given parent, `None` being the root of the system
 fn build_tree(
-    body_list: &mut Vec<BodyType>,
+    body_list: &[BodyType],
+    children: &HashMap<Option<u64>, Vec<usize>>,
     system_index: &mut usize,
-    current_parent: Parent,
+    parent_id: Option<u64>,
     ui: &mut egui::Ui,
-    parent_ids: Vec<u64>,
-) -> Vec<u64> {
-    let mut finished_bodies: Vec<u64> = vec![];
-    for i in 0..body_list.len() {
-        //look what the largest body id in parents is. If is the current parent, print the header
-        let mut largest_parent_id = 0;
-        let parents = body_list[i].get_parents();
-        for parent in parents {
-            //println!("{}: {}",parent.name, parent.id);
-            if parent.id > largest_parent_id && (parent.id > 0 && parent.name.as_str() != "Null") {
-                largest_parent_id = parent.id;
+) {
+    for &i in children.get(&parent_id).into_iter().flatten() {
+        let body = &body_list[i];
+        let id = ui.make_persistent_id(body.get_name());
+        let entry: CollapsingState = CollapsingState::load_with_default_open(ui.ctx(), id, true);
+
+        let header: HeaderResponse<()> = entry.show_header(ui, |ui| {
+            body.print_header_content(ui, system_index, i);
+        });
+
+        header.body(|ui| {
+            build_tree(body_list, children, system_index, Some(body.get_id()), ui);
+        });
+    }
+}
+
+/// Draws stars, planets and moons at their current orbital position around their parents.
+/// Distances are scaled logarithmically, otherwise moons would vanish inside their planets.
+fn draw_system_map(ui: &mut egui::Ui, system: &mut System, view: &mut SystemMapView) {
+    let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+    if response.dragged() {
+        view.offset += response.drag_delta();
+    }
+    if response.hovered() {
+        let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+        if scroll != 0.0 {
+            view.zoom = (view.zoom * (1.0 + scroll * 0.002)).clamp(0.1, 20.0);
+        }
+    }
+    let center = response.rect.center() + view.offset;
+    let now = Utc::now();
+
+    let mut positions: HashMap<u64, Pos2> = HashMap::new();
+    let mut drawn_bodies: Vec<(usize, Pos2, f32)> = vec![];
+    for (i, body) in system.body_list.iter().enumerate() {
+        //Parents have lower ids, so they are already placed. Unknown parents are skipped
+        let parent_position = body
+            .get_parents()
+            .iter()
+            .find_map(|parent| positions.get(&parent.id).copied())
+            .unwrap_or(center);
+
+        let elements = match body.get_orbital_elements() {
+            Some(elements) if !body.get_parents().is_empty() => elements,
+            _ => {
+                positions.insert(body.get_id(), parent_position);
+                if let BodyType::Star(_) = body {
+                    let radius = body_radius(body);
+                    painter.circle_filled(parent_position, radius, body_color(body));
+                    drawn_bodies.push((i, parent_position, radius));
+                }
+                continue;
             }
+        };
+
+        let orbit: Vec<Pos2> = elements
+            .orbit_points(64)
+            .iter()
+            .map(|(x, y)| parent_position + scale_to_map(*x, *y, view.zoom))
+            .collect();
+        painter.add(Shape::line(orbit, Stroke::new(1.0, Color32::from_gray(70))));
+
+        let (x, y) = elements.position_at(now);
+        let position = parent_position + scale_to_map(x, y, view.zoom);
+        positions.insert(body.get_id(), position);
+
+        if let BodyType::Barycentre(_) = body {
+            let stroke = Stroke::new(1.0, Color32::from_gray(120));
+            painter.line_segment(
+                [
+                    position - Vec2::new(3.0, 0.0),
+                    position + Vec2::new(3.0, 0.0),
+                ],
+                stroke,
+            );
+            painter.line_segment(
+                [
+                    position - Vec2::new(0.0, 3.0),
+                    position + Vec2::new(0.0, 3.0),
+                ],
+                stroke,
+            );
+            continue;
         }
 
-        //If it is in the parents list, this body has already been drawn
-        if largest_parent_id == current_parent.id && !parent_ids.contains(&largest_parent_id) {
-            let id = ui.make_persistent_id(body_list[i].get_name());
-            let entry: CollapsingState =
-                CollapsingState::load_with_default_open(ui.ctx(), id, true);
+        let radius = body_radius(body);
+        painter.circle_filled(position, radius, body_color(body));
+        drawn_bodies.push((i, position, radius));
+    }
 
-            let header: HeaderResponse<()> = entry.show_header(ui, |ui| {
-                body_list[i].print_header_content(ui, system_index, i);
-            });
+    for (i, position, radius) in &drawn_bodies {
+        let body = &system.body_list[*i];
+        if *i == system.index {
+            painter.circle_stroke(*position, radius + 3.0, Stroke::new(2.0, Color32::YELLOW));
+        }
+        painter.text(
+            *position + Vec2::new(radius + 4.0, 0.0),
+            Align2::LEFT_CENTER,
+            body.get_name().trim_start_matches(&system.name),
+            FontId::proportional(12.0),
+            ui.visuals().text_color(),
+        );
+    }
 
-            header.body(|ui| {
-                //Build header with this body as parent
-                let this_as_parent = Parent {
-                    name: "".to_string(),
-                    id: body_list[i].get_id(),
-                };
-                let mut this_parents = parent_ids.clone();
-                this_parents.push(largest_parent_id);
-                let finished_ids: Vec<u64> =
-                    build_tree(body_list, system_index, this_as_parent, ui, this_parents);
-                for finished_body_id in finished_ids {
-                    if !finished_bodies.contains(&finished_body_id) {
-                        finished_bodies.push(finished_body_id);
-                    }
-                }
-            });
-            if !finished_bodies.contains(&body_list[i].get_id()) {
-                finished_bodies.push(body_list[i].get_id());
+    if response.clicked() {
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let nearest = drawn_bodies
+                .iter()
+                .filter(|(_, position, radius)| position.distance(pointer) <= radius + 6.0)
+                .min_by(|a, b| a.1.distance(pointer).total_cmp(&b.1.distance(pointer)));
+            if let Some((i, _, _)) = nearest {
+                system.index = *i;
             }
         }
     }
-    finished_bodies
+}
+
+fn scale_to_map(x: f64, y: f64, zoom: f32) -> Vec2 {
+    let distance = x.hypot(y);
+    if distance == 0.0 {
+        return Vec2::ZERO;
+    }
+    let scaled = (1.0 + distance / 1e7).ln() * 12.0 * zoom as f64;
+    //Screen coordinates grow downwards
+    Vec2::new(
+        (x / distance * scaled) as f32,
+        (-y / distance * scaled) as f32,
+    )
+}
+
+fn body_radius(body: &BodyType) -> f32 {
+    let radius = match body {
+        BodyType::Star(star) => star.radius,
+        BodyType::Planet(planet) => planet.radius,
+        BodyType::Ring(_) | BodyType::BeltCluster(_) | BodyType::Barycentre(_) => 0.0,
+    };
+    (((radius / 1e6 + 1.0).ln() * 2.0 + 2.0) as f32).clamp(2.0, 16.0)
+}
+
+fn body_color(body: &BodyType) -> Color32 {
+    match body {
+        BodyType::Star(star) => star
+            .settings
+            .lock()
+            .unwrap()
+            .stars
+            .get(star.star_type.as_str())
+            .map(|icon| icon.color)
+            .unwrap_or(Color32::WHITE),
+        BodyType::Planet(planet) => planet
+            .settings
+            .lock()
+            .unwrap()
+            .planets
+            .get(planet.planet_class.as_str())
+            .map(|icon| icon.color)
+            .unwrap_or(Color32::GRAY),
+        BodyType::Ring(_) | BodyType::BeltCluster(_) | BodyType::Barycentre(_) => Color32::GRAY,
+    }
 }
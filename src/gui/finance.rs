@@ -0,0 +1,240 @@
+use crate::edcas::finance::{Activity, FinanceState, HistoryRange};
+use eframe::egui::{
+    Align2, Color32, Context, FontId, Pos2, Rect, RichText, Sense, Shape, Stroke, Vec2,
+};
+use eframe::{egui, App, Frame};
+
+impl App for FinanceState {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                match self.balance {
+                    Some(balance) => ui.heading(format!("Balance: {} Cr", balance)),
+                    None => ui.heading("Balance unknown until the next LoadGame"),
+                };
+            });
+            ui.horizontal(|ui| {
+                ui.label("Range:");
+                for range in HistoryRange::ALL {
+                    if ui
+                        .selectable_label(range == self.range, range.to_string())
+                        .clicked()
+                    {
+                        self.range = range;
+                    }
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Net worth");
+                draw_balance_chart(ui, &self.get_balance_series(self.range));
+                ui.separator();
+
+                ui.heading("By activity");
+                draw_daily_chart(ui, &self.get_daily_totals(self.range));
+                let totals = self.get_activity_totals(self.range);
+                egui::Grid::new("finance_activities")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (activity, total) in Activity::ALL.iter().zip(totals) {
+                            ui.label(
+                                RichText::new(activity.to_string()).color(activity_color(activity)),
+                            );
+                            ui.label(format!("{} Cr", total));
+                            ui.end_row();
+                        }
+                        ui.label(RichText::new("Total").strong());
+                        ui.label(
+                            RichText::new(format!("{} Cr", totals.iter().sum::<i64>())).strong(),
+                        );
+                        ui.end_row();
+                    });
+                ui.separator();
+
+                ui.heading("Reconciliation");
+                egui::Grid::new("finance_snapshots")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Time", "Balance", "Difference"] {
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+                        let start = self.range.get_start();
+                        for snapshot in self
+                            .snapshots
+                            .iter()
+                            .rev()
+                            .take_while(|snapshot| snapshot.timestamp >= start)
+                        {
+                            ui.label(snapshot.timestamp.format("%Y-%m-%d %H:%M").to_string());
+                            ui.label(format!("{} Cr", snapshot.balance));
+                            if snapshot.difference == 0 {
+                                ui.label("0 Cr");
+                            } else {
+                                ui.label(
+                                    RichText::new(format!("{} Cr", snapshot.difference))
+                                        .color(Color32::YELLOW),
+                                );
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    }
+}
+
+fn activity_color(activity: &Activity) -> Color32 {
+    match activity {
+        Activity::Trading => Color32::from_rgb(80, 160, 230),
+        Activity::Mining => Color32::from_rgb(230, 160, 60),
+        Activity::Exploration => Color32::from_rgb(90, 200, 120),
+        Activity::Combat => Color32::from_rgb(220, 70, 70),
+        Activity::Missions => Color32::from_rgb(180, 110, 220),
+        Activity::Ship => Color32::from_rgb(150, 150, 150),
+        Activity::Carrier => Color32::from_rgb(60, 190, 190),
+        Activity::Other => Color32::from_rgb(110, 110, 90),
+    }
+}
+
+/// Step line of the balance, time on the x axis
+fn draw_balance_chart(ui: &mut egui::Ui, series: &[(chrono::DateTime<chrono::Utc>, i64)]) {
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(ui.available_width(), 200.0), Sense::hover());
+    let rect = response.rect;
+    painter.rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::from_gray(70)));
+    let (Some(first), Some(last)) = (series.first(), series.last()) else {
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            "No balance recorded",
+            FontId::proportional(14.0),
+            ui.visuals().text_color(),
+        );
+        return;
+    };
+    let min = series
+        .iter()
+        .map(|(_, balance)| *balance)
+        .min()
+        .unwrap_or(0);
+    let max = series
+        .iter()
+        .map(|(_, balance)| *balance)
+        .max()
+        .unwrap_or(0);
+    let start = first.0.timestamp();
+    let duration = (last.0.timestamp() - start).max(1) as f32;
+    let height = (max - min).max(1) as f32;
+    let to_screen = |timestamp: i64, balance: i64| {
+        Pos2::new(
+            rect.left() + (timestamp - start) as f32 / duration * rect.width(),
+            rect.bottom() - (balance - min) as f32 / height * rect.height(),
+        )
+    };
+
+    let mut points: Vec<Pos2> = vec![];
+    for (timestamp, balance) in series {
+        let point = to_screen(timestamp.timestamp(), *balance);
+        if let Some(previous) = points.last().copied() {
+            points.push(Pos2::new(point.x, previous.y));
+        }
+        points.push(point);
+    }
+    painter.add(Shape::line(
+        points,
+        Stroke::new(2.0, Color32::from_rgb(80, 160, 230)),
+    ));
+
+    let text_color = ui.visuals().text_color();
+    painter.text(
+        rect.left_top() + Vec2::new(4.0, 2.0),
+        Align2::LEFT_TOP,
+        format!("{} Cr", max),
+        FontId::proportional(12.0),
+        text_color,
+    );
+    painter.text(
+        rect.left_bottom() + Vec2::new(4.0, -2.0),
+        Align2::LEFT_BOTTOM,
+        format!("{} Cr", min),
+        FontId::proportional(12.0),
+        text_color,
+    );
+    painter.text(
+        rect.right_bottom() + Vec2::new(-4.0, -2.0),
+        Align2::RIGHT_BOTTOM,
+        format!(
+            "{} - {}",
+            first.0.format("%Y-%m-%d %H:%M"),
+            last.0.format("%Y-%m-%d %H:%M")
+        ),
+        FontId::proportional(12.0),
+        text_color,
+    );
+}
+
+/// Stacked bars per day, income above and expenses below the zero line
+fn draw_daily_chart(ui: &mut egui::Ui, days: &[(chrono::NaiveDate, [i64; Activity::ALL.len()])]) {
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(ui.available_width(), 200.0), Sense::hover());
+    let rect = response.rect;
+    painter.rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::from_gray(70)));
+    if days.is_empty() {
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            "No credit changes recorded",
+            FontId::proportional(14.0),
+            ui.visuals().text_color(),
+        );
+        return;
+    }
+    let income = |totals: &[i64]| totals.iter().filter(|total| **total > 0).sum::<i64>();
+    let expenses = |totals: &[i64]| -totals.iter().filter(|total| **total < 0).sum::<i64>();
+    let max_income = days
+        .iter()
+        .map(|(_, totals)| income(totals))
+        .max()
+        .unwrap_or(0);
+    let max_expenses = days
+        .iter()
+        .map(|(_, totals)| expenses(totals))
+        .max()
+        .unwrap_or(0);
+    let scale = rect.height() / (max_income + max_expenses).max(1) as f32;
+    let zero = rect.top() + max_income as f32 * scale;
+    let bar_width = rect.width() / days.len() as f32;
+
+    painter.line_segment(
+        [Pos2::new(rect.left(), zero), Pos2::new(rect.right(), zero)],
+        Stroke::new(1.0, Color32::from_gray(120)),
+    );
+    for (i, (day, totals)) in days.iter().enumerate() {
+        let left = rect.left() + i as f32 * bar_width + bar_width * 0.1;
+        let right = left + bar_width * 0.8;
+        let mut top = zero;
+        let mut bottom = zero;
+        for (activity, total) in Activity::ALL.iter().zip(totals) {
+            let height = total.abs() as f32 * scale;
+            let bar = if *total > 0 {
+                top -= height;
+                Rect::from_x_y_ranges(left..=right, top..=top + height)
+            } else {
+                bottom += height;
+                Rect::from_x_y_ranges(left..=right, bottom - height..=bottom)
+            };
+            painter.rect_filled(bar, 0.0, activity_color(activity));
+        }
+        painter.text(
+            Pos2::new((left + right) / 2.0, rect.bottom() - 2.0),
+            Align2::CENTER_BOTTOM,
+            day.format("%m-%d").to_string(),
+            FontId::proportional(10.0),
+            ui.visuals().text_color(),
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::edcas::explorer::system::System;
+use crate::edcas::explorer::{Explorer, GalaxyMapView, GalaxyProjection};
+use eframe::egui;
+use eframe::egui::{Align2, Color32, Context, FontId, Pos2, Sense, Shape, Stroke, Vec2};
+
+impl Explorer {
+    /// Plots all systems of the session by their `StarPos`, centered on the current system
+    pub fn draw_galaxy_map(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::top("galaxy_map_options").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(
+                    &mut self.galaxy_map.projection,
+                    GalaxyProjection::TopDown,
+                    "Top-down (X/Z)",
+                );
+                ui.selectable_value(
+                    &mut self.galaxy_map.projection,
+                    GalaxyProjection::Side,
+                    "Side (X/Y)",
+                );
+                ui.separator();
+                ui.checkbox(&mut self.galaxy_map.show_trail, "Jump trail");
+                if ui.button("Center").clicked() {
+                    self.galaxy_map.offset = Vec2::ZERO;
+                }
+                ui.separator();
+                let trail_distance: f64 = self
+                    .systems
+                    .windows(2)
+                    .map(|pair| pair[0].distance_to(&pair[1]))
+                    .sum();
+                ui.label(format!(
+                    "{} systems, {:.2} ly travelled",
+                    self.systems.len(),
+                    trail_distance
+                ));
+                ui.separator();
+                ui.colored_label(Color32::GOLD, "★ bookmark");
+                ui.colored_label(Color32::from_rgb(255, 140, 0), "◯ unsold data");
+                ui.colored_label(Color32::LIGHT_GREEN, "◆ high value body");
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.systems.is_empty() {
+                ui.label("No systems visited yet");
+                return;
+            }
+            let current = self.index.min(self.systems.len() - 1);
+            if let Some(index) = draw_galaxy(ui, &self.systems, current, &mut self.galaxy_map) {
+                self.index = index;
+            }
+        });
+    }
+}
+
+/// Draws the map and returns the index of a clicked system
+fn draw_galaxy(
+    ui: &mut egui::Ui,
+    systems: &[System],
+    current: usize,
+    view: &mut GalaxyMapView,
+) -> Option<usize> {
+    let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+    if response.dragged() {
+        view.offset += response.drag_delta();
+    }
+    if response.hovered() {
+        let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+        if scroll != 0.0 {
+            view.zoom = (view.zoom * (1.0 + scroll * 0.002)).clamp(0.001, 200.0);
+        }
+    }
+
+    let origin = project(&systems[current], &view.projection);
+    let center = response.rect.center() + view.offset;
+    let to_screen = |system: &System| -> Pos2 {
+        let (horizontal, vertical) = project(system, &view.projection);
+        //Screen coordinates grow downwards
+        center
+            + Vec2::new(
+                ((horizontal - origin.0) * view.zoom as f64) as f32,
+                (-(vertical - origin.1) * view.zoom as f64) as f32,
+            )
+    };
+    let positions: Vec<Pos2> = systems.iter().map(to_screen).collect();
+
+    let (horizontal_axis, vertical_axis) = match view.projection {
+        GalaxyProjection::TopDown => ("X", "Z"),
+        GalaxyProjection::Side => ("X", "Y"),
+    };
+    painter.text(
+        response.rect.right_bottom() - Vec2::new(8.0, 8.0),
+        Align2::RIGHT_BOTTOM,
+        format!(
+            "→ {}   ↑ {}   {:.3} px/ly",
+            horizontal_axis, vertical_axis, view.zoom
+        ),
+        FontId::proportional(12.0),
+        Color32::GRAY,
+    );
+
+    if view.show_trail && positions.len() > 1 {
+        painter.add(Shape::line(
+            positions.clone(),
+            Stroke::new(1.0, Color32::from_rgb(70, 110, 160)),
+        ));
+    }
+
+    for (i, system) in systems.iter().enumerate() {
+        let position = positions[i];
+        if !response.rect.expand(20.0).contains(position) {
+            continue;
+        }
+        let color = if i == current {
+            Color32::YELLOW
+        } else {
+            Color32::LIGHT_GRAY
+        };
+        painter.circle_filled(position, 3.0, color);
+
+        if system.unsold_data {
+            painter.circle_stroke(
+                position,
+                6.0,
+                Stroke::new(1.5, Color32::from_rgb(255, 140, 0)),
+            );
+        }
+        if !system.get_high_value_bodies().is_empty() {
+            painter.text(
+                position + Vec2::new(-8.0, -8.0),
+                Align2::RIGHT_BOTTOM,
+                "◆",
+                FontId::proportional(12.0),
+                Color32::LIGHT_GREEN,
+            );
+        }
+        if system.is_bookmarked() {
+            painter.text(
+                position + Vec2::new(8.0, -8.0),
+                Align2::LEFT_BOTTOM,
+                "★",
+                FontId::proportional(14.0),
+                Color32::GOLD,
+            );
+        }
+    }
+
+    painter.text(
+        positions[current] + Vec2::new(8.0, 4.0),
+        Align2::LEFT_TOP,
+        &systems[current].name,
+        FontId::proportional(12.0),
+        ui.visuals().text_color(),
+    );
+
+    //Later entries are revisits, so the newest one is preferred
+    let hovered = response.hover_pos().and_then(|pointer| {
+        positions
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, position)| position.distance(pointer) <= 8.0)
+            .min_by(|a, b| a.1.distance(pointer).total_cmp(&b.1.distance(pointer)))
+            .map(|(i, _)| i)
+    });
+
+    if let Some(i) = hovered {
+        painter.circle_stroke(positions[i], 8.0, Stroke::new(1.0, Color32::WHITE));
+        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("galaxy_map_tooltip"), |ui| {
+            systems[i].draw_system_summary(ui);
+            if i != current {
+                ui.separator();
+                ui.label(format!(
+                    "{:.2} ly from {}",
+                    systems[i].distance_to(&systems[current]),
+                    systems[current].name
+                ));
+            }
+            ui.label("Right click to toggle bookmark");
+        });
+        if response.secondary_clicked() {
+            systems[i].toggle_bookmark();
+        }
+        if response.clicked() {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn project(system: &System, projection: &GalaxyProjection) -> (f64, f64) {
+    match projection {
+        GalaxyProjection::TopDown => (system.x, system.z),
+        GalaxyProjection::Side => (system.x, system.y),
+    }
+}
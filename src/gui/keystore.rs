@@ -0,0 +1,51 @@
+use crate::edcas::EliteRustClient;
+use eframe::egui;
+use eframe::egui::{Color32, Context, Key, RichText};
+
+/// Asks for the passphrase of the keystore, until then the EDCAS network is only read
+pub fn draw_unlock_window(client: &mut EliteRustClient, ctx: &Context) {
+    let keystore_path = client
+        .settings
+        .lock()
+        .unwrap()
+        .evm_settings
+        .keystore_path
+        .clone();
+    let Some(unlock) = &mut client.keystore_unlock else {
+        return;
+    };
+    let mut unlock_clicked = false;
+    let mut skip_clicked = false;
+    egui::Window::new("Unlock keystore")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!("Passphrase for {}", keystore_path));
+            let response =
+                ui.add(egui::TextEdit::singleline(&mut unlock.passphrase).password(true));
+            if response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                unlock_clicked = true;
+            }
+            if let Some(err) = &unlock.error {
+                ui.label(RichText::new(err).color(Color32::RED));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Unlock").clicked() {
+                    unlock_clicked = true;
+                }
+                if ui
+                    .button("Stay read-only")
+                    .on_hover_text("Journal events are not shared until the next start")
+                    .clicked()
+                {
+                    skip_clicked = true;
+                }
+            });
+        });
+    if unlock_clicked {
+        client.unlock_keystore();
+    } else if skip_clicked {
+        client.skip_unlock();
+    }
+}
@@ -0,0 +1,84 @@
+use crate::edcas::session::SessionState;
+use eframe::egui::{Context, RichText};
+use eframe::{egui, App, Frame};
+
+impl App for SessionState {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        let Some(report) = &self.report else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Session report")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} - {} ({})",
+                    report.start.format("%Y-%m-%d %H:%M"),
+                    report.end.format("%Y-%m-%d %H:%M"),
+                    report.get_duration()
+                ));
+                match &self.report_path {
+                    Ok(path) => ui.label(format!("Written to {}.md and .json", path)),
+                    Err(err) => ui.label(RichText::new(err).color(egui::Color32::RED)),
+                };
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("session_report")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Jumps:");
+                            ui.label(format!("{} ({:.2} ly)", report.jumps, report.distance));
+                            ui.end_row();
+                            ui.label("Systems first discovered:");
+                            ui.label(report.systems_discovered.to_string());
+                            ui.end_row();
+                            ui.label("Bodies scanned:");
+                            ui.label(report.bodies_scanned.to_string());
+                            ui.end_row();
+                            ui.label("Bodies mapped:");
+                            ui.label(report.bodies_mapped.to_string());
+                            ui.end_row();
+                            for (activity, amount) in &report.credits {
+                                ui.label(format!("{}:", activity));
+                                ui.label(format!("{} Cr", amount));
+                                ui.end_row();
+                            }
+                            ui.label(RichText::new("Credits:").strong());
+                            ui.label(
+                                RichText::new(format!("{} Cr", report.get_credits_total()))
+                                    .strong(),
+                            );
+                            ui.end_row();
+                            ui.label("Mined:");
+                            ui.label(format!("{} t", report.get_mined_total()));
+                            ui.end_row();
+                            for (name, count) in &report.mined {
+                                ui.label(format!("  {}", name));
+                                ui.label(format!("{} t", count));
+                                ui.end_row();
+                            }
+                            ui.label("Materials:");
+                            ui.label(report.get_materials_total().to_string());
+                            ui.end_row();
+                            for (name, count) in &report.materials {
+                                ui.label(format!("  {}", name));
+                                ui.label(count.to_string());
+                                ui.end_row();
+                            }
+                            ui.label("Missions completed:");
+                            ui.label(report.missions.len().to_string());
+                            ui.end_row();
+                            for mission in &report.missions {
+                                ui.label(format!("  {}", mission));
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        if !open {
+            self.report = None;
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use crate::edcas::backend::evm::journal_uploader;
-use crate::edcas::settings::{Settings, JournalReadStatus};
+use crate::edcas::backend::data_backend::BackendKind;
+use crate::edcas::backend::eddn_publisher::EddnSchema;
+use crate::edcas::backend::evm::journal_uploader::UploadOptions;
+use crate::edcas::backend::evm::privacy::DataCategory;
+use crate::edcas::backend::evm::{audit_log, journal_uploader, keystore};
+use crate::edcas::explorer::highlight::{self, HighlightRule};
+use crate::edcas::settings::{EvmMode, JournalReadStatus, Settings};
 use eframe::egui::scroll_area::ScrollBarVisibility::AlwaysVisible;
-use eframe::egui::{global_dark_light_mode_switch, vec2, Color32, Context, Window};
+use eframe::egui::{global_dark_light_mode_switch, vec2, Color32, Context, RichText, Window};
 use eframe::{egui, App, Frame};
 use ethers::prelude::{LocalWallet, Signer};
 use log::error;
@@ -68,6 +73,10 @@ impl App for Settings {
 
                 egui::CollapsingHeader::new("Explorer").show(ui, |ui| {
                     ui.checkbox(&mut self.explorer_settings.include_system_name, "Include system in body name");
+                    ui.horizontal(|ui| {
+                        ui.label("High value body threshold:");
+                        ui.add(egui::DragValue::new(&mut self.explorer_settings.high_value_threshold).speed(10000).suffix(" Cr"));
+                    });
                     egui::CollapsingHeader::new("Icons").show(ui, |ui| {
                         egui::Grid::new("explorer_icon_grid")
                             .num_columns(2)
@@ -134,6 +143,53 @@ impl App for Settings {
                             });
                     });
                     ui.end_row();
+                    egui::CollapsingHeader::new("Highlight Rules").show(ui, |ui| {
+                        ui.label("Rules are applied to all bodies when saved, e.g. landable && surface_gravity < 0.3 && atmosphere != ''")
+                            .on_hover_text(format!("Operators: && || ! == != < <= > >= ~ (contains)\nFields: {}", highlight::FIELDS.join(", ")));
+                        let mut remove_index = None;
+                        egui::Grid::new("explorer_highlight_rule_grid")
+                            .num_columns(3)
+                            .spacing([60.0, 5.0])
+                            .min_col_width(200.0)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (index, rule) in self.highlight_rules.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        ui.add(egui::TextEdit::singleline(&mut rule.char).desired_width(30.0));
+                                        ui.text_edit_singleline(&mut rule.name);
+                                    });
+                                    ui.vertical(|ui| {
+                                        if ui.add(egui::TextEdit::singleline(&mut rule.expression).desired_width(500.0)).changed() {
+                                            rule.parse();
+                                        }
+                                        if let Err(err) = &rule.parsed {
+                                            ui.colored_label(Color32::RED, format!("⚠ {}", err));
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut rule.enabled, "Enabled");
+                                        ui.color_edit_button_srgba(&mut rule.color);
+                                        if ui.button("🗑").clicked() {
+                                            remove_index = Some(index);
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                        if let Some(index) = remove_index {
+                            self.highlight_rules.remove(index);
+                        }
+                        if ui.button("Add rule").clicked() {
+                            self.highlight_rules.push(HighlightRule::new(
+                                "New rule".to_string(),
+                                "landable".to_string(),
+                                "★".to_string(),
+                                Color32::from_rgb(255, 215, 0),
+                                true,
+                            ));
+                        }
+                    });
+                    ui.end_row();
                 });
                 ui.separator();
                 ui.heading("Graphics Override");
@@ -182,8 +238,28 @@ impl App for Settings {
                         ui.label("Allow to share journal log data:").on_hover_ui(info_ui);
                         ui.checkbox(&mut self.evm_settings.allow_share_data, "");
                         ui.end_row();
+                        for category in DataCategory::ALL {
+                            ui.label(format!("Share {}:", category)).on_hover_text(category.get_description());
+                            let mut shared = self.evm_settings.shared_categories.contains(&category);
+                            if ui.checkbox(&mut shared, "").changed() {
+                                if shared {
+                                    self.evm_settings.shared_categories.push(category);
+                                } else {
+                                    self.evm_settings.shared_categories.retain(|other| other != &category);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                        ui.label("Publish delay:").on_hover_text("Events are held back until they are this old, so your live position is not published.\n\
+                        Held back events are lost if EDCAS is closed before.");
+                        ui.add(egui::DragValue::new(&mut self.evm_settings.publish_delay).suffix(" min"));
+                        ui.end_row();
+                        ui.label("Audit log:").on_hover_text("Every transaction sent to the EDCAS network with its time");
+                        ui.label(audit_log::get_path());
+                        ui.end_row();
                         if ui
-                            .button("Upload journal data ⬆")
+                            .add_enabled(self.evm_settings.get_mode() == EvmMode::Signing, egui::Button::new("Upload journal data ⬆"))
+                            .on_disabled_hover_text("Needs sharing enabled and a valid private key")
                             .clicked()
                         {
                             self.evm_settings.show_upload_data_window = true;
@@ -194,13 +270,13 @@ impl App for Settings {
                                 ui.vertical_centered(|ui|{
                                     if let Some(ref mut upload_status) = self.evm_settings.journal_read_status {
                                         ui.label("You are able to close this window and still use EDCAS. It will run in the background");
-                                        if let Ok(index) = upload_status.log_index_updates.try_recv() {upload_status.current_log = index as u32;}
-                                        let status = (upload_status.total_logs - upload_status.current_log) as f32 / upload_status.total_logs as f32;
-                                        ui.add(egui::ProgressBar::new(status).text(format!("{} of {} logs read", upload_status.total_logs - upload_status.current_log, upload_status.total_logs)));
+                                        if let Ok(progress) = upload_status.log_index_updates.try_recv() {upload_status.current_log = progress.done as u32;}
+                                        let status = upload_status.current_log as f32 / upload_status.total_logs.max(1) as f32;
+                                        ui.add(egui::ProgressBar::new(status).text(format!("{} of {} logs read", upload_status.current_log, upload_status.total_logs)));
                                     }else if ui.button("Do it!").clicked(){
-                                        let (progress_bus_reader, total) = journal_uploader::initialize(&self.evm_settings, self.journal_reader_settings.journal_directory.clone());
+                                        let (progress_bus_reader, total) = journal_uploader::initialize(&self.evm_settings, self.journal_reader_settings.journal_directory.clone(), UploadOptions::default());
                                         self.evm_settings.journal_read_status = Some(JournalReadStatus {
-                                            current_log: 1,
+                                            current_log: 0,
                                             total_logs: total as u32,
                                             log_index_updates: progress_bus_reader,
                                         });
@@ -220,26 +296,130 @@ impl App for Settings {
                         ui.label("Smart Contract Address:");
                         ui.text_edit_singleline(&mut self.evm_settings.smart_contract_address);
                         ui.end_row();
-                        ui.label("Private Key:");
-                        ui.add(egui::TextEdit::singleline(&mut self.evm_settings.private_key).password(true));
+                        ui.label("Keystore:").on_hover_text(format!("Encrypted private key. Unlocked at startup in a dialog or with the passphrase from {}.\n\
+                        Keys can be generated, imported and exported with --generate-key, --import-key and --export-key.", keystore::PASSPHRASE_VARIABLE));
+                        ui.text_edit_singleline(&mut self.evm_settings.keystore_path);
                         ui.end_row();
+                        if self.evm_settings.keystore_path.is_empty() {
+                            ui.label("Private Key:").on_hover_text("Stored in plain text until it is moved into a keystore");
+                            ui.add(egui::TextEdit::singleline(&mut self.evm_settings.private_key).password(true));
+                            ui.end_row();
+                        }
                         ui.label("Address:");
-                        let address = format!("{:?}", self.evm_settings.private_key.parse::<LocalWallet>().unwrap().address());
-                        if ui
-                            .button(format!("{} 🗐", &address))
-                            .clicked()
-                        {
-                            ui.output_mut(|o| {
-                                o.copied_text =
-                                    address
-                            });
+                        match self.evm_settings.private_key.parse::<LocalWallet>() {
+                            Ok(wallet) => {
+                                let address = format!("{:?}", wallet.address());
+                                if ui
+                                    .button(format!("{} 🗐", &address))
+                                    .clicked()
+                                {
+                                    ui.output_mut(|o| {
+                                        o.copied_text =
+                                            address
+                                    });
+                                }
+                            }
+                            Err(_) if self.evm_settings.private_key.is_empty() && !self.evm_settings.keystore_path.is_empty() => {
+                                ui.label("Keystore locked");
+                            }
+                            Err(_) if self.evm_settings.private_key.is_empty() => {
+                                ui.label("No private key");
+                            }
+                            Err(_) => {
+                                ui.label(RichText::new("Invalid private key").color(Color32::RED));
+                            }
                         }
                         ui.end_row();
+                        ui.label("Mode:").on_hover_text("Without sharing or without a valid private key the EDCAS network is only read.\nChanges apply after a restart.");
+                        ui.label(self.evm_settings.get_mode().to_string());
+                        ui.end_row();
                         ui.label("EVM Adapter Timeout:");
                         ui.add(egui::Slider::new(&mut self.evm_settings.n_timeout, 0..=20).suffix(" Seconds"));
                         ui.end_row();
                         ui.label("EVM Adapter Attempts:");
                         ui.add(egui::Slider::new(&mut self.evm_settings.n_attempts, 0..=20).suffix(" Attempts"));
+                        ui.end_row();
+                        let queue_status = self.evm_settings.queue_status.lock().unwrap();
+                        ui.label("Pending transactions:").on_hover_text("Transactions are kept on disk until they are mined and are sent again after a restart");
+                        ui.label(queue_status.pending.to_string());
+                        ui.end_row();
+                        ui.label("Submitted / skipped / failed:").on_hover_text("Writes are skipped if the contract already has the same or newer data");
+                        ui.label(format!("{} / {} / {}", queue_status.submitted, queue_status.skipped, queue_status.failed));
+                        ui.end_row();
+                        ui.label("Last transaction error:");
+                        match &queue_status.last_error {
+                            None => ui.label("-"),
+                            Some(error) => ui.label(RichText::new(error).color(Color32::RED)),
+                        };
+                    });
+
+                ui.separator();
+                ui.heading("Data source").on_hover_text("Where stations, carriers and system data are read from.\n\
+                The local database is filled by an eddn adapter running with the database sink.");
+                egui::Grid::new("database_grid")
+                    .num_columns(2)
+                    .spacing([60.0, 5.0])
+                    .min_col_width(300.0)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Backend:");
+                        egui::ComboBox::from_id_source("data_backend")
+                            .selected_text(self.backend_settings.kind.to_string())
+                            .show_ui(ui, |ui| {
+                                for kind in BackendKind::ALL {
+                                    ui.selectable_value(&mut self.backend_settings.kind, kind, kind.to_string());
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("REST url:");
+                        ui.text_edit_singleline(&mut self.backend_settings.rest_url);
+                        ui.end_row();
+                        ui.label("Database path:");
+                        ui.text_edit_singleline(&mut self.database_settings.path);
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.heading("Trade").on_hover_text("Trade routes are calculated from the markets you docked at and the markets of the local database.");
+                egui::Grid::new("trade_grid")
+                    .num_columns(2)
+                    .spacing([60.0, 5.0])
+                    .min_col_width(300.0)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Maximum market age:");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut self.trade_settings.max_age).clamp_range(1..=u64::MAX));
+                            ui.label("hours");
+                        });
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.heading("EDDN").on_hover_text("Publishes your discoveries and market data to the Elite Dangerous Data Network.\n\
+                Personal data like fines, reputation and localised names is removed before sending.\n\
+                Changes apply after a restart.");
+                egui::Grid::new("eddn_grid")
+                    .num_columns(2)
+                    .spacing([60.0, 5.0])
+                    .min_col_width(300.0)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Upload URL:");
+                        ui.text_edit_singleline(&mut self.eddn_settings.upload_url);
+                        ui.end_row();
+                        for schema in EddnSchema::ALL {
+                            ui.label(format!("Share {}:", schema)).on_hover_text(schema.get_schema_ref());
+                            let mut shared = self.eddn_settings.shared_schemas.contains(&schema);
+                            if ui.checkbox(&mut shared, "").changed() {
+                                if shared {
+                                    self.eddn_settings.shared_schemas.push(schema);
+                                } else {
+                                    self.eddn_settings.shared_schemas.retain(|other| other != &schema);
+                                }
+                            }
+                            ui.end_row();
+                        }
                     });
             });
             ui.separator();
@@ -1,4 +1,5 @@
-use crate::edcas::backend::evm::request_handler::EvmRequest;
+use crate::edcas::backend::data_backend::DataRequest;
+use crate::edcas::station::{MarketSortKey, PadSize, StationFilter, StationService};
 use eframe::egui::collapsing_header::CollapsingState;
 use eframe::egui::Context;
 use eframe::{egui, App, Frame};
@@ -13,54 +14,192 @@ impl App for crate::edcas::station::StationState {
             });
             ui.end_row();
 
-            let search = self.search.to_ascii_lowercase();
+            ui.collapsing("Filter", |ui| {
+                egui::Grid::new("station_filter")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Services:");
+                        ui.horizontal_wrapped(|ui| {
+                            for service in StationService::ALL {
+                                let mut checked = self.filter.services.contains(&service);
+                                if ui.checkbox(&mut checked, service.to_string()).changed() {
+                                    self.filter.toggle_service(service);
+                                }
+                            }
+                        });
+                        ui.end_row();
 
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for station in &mut self.stations {
-                    if station.name.to_ascii_lowercase().contains(search.as_str()) {
-                        let id = ui.make_persistent_id(station.market_id);
-                        CollapsingState::load_with_default_open(ui.ctx(), id, false)
-                            .show_header(ui, |ui| {
-                                ui.label(&station.name);
+                        ui.label("Minimum pad:");
+                        egui::ComboBox::from_id_source("station_filter_pad")
+                            .selected_text(
+                                self.filter
+                                    .min_pad
+                                    .map(|pad| pad.to_string())
+                                    .unwrap_or("Any".to_string()),
+                            )
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.filter.min_pad, None, "Any");
+                                for pad in PadSize::ALL {
+                                    ui.selectable_value(
+                                        &mut self.filter.min_pad,
+                                        Some(pad),
+                                        pad.to_string(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Economy:");
+                        egui::ComboBox::from_id_source("station_filter_economy")
+                            .selected_text(if self.filter.economy.is_empty() {
+                                "Any"
+                            } else {
+                                self.filter.economy.as_str()
                             })
-                            .body(|ui| {
-                                egui::Grid::new(&station.name)
-                                    .num_columns(1)
-                                    .striped(true)
-                                    .show(ui, |ui| {
-                                        match &station.meta_data {
-                                            None => {
-                                                if !station.requested_meta_data {
-                                                    if let Err(err) = self.evm_request_writer.send(EvmRequest::StationMetaData(station.market_id)){
-                                                        error!("Error sending StationMetaDataRequest: {err}");
-                                                    }
-                                                    station.requested_meta_data = true;
-                                                }else {
-                                                    ui.label("Fetching...");
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.filter.economy, String::new(), "Any");
+                                for economy in StationFilter::ECONOMIES {
+                                    ui.selectable_value(
+                                        &mut self.filter.economy,
+                                        economy.to_string(),
+                                        economy,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Max. arrival distance:");
+                        ui.horizontal(|ui| {
+                            let mut limited = self.filter.max_arrival_distance.is_some();
+                            if ui.checkbox(&mut limited, "").changed() {
+                                self.filter.max_arrival_distance = limited.then_some(1000.0);
+                            }
+                            if let Some(max_arrival_distance) =
+                                &mut self.filter.max_arrival_distance
+                            {
+                                ui.add(
+                                    egui::DragValue::new(max_arrival_distance)
+                                        .speed(100.0)
+                                        .clamp_range(0.0..=f64::MAX),
+                                );
+                                ui.label("LS");
+                            }
+                        });
+                        ui.end_row();
+                    });
+                if self.position.is_none() && !self.filter.is_empty() {
+                    ui.label("Current system unknown, results are not sorted by distance");
+                }
+            });
+            self.request_filter_data();
+
+            let mut market_sort = self.market_sort;
+            let matching_stations = self.get_matching_stations(&self.search);
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for index in matching_stations {
+                    let distance = self.get_distance(&self.stations[index]);
+                    let station = &mut self.stations[index];
+                    let id = ui.make_persistent_id(station.market_id);
+                    CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                        .show_header(ui, |ui| {
+                            ui.label(&station.name);
+                            if let Some(distance) = distance {
+                                ui.label(format!("{:.2} ly", distance));
+                            }
+                        })
+                        .body(|ui| {
+                            egui::Grid::new(&station.name)
+                                .num_columns(1)
+                                .striped(true)
+                                .show(ui, |ui| match &station.meta_data {
+                                    None => {
+                                        if !station.requested_meta_data {
+                                            if let Err(err) = self.data_request_writer.send(
+                                                DataRequest::StationMetaData(station.market_id),
+                                            ) {
+                                                error!(
+                                                    "Error sending StationMetaDataRequest: {err}"
+                                                );
+                                            }
+                                            station.requested_meta_data = true;
+                                        } else {
+                                            ui.label("Fetching...");
+                                        }
+                                    }
+                                    Some(meta_data) => {
+                                        let mut distance = meta_data.distance.decimal.to_string();
+                                        distance.insert(
+                                            meta_data.distance.floating_point as usize,
+                                            '.',
+                                        );
+                                        ui.label(&meta_data.system_name);
+                                        ui.end_row();
+                                        ui.label(distance);
+                                        ui.label("LS");
+                                        ui.end_row();
+                                        ui.label(&meta_data.economy);
+                                        ui.end_row();
+                                        ui.label(&meta_data.government);
+                                        ui.end_row();
+                                        ui.label(&meta_data.services);
+                                        ui.end_row();
+                                        ui.label(&meta_data.landingpads);
+                                    }
+                                });
+                            ui.separator();
+                            match &station.market {
+                                None => {
+                                    if !station.requested_market {
+                                        if let Err(err) = self.data_request_writer.send(
+                                            DataRequest::StationCommodityListener(
+                                                station.market_id,
+                                            ),
+                                        ) {
+                                            error!("Error sending StationCommodityListener: {err}");
+                                        }
+                                        station.requested_market = true;
+                                    } else {
+                                        ui.label("Fetching market...");
+                                    }
+                                }
+                                Some(market) if market.is_empty() => {
+                                    ui.label("No market data");
+                                }
+                                Some(market) => {
+                                    egui::Grid::new(format!("{}_market", station.market_id))
+                                        .num_columns(5)
+                                        .striped(true)
+                                        .show(ui, |ui| {
+                                            for key in MarketSortKey::ALL {
+                                                if ui
+                                                    .selectable_label(
+                                                        key == market_sort,
+                                                        key.to_string(),
+                                                    )
+                                                    .clicked()
+                                                {
+                                                    market_sort = key;
                                                 }
                                             }
-                                            Some(meta_data) => {
-                                                let mut distance = meta_data.distance.decimal.to_string();
-                                                distance.insert(meta_data.distance.floating_point as usize,'.');
-                                                ui.label(&meta_data.system_name);
-                                                ui.end_row();
-                                                ui.label(distance);
-                                                ui.label("LS");
-                                                ui.end_row();
-                                                ui.label(&meta_data.economy);
+                                            ui.end_row();
+                                            for listening in market {
+                                                ui.label(&listening.name);
+                                                ui.label(listening.buy_price.to_string());
+                                                ui.label(listening.sell_price.to_string());
+                                                ui.label(listening.stock.to_string());
+                                                ui.label(listening.demand.to_string());
                                                 ui.end_row();
-                                                ui.label(&meta_data.government);
-                                                ui.end_row();
-                                                ui.label(&meta_data.services);
-                                                ui.end_row();
-                                                ui.label(&meta_data.landingpads);
                                             }
-                                        }
-                                    });
-                            });
-                    }
+                                        });
+                                }
+                            }
+                        });
                 }
             });
+            if market_sort != self.market_sort {
+                self.set_market_sort(market_sort);
+            }
         });
     }
 }
@@ -0,0 +1,203 @@
+use crate::edcas::station::PadSize;
+use crate::edcas::trade::{RouteSortKey, TradeRoute, TradeState};
+use eframe::egui::{Context, RichText};
+use eframe::{egui, App, Frame};
+
+impl App for TradeState {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::Grid::new("trade_query")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Cargo capacity:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut self.query.cargo_capacity));
+                        ui.label("t");
+                    });
+                    ui.end_row();
+
+                    ui.label("Credits:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut self.query.credits).speed(10000.0));
+                        ui.label("Cr");
+                    });
+                    ui.end_row();
+
+                    ui.label("Landing pad:");
+                    egui::ComboBox::from_id_source("trade_pad_size")
+                        .selected_text(self.query.pad_size.to_string())
+                        .show_ui(ui, |ui| {
+                            for pad in PadSize::ALL {
+                                ui.selectable_value(&mut self.query.pad_size, pad, pad.to_string());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Max. hop distance:");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.query.max_hop_distance)
+                                .clamp_range(0.0..=f64::MAX),
+                        );
+                        ui.label("ly");
+                    });
+                    ui.end_row();
+
+                    ui.label("Loop hops:");
+                    ui.add(egui::DragValue::new(&mut self.query.loop_hops).clamp_range(2..=4));
+                    ui.end_row();
+                });
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!self.is_calculating(), egui::Button::new("Calculate"))
+                    .clicked()
+                {
+                    self.calculate();
+                }
+                if ui
+                    .add_enabled(!self.is_loading(), egui::Button::new("Reload database"))
+                    .clicked()
+                {
+                    self.reload_database();
+                }
+                if self.is_calculating() || self.is_loading() {
+                    ui.spinner();
+                }
+                ui.label(format!("{} markets known", self.get_market_count()));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Sort by:");
+                for key in RouteSortKey::ALL {
+                    if ui
+                        .selectable_label(key == self.sort, key.to_string())
+                        .clicked()
+                    {
+                        self.set_sort(key);
+                    }
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Single hop");
+                show_routes(ui, "trade_single_hop", &self.single_hop_routes);
+                ui.separator();
+                ui.heading("Loops");
+                show_routes(ui, "trade_loops", &self.loop_routes);
+                ui.separator();
+                ui.heading("Cargo ledger");
+                ui.label(format!(
+                    "Session profit: {} Cr, unrealised: {} Cr",
+                    self.ledger.get_session_profit(),
+                    self.get_unrealised_profit()
+                ));
+                show_ledger(ui, self);
+            });
+        });
+    }
+}
+
+fn show_routes(ui: &mut egui::Ui, id: &str, routes: &[TradeRoute]) {
+    if routes.is_empty() {
+        ui.label("No routes");
+        return;
+    }
+    egui::Grid::new(id)
+        .num_columns(8)
+        .striped(true)
+        .show(ui, |ui| {
+            for header in [
+                "From",
+                "To",
+                "Commodity",
+                "Buy",
+                "Sell",
+                "Margin",
+                "Amount",
+                "Distance",
+            ] {
+                ui.label(RichText::new(header).strong());
+            }
+            ui.end_row();
+            for route in routes {
+                for hop in &route.hops {
+                    ui.label(format!("{} ({})", hop.from_station, hop.from_system));
+                    ui.label(format!("{} ({})", hop.to_station, hop.to_system));
+                    ui.label(&hop.commodity);
+                    ui.label(hop.buy_price.to_string());
+                    ui.label(hop.sell_price.to_string());
+                    ui.label(format!("{} Cr/t", hop.get_margin()));
+                    ui.label(format!("{} t", hop.amount));
+                    ui.label(format!("{:.2} ly", hop.distance));
+                    ui.end_row();
+                }
+                ui.label(
+                    RichText::new(format!(
+                        "{} Cr, {:.0} Cr/t, {:.0} Cr/h",
+                        route.get_profit(),
+                        route.get_profit_per_ton(),
+                        route.get_profit_per_hour()
+                    ))
+                    .strong(),
+                );
+                ui.end_row();
+            }
+        });
+}
+
+fn show_ledger(ui: &mut egui::Ui, trade: &TradeState) {
+    egui::Grid::new("trade_ledger_hold")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            for header in [
+                "Commodity",
+                "Amount",
+                "Avg. cost",
+                "Best sell",
+                "Unrealised",
+            ] {
+                ui.label(RichText::new(header).strong());
+            }
+            ui.end_row();
+            for (name, position) in &trade.ledger.positions {
+                let sell_price = trade.get_best_sell_price(name);
+                ui.label(&position.name_localised);
+                ui.label(format!("{} t", position.count));
+                ui.label(format!("{:.0} Cr", position.get_average_cost()));
+                match sell_price {
+                    Some(sell_price) => {
+                        ui.label(format!("{} Cr", sell_price));
+                        ui.label(format!(
+                            "{} Cr",
+                            sell_price as i64 * position.count as i64 - position.cost as i64
+                        ));
+                    }
+                    None => {
+                        ui.label("n/v");
+                        ui.label("n/v");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    ui.add_space(8.0);
+    egui::Grid::new("trade_ledger_trips")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            for header in ["Time", "Station", "Sold", "Revenue", "Profit"] {
+                ui.label(RichText::new(header).strong());
+            }
+            ui.end_row();
+            for trip in trade.ledger.get_trips() {
+                ui.label(&trip.timestamp);
+                ui.label(format!("{} ({})", trip.station, trip.system));
+                ui.label(format!("{} t", trip.tons_sold));
+                ui.label(format!("{} Cr", trip.revenue));
+                ui.label(format!("{} Cr", trip.profit));
+                ui.end_row();
+            }
+        });
+}
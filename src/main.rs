@@ -60,22 +60,59 @@ fn main() {
                 println!("--width <f32>\t\tSets the width for the edcas gui");
                 println!("--set-sc-address\tSet the smart contract address");
                 println!("--upload-journal\tUpload Journal to EDCAS network");
+                println!("  --from <date>\t\tOnly events since the date (YYYY-MM-DD)");
+                println!("  --to <date>\t\tOnly events until the date (YYYY-MM-DD)");
+                println!("  --events <list>\tOnly these events, comma separated");
+                println!("  --dry-run\t\tPrint the contract calls without sending them");
+                println!("  --restart\t\tIgnore the checkpoint of the last upload");
+                println!("--generate-key\t\tGenerate a private key into an encrypted keystore");
+                println!("--import-key\t\tImport a private key into an encrypted keystore");
+                println!("--export-key\t\tPrint the private key of the keystore");
                 #[cfg(feature = "tui")]
                 println!("--tui\t\t\tStart edcas in tui mode");
                 #[cfg(feature = "eddn")]
                 println!("--eddn\t\tStart EDCAS with EDDN support");
+                #[cfg(feature = "eddn")]
+                println!("  --config <path>\tEDDN adapter config file (json)");
+                #[cfg(feature = "eddn")]
+                println!("  --zeromq-url <url>\tEDDN relay to subscribe to");
+                #[cfg(feature = "eddn")]
+                println!("  --evm-url <url>\tEVM RPC to send the data to");
+                #[cfg(feature = "eddn")]
+                println!("  --sc-address <addr>\tSmart contract address");
+                #[cfg(feature = "eddn")]
+                println!("  --timeout <u64>\tEVM timeout in seconds");
+                #[cfg(feature = "eddn")]
+                println!("  --attempts <u64>\tEVM attempts");
+                #[cfg(feature = "eddn")]
+                println!("  --sink <evm|database>\tWhere to store the received data");
+                #[cfg(feature = "eddn")]
+                println!("  --database <path>\tDatabase file of the database sink");
+                #[cfg(feature = "eddn")]
+                println!("  --dry-run\t\tLog what would be sent without sending it");
                 return;
             }
             "--set-sc-address" => {
-                let client = EliteRustClient::default();
                 let new_smart_contract_address = String::from_str(args[i + 1].as_str())
                     .unwrap_or_else(|_| panic!("Wrong argument for SC Address: {}", &args[i + 1]));
-                cli::set_sc_address(new_smart_contract_address, client);
+                cli::set_sc_address(new_smart_contract_address);
                 return;
             }
             "--upload-journal" => {
                 let client = EliteRustClient::default();
-                cli::upload_journal(client);
+                cli::upload_journal(client, &args[i + 1..]);
+                return;
+            }
+            "--generate-key" => {
+                cli::generate_key();
+                return;
+            }
+            "--import-key" => {
+                cli::import_key();
+                return;
+            }
+            "--export-key" => {
+                cli::export_key();
                 return;
             }
             "--set-journal-path" => {
@@ -111,7 +148,7 @@ fn main() {
             #[cfg(feature = "eddn")]
             "--eddn" => {
                 println!("{}", ascii_art);
-                eddn::initialize();
+                eddn::initialize(&args[i + 1..]);
                 return;
             }
             _ => {}
@@ -8,18 +8,28 @@ use crossterm::{
 };
 use ratatui::{prelude::*, style::Stylize, widgets::*};
 
+use crate::edcas::carrier::CarrierFilter;
+use crate::edcas::explorer::signal::SignalClass;
+use crate::edcas::settings::EvmMode;
+use crate::edcas::station::{PadSize, StationFilter, StationService};
 use crate::edcas::EliteRustClient;
 use crate::tui::about::tab_about;
 use crate::tui::dockables::tab_dockables;
 use crate::tui::explorer::tab_explorer;
+use crate::tui::keystore::popup_keystore_unlock;
 use crate::tui::materials::tab_materials;
 use crate::tui::mining::tab_mining;
+use crate::tui::session::popup_session_report;
+use crate::tui::trade::tab_trade;
 
 mod about;
 mod dockables;
 mod explorer;
+mod keystore;
 mod materials;
 mod mining;
+mod session;
+mod trade;
 
 enum InputMode {
     Normal,
@@ -121,12 +131,21 @@ struct App<'a> {
     pub dockable_list_index: usize,
     pub dockable_search: Search,
     pub dockable_mode: DockableMode,
+    pub dockable_service_index: usize,
+    pub signal_filter: Option<SignalClass>,
 }
 
 impl<'a> App<'a> {
     fn new() -> App<'a> {
         App {
-            titles: vec!["Explorer", "Mining", "Materials", "Dockables", "About"],
+            titles: vec![
+                "Explorer",
+                "Mining",
+                "Materials",
+                "Dockables",
+                "Trade",
+                "About",
+            ],
             tab_index: 0,
             body_list_state: ListState::default(),
             prospector_list_state: ListState::default(),
@@ -142,6 +161,8 @@ impl<'a> App<'a> {
             dockable_list_index: 0,
             dockable_search: Search::new(),
             dockable_mode: DockableMode::Carriers,
+            dockable_service_index: 0,
+            signal_filter: None,
         }
     }
 
@@ -323,6 +344,20 @@ impl<'a> App<'a> {
             DockableMode::Carriers => self.dockable_mode = DockableMode::Stations,
             DockableMode::Stations => self.dockable_mode = DockableMode::Carriers,
         }
+        self.dockable_service_index = 0;
+    }
+
+    /// Services the filter of the dockable list can require
+    pub fn get_dockable_services(&self) -> &'static [StationService] {
+        match self.dockable_mode {
+            DockableMode::Carriers => &CarrierFilter::SERVICES,
+            DockableMode::Stations => &StationService::ALL,
+        }
+    }
+
+    /// Service toggled with t
+    pub fn get_dockable_service(&self) -> StationService {
+        self.get_dockable_services()[self.dockable_service_index]
     }
     // TODO: add functions for cursor navigation through signals lists
 }
@@ -368,11 +403,30 @@ fn run_app<B: Backend>(
         if event::poll(std::time::Duration::from_millis(33))? {
             if let Key(key) = event::read()? {
                 if key.kind == event::KeyEventKind::Press {
+                    if let Some(unlock) = &mut client.keystore_unlock {
+                        match key.code {
+                            KeyCode::Enter => client.unlock_keystore(),
+                            KeyCode::Esc => client.skip_unlock(),
+                            KeyCode::Backspace => {
+                                unlock.passphrase.pop();
+                            }
+                            KeyCode::Char(char) => unlock.passphrase.push(char),
+                            _ => {}
+                        }
+                        continue;
+                    }
                     match app.search_input_mode {
                         InputMode::Normal => match key.code {
-                            KeyCode::Char('Q') => return Ok(()),
+                            KeyCode::Char('Q') => {
+                                client.finance.save();
+                                return Ok(());
+                            }
                             KeyCode::Char('e') => app.next_tab(),
                             KeyCode::Char('q') => app.previous_tab(),
+                            KeyCode::Char('R') => match client.session.report {
+                                Some(_) => client.session.report = None,
+                                None => client.session.create_report(&client.finance),
+                            },
                             KeyCode::Right => match app.tab_index {
                                 0 => app.next_system(&mut client),
                                 1 => app.next_prospector(&client),
@@ -401,6 +455,103 @@ fn run_app<B: Backend>(
                                 3 => app.previous_dockable(&mut client),
                                 _ => {}
                             },
+                            KeyCode::Char('f') if app.tab_index == 0 => {
+                                app.signal_filter = SignalClass::next_filter(app.signal_filter)
+                            }
+                            KeyCode::Char('s') if app.tab_index == 3 => {
+                                let market_sort = client.station.market_sort.next();
+                                client.station.set_market_sort(market_sort);
+                            }
+                            KeyCode::Char('p') if app.tab_index == 3 => {
+                                let filter = &mut client.station.filter;
+                                filter.min_pad = match filter.min_pad {
+                                    None => Some(PadSize::Small),
+                                    Some(pad) => pad.next(),
+                                };
+                            }
+                            KeyCode::Char('o') if app.tab_index == 3 => {
+                                let filter = &mut client.station.filter;
+                                //Any, then each economy
+                                let next = StationFilter::ECONOMIES
+                                    .iter()
+                                    .position(|economy| economy == &filter.economy)
+                                    .map_or(0, |index| index + 1);
+                                filter.economy = StationFilter::ECONOMIES
+                                    .get(next)
+                                    .map(|economy| economy.to_string())
+                                    .unwrap_or_default();
+                            }
+                            KeyCode::Char('l') if app.tab_index == 3 => match app.dockable_mode {
+                                DockableMode::Carriers => {
+                                    let filter = &mut client.carrier.filter;
+                                    filter.max_distance = match filter.max_distance {
+                                        None => Some(10.0),
+                                        Some(distance) if distance < 1000.0 => {
+                                            Some(distance * 10.0)
+                                        }
+                                        Some(_) => None,
+                                    };
+                                }
+                                DockableMode::Stations => {
+                                    let filter = &mut client.station.filter;
+                                    filter.max_arrival_distance = match filter.max_arrival_distance
+                                    {
+                                        None => Some(1000.0),
+                                        Some(distance) if distance < 10000.0 => {
+                                            Some(distance * 10.0)
+                                        }
+                                        Some(_) => None,
+                                    };
+                                }
+                            },
+                            KeyCode::Char('a') if app.tab_index == 3 => {
+                                client.carrier.filter.open_access =
+                                    !client.carrier.filter.open_access;
+                            }
+                            KeyCode::Char('d') if app.tab_index == 3 => {
+                                client.carrier.filter.departing_soon =
+                                    !client.carrier.filter.departing_soon;
+                            }
+                            KeyCode::Char('v') if app.tab_index == 3 => {
+                                app.dockable_service_index = (app.dockable_service_index + 1)
+                                    % app.get_dockable_services().len();
+                            }
+                            KeyCode::Char('t') if app.tab_index == 3 => {
+                                let service = app.get_dockable_service();
+                                match app.dockable_mode {
+                                    DockableMode::Carriers => {
+                                        client.carrier.filter.toggle_service(service)
+                                    }
+                                    DockableMode::Stations => {
+                                        client.station.filter.toggle_service(service)
+                                    }
+                                }
+                            }
+                            KeyCode::Char('r') if app.tab_index == 4 => client.trade.calculate(),
+                            KeyCode::Char('s') if app.tab_index == 4 => {
+                                let sort = client.trade.sort.next();
+                                client.trade.set_sort(sort);
+                            }
+                            KeyCode::Char('p') if app.tab_index == 4 => {
+                                let query = &mut client.trade.query;
+                                query.pad_size = query.pad_size.next().unwrap_or(PadSize::Small);
+                            }
+                            KeyCode::Char('l') if app.tab_index == 4 => {
+                                let query = &mut client.trade.query;
+                                query.max_hop_distance = if query.max_hop_distance < 80.0 {
+                                    query.max_hop_distance * 2.0
+                                } else {
+                                    10.0
+                                };
+                            }
+                            KeyCode::Char('h') if app.tab_index == 4 => {
+                                let query = &mut client.trade.query;
+                                query.loop_hops = if query.loop_hops < 4 {
+                                    query.loop_hops + 1
+                                } else {
+                                    2
+                                };
+                            }
                             KeyCode::Char('i') => match app.tab_index {
                                 2 => app.search_input_mode = InputMode::Editing,
                                 3 => app.search_input_mode = InputMode::Editing,
@@ -454,7 +605,11 @@ fn ui(f: &mut Frame, app: &mut App, client: &mut EliteRustClient) {
 
     let tabs_and_timestamp = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Fill(1), Constraint::Length(22)])
+        .constraints([
+            Constraint::Fill(1),
+            Constraint::Length(18),
+            Constraint::Length(22),
+        ])
         .split(chunks[0]);
 
     let titles: Vec<&str> = app.titles.clone();
@@ -471,7 +626,29 @@ fn ui(f: &mut Frame, app: &mut App, client: &mut EliteRustClient) {
     let timestamp = Paragraph::new(client.timestamp.clone())
         .white()
         .block(Block::default().borders(Borders::LEFT).white());
-    f.render_widget(timestamp, tabs_and_timestamp[1]);
+    f.render_widget(timestamp, tabs_and_timestamp[2]);
+
+    //pending evm transactions, red after a failed one
+    let (mode, pending, failed) = {
+        let settings = client.settings.lock().unwrap();
+        let queue_status = settings.evm_settings.queue_status.lock().unwrap();
+        (
+            settings.evm_settings.get_mode(),
+            queue_status.pending,
+            queue_status.last_error.is_some(),
+        )
+    };
+    let queue = Paragraph::new(match mode {
+        EvmMode::ReadOnly => "EVM: read-only".to_string(),
+        EvmMode::Signing => format!("EVM queue: {}", pending),
+    })
+    .style(if failed {
+        Style::default().red()
+    } else {
+        Style::default().white()
+    })
+    .block(Block::default().borders(Borders::LEFT).white());
+    f.render_widget(queue, tabs_and_timestamp[1]);
 
     //render tab contents
     match app.tab_index {
@@ -479,7 +656,16 @@ fn ui(f: &mut Frame, app: &mut App, client: &mut EliteRustClient) {
         1 => tab_mining(chunks[1], f, client, app),
         2 => tab_materials(chunks[1], f, client, app),
         3 => tab_dockables(chunks[1], f, client, app),
-        4 => tab_about(chunks[1], f),
+        4 => tab_trade(chunks[1], f, client),
+        5 => tab_about(chunks[1], f, client),
         _ => unreachable!(),
     };
+
+    if let Some(report) = &client.session.report {
+        popup_session_report(chunks[1], f, report, &client.session.report_path);
+    }
+    if let Some(unlock) = &client.keystore_unlock {
+        let keystore_path = &client.settings.lock().unwrap().evm_settings.keystore_path;
+        popup_keystore_unlock(chunks[1], f, unlock, keystore_path);
+    }
 }
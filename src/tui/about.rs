@@ -1,12 +1,21 @@
 use ratatui::{prelude::*, style::Stylize, widgets::*};
 
-pub fn tab_about(chunk: ratatui::layout::Rect, f: &mut ratatui::Frame) {
+use crate::edcas::EliteRustClient;
+
+pub fn tab_about(chunk: ratatui::layout::Rect, f: &mut ratatui::Frame, client: &EliteRustClient) {
     // data here if needed
     let data_controls_list = vec![
         "Quit: Q, Change Tabs: q and e",
         "System/Material List/Prospector/Dockables List selection: Left and Right arrows",
         "Body/Cargo/Materials/Dockables selection: Up and Down arrows",
         "Search: i",
+        "Filter system signals by class: f",
+        "Sort station market: s",
+        "Filter stations by pad: p, economy: o, arrival distance: l",
+        "Filter carriers by open access: a, departure within 1h: d, distance: l",
+        "Select dockable service: v, require it: t",
+        "Trade routes: calculate: r, sort: s, pad: p, hop distance: l, loop hops: h",
+        "Session report: R",
         "Quit Search: esc",
     ];
 
@@ -19,6 +28,7 @@ pub fn tab_about(chunk: ratatui::layout::Rect, f: &mut ratatui::Frame) {
             Constraint::Length(data_controls_list_size), // TODO: hardcode correct value?
             Constraint::Length(3),
             Constraint::Length(3),
+            Constraint::Length(3),
         ])
         .split(chunk);
 
@@ -46,8 +56,29 @@ pub fn tab_about(chunk: ratatui::layout::Rect, f: &mut ratatui::Frame) {
             .white(),
     );
 
+    let last_evm_error = client
+        .settings
+        .lock()
+        .unwrap()
+        .evm_settings
+        .queue_status
+        .lock()
+        .unwrap()
+        .last_error
+        .clone()
+        .unwrap_or("-".to_string());
+    let widget_about_evm_error = Paragraph::new(last_evm_error)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Last EVM transaction error ")
+                .borders(Borders::TOP | Borders::LEFT)
+                .white(),
+        );
+
     // Render calls
     f.render_widget(widget_about_controls, layout_about[0]);
     f.render_widget(widget_about_github, layout_about[1]);
     f.render_widget(widget_about_version, layout_about[2]);
+    f.render_widget(widget_about_evm_error, layout_about[3]);
 }
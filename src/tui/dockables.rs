@@ -1,4 +1,5 @@
-use crate::edcas::backend::evm::request_handler::EvmRequest;
+use crate::edcas::backend::data_backend::DataRequest;
+use crate::edcas::station::MarketSortKey;
 use crate::edcas::EliteRustClient;
 use crate::tui::{App, InputMode};
 use ratatui::{prelude::*, style::Stylize, widgets::*};
@@ -22,6 +23,7 @@ pub fn tab_dockables(
     let mut data_dockable_info_modules: Vec<String> = vec!["no data".to_string()];
     let mut data_dockable_info_other = "no data".to_string();
     let mut data_station_info = vec![Row::new(vec!["no data".to_string()])];
+    let mut data_station_market = vec![Row::new(vec!["no data".to_string()])];
 
     //common layout definitions
     let layout_carrier = Layout::default()
@@ -33,45 +35,73 @@ pub fn tab_dockables(
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(2), Constraint::Fill(1)])
         .split(layout_carrier[0]);
+    let mut dockable_list_area = layout_dockable_search_list[1];
 
     //tab dependent data, layout and widgets definitions
     match app.dockable_mode {
         super::DockableMode::Carriers => {
+            client.carrier.request_filter_data();
+            let filter = &client.carrier.filter;
+            let widget_carrier_filter = Paragraph::new(vec![
+                Line::from(format!(
+                    "Services: {}",
+                    if filter.services.is_empty() {
+                        "any".to_string()
+                    } else {
+                        filter
+                            .services
+                            .iter()
+                            .map(|service| service.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    }
+                )),
+                Line::from(format!(
+                    "Access: {}  Within: {}  Departing: {}",
+                    if filter.open_access { "open" } else { "any" },
+                    filter
+                        .max_distance
+                        .map(|distance| format!("{} ly", distance))
+                        .unwrap_or("any".to_string()),
+                    if filter.departing_soon {
+                        "within 1h"
+                    } else {
+                        "any"
+                    }
+                )),
+                Line::from(format!("Toggle: {}", app.get_dockable_service())),
+            ])
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(" Filter ")
+                    .borders(Borders::TOP | Borders::LEFT)
+                    .white(),
+            );
+            let layout_carrier_filter = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(5), Constraint::Fill(1)])
+                .split(dockable_list_area);
+            f.render_widget(widget_carrier_filter, layout_carrier_filter[0]);
+            dockable_list_area = layout_carrier_filter[1];
+
             if !client.carrier.carriers.is_empty() {
                 dataset_carriers_list_selected = client
                     .carrier
-                    .carriers
-                    .iter()
-                    .filter(|f| {
-                        f.name
-                            .to_lowercase()
-                            .contains(&app.dockable_search.input.to_lowercase())
-                            || f.callsign
-                                .to_lowercase()
-                                .contains(&app.dockable_search.input.to_lowercase())
-                    })
+                    .get_matching_carriers(&app.dockable_search.input)
+                    .into_iter()
+                    .map(|index| &client.carrier.carriers[index])
                     .collect::<Vec<_>>();
 
-                /*
-                for carrier in &client.carrier.carriers {
-                    if carrier
-                        .name
-                        .to_lowercase()
-                        .contains(&app.dockable_search.input.to_lowercase())
-                        || carrier
-                            .callsign
-                            .to_lowercase()
-                            .contains(&app.dockable_search.input.to_lowercase())
-                    {
-                        dataset_carriers_list_selected.push(carrier);
-                    }
-                }
-                */
-
                 data_dockable_list_selected = dataset_carriers_list_selected
                     .iter()
                     .map(|carrier| {
-                        [carrier.name.to_string(), carrier.callsign.to_string()].join(" - ")
+                        let entry =
+                            [carrier.name.to_string(), carrier.callsign.to_string()].join(" - ");
+                        match client.carrier.get_distance(carrier) {
+                            Some(distance) => format!("{} - {:.2} ly", entry, distance),
+                            None => entry,
+                        }
                     })
                     .collect::<Vec<String>>();
 
@@ -107,6 +137,9 @@ pub fn tab_dockables(
                         dataset_carriers_list_selected[app.dockable_list_index]
                             .departure
                             .to_string(),
+                        " (".to_string(),
+                        dataset_carriers_list_selected[app.dockable_list_index].get_countdown(),
+                        ")".to_string(),
                     ]
                     .join("");
 
@@ -124,7 +157,7 @@ pub fn tab_dockables(
                             .to_string(),
                         "\nDocking Access:".to_string(),
                         dataset_carriers_list_selected[app.dockable_list_index]
-                            .docking_access
+                            .get_docking_access()
                             .to_string(),
                     ]
                     .join(" ");
@@ -179,33 +212,68 @@ pub fn tab_dockables(
         }
 
         super::DockableMode::Stations => {
+            client.station.request_filter_data();
+            let filter = &client.station.filter;
+            let widget_station_filter = Paragraph::new(vec![
+                Line::from(format!(
+                    "Services: {}",
+                    if filter.services.is_empty() {
+                        "any".to_string()
+                    } else {
+                        filter
+                            .services
+                            .iter()
+                            .map(|service| service.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    }
+                )),
+                Line::from(format!(
+                    "Pad: {}  Economy: {}  Arrival: {}",
+                    filter
+                        .min_pad
+                        .map(|pad| pad.to_string())
+                        .unwrap_or("any".to_string()),
+                    if filter.economy.is_empty() {
+                        "any"
+                    } else {
+                        filter.economy.as_str()
+                    },
+                    filter
+                        .max_arrival_distance
+                        .map(|distance| format!("<= {} LS", distance))
+                        .unwrap_or("any".to_string())
+                )),
+                Line::from(format!("Toggle: {}", app.get_dockable_service())),
+            ])
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(" Filter ")
+                    .borders(Borders::TOP | Borders::LEFT)
+                    .white(),
+            );
+            let layout_station_filter = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(5), Constraint::Fill(1)])
+                .split(dockable_list_area);
+            f.render_widget(widget_station_filter, layout_station_filter[0]);
+            dockable_list_area = layout_station_filter[1];
+
             if !client.station.stations.is_empty() {
                 dataset_stations_list_selected = client
                     .station
-                    .stations
-                    .iter()
-                    .filter(|f| {
-                        f.name
-                            .to_lowercase()
-                            .contains(&app.dockable_search.input.to_lowercase())
-                    })
+                    .get_matching_stations(&app.dockable_search.input)
+                    .into_iter()
+                    .map(|index| &client.station.stations[index])
                     .collect::<Vec<_>>();
 
-                /*
-                for station in &client.station.stations {
-                    if station
-                        .name
-                        .to_lowercase()
-                        .contains(&app.dockable_search.input.to_lowercase())
-                    {
-                        dataset_stations_list_selected.push(station);
-                    }
-                }
-                */
-
                 data_dockable_list_selected = dataset_stations_list_selected
                     .iter()
-                    .map(|station| station.name.to_string())
+                    .map(|station| match client.station.get_distance(station) {
+                        Some(distance) => format!("{} - {:.2} ly", station.name, distance),
+                        None => station.name.to_string(),
+                    })
                     .collect::<Vec<String>>();
 
                 if !data_dockable_list_selected.is_empty() {
@@ -218,13 +286,36 @@ pub fn tab_dockables(
                 _ => {}
             }
 
+                    //Taken before the metadata request, which needs the stations mutable
+                    let market_id =
+                        dataset_stations_list_selected[app.dockable_list_index].market_id;
+                    let requested_market =
+                        dataset_stations_list_selected[app.dockable_list_index].requested_market;
+                    let market_rows = dataset_stations_list_selected[app.dockable_list_index]
+                        .market
+                        .as_ref()
+                        .map(|market| {
+                            market
+                                .iter()
+                                .map(|listening| {
+                                    Row::new(vec![
+                                        listening.name.to_string(),
+                                        listening.buy_price.to_string(),
+                                        listening.sell_price.to_string(),
+                                        listening.stock.to_string(),
+                                        listening.demand.to_string(),
+                                    ])
+                                })
+                                .collect::<Vec<Row>>()
+                        });
+
                     match &dataset_stations_list_selected[app.dockable_list_index].meta_data {
                         None => {
                             if !dataset_stations_list_selected[app.dockable_list_index]
                                 .requested_meta_data
                             {
-                                if let Err(err) = client.station.evm_request_writer.send(
-                                    EvmRequest::StationMetaData(
+                                if let Err(err) = client.station.data_request_writer.send(
+                                    DataRequest::StationMetaData(
                                         dataset_stations_list_selected[app.dockable_list_index]
                                             .market_id,
                                     ),
@@ -291,6 +382,32 @@ pub fn tab_dockables(
                             data_dockable_info_other = station_metadata.landingpads.to_string();
                         }
                     }
+
+                    match market_rows {
+                        Some(rows) => data_station_market = rows,
+                        None if requested_market => {
+                            data_station_market = vec![Row::new(vec!["Fetching".to_string()])]
+                        }
+                        None => {
+                            if client
+                                .station
+                                .data_request_writer
+                                .send(DataRequest::StationCommodityListener(market_id))
+                                .is_err()
+                            {
+                                data_station_market =
+                                    vec![Row::new(vec!["Error sending request".to_string()])];
+                            }
+                            if let Some(station) = client
+                                .station
+                                .stations
+                                .iter_mut()
+                                .find(|station| station.market_id == market_id)
+                            {
+                                station.requested_market = true;
+                            }
+                        }
+                    }
                 }
             } else {
                 app.dockable_list_index = 0;
@@ -301,7 +418,8 @@ pub fn tab_dockables(
                 .constraints([
                     Constraint::Length(data_station_info.len() as u16 + 2),
                     Constraint::Length(data_dockable_info_modules.len() as u16 + 2), // services
-                    Constraint::Fill(1),                                             // other
+                    Constraint::Length(3),                                           // other
+                    Constraint::Fill(1),                                             // market
                 ])
                 .split(layout_carrier[1]);
 
@@ -329,9 +447,37 @@ pub fn tab_dockables(
                     .borders(Borders::TOP | Borders::LEFT),
             );
 
+            let widget_dockable_info_market = Table::new(
+                data_station_market,
+                [
+                    Constraint::Length(30),
+                    Constraint::Length(8),
+                    Constraint::Length(8),
+                    Constraint::Length(8),
+                    Constraint::Length(8),
+                ],
+            )
+            .header(
+                Row::new(MarketSortKey::ALL.map(|key| {
+                    if key == client.station.market_sort {
+                        format!("{} ▼", key)
+                    } else {
+                        key.to_string()
+                    }
+                }))
+                .bold(),
+            )
+            .block(
+                Block::default()
+                    .title(" Market ")
+                    .bold()
+                    .borders(Borders::TOP | Borders::LEFT),
+            );
+
             f.render_widget(widget_dockable_info_table, layout_dockable_info[0]);
             f.render_widget(widget_dockable_info_modules, layout_dockable_info[1]);
             f.render_widget(widget_dockable_info_other, layout_dockable_info[2]);
+            f.render_widget(widget_dockable_info_market, layout_dockable_info[3]);
         }
     }
 
@@ -363,7 +509,7 @@ pub fn tab_dockables(
     f.render_widget(widget_dockable_search, layout_dockable_search_list[0]);
     f.render_stateful_widget(
         widget_dockable_list,
-        layout_dockable_search_list[1],
+        dockable_list_area,
         &mut app.dockable_list_state,
     );
 
@@ -1,3 +1,4 @@
+use crate::edcas::explorer::orbit::get_direct_parent;
 use crate::edcas::{explorer::body::BodyType, EliteRustClient};
 use crate::tui::{round_to_2, round_to_4, App};
 use core::f64;
@@ -86,9 +87,19 @@ pub fn tab_explorer(
         ];
 
         data_signals_list = client.explorer.systems[client.explorer.index]
-            .signal_list
+            .get_signals(app.signal_filter)
             .iter()
-            .map(|signal| Row::new(vec![signal.name.to_string(), signal.threat.to_string()]))
+            .map(|signal| {
+                let mut name = signal.name.to_string();
+                if !signal.detail.is_empty() {
+                    name = format!("{} ({})", name, signal.detail);
+                }
+                Row::new(vec![
+                    signal.class.to_string(),
+                    name,
+                    signal.threat.to_string(),
+                ])
+            })
             .collect::<Vec<Row>>();
 
         if !client.explorer.systems[client.explorer.index]
@@ -127,9 +138,8 @@ pub fn tab_explorer(
         if client.explorer.systems[client.explorer.index].non_body_count != "n/v"
             && client.explorer.systems[client.explorer.index].body_count != "n/v"
         {
-            data_system_gauge_scanned = client.explorer.systems[client.explorer.index]
-                .body_list
-                .len() as i32;
+            data_system_gauge_scanned =
+                client.explorer.systems[client.explorer.index].get_scanned_body_count() as i32;
 
             data_system_gauge_all = client.explorer.systems[client.explorer.index]
                 .non_body_count
@@ -158,22 +168,10 @@ pub fn tab_explorer(
             .body_list
             .is_empty()
         {
-            //preparet to implement fancy tree, too dumb rn to do it.
             data_body_list.clear();
-            for body in client.explorer.systems[client.explorer.index]
-                .body_list
-                .iter()
-                .rev()
-            {
-                let mut space_string = "".to_string();
-
-                for i in 0..body.get_parents().len() {
-                    if i < body.get_parents().len() - 1 {
-                        space_string.push_str("│  ")
-                    } else {
-                        space_string.push_str("│  "); //├
-                    }
-                }
+            let body_list = &client.explorer.systems[client.explorer.index].body_list;
+            for (index, body) in body_list.iter().enumerate().rev() {
+                let mut space_string = get_tree_prefix(body_list, index);
                 space_string.push_str(body.get_name().as_str());
                 let signals_type_string: Vec<String> = body
                     .get_signals()
@@ -186,14 +184,23 @@ pub fn tab_explorer(
                         }
                     })
                     .collect();
-                data_body_list.push(
-                    vec![
-                        space_string.fg(Color::White),
-                        " ".into(),
-                        signals_type_string.join(" ").light_green().italic(),
-                    ]
-                    .into(),
-                )
+                let mut line = vec![
+                    space_string.fg(Color::White),
+                    " ".into(),
+                    signals_type_string.join(" ").light_green().italic(),
+                ];
+                let highlights = body.get_highlights();
+                for rule in &client.settings.lock().unwrap().highlight_rules {
+                    if rule.enabled && highlights.contains(&rule.name) {
+                        line.push(" ".into());
+                        line.push(rule.char.clone().fg(Color::Rgb(
+                            rule.color.r(),
+                            rule.color.g(),
+                            rule.color.b(),
+                        )));
+                    }
+                }
+                data_body_list.push(line.into())
             }
 
             data_body_list.reverse();
@@ -338,6 +345,40 @@ pub fn tab_explorer(
                     ]
                 }
 
+                BodyType::Barycentre(barycentre) => {
+                    data_body_name = Span::from(barycentre.body_name.to_string());
+                    vec![
+                        Row::new(vec![
+                            "Semi major Axis".to_string(),
+                            match barycentre.semi_major_axis {
+                                Some(sma) => [
+                                    round_to_4(sma / 149597870700.0).to_string(),
+                                    "AU".to_string(),
+                                ]
+                                .join(" "),
+                                None => "no data".to_string(),
+                            },
+                        ]),
+                        Row::new(vec![
+                            "Eccentricity".to_string(),
+                            match barycentre.eccentricity {
+                                Some(eccentricity) => round_to_4(eccentricity).to_string(),
+                                None => "no data".to_string(),
+                            },
+                        ]),
+                        Row::new(vec![
+                            "Orbital Period".to_string(),
+                            match barycentre.orbital_period {
+                                Some(period) => {
+                                    [round_to_2(period / 86400.0).to_string(), "D".to_string()]
+                                        .join(" ")
+                                }
+                                None => "no data".to_string(),
+                            },
+                        ]),
+                    ]
+                }
+
                 BodyType::BeltCluster(belt_body) => {
                     data_body_name = Span::from(belt_body.body_name.to_string());
                     vec![
@@ -784,12 +825,19 @@ pub fn tab_explorer(
 
     let widget_signal_list = Table::new(
         data_signals_list,
-        [Constraint::Fill(1), Constraint::Length(2)],
+        [
+            Constraint::Length(13),
+            Constraint::Fill(1),
+            Constraint::Length(2),
+        ],
     )
-    .header(Row::new(vec!["Name", "TL"]))
+    .header(Row::new(vec!["Class", "Name", "TL"]))
     .block(
         Block::default()
-            .title(" Signals ")
+            .title(match app.signal_filter {
+                None => " Signals ".to_string(),
+                Some(class) => format!(" Signals ({}) ", class),
+            })
             .borders(Borders::TOP | Borders::LEFT)
             .bold()
             .white(),
@@ -866,3 +914,32 @@ pub fn tab_explorer(
     f.render_widget(widget_planet_signals_list, layout_body[1])
     //f.render_widget(widget_body_signals_list, layout_body[1]);
 }
+
+/// Tree connectors for a body, derived from its `Parents` chain.
+/// The body list is sorted by id, so children always come after their parents.
+fn get_tree_prefix(body_list: &[BodyType], index: usize) -> String {
+    let has_later_sibling = |parent_id: u64| {
+        body_list[index + 1..].iter().any(|other| {
+            get_direct_parent(other).is_some_and(|other_parent| other_parent.id == parent_id)
+        })
+    };
+
+    let parents = body_list[index].get_parents();
+    let mut prefix = String::new();
+    //Parents are listed nearest first, the tree is drawn from the root
+    for level in (1..parents.len()).rev() {
+        if has_later_sibling(parents[level].id) {
+            prefix.push_str("│  ");
+        } else {
+            prefix.push_str("   ");
+        }
+    }
+    if let Some(parent) = parents.first() {
+        if has_later_sibling(parent.id) {
+            prefix.push_str("├─ ");
+        } else {
+            prefix.push_str("└─ ");
+        }
+    }
+    prefix
+}
@@ -0,0 +1,37 @@
+use crate::edcas::KeystoreUnlock;
+use ratatui::{prelude::*, style::Stylize, widgets::*};
+
+/// Passphrase prompt for the keystore, the input is shown masked
+pub fn popup_keystore_unlock(
+    chunk: ratatui::layout::Rect,
+    f: &mut ratatui::Frame,
+    unlock: &KeystoreUnlock,
+    keystore_path: &str,
+) {
+    let area = Rect {
+        x: chunk.x + chunk.width / 6,
+        y: chunk.y + chunk.height / 3,
+        width: chunk.width * 2 / 3,
+        height: 7.min(chunk.height),
+    };
+
+    let mut lines = vec![
+        Line::from(format!("Passphrase for {}", keystore_path)),
+        Line::from("*".repeat(unlock.passphrase.chars().count())).bold(),
+    ];
+    if let Some(err) = &unlock.error {
+        lines.push(Line::from(err.as_str()).red());
+    }
+    lines.push(Line::from("enter: unlock  esc: stay read-only").italic());
+
+    let widget_unlock = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(" Unlock keystore ")
+            .bold()
+            .borders(Borders::ALL)
+            .white(),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(widget_unlock, area);
+}
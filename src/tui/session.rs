@@ -0,0 +1,67 @@
+use crate::edcas::session::SessionReport;
+use ratatui::{prelude::*, style::Stylize, widgets::*};
+
+/// Report of the session in a popup over the current tab
+pub fn popup_session_report(
+    chunk: ratatui::layout::Rect,
+    f: &mut ratatui::Frame,
+    report: &SessionReport,
+    report_path: &Result<String, String>,
+) {
+    let area = Rect {
+        x: chunk.x + chunk.width / 6,
+        y: chunk.y + chunk.height / 8,
+        width: chunk.width * 2 / 3,
+        height: chunk.height * 3 / 4,
+    };
+
+    let mut lines = vec![
+        Line::from(format!(
+            "{} - {} ({})",
+            report.start.format("%Y-%m-%d %H:%M"),
+            report.end.format("%Y-%m-%d %H:%M"),
+            report.get_duration()
+        )),
+        match report_path {
+            Ok(path) => Line::from(format!("Written to {}.md and .json", path)),
+            Err(err) => Line::from(err.as_str()).red(),
+        },
+        Line::from(""),
+        Line::from(format!(
+            "Jumps: {} ({:.2} ly)",
+            report.jumps, report.distance
+        )),
+        Line::from(format!(
+            "Systems first discovered: {}  Bodies scanned: {}  Bodies mapped: {}",
+            report.systems_discovered, report.bodies_scanned, report.bodies_mapped
+        )),
+        Line::from(""),
+        Line::from(format!("Credits: {} Cr", report.get_credits_total())).bold(),
+    ];
+    for (activity, amount) in &report.credits {
+        lines.push(Line::from(format!("  {}: {} Cr", activity, amount)));
+    }
+    lines.push(Line::from(format!("Mined: {} t", report.get_mined_total())).bold());
+    for (name, count) in &report.mined {
+        lines.push(Line::from(format!("  {}: {} t", name, count)));
+    }
+    lines.push(Line::from(format!("Materials: {}", report.get_materials_total())).bold());
+    for (name, count) in &report.materials {
+        lines.push(Line::from(format!("  {}: {}", name, count)));
+    }
+    lines.push(Line::from(format!("Missions completed: {}", report.missions.len())).bold());
+    for mission in &report.missions {
+        lines.push(Line::from(format!("  {}", mission)));
+    }
+
+    let widget_report = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(" Session report (close: R) ")
+            .bold()
+            .borders(Borders::ALL)
+            .white(),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(widget_report, area);
+}
@@ -0,0 +1,210 @@
+use crate::edcas::EliteRustClient;
+use ratatui::{prelude::*, style::Stylize, widgets::*};
+
+pub fn tab_trade(chunk: ratatui::layout::Rect, f: &mut ratatui::Frame, client: &EliteRustClient) {
+    let trade = &client.trade;
+
+    let layout_trade = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Percentage(40),
+            Constraint::Fill(1),
+            Constraint::Percentage(30),
+        ])
+        .split(chunk);
+
+    let widget_query = Paragraph::new(format!(
+        "Cargo: {} t  Credits: {} Cr  Pad: {}  Hop: {} ly  Loop: {} hops  Sort: {}  Markets: {}{}",
+        trade.query.cargo_capacity,
+        trade.query.credits,
+        trade.query.pad_size,
+        trade.query.max_hop_distance,
+        trade.query.loop_hops,
+        trade.sort,
+        trade.get_market_count(),
+        if trade.is_calculating() || trade.is_loading() {
+            "  Calculating..."
+        } else {
+            ""
+        }
+    ))
+    .wrap(Wrap { trim: true })
+    .block(
+        Block::default()
+            .title(" Query ")
+            .bold()
+            .borders(Borders::TOP | Borders::LEFT),
+    );
+
+    let data_single_hop = trade
+        .single_hop_routes
+        .iter()
+        .flat_map(|route| route.hops.iter().map(move |hop| (route, hop)))
+        .map(|(route, hop)| {
+            Row::new(vec![
+                format!("{} ({})", hop.from_station, hop.from_system),
+                format!("{} ({})", hop.to_station, hop.to_system),
+                hop.commodity.to_string(),
+                hop.get_margin().to_string(),
+                hop.amount.to_string(),
+                format!("{:.2}", hop.distance),
+                format!("{:.0}", route.get_profit_per_hour()),
+            ])
+        })
+        .collect::<Vec<Row>>();
+
+    let widget_single_hop = Table::new(
+        data_single_hop,
+        [
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(24),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(12),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "From",
+            "To",
+            "Commodity",
+            "Cr/t",
+            "Tons",
+            "ly",
+            "Cr/h",
+        ])
+        .bold(),
+    )
+    .block(
+        Block::default()
+            .title(" Single hop ")
+            .bold()
+            .borders(Borders::TOP | Borders::LEFT),
+    );
+
+    let data_loops = trade
+        .loop_routes
+        .iter()
+        .map(|route| {
+            Row::new(vec![
+                route
+                    .hops
+                    .iter()
+                    .map(|hop| format!("{} ({})", hop.from_station, hop.commodity))
+                    .collect::<Vec<String>>()
+                    .join(" → "),
+                route.get_profit().to_string(),
+                format!("{:.0}", route.get_profit_per_ton()),
+                format!("{:.0}", route.get_profit_per_hour()),
+            ])
+        })
+        .collect::<Vec<Row>>();
+
+    let widget_loops = Table::new(
+        data_loops,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(12),
+        ],
+    )
+    .header(Row::new(vec!["Stations (commodity)", "Profit", "Cr/t", "Cr/h"]).bold())
+    .block(
+        Block::default()
+            .title(" Loops ")
+            .bold()
+            .borders(Borders::TOP | Borders::LEFT),
+    );
+
+    let layout_ledger = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(layout_trade[3]);
+
+    let data_hold = trade
+        .ledger
+        .positions
+        .iter()
+        .map(|(name, position)| {
+            let sell_price = trade.get_best_sell_price(name);
+            Row::new(vec![
+                position.name_localised.clone(),
+                position.count.to_string(),
+                format!("{:.0}", position.get_average_cost()),
+                sell_price
+                    .map(|sell_price| sell_price.to_string())
+                    .unwrap_or("n/v".to_string()),
+                sell_price
+                    .map(|sell_price| {
+                        (sell_price as i64 * position.count as i64 - position.cost as i64)
+                            .to_string()
+                    })
+                    .unwrap_or("n/v".to_string()),
+            ])
+        })
+        .collect::<Vec<Row>>();
+
+    let widget_hold = Table::new(
+        data_hold,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(6),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(12),
+        ],
+    )
+    .header(Row::new(vec!["Commodity", "Tons", "Cost", "Sell", "Unrealised"]).bold())
+    .block(
+        Block::default()
+            .title(format!(
+                " Cargo ledger: {} Cr unrealised ",
+                trade.get_unrealised_profit()
+            ))
+            .bold()
+            .borders(Borders::TOP | Borders::LEFT),
+    );
+
+    let data_trips = trade
+        .ledger
+        .get_trips()
+        .map(|trip| {
+            Row::new(vec![
+                format!("{} ({})", trip.station, trip.system),
+                trip.tons_sold.to_string(),
+                trip.revenue.to_string(),
+                trip.profit.to_string(),
+            ])
+        })
+        .collect::<Vec<Row>>();
+
+    let widget_trips = Table::new(
+        data_trips,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(6),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(Row::new(vec!["Station", "Tons", "Revenue", "Profit"]).bold())
+    .block(
+        Block::default()
+            .title(format!(
+                " Trips: {} Cr session profit ",
+                trade.ledger.get_session_profit()
+            ))
+            .bold()
+            .borders(Borders::TOP | Borders::LEFT),
+    );
+
+    f.render_widget(widget_query, layout_trade[0]);
+    f.render_widget(widget_single_hop, layout_trade[1]);
+    f.render_widget(widget_loops, layout_trade[2]);
+    f.render_widget(widget_hold, layout_ledger[0]);
+    f.render_widget(widget_trips, layout_ledger[1]);
+}